@@ -1,7 +1,7 @@
 use std::{collections::HashMap, rc::Rc};
 
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     ident::CaseInsensitive,
     span::Spanned,
 };
@@ -137,6 +137,7 @@ impl<'a> Compiler<'a> {
                 &self.sources.as_borrowed(),
                 &cst.specifiers,
             )),
+            doc: cst.doc,
         };
         self.env.register_var(var)
     }
@@ -186,6 +187,7 @@ impl<'a> Compiler<'a> {
                     // specifier is banned.
                     self.env.emit(
                         Diagnostic::error("specifier cannot be used in struct variables")
+                            .with_code(codes::M0403)
                             .with_label(Label::primary(
                                 specifier,
                                 "this specifier cannot be used in a struct",
@@ -222,6 +224,7 @@ impl<'a> Compiler<'a> {
                         // TODO: Point to the declaration of the mismatched type?
                         self.env.emit(
                             Diagnostic::error("base type of a struct must also be a struct")
+                                .with_code(codes::M0404)
                                 .with_label(Label::primary(&span, "this is not a struct type")),
                         );
                         let class_struct = self
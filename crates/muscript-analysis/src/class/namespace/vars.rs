@@ -50,6 +50,7 @@ impl<'a> Compiler<'a> {
                     name,
                     ty: constant.type_id(),
                     kind: VarKind::Const(constant),
+                    doc: item_const.doc,
                 }
             }
             VarCst::Var(item_var) => Var {
@@ -60,6 +61,7 @@ impl<'a> Compiler<'a> {
                     &self.sources.as_borrowed(),
                     &item_var.specifiers,
                 )),
+                doc: item_var.doc,
             },
         };
         self.env.register_var(var)
@@ -81,6 +83,7 @@ impl<'a> Compiler<'a> {
             flags: FunctionFlags::empty(),
             kind: FunctionKind::Function,
             implementation: FunctionImplementation::Script,
+            doc: cst::DocComment::default(),
         });
         let function = self.env.get_function(function_id);
         let mut builder = FunctionBuilder::new(function_id, function, value.span());
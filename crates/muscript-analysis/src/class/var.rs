@@ -1,5 +1,5 @@
 use bitflags::bitflags;
-use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label};
+use muscript_foundation::errors::{codes, Diagnostic, DiagnosticSink, Label};
 use muscript_lexer::{sources::LexedSources, token::Token};
 use muscript_syntax::cst::{self, ItemName};
 
@@ -10,6 +10,8 @@ pub struct Var {
     pub name: ItemName,
     pub ty: TypeId,
     pub kind: VarKind,
+    /// Doc comment of the variable. Empty for locals and parameters.
+    pub doc: cst::DocComment,
 }
 
 #[derive(Debug, Clone)]
@@ -132,6 +134,7 @@ impl VarFlags {
             if ignored {
                 diagnostics.emit({
                     let mut diagnostic = Diagnostic::warning("specifier is ignored")
+                        .with_code(codes::M0401)
                         .with_label(Label::primary(specifier, ""));
                     match specifier {
                         cst::VarSpecifier::NotForConsole(_) => {
@@ -160,6 +163,7 @@ impl VarFlags {
                         "repeated `{}` specifier",
                         sources.source(specifier)
                     ))
+                    .with_code(codes::M0402)
                     .with_label(Label::primary(specifier, "")),
                 )
             }
@@ -1,16 +1,18 @@
 //! Commonly used diagnostic messages.
 
 use indoc::indoc;
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::token::{Token, TokenSpan};
 use muscript_syntax::token;
 
 pub fn unnecessary_semicolon(semi: token::Semi) -> Diagnostic<Token> {
-    Diagnostic::warning("unnecessary semicolon `;`").with_label(Label::primary(&semi, ""))
+    Diagnostic::warning("unnecessary semicolon `;`")
+        .with_code(codes::M0423)
+        .with_label(Label::primary(&semi, ""))
 }
 
 pub fn stmt_outside_of_function(span: TokenSpan) -> Diagnostic<Token> {
-    Diagnostic::error("statement found outside of function")
+    Diagnostic::error("statement found outside of function").with_code(codes::M0424)
         .with_label(Label::primary(&span, "statements are not allowed here"))
         .with_note(indoc!("
             note: in contrast to most modern scripting languages, UnrealScript requires all executable code to belong to a function.
@@ -30,7 +32,9 @@ pub mod notes {
     pub const CONST_EVAL_SUPPORTED_FEATURES: &str = indoc! {"
         note: compile-time evaluation currently supports:
             - literal values
-            - the unary `-` operator on Int and Float values
+            - built-in operators on Bool, Int, Float, and String values
     "};
+    pub const BUG: &str =
+        "note: this is a bug, please report it at <https://github.com/abyteintime/stitchkit>";
     pub const WIP: &str = "note: MuScript is still unfinished; you can help contribute at <https://github.com/abyteintime/stitchkit>";
 }
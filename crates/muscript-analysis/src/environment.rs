@@ -1,7 +1,11 @@
 use std::collections::HashMap;
 
 use muscript_foundation::{
-    errors::{pipe_all_diagnostics_into, Diagnostic, DiagnosticSink, Label},
+    errors::{
+        codes,
+        lints::{LintLevels, LintScope},
+        pipe_all_diagnostics_into, Diagnostic, DiagnosticSink, Label,
+    },
     ident::CaseInsensitive,
 };
 use muscript_lexer::token::{Token, TokenSpan};
@@ -11,7 +15,7 @@ use tracing::trace;
 use crate::{
     class::{ClassNamespace, Var},
     function::Function,
-    ir::Ir,
+    ir::{optimize::PassManager, Ir},
     partition::UntypedClassPartition,
     type_system::{lookup::TypeSource, Primitive, Type, TypeName},
     Compiler,
@@ -32,6 +36,8 @@ pub struct FunctionId(u32);
 #[derive(Debug, Default)]
 pub struct Environment {
     pub diagnostics: Vec<Diagnostic<Token>>,
+    /// Lint levels applied to all diagnostics emitted into the environment.
+    pub lint_levels: LintLevels<Token>,
 
     class_ids_by_name: HashMap<CaseInsensitive<String>, ClassId>,
     class_names_by_id: Vec<CaseInsensitive<String>>,
@@ -54,6 +60,7 @@ impl Environment {
     pub fn new() -> Self {
         let mut env = Self {
             diagnostics: vec![],
+            lint_levels: LintLevels::new(),
             class_ids_by_name: HashMap::new(),
             class_names_by_id: vec![],
             class_namespaces_by_id: vec![],
@@ -133,9 +140,13 @@ impl<'a> Compiler<'a> {
         if self.input.class_exists(name) {
             Some(self.env.get_or_create_class(name))
         } else {
+            let suggestion =
+                self.did_you_mean(&error_span, name, "class", self.input.class_names());
             self.env.emit(
                 Diagnostic::error(format!("class `{name}` does not exist"))
-                    .with_label(Label::primary(&error_span, "")),
+                    .with_code(codes::M0400)
+                    .with_label(Label::primary(&error_span, ""))
+                    .with_optional_note(suggestion),
             );
             None
         }
@@ -201,11 +212,28 @@ impl Environment {
     pub fn get_function_ir(&self, function_id: FunctionId) -> Option<&Ir> {
         self.irs_by_function_id.get(&function_id)
     }
+
+    /// Runs the given passes over the function's IR. Does nothing if the function's body has not
+    /// been analyzed yet.
+    pub fn optimize_function_ir(&mut self, function_id: FunctionId, passes: &PassManager) {
+        // The IR is temporarily taken out of the environment, such that passes can look
+        // things up in it.
+        if let Some(mut ir) = self.irs_by_function_id.remove(&function_id) {
+            passes.run(self, &mut ir);
+            self.irs_by_function_id.insert(function_id, ir);
+        }
+    }
 }
 
 impl DiagnosticSink<Token> for Environment {
     fn emit(&mut self, diagnostic: Diagnostic<Token>) {
-        self.diagnostics.push(diagnostic);
+        if let Some(diagnostic) = self.lint_levels.apply(diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn set_lint_levels(&mut self, scope: LintScope<Token>) {
+        self.lint_levels.scopes.push(scope);
     }
 }
 
@@ -216,9 +244,17 @@ impl<'a> Compiler<'a> {
         &mut self,
         class_id: ClassId,
     ) -> Option<&[UntypedClassPartition]> {
-        if self.env.untyped_class_partitions.get(&class_id).is_none() {
+        if !self.env.untyped_class_partitions.contains_key(&class_id) {
             let class_name = self.env.class_name(class_id).to_owned();
-            if let Some(class_sources) =
+            let diagnostic_count = self.env.diagnostics.len();
+            if let Some(partitions) =
+                self.input
+                    .cached_class_partitions(self.sources, &class_name, self.env)
+            {
+                self.env
+                    .untyped_class_partitions
+                    .insert(class_id, Some(partitions));
+            } else if let Some(class_sources) =
                 self.input
                     .parsed_class_sources(self.sources, &class_name, self.env)
             {
@@ -247,6 +283,15 @@ impl<'a> Compiler<'a> {
                     &partitions,
                 );
 
+                // Parsing emits diagnostics straight into the environment, so those have to be
+                // checked for, too.
+                if diagnostics.is_empty() && self.env.diagnostics.len() == diagnostic_count {
+                    self.input.save_class_partitions(
+                        &self.sources.as_borrowed(),
+                        &class_name,
+                        &partitions,
+                    );
+                }
                 pipe_all_diagnostics_into(self.env, diagnostics);
                 self.env
                     .untyped_class_partitions
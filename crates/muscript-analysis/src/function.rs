@@ -1,7 +1,7 @@
 use bitflags::bitflags;
 use indoc::indoc;
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label, Severity},
     ident::CaseInsensitive,
     span::Spanned,
 };
@@ -20,6 +20,7 @@ use self::builder::FunctionBuilder;
 
 pub mod builder;
 pub mod expr;
+mod lints;
 pub mod mangling;
 mod stmt;
 
@@ -35,6 +36,8 @@ pub struct Function {
     pub flags: FunctionFlags,
     pub kind: FunctionKind,
     pub implementation: FunctionImplementation,
+
+    pub doc: cst::DocComment,
 }
 
 bitflags! {
@@ -162,6 +165,7 @@ impl<'a> Compiler<'a> {
                 name: ItemName::from_spanned(&param.name),
                 ty,
                 kind: VarKind::Var(var_flags),
+                doc: cst::DocComment::default(),
             });
             params.push(Param {
                 var: param_var,
@@ -180,6 +184,7 @@ impl<'a> Compiler<'a> {
             flags,
             kind,
             implementation,
+            doc: cst.doc.clone(),
         })
     }
 
@@ -217,6 +222,7 @@ impl<'a> Compiler<'a> {
             self.declare_local(&mut builder, param.var);
         }
 
+        let diagnostics_before_body = self.env.diagnostics.len();
         let function = self.env.get_function(function_id);
         match &cst.body {
             cst::Body::Stub(semi) => {
@@ -229,7 +235,7 @@ impl<'a> Compiler<'a> {
                 ) || function.kind == FunctionKind::Event;
                 if !can_be_stubbed_out {
                     self.env.emit(
-                        Diagnostic::error("function body expected")
+                        Diagnostic::error("function body expected").with_code(codes::M0406)
                             .with_label(Label::primary(semi, ""))
                             .with_note("note: functions can only be stubbed out when they're in interfaces, or when they're `native`"),
                     )
@@ -260,6 +266,14 @@ impl<'a> Compiler<'a> {
         builder.ir.set_terminator(Terminator::Return(returned_void));
 
         let ir = builder.into_ir();
+        // Stubbed out functions do not have any code that could use their parameters, and
+        // functions with errors in them may be missing code, which would produce false positives.
+        let body_had_errors = self.env.diagnostics[diagnostics_before_body..]
+            .iter()
+            .any(|diagnostic| diagnostic.severity >= Severity::Error);
+        if matches!(cst.body, cst::Body::Impl(_)) && !body_had_errors {
+            self.lint_function_ir(function_id, &ir);
+        }
 
         self.untyped_class_partitions_for_theft(class_id).unwrap()[partition_index]
             .functions
@@ -313,7 +327,7 @@ impl FunctionFlags {
                             .parse(sources, diagnostics);
                     if !(0..=4095).contains(&opcode_index) {
                         diagnostics.emit(
-                            Diagnostic::error("`native` index out of range")
+                            Diagnostic::error("`native` index out of range").with_code(codes::M0407)
                                 .with_label(Label::primary(&opcode_index_cst.number, ""))
                                 .with_note("note: indices of native functions bound to opcodes must lie within the [0, 4095] range"),
                         )
@@ -325,7 +339,7 @@ impl FunctionFlags {
                 cst::FunctionSpecifier::Const(ident) => diagnostics.emit(
                     Diagnostic::error(
                         "`const` specifier must be placed after the function's parameters",
-                    )
+                    ).with_code(codes::M0408)
                     .with_label(Label::primary(ident, ""))
                     .with_note(indoc!{"
                         note: even if placed there, `const` is ignored because it's only relevant for exporting
@@ -344,6 +358,7 @@ impl FunctionFlags {
 
             if flags == previous_flags {
                 let mut diagnostic = Diagnostic::warning("specifier is ignored")
+                    .with_code(codes::M0401)
                     .with_label(Label::primary(specifier, ""));
 
                 match specifier {
@@ -378,7 +393,7 @@ fn unsupported_post_specifiers(
     for specifier in specifiers {
         if let cst::FunctionSpecifier::Const(ident) = specifier {
             diagnostics.emit(
-                Diagnostic::warning("`const` specifier is ignored")
+                Diagnostic::warning("`const` specifier is ignored").with_code(codes::M0401)
                     .with_label(Label::primary(ident, ""))
                     .with_note(indoc! {"
                         note: `const` after the function's parameters is only relevant for exporting C++ headers,
@@ -388,6 +403,7 @@ fn unsupported_post_specifiers(
         } else {
             diagnostics.emit(
                 Diagnostic::error("specifiers other than `const` are not allowed here")
+                    .with_code(codes::M0408)
                     .with_label(Label::primary(specifier, ""))
                     .with_note("help: try placing the specifier before `function`"),
             )
@@ -420,7 +436,7 @@ fn unsupported_param_specifiers(
     for specifier in specifiers {
         if let cst::ParamSpecifier::Skip(ident) = specifier {
             diagnostics.emit(
-                Diagnostic::warning("`skip` specifier is ignored")
+                Diagnostic::warning("`skip` specifier is ignored").with_code(codes::M0401)
                     .with_label(Label::primary(ident, ""))
                     .with_note("note: MuScript currently does not support the `skip` specifier on non-`native` functions"),
             );
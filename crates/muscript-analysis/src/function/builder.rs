@@ -77,6 +77,13 @@ impl FunctionBuilder {
             .insert(CaseInsensitive::new(name.to_owned()), var_id)
     }
 
+    /// Returns the names of all locals that are currently in scope.
+    pub fn local_names(&self) -> impl Iterator<Item = &str> {
+        self.local_scopes
+            .iter()
+            .flat_map(|scope| scope.locals.keys().map(|name| name.as_str()))
+    }
+
     pub fn lookup_local(&self, name: &str) -> Option<VarId> {
         self.local_scopes
             .iter()
@@ -1,6 +1,6 @@
 use indoc::indoc;
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_syntax::cst;
@@ -110,7 +110,7 @@ impl<'a> Compiler<'a> {
             cst::Expr::FailedExp(token) => {
                 let macro_name = self.sources.source(token);
                 self.env.emit(
-                    Diagnostic::error("use of undefined macro as an expression")
+                    Diagnostic::error("use of undefined macro as an expression").with_code(codes::M0425)
                         .with_label(Label::primary(expr, ""))
                         .with_note(format!("the macro `{macro_name}` was not defined anywhere, and expanded to no tokens where an expression was expected"))
                         .with_note(format!(indoc!{"
@@ -129,6 +129,7 @@ impl<'a> Compiler<'a> {
             _ => {
                 self.env.emit(
                     Diagnostic::error("unsupported expression")
+                        .with_code(codes::M0416)
                         .with_label(Label::primary(expr, ""))
                         .with_note(notes::WIP),
                 );
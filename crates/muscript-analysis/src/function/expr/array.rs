@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_syntax::{cst, token};
@@ -44,13 +44,15 @@ impl<'a> Compiler<'a> {
             let index_type_id = builder.ir.register(index_register_id).ty;
             if index_type_id != TypeId::INT {
                 self.env.emit(
-                    Diagnostic::error("array index must be an `Int`").with_label(Label::primary(
-                        left,
-                        format!(
-                            "this was found to be of type `{}`",
-                            self.env.type_name(index_type_id)
-                        ),
-                    )),
+                    Diagnostic::error("array index must be an `Int`")
+                        .with_code(codes::M0429)
+                        .with_label(Label::primary(
+                            left,
+                            format!(
+                                "this was found to be of type `{}`",
+                                self.env.type_name(index_type_id)
+                            ),
+                        )),
                 );
             }
             builder.ir.append_register(
@@ -65,6 +67,7 @@ impl<'a> Compiler<'a> {
         } else {
             self.env.emit(
                 Diagnostic::error("indexing `[]` can only be done on arrays")
+                    .with_code(codes::M0430)
                     .with_label(Label::primary(
                         left,
                         format!(
@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_syntax::cst;
@@ -26,6 +26,7 @@ impl<'a> Compiler<'a> {
         {
             self.env.emit(
                 Diagnostic::error("left-hand side of `=` is not a place that can be assigned to")
+                    .with_code(codes::M0431)
                     .with_label(Label::primary(lvalue, "this is not a place in memory")),
             )
         }
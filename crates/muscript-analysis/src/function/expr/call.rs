@@ -2,7 +2,7 @@ use std::fmt::Write as _;
 
 use muscript_foundation::{
     self,
-    errors::{Diagnostic, DiagnosticSink, Label, Note, NoteKind},
+    errors::{codes, Diagnostic, DiagnosticSink, Label, Note, NoteKind},
     span::Spanned,
 };
 use muscript_lexer::token::{Token, TokenSpan};
@@ -82,7 +82,7 @@ impl<'a> Compiler<'a> {
                     _ = write!(error, "`{type_name}`");
                 }
                 self.env.emit(
-                    Diagnostic::error( error)
+                    Diagnostic::error( error).with_code(codes::M0432)
                         .with_label(Label::primary(&operator.span(), ""))
                         .with_note(Note {
                             kind: NoteKind::Debug,
@@ -197,6 +197,7 @@ impl<'a> Compiler<'a> {
                             "too many parameters; expected {num_params}, but got {}",
                             args.len()
                         ))
+                        .with_code(codes::M0433)
                         .with_label(Label::primary(&args[num_params], ""))
                         .with_label(Label::secondary(&function.name, "function declared here")),
                     );
@@ -240,12 +241,15 @@ impl<'a> Compiler<'a> {
                         if let cst::Arg::Provided(value_expr) = arg {
                             return self.expr_cast(builder, outer, function, type_id, value_expr);
                         } else {
-                            self.env
-                                .emit(Diagnostic::error("type cast argument cannot be omitted"))
+                            self.env.emit(
+                                Diagnostic::error("type cast argument cannot be omitted")
+                                    .with_code(codes::M0433),
+                            )
                         }
                     } else {
                         self.env.emit(
                             Diagnostic::error("type cast expects one argument")
+                                .with_code(codes::M0433)
                                 .with_label(Label::primary(&open.span().join(&close.span()), "")),
                         )
                     }
@@ -253,17 +257,27 @@ impl<'a> Compiler<'a> {
                     self.env
                         .diagnostics
                         .resize_with(num_diagnostics, || unreachable!("must only shrink"));
+                    let candidates = self.function_names_in_scope(builder.class_id);
+                    let suggestion = self.did_you_mean(
+                        ident,
+                        name,
+                        "function",
+                        candidates.iter().map(String::as_str),
+                    );
                     self.env.emit(
                         Diagnostic::error(format!(
                             "function `{name}` could not be found in this scope"
                         ))
-                        .with_label(Label::primary(ident, "")),
+                        .with_code(codes::M0434)
+                        .with_label(Label::primary(ident, ""))
+                        .with_optional_note(suggestion),
                     )
                 }
             }
         } else {
             self.env.emit(
                 Diagnostic::error("expression cannot be called")
+                    .with_code(codes::M0435)
                     .with_label(Label::primary(
                         function,
                         "this expression does not denote a function",
@@ -311,6 +325,7 @@ impl<'a> Compiler<'a> {
                 {
                     self.env.emit(
                         Diagnostic::error("expression passed to `out` parameter must be a place")
+                            .with_code(codes::M0431)
                             .with_label(Label::primary(expr, "this is not a place in memory")),
                     );
                 }
@@ -324,6 +339,7 @@ impl<'a> Compiler<'a> {
                         Diagnostic::error(format!(
                             "required argument `{param_name}` was not provided"
                         ))
+                        .with_code(codes::M0433)
                         .with_label(Label::primary(span, "argument expected here..."))
                         .with_label(Label::primary(
                             &param_var.name,
@@ -1,6 +1,6 @@
 use indoc::formatdoc;
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_lexer::token::{Token, TokenSpan};
@@ -97,6 +97,7 @@ impl<'a> Compiler<'a> {
             Type::Object(_class_id) => {
                 self.env.emit(
                     Diagnostic::error("object type casts are not yet supported")
+                        .with_code(codes::M0416)
                         .with_label(Label::primary(outer, "")),
                 );
                 builder.ir.append_register(
@@ -109,6 +110,7 @@ impl<'a> Compiler<'a> {
             Type::Class(_class_id) => {
                 self.env.emit(
                     Diagnostic::error("class type casts are not yet supported")
+                        .with_code(codes::M0416)
                         .with_label(Label::primary(outer, "")),
                 );
                 builder.ir.append_register(
@@ -121,6 +123,7 @@ impl<'a> Compiler<'a> {
             Type::Struct { outer: _ } => {
                 self.env.emit(
                     Diagnostic::error("struct type casts are not yet supported")
+                        .with_code(codes::M0416)
                         .with_label(Label::primary(outer, "")),
                 );
                 builder.ir.append_register(
@@ -133,6 +136,7 @@ impl<'a> Compiler<'a> {
             Type::Enum { outer: _ } => {
                 self.env.emit(
                     Diagnostic::error("enum type casts are not yet supported")
+                        .with_code(codes::M0416)
                         .with_label(Label::primary(outer, "")),
                 );
                 builder.ir.append_register(
@@ -147,7 +151,7 @@ impl<'a> Compiler<'a> {
                 self.env.emit(
                     Diagnostic::error(
                         "casting between dynamic array types is not supported",
-                    )
+                    ).with_code(codes::M0437)
                     .with_label(Label::primary(outer, ""))
                     .with_note("note: casting between array types like `Array<Int>` and `Array<Float>` is not supported by the VM and would be a very expensive operation"),
                 );
@@ -195,6 +199,7 @@ impl<'a> Compiler<'a> {
 
         self.env.emit(
             Diagnostic::error("invalid cast")
+                .with_code(codes::M0437)
                 .with_label(Label::primary(
                     value_expr,
                     format!("from type `{}`", self.env.type_name(from_type)),
@@ -253,6 +258,7 @@ impl<'a> Compiler<'a> {
         got_ty: TypeId,
     ) -> Diagnostic<Token> {
         Diagnostic::error("type mismatch")
+            .with_code(codes::M0438)
             .with_label(Label::primary(&span, ""))
             .with_note(formatdoc! {"
                     expected `{}`
@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Applicability, Diagnostic, DiagnosticSink, Label, Note},
     ident::CaseInsensitive,
     span::Spanned,
 };
@@ -69,6 +69,7 @@ impl<'a> Compiler<'a> {
                         Diagnostic::error(
                             "the `.` operator can only be used on objects, structs, and arrays",
                         )
+                        .with_code(codes::M0426)
                         .with_label(Label::primary(&dot, ""))
                         .with_label(Label::secondary(
                             left,
@@ -113,13 +114,32 @@ impl<'a> Compiler<'a> {
                 },
             )
         } else {
-            // TODO: If a function with the same name exists, suggest calling it.
+            let suggestion = if self.function_name_exists(class_id, field_name) {
+                Some(Note::from((
+                    format!("help: `{field_name}` is a function, try calling it"),
+                    self.sources.replacement_suggestion(
+                        &field,
+                        format!("{field_name}()"),
+                        Applicability::MaybeIncorrect,
+                    ),
+                )))
+            } else {
+                let candidates = self.var_names_in_scope(class_id);
+                self.did_you_mean(
+                    &field,
+                    field_name,
+                    "variable",
+                    candidates.iter().map(String::as_str),
+                )
+            };
             self.env.emit(
                 Diagnostic::error(format!(
                     "cannot find variable `{field_name}` in class `{}`",
                     self.env.class_name(class_id)
                 ))
-                .with_label(Label::primary(&field, "")),
+                .with_code(codes::M0427)
+                .with_label(Label::primary(&field, ""))
+                .with_optional_note(suggestion),
             );
             builder
                 .ir
@@ -191,6 +211,7 @@ impl<'a> Compiler<'a> {
         } else {
             self.env.emit(
                 Diagnostic::error("`Length` expected")
+                    .with_code(codes::M0428)
                     .with_label(Label::primary(&field, ""))
                     .with_note("note: arrays do not have properties other than `Length`"),
             );
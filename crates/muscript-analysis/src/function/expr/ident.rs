@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_syntax::token::Ident;
@@ -46,9 +46,18 @@ impl<'a> Compiler<'a> {
                 .ir
                 .append_register(ident.span(), "self", ty, Value::This)
         } else {
+            let candidates = self.value_names_in_scope(builder);
+            let suggestion = self.did_you_mean(
+                &ident,
+                name,
+                "variable",
+                candidates.iter().map(String::as_str),
+            );
             self.env.emit(
                 Diagnostic::error(format!("cannot find variable `{name}` in this scope"))
-                    .with_label(Label::primary(&ident, "")),
+                    .with_code(codes::M0427)
+                    .with_label(Label::primary(&ident, ""))
+                    .with_optional_note(suggestion),
             );
             builder.ir.append_register(
                 ident.span(),
@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_syntax::{
@@ -71,6 +71,7 @@ impl<'a> Compiler<'a> {
                 if byte.is_err() {
                     self.env.emit(
                         Diagnostic::error("byte value out of range")
+                            .with_code(codes::M0436)
                             .with_label(Label::primary(lit, ""))
                             .with_note("note: byte literals must fit in the range [0, 255]"),
                     )
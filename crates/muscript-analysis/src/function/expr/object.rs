@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Applicability, Diagnostic, DiagnosticSink, Label},
     ident::CaseInsensitive,
     span::Spanned,
 };
@@ -39,12 +39,14 @@ impl<'a> Compiler<'a> {
                     Diagnostic::error(
                         "references to classes located within packages are not supported",
                     )
+                    .with_code(codes::M0416)
                     .with_label(Label::primary(&name_lit, ""))
                     .with_note((
                         "help: try referencing the class using just its name",
                         self.sources.replacement_suggestion(
                             outer,
                             format!("class'{}'", &object_name[dot_index + 1..]),
+                            Applicability::MachineApplicable,
                         ),
                     )),
                 );
@@ -80,6 +82,7 @@ impl<'a> Compiler<'a> {
         } else {
             self.env.emit(
                 Diagnostic::error("object references are not yet implemented")
+                    .with_code(codes::M0416)
                     .with_label(Label::primary(outer, "")),
             );
             builder.ir.append_register(
@@ -0,0 +1,380 @@
+//! Dataflow-based lints over a function's IR.
+
+use std::collections::{HashMap, HashSet};
+
+use muscript_foundation::errors::{codes, Applicability, Diagnostic, DiagnosticSink, Label};
+use muscript_lexer::token::TokenSpan;
+
+use crate::{
+    function::{FunctionKind, ParamFlags},
+    ir::{BasicBlockId, Ir, NodeKind, RegisterId, Sink, Terminator, Value},
+    type_system::Type,
+    Compiler, Environment, FunctionId, VarId,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A read or write of a variable's value.
+#[derive(Debug, Clone, Copy)]
+struct Access {
+    var_id: VarId,
+    kind: AccessKind,
+    span: TokenSpan,
+}
+
+/// How the value of a register is used by whatever consumes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Usage {
+    Read,
+    Write,
+    /// The value is evaluated, but its result is thrown away. Discarding a variable is not
+    /// considered a read, since this is what happens to the result of assignment expressions.
+    Discard,
+}
+
+struct AccessCollector<'a> {
+    env: &'a Environment,
+    ir: &'a Ir,
+    accesses: Vec<Access>,
+}
+
+impl<'a> AccessCollector<'a> {
+    fn access(&mut self, register_id: RegisterId, usage: Usage) {
+        let span = self.ir.node(register_id.into()).span;
+        match &self.ir.register(register_id).value {
+            &Value::Local(var_id) => {
+                let kind = match usage {
+                    Usage::Read => AccessKind::Read,
+                    Usage::Write => AccessKind::Write,
+                    Usage::Discard => return,
+                };
+                self.accesses.push(Access {
+                    var_id,
+                    kind,
+                    span,
+                });
+            }
+
+            &Value::PrimitiveCast { value, .. } => self.access(value, Usage::Read),
+            &Value::Len(array) => self.access(array, Usage::Read),
+            &Value::Index { array, index } => {
+                self.access(index, Usage::Read);
+                // Writing to an element of an array counts as writing to the whole array.
+                self.access(array, Self::partial_usage(usage));
+            }
+            &Value::In { context, action } => {
+                // Writing to a field of a struct counts as writing to the whole struct, but
+                // writing to a field of an object only reads the variable holding the object.
+                let context_ty = self.ir.register(context).ty;
+                let context_usage = if let Type::Struct { .. } = self.env.get_type(context_ty) {
+                    Self::partial_usage(usage)
+                } else {
+                    Usage::Read
+                };
+                self.access(context, context_usage);
+                self.access(action, usage);
+            }
+            Value::CallFinal {
+                function,
+                arguments,
+            } => {
+                let function = self.env.get_function(*function);
+                // Operators with `out` operands, such as `+=` and `++`, update the variable in
+                // place, so they read it before writing to it.
+                let is_operator = matches!(
+                    function.kind,
+                    FunctionKind::PrefixOperator
+                        | FunctionKind::PostfixOperator
+                        | FunctionKind::InfixOperator
+                );
+                let params = &function.params;
+                for (i, &argument) in arguments.iter().enumerate() {
+                    // Passing a variable to an `out` parameter initializes it.
+                    let is_out = params
+                        .get(i)
+                        .is_some_and(|param| param.flags.contains(ParamFlags::OUT));
+                    if is_out && is_operator {
+                        self.access(argument, Usage::Read);
+                    }
+                    self.access(
+                        argument,
+                        if is_out { Usage::Write } else { Usage::Read },
+                    );
+                }
+            }
+
+            Value::Void
+            | Value::Bool(_)
+            | Value::Byte(_)
+            | Value::Int(_)
+            | Value::Float(_)
+            | Value::String(_)
+            | Value::Name(_)
+            | Value::Field(_)
+            | Value::None
+            | Value::This
+            | Value::Object { .. }
+            // Omitted arguments do not refer to any variables.
+            | Value::Default => (),
+        }
+    }
+
+    fn partial_usage(usage: Usage) -> Usage {
+        match usage {
+            Usage::Write => Usage::Write,
+            Usage::Read | Usage::Discard => Usage::Read,
+        }
+    }
+
+    /// Collects all accesses performed by a basic block, in evaluation order.
+    fn basic_block(&mut self, basic_block_id: BasicBlockId) {
+        let basic_block = self.ir.basic_block(basic_block_id);
+        for &node_id in &basic_block.flow {
+            if let NodeKind::Sink(sink) = &self.ir.node(node_id).kind {
+                match *sink {
+                    Sink::Discard(register_id) => self.access(register_id, Usage::Discard),
+                    Sink::Store(lvalue, rvalue) => {
+                        self.access(rvalue, Usage::Read);
+                        self.access(lvalue, Usage::Write);
+                    }
+                }
+            }
+        }
+        match basic_block.terminator {
+            Terminator::GotoIf { condition, .. } => self.access(condition, Usage::Read),
+            Terminator::Return(register_id) => self.access(register_id, Usage::Read),
+            Terminator::Unreachable | Terminator::Goto(_) => (),
+        }
+    }
+}
+
+fn accesses(env: &Environment, ir: &Ir, basic_block_id: BasicBlockId) -> Vec<Access> {
+    let mut collector = AccessCollector {
+        env,
+        ir,
+        accesses: vec![],
+    };
+    collector.basic_block(basic_block_id);
+    collector.accesses
+}
+
+impl<'a> Compiler<'a> {
+    /// Emits warnings about unused variables and parameters, as well as local variables that may
+    /// be read before they are assigned.
+    pub(super) fn lint_function_ir(&mut self, function_id: FunctionId, ir: &Ir) {
+        let accesses_by_block: Vec<_> = ir
+            .basic_block_ids()
+            .map(|basic_block_id| accesses(self.env, ir, basic_block_id))
+            .collect();
+
+        self.lint_uninitialized_locals(ir, &accesses_by_block);
+        self.lint_unused_vars(function_id, ir, &accesses_by_block);
+    }
+
+    fn lint_uninitialized_locals(&mut self, ir: &Ir, accesses_by_block: &[Vec<Access>]) {
+        let local_indices: HashMap<VarId, usize> = ir
+            .locals
+            .iter()
+            .enumerate()
+            .map(|(i, &var_id)| (var_id, i))
+            .collect();
+        let num_locals = ir.locals.len();
+        let num_blocks = ir.basic_blocks.len();
+        if num_locals == 0 || num_blocks == 0 {
+            return;
+        }
+
+        let mut predecessors = vec![vec![]; num_blocks];
+        for basic_block_id in ir.basic_block_ids() {
+            ir.basic_block(basic_block_id)
+                .terminator
+                .visit_successors(|successor| {
+                    predecessors[successor.to_u32() as usize].push(basic_block_id.to_u32() as usize)
+                });
+        }
+        let mut reachable = vec![false; num_blocks];
+        let mut worklist = vec![0];
+        while let Some(b) = worklist.pop() {
+            if !reachable[b] {
+                reachable[b] = true;
+                ir.basic_blocks[b]
+                    .terminator
+                    .visit_successors(|successor| worklist.push(successor.to_u32() as usize));
+            }
+        }
+
+        let transfer = |b: usize, assigned: &mut [bool]| {
+            for access in &accesses_by_block[b] {
+                if let (AccessKind::Write, Some(&i)) =
+                    (access.kind, local_indices.get(&access.var_id))
+                {
+                    assigned[i] = true;
+                }
+            }
+        };
+
+        // Forward "definitely assigned" analysis. A local is definitely assigned at the start of
+        // a block if it's assigned at the end of all the block's reachable predecessors.
+        let mut assigned_out = vec![vec![true; num_locals]; num_blocks];
+        let mut assigned_in = vec![vec![true; num_locals]; num_blocks];
+        assigned_in[0] = vec![false; num_locals];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 0..num_blocks {
+                if !reachable[b] {
+                    continue;
+                }
+                let mut assigned = if b == 0 {
+                    vec![false; num_locals]
+                } else {
+                    let mut assigned = vec![true; num_locals];
+                    for &p in predecessors[b].iter().filter(|&&p| reachable[p]) {
+                        for (a, &p) in assigned.iter_mut().zip(&assigned_out[p]) {
+                            *a &= p;
+                        }
+                    }
+                    assigned
+                };
+                assigned_in[b].clone_from(&assigned);
+                transfer(b, &mut assigned);
+                if assigned != assigned_out[b] {
+                    assigned_out[b] = assigned;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut reported = HashSet::new();
+        for b in 0..num_blocks {
+            if !reachable[b] {
+                continue;
+            }
+            let mut assigned = assigned_in[b].clone();
+            for access in &accesses_by_block[b] {
+                let Some(&i) = local_indices.get(&access.var_id) else {
+                    continue;
+                };
+                match access.kind {
+                    AccessKind::Write => assigned[i] = true,
+                    AccessKind::Read if !assigned[i] && reported.insert(access.var_id) => {
+                        let var = self.env.get_var(access.var_id);
+                        let name = self.sources.source(&var.name);
+                        self.env.emit(
+                            Diagnostic::warning(format!(
+                                "variable `{name}` may be read before it is assigned"
+                            ))
+                            .with_code(codes::M0439)
+                            .with_label(Label::primary(&access.span, "read here"))
+                            .with_label(Label::secondary(&var.name, "variable declared here"))
+                            .with_note(format!(
+                                "note: until it's assigned, `{name}` holds its type's default value (such as `0` or `none`)"
+                            )),
+                        );
+                    }
+                    AccessKind::Read => (),
+                }
+            }
+        }
+    }
+
+    fn lint_unused_vars(
+        &mut self,
+        function_id: FunctionId,
+        ir: &Ir,
+        accesses_by_block: &[Vec<Access>],
+    ) {
+        let mut read = HashSet::new();
+        let mut written = HashSet::new();
+        for access in accesses_by_block.iter().flatten() {
+            match access.kind {
+                AccessKind::Read => read.insert(access.var_id),
+                AccessKind::Write => written.insert(access.var_id),
+            };
+        }
+
+        let unused_params: Vec<_> = self
+            .env
+            .get_function(function_id)
+            .params
+            .iter()
+            .filter(|param| {
+                let is_out = param.flags.contains(ParamFlags::OUT);
+                // Assigning to an `out` parameter is how a function returns values through it.
+                !(read.contains(&param.var) || (is_out && written.contains(&param.var)))
+            })
+            .map(|param| param.var)
+            .collect();
+        // The parameters of events and overrides are dictated by the signature being implemented,
+        // so there is nothing the user could do about them being unused.
+        if !unused_params.is_empty() && !self.has_fixed_signature(function_id) {
+            for var_id in unused_params {
+                self.emit_unused_var(var_id, written.contains(&var_id), |name| {
+                    format!("unused parameter `{name}`")
+                });
+            }
+        }
+        for &var_id in &ir.locals {
+            if !read.contains(&var_id) {
+                if written.contains(&var_id) {
+                    self.emit_unused_var(var_id, true, |name| {
+                        format!("variable `{name}` is assigned to, but never read")
+                    });
+                } else {
+                    self.emit_unused_var(var_id, false, |name| format!("unused variable `{name}`"));
+                }
+            }
+        }
+    }
+
+    /// Returns whether the function is an event, or overrides a function from a superclass.
+    fn has_fixed_signature(&mut self, function_id: FunctionId) -> bool {
+        let function = self.env.get_function(function_id);
+        if function.kind == FunctionKind::Event {
+            return true;
+        }
+        let class_id = function.class_id;
+        let name = function.mangled_name.clone();
+        self.super_class_id(class_id)
+            .and_then(|super_class_id| self.lookup_function(super_class_id, &name))
+            .is_some()
+    }
+
+    /// `is_written` should be set if the variable is referenced anywhere, in which case renaming
+    /// just the declaration would break the code.
+    fn emit_unused_var(
+        &mut self,
+        var_id: VarId,
+        is_written: bool,
+        message: impl FnOnce(&str) -> String,
+    ) {
+        let var = self.env.get_var(var_id);
+        let name = self.sources.source(&var.name);
+        // Like in many other languages, prefixing a name with an underscore is a way of saying
+        // that it's unused on purpose.
+        if name.starts_with('_') {
+            return;
+        }
+        self.env.emit(
+            Diagnostic::warning(message(name))
+                .with_code(codes::M0440)
+                .with_label(Label::primary(&var.name, ""))
+                .with_note((
+                    "help: if this is intentional, prefix the name with an underscore",
+                    self.sources.replacement_suggestion(
+                        &var.name,
+                        format!("_{name}"),
+                        if is_written {
+                            Applicability::MaybeIncorrect
+                        } else {
+                            Applicability::MachineApplicable
+                        },
+                    ),
+                )),
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_syntax::cst;
@@ -22,6 +22,7 @@ impl<'a> Compiler<'a> {
         match stmt {
             cst::Stmt::Empty(semi) => self.env.emit(
                 Diagnostic::warning("empty statement has no effect")
+                    .with_code(codes::M0423)
                     .with_label(Label::primary(semi, "this semicolon is redundant")),
             ),
             cst::Stmt::Expr(expr) => self.stmt_expr(builder, expr),
@@ -37,6 +38,7 @@ impl<'a> Compiler<'a> {
             _ => {
                 self.env.emit(
                     Diagnostic::error("unsupported statement")
+                        .with_code(codes::M0416)
                         .with_label(Label::primary(stmt, ""))
                         .with_note(notes::WIP),
                 );
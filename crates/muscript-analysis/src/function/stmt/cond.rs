@@ -1,4 +1,4 @@
-use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label};
+use muscript_foundation::errors::{codes, Diagnostic, DiagnosticSink, Label};
 
 use crate::{function::builder::FunctionBuilder, ir::RegisterId, Compiler, TypeId};
 
@@ -19,6 +19,7 @@ impl<'a> Compiler<'a> {
                     "condition must be a `Bool`, but was found to be `{}`",
                     self.env.type_name(register.ty)
                 ))
+                .with_code(codes::M0422)
                 .with_label(Label::primary(builder.ir.node(register_id.into()), "")),
             );
         }
@@ -1,4 +1,4 @@
-use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label};
+use muscript_foundation::errors::{codes, Diagnostic, DiagnosticSink, Label};
 use muscript_syntax::cst::{self, ItemName};
 
 use crate::{
@@ -15,12 +15,14 @@ impl<'a> Compiler<'a> {
             if let Some(cpptype) = &var_def.cpptype {
                 self.env.emit(
                     Diagnostic::error("C++ type is not allowed on local variables")
+                        .with_code(codes::M0421)
                         .with_label(Label::primary(cpptype, "")),
                 );
             }
             if let Some(meta) = &var_def.meta {
                 self.env.emit(
                     Diagnostic::error("metadata are not allowed on local variables")
+                        .with_code(codes::M0421)
                         .with_label(Label::primary(meta, "")),
                 );
             }
@@ -28,6 +30,7 @@ impl<'a> Compiler<'a> {
             if let Some(array) = &var_def.array {
                 self.env.emit(
                     Diagnostic::error("arrays are not supported yet")
+                        .with_code(codes::M0416)
                         .with_label(Label::primary(array, ""))
                         .with_note(notes::WIP),
                 );
@@ -37,6 +40,7 @@ impl<'a> Compiler<'a> {
                 name: ItemName::from_spanned(&var_def.name),
                 ty,
                 kind: VarKind::Var(VarFlags::empty()),
+                doc: cst::DocComment::default(),
             });
             builder.ir.add_local(var_id);
             self.declare_local(builder, var_id);
@@ -50,6 +54,7 @@ impl<'a> Compiler<'a> {
             let previous_var = self.env.get_var(var_id);
             self.env.emit(
                 Diagnostic::error(format!("redefinition of variable `{name_str}`"))
+                    .with_code(codes::M0418)
                     .with_label(Label::primary(&var.name, ""))
                     .with_label(Label::secondary(
                         &previous_var.name,
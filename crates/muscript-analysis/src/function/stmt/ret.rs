@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Applicability, Diagnostic, DiagnosticSink, Label},
     span::Spanned,
 };
 use muscript_syntax::cst;
@@ -56,6 +56,7 @@ impl<'a> Compiler<'a> {
                 Diagnostic::error(
                     "function does not return anything, but a return value was provided",
                 )
+                .with_code(codes::M0420)
                 .with_label(Label::primary(&ret.value, ""))
                 .with_label(Label::secondary(
                     &builder.function(self.env).name,
@@ -63,7 +64,12 @@ impl<'a> Compiler<'a> {
                 ))
                 .with_note((
                     "help: try removing the return value",
-                    self.sources.replacement_suggestion(ret, "return;"),
+                    // Not machine-applicable, since the value may have side effects.
+                    self.sources.replacement_suggestion(
+                        ret,
+                        "return;",
+                        Applicability::MaybeIncorrect,
+                    ),
                 )),
             );
             false
@@ -73,6 +79,7 @@ impl<'a> Compiler<'a> {
                     "function was declared to return `{}`, but no return value was provided",
                     self.env.type_name(builder.return_ty)
                 ))
+                .with_code(codes::M0420)
                 .with_label(Label::primary(ret, ""))
                 .with_label(Label::secondary(
                     &builder.function(self.env).name,
@@ -81,8 +88,11 @@ impl<'a> Compiler<'a> {
                 .with_note((
                     "help: try adding a return value",
                     // TODO: Type-specific suggestions?
-                    self.sources
-                        .replacement_suggestion(ret, "return SomeValueHere;"),
+                    self.sources.replacement_suggestion(
+                        ret,
+                        "return SomeValueHere;",
+                        Applicability::HasPlaceholders,
+                    ),
                 )),
             );
             false
@@ -2,6 +2,11 @@ mod basic_block;
 pub mod dump;
 mod insn;
 pub mod interpret;
+pub(crate) mod natives;
+pub mod optimize;
+#[cfg(test)]
+mod testing;
+mod verify;
 
 use std::borrow::Cow;
 
@@ -9,6 +14,8 @@ use crate::{TypeId, VarId};
 
 pub use basic_block::*;
 pub use insn::*;
+pub use verify::*;
+
 use muscript_foundation::span::Spanned;
 use muscript_lexer::token::{Token, TokenSpan};
 
@@ -150,12 +157,80 @@ impl Ir {
         &self.nodes[node_id.0 as usize]
     }
 
+    pub fn node_mut(&mut self, node_id: NodeId) -> &mut Node {
+        &mut self.nodes[node_id.0 as usize]
+    }
+
     pub fn register(&self, register_id: RegisterId) -> &Register {
         match &self.node(register_id.into()).kind {
             NodeKind::Register(register) => register,
             NodeKind::Sink(_) => unreachable!("RegisterId must point to a register"),
         }
     }
+
+    /// Returns the [`RegisterId`] of the given node if it's a register.
+    pub fn node_register_id(&self, node_id: NodeId) -> Option<RegisterId> {
+        match self.node(node_id).kind {
+            NodeKind::Register(_) => Some(RegisterId(node_id.0)),
+            NodeKind::Sink(_) => None,
+        }
+    }
+
+    pub fn register_mut(&mut self, register_id: RegisterId) -> &mut Register {
+        match &mut self.node_mut(register_id.into()).kind {
+            NodeKind::Register(register) => register,
+            NodeKind::Sink(_) => unreachable!("RegisterId must point to a register"),
+        }
+    }
+
+    /// Iterates over the IDs of all basic blocks in the chunk, in order.
+    pub fn basic_block_ids(&self) -> impl Iterator<Item = BasicBlockId> {
+        (0..self.basic_blocks.len() as u32).map(BasicBlockId)
+    }
+
+    /// Makes every node and terminator reading from `from` read from `to` instead.
+    /// Returns whether any uses were replaced.
+    ///
+    /// Only nodes that are part of a basic block's flow are considered.
+    pub fn replace_register_uses(&mut self, from: RegisterId, to: RegisterId) -> bool {
+        let mut replaced = false;
+        let mut replace = |register: &mut RegisterId| {
+            if *register == from {
+                *register = to;
+                replaced = true;
+            }
+        };
+        for basic_block_index in 0..self.basic_blocks.len() {
+            for flow_index in 0..self.basic_blocks[basic_block_index].flow.len() {
+                let node_id = self.basic_blocks[basic_block_index].flow[flow_index];
+                self.nodes[node_id.0 as usize]
+                    .kind
+                    .visit_registers_mut(&mut replace);
+            }
+            self.basic_blocks[basic_block_index]
+                .terminator
+                .visit_registers_mut(&mut replace);
+        }
+        replaced
+    }
+}
+
+impl NodeKind {
+    /// Calls `f` with every register this node reads.
+    pub fn visit_registers(&self, f: impl FnMut(RegisterId)) {
+        match self {
+            NodeKind::Register(register) => register.value.visit_registers(f),
+            NodeKind::Sink(sink) => sink.visit_registers(f),
+        }
+    }
+
+    /// Calls `f` with a mutable reference to every register this node reads.
+    pub fn visit_registers_mut(&mut self, f: impl FnMut(&mut RegisterId)) {
+        match self {
+            NodeKind::Register(register) => register.value.visit_registers_mut(f),
+            NodeKind::Sink(sink) => sink.visit_registers_mut(f),
+        }
+    }
 }
 
 impl NodeId {
@@ -164,6 +239,12 @@ impl NodeId {
     }
 }
 
+impl RegisterId {
+    pub fn to_u32(&self) -> u32 {
+        self.0
+    }
+}
+
 impl From<RegisterId> for NodeId {
     fn from(value: RegisterId) -> Self {
         NodeId(value.0)
@@ -171,6 +252,9 @@ impl From<RegisterId> for NodeId {
 }
 
 impl BasicBlockId {
+    /// The ID of the entry point block.
+    pub const ENTRY: Self = Self(0);
+
     pub fn to_u32(&self) -> u32 {
         self.0
     }
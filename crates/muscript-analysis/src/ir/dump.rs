@@ -220,6 +220,112 @@ impl<'a> Debug for DumpIr<'a> {
     }
 }
 
+/// Formats a single node the same way [`DumpIr`] does.
+struct DumpNode<'a> {
+    dump: &'a DumpIr<'a>,
+    node_id: NodeId,
+}
+
+impl<'a> Debug for DumpNode<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.dump.ir.node(self.node_id).kind {
+            NodeKind::Register(register) => self.dump.register(f, self.node_id, register),
+            NodeKind::Sink(sink) => self.dump.sink(f, sink),
+        }
+    }
+}
+
+/// Formats a terminator the same way [`DumpIr`] does.
+struct DumpTerminator<'a> {
+    dump: &'a DumpIr<'a>,
+    terminator: &'a Terminator,
+}
+
+impl<'a> Debug for DumpTerminator<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.dump.terminator(f, self.terminator)
+    }
+}
+
+/// Dumps a function's control flow graph in Graphviz DOT format.
+///
+/// Each basic block becomes a node listing its registers, sinks, and terminator; edges represent
+/// jumps between blocks.
+pub struct DumpCfg<'a> {
+    pub sources: &'a LexedSources<'a>,
+    pub env: &'a Environment,
+    pub function: &'a Function,
+    pub ir: &'a Ir,
+}
+
+impl<'a> DumpCfg<'a> {
+    /// Escapes text for use inside a quoted DOT string, with each line left-justified.
+    fn escaped(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\l"),
+                _ => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+
+impl<'a> Debug for DumpCfg<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let dump = DumpIr {
+            sources: self.sources,
+            env: self.env,
+            ir: self.ir,
+        };
+
+        let class_name = self.env.class_name(self.function.class_id);
+        writeln!(
+            f,
+            "digraph \"{}\" {{",
+            Self::escaped(&format!("{class_name}.{}", self.function.mangled_name))
+        )?;
+        writeln!(f, "    node [shape=box, fontname=\"monospace\"];")?;
+
+        for (i, basic_block) in self.ir.basic_blocks.iter().enumerate() {
+            let mut label = format!("{}_{i}:\n", basic_block.label);
+            for &node_id in &basic_block.flow {
+                label.push_str(&format!(
+                    "{:?}\n",
+                    DumpNode {
+                        dump: &dump,
+                        node_id
+                    }
+                ));
+            }
+            label.push_str(&format!(
+                "{:?}\n",
+                DumpTerminator {
+                    dump: &dump,
+                    terminator: &basic_block.terminator
+                }
+            ));
+            writeln!(f, "    bb{i} [label=\"{}\"];", Self::escaped(&label))?;
+
+            match basic_block.terminator {
+                Terminator::Unreachable | Terminator::Return(_) => (),
+                Terminator::Goto(target) => writeln!(f, "    bb{i} -> bb{};", target.to_u32())?,
+                Terminator::GotoIf {
+                    if_true, if_false, ..
+                } => {
+                    writeln!(f, "    bb{i} -> bb{} [label=\"true\"];", if_true.to_u32())?;
+                    writeln!(f, "    bb{i} -> bb{} [label=\"false\"];", if_false.to_u32())?;
+                }
+            }
+        }
+
+        f.write_str("}\n")
+    }
+}
+
 pub struct DumpFunction<'a> {
     pub sources: &'a LexedSources<'a>,
     pub env: &'a Environment,
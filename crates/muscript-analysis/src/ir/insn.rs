@@ -177,3 +177,138 @@ impl Ir {
         }
     }
 }
+
+/// # Operands
+impl Value {
+    /// Calls `f` with every register this value reads.
+    pub fn visit_registers(&self, mut f: impl FnMut(RegisterId)) {
+        match self {
+            Value::Void
+            | Value::Bool(_)
+            | Value::Byte(_)
+            | Value::Int(_)
+            | Value::Float(_)
+            | Value::String(_)
+            | Value::Name(_)
+            | Value::Local(_)
+            | Value::Field(_)
+            | Value::None
+            | Value::This
+            | Value::Object { .. }
+            | Value::Default => (),
+            Value::PrimitiveCast { value, .. } => f(*value),
+            Value::Len(array) => f(*array),
+            Value::Index { array, index } => {
+                f(*array);
+                f(*index);
+            }
+            Value::In { context, action } => {
+                f(*context);
+                f(*action);
+            }
+            Value::CallFinal { arguments, .. } => arguments.iter().copied().for_each(f),
+        }
+    }
+
+    /// Calls `f` with a mutable reference to every register this value reads.
+    pub fn visit_registers_mut(&mut self, mut f: impl FnMut(&mut RegisterId)) {
+        match self {
+            Value::Void
+            | Value::Bool(_)
+            | Value::Byte(_)
+            | Value::Int(_)
+            | Value::Float(_)
+            | Value::String(_)
+            | Value::Name(_)
+            | Value::Local(_)
+            | Value::Field(_)
+            | Value::None
+            | Value::This
+            | Value::Object { .. }
+            | Value::Default => (),
+            Value::PrimitiveCast { value, .. } => f(value),
+            Value::Len(array) => f(array),
+            Value::Index { array, index } => {
+                f(array);
+                f(index);
+            }
+            Value::In { context, action } => {
+                f(context);
+                f(action);
+            }
+            Value::CallFinal { arguments, .. } => arguments.iter_mut().for_each(f),
+        }
+    }
+}
+
+impl Sink {
+    /// Calls `f` with every register this sink reads.
+    pub fn visit_registers(&self, mut f: impl FnMut(RegisterId)) {
+        match self {
+            Sink::Discard(register) => f(*register),
+            Sink::Store(lvalue, rvalue) => {
+                f(*lvalue);
+                f(*rvalue);
+            }
+        }
+    }
+
+    /// Calls `f` with a mutable reference to every register this sink reads.
+    pub fn visit_registers_mut(&mut self, mut f: impl FnMut(&mut RegisterId)) {
+        match self {
+            Sink::Discard(register) => f(register),
+            Sink::Store(lvalue, rvalue) => {
+                f(lvalue);
+                f(rvalue);
+            }
+        }
+    }
+}
+
+impl Terminator {
+    /// Calls `f` with every register this terminator reads.
+    pub fn visit_registers(&self, mut f: impl FnMut(RegisterId)) {
+        match self {
+            Terminator::Unreachable | Terminator::Goto(_) => (),
+            Terminator::GotoIf { condition, .. } => f(*condition),
+            Terminator::Return(register) => f(*register),
+        }
+    }
+
+    /// Calls `f` with a mutable reference to every register this terminator reads.
+    pub fn visit_registers_mut(&mut self, mut f: impl FnMut(&mut RegisterId)) {
+        match self {
+            Terminator::Unreachable | Terminator::Goto(_) => (),
+            Terminator::GotoIf { condition, .. } => f(condition),
+            Terminator::Return(register) => f(register),
+        }
+    }
+
+    /// Calls `f` with every basic block this terminator may jump to.
+    pub fn visit_successors(&self, mut f: impl FnMut(BasicBlockId)) {
+        match self {
+            Terminator::Unreachable | Terminator::Return(_) => (),
+            Terminator::Goto(target) => f(*target),
+            Terminator::GotoIf {
+                if_true, if_false, ..
+            } => {
+                f(*if_true);
+                f(*if_false);
+            }
+        }
+    }
+
+    /// Calls `f` with a mutable reference to every basic block this terminator may jump to.
+    pub fn visit_successors_mut(&mut self, mut f: impl FnMut(&mut BasicBlockId)) {
+        match self {
+            Terminator::Unreachable | Terminator::Return(_) => (),
+            Terminator::Goto(target) => f(target),
+            Terminator::GotoIf {
+                if_true, if_false, ..
+            } => {
+                f(if_true);
+                f(if_false);
+            }
+        }
+    }
+}
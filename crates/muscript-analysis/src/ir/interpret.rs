@@ -1,6 +1,6 @@
 //! Constant evaluation engine (IR interpreter.)
 
-use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label};
+use muscript_foundation::errors::{codes, Diagnostic, DiagnosticSink, Label};
 use muscript_lexer::token::{Token, TokenSpan};
 
 use crate::{
@@ -9,7 +9,7 @@ use crate::{
     Compiler, TypeId,
 };
 
-use super::{Ir, NodeKind, RegisterId, Terminator, Value};
+use super::{natives, Ir, NodeKind, RegisterId, Terminator, Value};
 
 /// Constant expression value. Corresponds directly to a subset of [`Value`] variants.
 #[derive(Debug, Clone, PartialEq)]
@@ -37,21 +37,35 @@ impl Constant {
         }
     }
 
+    /// Returns the constant represented by the given value, or `None` if the value is not
+    /// a literal.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Void => Some(Constant::Void),
+            &Value::Bool(x) => Some(Constant::Bool(x)),
+            &Value::Byte(x) => Some(Constant::Byte(x)),
+            &Value::Int(x) => Some(Constant::Int(x)),
+            &Value::Float(x) => Some(Constant::Float(x)),
+            Value::String(x) => Some(Constant::String(x.clone())),
+            Value::Name(x) => Some(Constant::Name(x.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Constant::Void => Value::Void,
+            &Constant::Bool(x) => Value::Bool(x),
+            &Constant::Byte(x) => Value::Byte(x),
+            &Constant::Int(x) => Value::Int(x),
+            &Constant::Float(x) => Value::Float(x),
+            Constant::String(x) => Value::String(x.clone()),
+            Constant::Name(x) => Value::Name(x.clone()),
+        }
+    }
+
     pub fn append_to(&self, ir: &mut IrBuilder, span: TokenSpan, name: &str) -> RegisterId {
-        ir.append_register(
-            span,
-            name.to_owned(),
-            self.type_id(),
-            match self {
-                Constant::Void => Value::Void,
-                &Constant::Bool(x) => Value::Bool(x),
-                &Constant::Byte(x) => Value::Byte(x),
-                &Constant::Int(x) => Value::Int(x),
-                &Constant::Float(x) => Value::Float(x),
-                Constant::String(x) => Value::String(x.clone()),
-                Constant::Name(x) => Value::Name(x.clone()),
-            },
-        )
+        ir.append_register(span, name.to_owned(), self.type_id(), self.to_value())
     }
 
     pub fn expect_int(&self) -> i32 {
@@ -69,12 +83,6 @@ impl Constant {
     }
 }
 
-// TODO: Should be in its own crate for handling low-level bytecode stuff.
-mod natives {
-    pub const SUBTRACT_PRE_INT: u16 = 143;
-    pub const SUBTRACT_PRE_FLOAT: u16 = 169;
-}
-
 impl<'a> Compiler<'a> {
     pub fn eval_ir(&mut self, ir: &Ir) -> Constant {
         let block = &ir.basic_blocks[0];
@@ -106,6 +114,7 @@ impl<'a> Compiler<'a> {
                 // avoid compiling indefinitely.
                 self.env.emit(
                     Diagnostic::error("loops cannot be evaluated at compile time")
+                        .with_code(codes::M0405)
                         .with_label(Label::primary(&block.span, "")),
                 );
                 Constant::Void
@@ -115,6 +124,7 @@ impl<'a> Compiler<'a> {
                     Diagnostic::error(
                         "conditional branches (`if`s and `?:`) cannot be evaluated at compile time",
                     )
+                    .with_code(codes::M0405)
                     .with_label(Label::primary(&block.span, "")),
                 );
                 Constant::Void
@@ -124,7 +134,7 @@ impl<'a> Compiler<'a> {
                 self.env.emit(
                     Diagnostic::bug("unreachable IR reached")
                         .with_label(Label::primary(&block.span, ""))
-                        .with_note("note: this is a bug, please report it at <https://github.com/abyteintime/stitchkit>"),
+                        .with_note(notes::BUG),
                 );
                 Constant::Void
             }
@@ -150,22 +160,30 @@ impl<'a> Compiler<'a> {
                 arguments,
             } => {
                 let function = self.env.get_function(*function_id);
-                dbg!(&function.mangled_name);
-                match function.implementation {
-                    FunctionImplementation::Opcode(natives::SUBTRACT_PRE_INT) => {
-                        let x = self.eval_register(ir, arguments[0]).expect_int();
-                        Constant::Int(-x)
-                    }
-                    FunctionImplementation::Opcode(natives::SUBTRACT_PRE_FLOAT) => {
-                        let x = self.eval_register(ir, arguments[0]).expect_float();
-                        Constant::Float(-x)
+                let result = if let FunctionImplementation::Opcode(opcode) = function.implementation
+                {
+                    let arguments: Vec<_> = arguments
+                        .iter()
+                        .map(|&argument| self.eval_register(ir, argument))
+                        .collect();
+                    if arguments.contains(&Constant::Void) {
+                        // Errors have already been reported while evaluating the arguments.
+                        return Constant::Void;
                     }
-                    _ => {
+                    natives::eval_pure(opcode, &arguments)
+                } else {
+                    None
+                };
+                match result {
+                    Some(constant) => constant,
+                    None => {
+                        let function = self.env.get_function(*function_id);
                         self.env.emit(
                             Diagnostic::error(format!(
                                 "function `{}` cannot be evaluated at compile time",
                                 self.sources.source(&function.name)
                             ))
+                            .with_code(codes::M0405)
                             .with_label(Label::primary(&span, ""))
                             .with_note(notes::CONST_EVAL_SUPPORTED_FEATURES),
                         );
@@ -199,6 +217,7 @@ fn cannot_evaluate_at_compile_time(
         CannotEvaluateAtCompileTime::Expression => "expression cannot be evaluated at compile time",
         CannotEvaluateAtCompileTime::Statement => "statement cannot be evaluated at compile time",
     })
+    .with_code(codes::M0405)
     .with_label(Label::primary(&span, ""))
     .with_note(notes::CONST_EVAL_SUPPORTED_FEATURES)
 }
@@ -0,0 +1,209 @@
+//! Opcodes of native functions the compiler knows about.
+
+// TODO: Should be in its own crate for handling low-level bytecode stuff.
+
+use super::interpret::Constant;
+
+pub const CONCAT_STRING: u16 = 112;
+pub const NOT_PRE_BOOL: u16 = 129;
+pub const AND_AND_BOOL: u16 = 130;
+pub const XOR_XOR_BOOL: u16 = 131;
+pub const OR_OR_BOOL: u16 = 132;
+pub const COMPLEMENT_PRE_INT: u16 = 141;
+pub const SUBTRACT_PRE_INT: u16 = 143;
+pub const MULTIPLY_INT: u16 = 144;
+pub const DIVIDE_INT: u16 = 145;
+pub const ADD_INT: u16 = 146;
+pub const SUBTRACT_INT: u16 = 147;
+pub const LESS_LESS_INT: u16 = 148;
+pub const GREATER_GREATER_INT: u16 = 149;
+pub const LESS_INT: u16 = 150;
+pub const GREATER_INT: u16 = 151;
+pub const LESS_EQUAL_INT: u16 = 152;
+pub const GREATER_EQUAL_INT: u16 = 153;
+pub const EQUAL_EQUAL_INT: u16 = 154;
+pub const NOT_EQUAL_INT: u16 = 155;
+pub const AND_INT: u16 = 156;
+pub const XOR_INT: u16 = 157;
+pub const OR_INT: u16 = 158;
+pub const AT_STRING: u16 = 168;
+pub const SUBTRACT_PRE_FLOAT: u16 = 169;
+pub const MULTIPLY_MULTIPLY_FLOAT: u16 = 170;
+pub const MULTIPLY_FLOAT: u16 = 171;
+pub const DIVIDE_FLOAT: u16 = 172;
+pub const PERCENT_FLOAT: u16 = 173;
+pub const ADD_FLOAT: u16 = 174;
+pub const SUBTRACT_FLOAT: u16 = 175;
+pub const LESS_FLOAT: u16 = 176;
+pub const GREATER_FLOAT: u16 = 177;
+pub const LESS_EQUAL_FLOAT: u16 = 178;
+pub const GREATER_EQUAL_FLOAT: u16 = 179;
+pub const EQUAL_EQUAL_FLOAT: u16 = 180;
+pub const NOT_EQUAL_FLOAT: u16 = 181;
+pub const GREATER_GREATER_GREATER_INT: u16 = 196;
+pub const COMPLEMENT_EQUAL_FLOAT: u16 = 210;
+pub const EQUAL_EQUAL_BOOL: u16 = 242;
+pub const NOT_EQUAL_BOOL: u16 = 243;
+pub const PERCENT_INT: u16 = 253;
+
+/// Evaluates a call to the pure native function with the given opcode.
+///
+/// Returns `None` if the opcode is not known to be pure, the arguments do not match the function's
+/// signature, or evaluating the function would result in a runtime error (such as division
+/// by zero.)
+pub fn eval_pure(opcode: u16, arguments: &[Constant]) -> Option<Constant> {
+    use Constant::*;
+
+    Some(match (opcode, arguments) {
+        (NOT_PRE_BOOL, [Bool(a)]) => Bool(!a),
+        (AND_AND_BOOL, [Bool(a), Bool(b)]) => Bool(*a && *b),
+        (XOR_XOR_BOOL, [Bool(a), Bool(b)]) => Bool(a != b),
+        (OR_OR_BOOL, [Bool(a), Bool(b)]) => Bool(*a || *b),
+        (EQUAL_EQUAL_BOOL, [Bool(a), Bool(b)]) => Bool(a == b),
+        (NOT_EQUAL_BOOL, [Bool(a), Bool(b)]) => Bool(a != b),
+
+        (COMPLEMENT_PRE_INT, [Int(a)]) => Int(!a),
+        (SUBTRACT_PRE_INT, [Int(a)]) => Int(a.wrapping_neg()),
+        (MULTIPLY_INT, [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        (DIVIDE_INT, [Int(a), Int(b)]) => Int(a.checked_div(*b)?),
+        (PERCENT_INT, [Int(a), Int(b)]) => Int(a.checked_rem(*b)?),
+        (ADD_INT, [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        (SUBTRACT_INT, [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        (LESS_LESS_INT, [Int(a), Int(b)]) => Int(a.wrapping_shl(*b as u32)),
+        (GREATER_GREATER_INT, [Int(a), Int(b)]) => Int(a.wrapping_shr(*b as u32)),
+        (GREATER_GREATER_GREATER_INT, [Int(a), Int(b)]) => {
+            Int((*a as u32).wrapping_shr(*b as u32) as i32)
+        }
+        (LESS_INT, [Int(a), Int(b)]) => Bool(a < b),
+        (GREATER_INT, [Int(a), Int(b)]) => Bool(a > b),
+        (LESS_EQUAL_INT, [Int(a), Int(b)]) => Bool(a <= b),
+        (GREATER_EQUAL_INT, [Int(a), Int(b)]) => Bool(a >= b),
+        (EQUAL_EQUAL_INT, [Int(a), Int(b)]) => Bool(a == b),
+        (NOT_EQUAL_INT, [Int(a), Int(b)]) => Bool(a != b),
+        (AND_INT, [Int(a), Int(b)]) => Int(a & b),
+        (XOR_INT, [Int(a), Int(b)]) => Int(a ^ b),
+        (OR_INT, [Int(a), Int(b)]) => Int(a | b),
+
+        (SUBTRACT_PRE_FLOAT, [Float(a)]) => Float(-a),
+        (MULTIPLY_MULTIPLY_FLOAT, [Float(a), Float(b)]) => Float(a.powf(*b)),
+        (MULTIPLY_FLOAT, [Float(a), Float(b)]) => Float(a * b),
+        // NOTE: The VM reports division by zero as a runtime warning, so we leave it up to the VM
+        // to do that.
+        (DIVIDE_FLOAT, [Float(a), Float(b)]) if *b != 0.0 => Float(a / b),
+        (PERCENT_FLOAT, [Float(a), Float(b)]) if *b != 0.0 => Float(a % b),
+        (ADD_FLOAT, [Float(a), Float(b)]) => Float(a + b),
+        (SUBTRACT_FLOAT, [Float(a), Float(b)]) => Float(a - b),
+        (LESS_FLOAT, [Float(a), Float(b)]) => Bool(a < b),
+        (GREATER_FLOAT, [Float(a), Float(b)]) => Bool(a > b),
+        (LESS_EQUAL_FLOAT, [Float(a), Float(b)]) => Bool(a <= b),
+        (GREATER_EQUAL_FLOAT, [Float(a), Float(b)]) => Bool(a >= b),
+        (EQUAL_EQUAL_FLOAT, [Float(a), Float(b)]) => Bool(a == b),
+        (NOT_EQUAL_FLOAT, [Float(a), Float(b)]) => Bool(a != b),
+        (COMPLEMENT_EQUAL_FLOAT, [Float(a), Float(b)]) => Bool((a - b).abs() < 0.0001),
+
+        (CONCAT_STRING, [String(a), String(b)]) => String(format!("{a}{b}")),
+        (AT_STRING, [String(a), String(b)]) => String(format!("{a} {b}")),
+
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Constant::*;
+
+    #[test]
+    fn bool_operators() {
+        assert_eq!(eval_pure(NOT_PRE_BOOL, &[Bool(true)]), Some(Bool(false)));
+        assert_eq!(
+            eval_pure(AND_AND_BOOL, &[Bool(true), Bool(false)]),
+            Some(Bool(false))
+        );
+        assert_eq!(
+            eval_pure(XOR_XOR_BOOL, &[Bool(true), Bool(false)]),
+            Some(Bool(true))
+        );
+        assert_eq!(
+            eval_pure(OR_OR_BOOL, &[Bool(false), Bool(false)]),
+            Some(Bool(false))
+        );
+    }
+
+    #[test]
+    fn int_arithmetic_wraps() {
+        assert_eq!(eval_pure(ADD_INT, &[Int(1), Int(2)]), Some(Int(3)));
+        assert_eq!(
+            eval_pure(ADD_INT, &[Int(i32::MAX), Int(1)]),
+            Some(Int(i32::MIN))
+        );
+        assert_eq!(
+            eval_pure(SUBTRACT_PRE_INT, &[Int(i32::MIN)]),
+            Some(Int(i32::MIN))
+        );
+        assert_eq!(eval_pure(COMPLEMENT_PRE_INT, &[Int(0)]), Some(Int(-1)));
+        assert_eq!(eval_pure(LESS_LESS_INT, &[Int(1), Int(33)]), Some(Int(2)));
+    }
+
+    #[test]
+    fn int_shifts() {
+        assert_eq!(
+            eval_pure(GREATER_GREATER_INT, &[Int(-8), Int(1)]),
+            Some(Int(-4))
+        );
+        assert_eq!(
+            eval_pure(GREATER_GREATER_GREATER_INT, &[Int(-8), Int(28)]),
+            Some(Int(0xF))
+        );
+    }
+
+    #[test]
+    fn int_division_by_zero_is_not_evaluated() {
+        assert_eq!(eval_pure(DIVIDE_INT, &[Int(7), Int(2)]), Some(Int(3)));
+        assert_eq!(eval_pure(PERCENT_INT, &[Int(7), Int(2)]), Some(Int(1)));
+        assert_eq!(eval_pure(DIVIDE_INT, &[Int(1), Int(0)]), None);
+        assert_eq!(eval_pure(PERCENT_INT, &[Int(1), Int(0)]), None);
+        assert_eq!(eval_pure(DIVIDE_INT, &[Int(i32::MIN), Int(-1)]), None);
+    }
+
+    #[test]
+    fn float_operators() {
+        assert_eq!(
+            eval_pure(ADD_FLOAT, &[Float(0.5), Float(0.25)]),
+            Some(Float(0.75))
+        );
+        assert_eq!(
+            eval_pure(MULTIPLY_MULTIPLY_FLOAT, &[Float(2.0), Float(3.0)]),
+            Some(Float(8.0))
+        );
+        assert_eq!(
+            eval_pure(COMPLEMENT_EQUAL_FLOAT, &[Float(1.0), Float(1.00001)]),
+            Some(Bool(true))
+        );
+        assert_eq!(eval_pure(DIVIDE_FLOAT, &[Float(1.0), Float(0.0)]), None);
+        assert_eq!(eval_pure(PERCENT_FLOAT, &[Float(1.0), Float(0.0)]), None);
+    }
+
+    #[test]
+    fn string_concatenation() {
+        assert_eq!(
+            eval_pure(CONCAT_STRING, &[String("a".into()), String("b".into())]),
+            Some(String("ab".into()))
+        );
+        assert_eq!(
+            eval_pure(AT_STRING, &[String("a".into()), String("b".into())]),
+            Some(String("a b".into()))
+        );
+    }
+
+    #[test]
+    fn mismatched_calls_are_not_evaluated() {
+        // Unknown opcode.
+        assert_eq!(eval_pure(0, &[Int(1), Int(2)]), None);
+        // Wrong argument types.
+        assert_eq!(eval_pure(ADD_INT, &[Int(1), Float(2.0)]), None);
+        // Wrong number of arguments.
+        assert_eq!(eval_pure(ADD_INT, &[Int(1)]), None);
+    }
+}
@@ -0,0 +1,445 @@
+//! IR optimization passes.
+
+mod dead_registers;
+mod fold;
+mod jump_threading;
+mod redundant_in;
+mod unreachable_blocks;
+
+use tracing::trace;
+
+use crate::Environment;
+
+use super::Ir;
+
+pub use dead_registers::*;
+pub use fold::*;
+pub use jump_threading::*;
+pub use redundant_in::*;
+pub use unreachable_blocks::*;
+
+/// How hard the compiler should try to optimize the IR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptimizationLevel {
+    /// Leave the IR exactly as it was produced by analysis.
+    #[default]
+    None,
+    /// Only clean up the redundancies that analysis leaves behind, without changing what
+    /// the code computes.
+    Basic,
+    /// Run all available passes.
+    Full,
+}
+
+/// A single transformation over a function's IR.
+pub trait Pass {
+    /// Name of the pass, for tracing purposes.
+    fn name(&self) -> &'static str;
+
+    /// Runs the pass over the given IR. Returns whether the IR was changed.
+    fn run(&self, env: &Environment, ir: &mut Ir) -> bool;
+}
+
+/// Runs a sequence of [`Pass`]es over IR until none of them are able to make any more changes.
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassManager {
+    /// Upper bound on how many times the whole pipeline will run over a single chunk of IR.
+    /// This is a safety net in case two passes keep undoing each other's changes.
+    const MAX_ITERATIONS: usize = 16;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pass manager with the standard pipeline for the given optimization level.
+    pub fn for_level(level: OptimizationLevel) -> Self {
+        let mut passes = Self::new();
+        if level >= OptimizationLevel::Full {
+            passes.add(ConstantFolding).add(JumpThreading);
+        }
+        if level >= OptimizationLevel::Basic {
+            passes
+                .add(RedundantIn)
+                .add(DeadRegisters)
+                .add(UnreachableBlocks);
+        }
+        passes
+    }
+
+    pub fn add(&mut self, pass: impl Pass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Runs all passes over the IR.
+    pub fn run(&self, env: &Environment, ir: &mut Ir) {
+        for iteration in 0..Self::MAX_ITERATIONS {
+            let mut changed = false;
+            for pass in &self.passes {
+                if pass.run(env, ir) {
+                    trace!(pass = pass.name(), iteration, "pass changed the IR");
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{
+        ir::{
+            interpret::Constant,
+            natives,
+            testing::{block, opcode_function, register, return_void, sink},
+            BasicBlockId, Ir, NodeKind, RegisterId, Sink, Terminator, Value,
+        },
+        Environment, TypeId,
+    };
+
+    use super::*;
+
+    fn constant(ir: &Ir, register_id: RegisterId) -> Option<Constant> {
+        Constant::from_value(&ir.register(register_id).value)
+    }
+
+    fn goto_target(ir: &Ir, basic_block_id: BasicBlockId) -> BasicBlockId {
+        match ir.basic_block(basic_block_id).terminator {
+            Terminator::Goto(target) => target,
+            _ => panic!("block {basic_block_id:?} does not end with a goto"),
+        }
+    }
+
+    #[test]
+    fn constant_folding_folds_nested_operators() {
+        let mut env = Environment::new();
+        let add = opcode_function(&mut env, natives::ADD_INT, TypeId::INT);
+        let multiply = opcode_function(&mut env, natives::MULTIPLY_INT, TypeId::INT);
+
+        // (1 + 2) * 3
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let one = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        let two = register(&mut ir, entry, TypeId::INT, Value::Int(2));
+        let sum = register(
+            &mut ir,
+            entry,
+            TypeId::INT,
+            Value::CallFinal {
+                function: add,
+                arguments: vec![one, two],
+            },
+        );
+        let three = register(&mut ir, entry, TypeId::INT, Value::Int(3));
+        let product = register(
+            &mut ir,
+            entry,
+            TypeId::INT,
+            Value::CallFinal {
+                function: multiply,
+                arguments: vec![sum, three],
+            },
+        );
+        sink(&mut ir, entry, Sink::Discard(product));
+        return_void(&mut ir, entry);
+
+        assert!(ConstantFolding.run(&env, &mut ir));
+        assert_eq!(constant(&ir, sum), Some(Constant::Int(3)));
+        assert_eq!(constant(&ir, product), Some(Constant::Int(9)));
+        assert!(!ConstantFolding.run(&env, &mut ir));
+    }
+
+    #[test]
+    fn constant_folding_leaves_runtime_errors_alone() {
+        let mut env = Environment::new();
+        let divide = opcode_function(&mut env, natives::DIVIDE_INT, TypeId::INT);
+
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let one = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        let zero = register(&mut ir, entry, TypeId::INT, Value::Int(0));
+        let quotient = register(
+            &mut ir,
+            entry,
+            TypeId::INT,
+            Value::CallFinal {
+                function: divide,
+                arguments: vec![one, zero],
+            },
+        );
+        sink(&mut ir, entry, Sink::Discard(quotient));
+        return_void(&mut ir, entry);
+
+        assert!(!ConstantFolding.run(&env, &mut ir));
+        assert!(matches!(
+            ir.register(quotient).value,
+            Value::CallFinal { .. }
+        ));
+    }
+
+    #[test]
+    fn jump_threading_resolves_constant_conditions() {
+        let env = Environment::new();
+
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let if_true = block(&mut ir, "if_true");
+        let if_false = block(&mut ir, "if_false");
+        let condition = register(&mut ir, entry, TypeId::BOOL, Value::Bool(false));
+        ir.basic_block_mut(entry).terminator = Terminator::GotoIf {
+            condition,
+            if_true,
+            if_false,
+        };
+        return_void(&mut ir, if_true);
+        return_void(&mut ir, if_false);
+
+        assert!(JumpThreading.run(&env, &mut ir));
+        assert_eq!(goto_target(&ir, entry), if_false);
+    }
+
+    #[test]
+    fn jump_threading_skips_empty_blocks() {
+        let env = Environment::new();
+
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let first = block(&mut ir, "first");
+        let second = block(&mut ir, "second");
+        let end = block(&mut ir, "end");
+        ir.basic_block_mut(entry).terminator = Terminator::Goto(first);
+        ir.basic_block_mut(first).terminator = Terminator::Goto(second);
+        ir.basic_block_mut(second).terminator = Terminator::Goto(end);
+        return_void(&mut ir, end);
+
+        assert!(JumpThreading.run(&env, &mut ir));
+        assert_eq!(goto_target(&ir, entry), end);
+        assert!(!JumpThreading.run(&env, &mut ir));
+    }
+
+    #[test]
+    fn jump_threading_terminates_on_empty_loops() {
+        let env = Environment::new();
+
+        // while (true) {}
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let head = block(&mut ir, "head");
+        let body = block(&mut ir, "body");
+        ir.basic_block_mut(entry).terminator = Terminator::Goto(head);
+        ir.basic_block_mut(head).terminator = Terminator::Goto(body);
+        ir.basic_block_mut(body).terminator = Terminator::Goto(head);
+
+        JumpThreading.run(&env, &mut ir);
+        // No matter where the chain ends up, it must stay inside the loop.
+        assert!([head, body].contains(&goto_target(&ir, entry)));
+    }
+
+    #[test]
+    fn redundant_in_replaces_uses_of_in_self() {
+        let env = Environment::new();
+
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let this = register(&mut ir, entry, TypeId::OBJECT, Value::This);
+        let action = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        let in_this = register(
+            &mut ir,
+            entry,
+            TypeId::INT,
+            Value::In {
+                context: this,
+                action,
+            },
+        );
+        sink(&mut ir, entry, Sink::Discard(in_this));
+        ir.basic_block_mut(entry).terminator = Terminator::Return(in_this);
+
+        assert!(RedundantIn.run(&env, &mut ir));
+        let flow = &ir.basic_block(entry).flow;
+        assert!(matches!(
+            ir.node(*flow.last().unwrap()).kind,
+            NodeKind::Sink(Sink::Discard(register_id)) if register_id == action
+        ));
+        assert!(matches!(
+            ir.basic_block(entry).terminator,
+            Terminator::Return(register_id) if register_id == action
+        ));
+        assert!(!RedundantIn.run(&env, &mut ir));
+    }
+
+    #[test]
+    fn redundant_in_keeps_other_contexts() {
+        let env = Environment::new();
+
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let none = register(&mut ir, entry, TypeId::OBJECT, Value::None);
+        let action = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        let in_none = register(
+            &mut ir,
+            entry,
+            TypeId::INT,
+            Value::In {
+                context: none,
+                action,
+            },
+        );
+        sink(&mut ir, entry, Sink::Discard(in_none));
+        return_void(&mut ir, entry);
+
+        assert!(!RedundantIn.run(&env, &mut ir));
+    }
+
+    #[test]
+    fn dead_registers_removes_unused_registers() {
+        let mut env = Environment::new();
+        let add = opcode_function(&mut env, natives::ADD_INT, TypeId::INT);
+
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let unused = register(&mut ir, entry, TypeId::INT, Value::Int(0));
+        let one = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        let two = register(&mut ir, entry, TypeId::INT, Value::Int(2));
+        let sum = register(
+            &mut ir,
+            entry,
+            TypeId::INT,
+            Value::CallFinal {
+                function: add,
+                arguments: vec![one, two],
+            },
+        );
+        sink(&mut ir, entry, Sink::Discard(sum));
+        let result = register(&mut ir, entry, TypeId::INT, Value::Int(3));
+        ir.basic_block_mut(entry).terminator = Terminator::Return(result);
+
+        assert!(DeadRegisters.run(&env, &mut ir));
+        let remaining: Vec<_> = ir
+            .basic_block(entry)
+            .flow
+            .iter()
+            .filter_map(|&node_id| ir.node_register_id(node_id))
+            .collect();
+        assert_eq!(remaining, [one, two, sum, result]);
+        assert!(!remaining.contains(&unused));
+        // The sink itself must stay.
+        assert_eq!(ir.basic_block(entry).flow.len(), 5);
+        assert!(!DeadRegisters.run(&env, &mut ir));
+    }
+
+    #[test]
+    fn unreachable_blocks_removes_and_renumbers_blocks() {
+        let env = Environment::new();
+
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let dead = block(&mut ir, "dead");
+        let end = block(&mut ir, "end");
+        ir.basic_block_mut(entry).terminator = Terminator::Goto(end);
+        ir.basic_block_mut(dead).terminator = Terminator::Goto(end);
+        return_void(&mut ir, end);
+
+        assert!(UnreachableBlocks.run(&env, &mut ir));
+        assert_eq!(ir.basic_blocks.len(), 2);
+        assert_eq!(ir.basic_block(BasicBlockId::ENTRY).label, "entry");
+        let end = goto_target(&ir, BasicBlockId::ENTRY);
+        assert_eq!(ir.basic_block(end).label, "end");
+        assert!(!UnreachableBlocks.run(&env, &mut ir));
+    }
+
+    #[test]
+    fn full_pipeline_reaches_fixpoint() {
+        let mut env = Environment::new();
+        let less = opcode_function(&mut env, natives::LESS_INT, TypeId::BOOL);
+
+        // if (1 < 2) { return; } else { return; }
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let if_true = block(&mut ir, "if_true");
+        let if_false = block(&mut ir, "if_false");
+        let one = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        let two = register(&mut ir, entry, TypeId::INT, Value::Int(2));
+        let condition = register(
+            &mut ir,
+            entry,
+            TypeId::BOOL,
+            Value::CallFinal {
+                function: less,
+                arguments: vec![one, two],
+            },
+        );
+        ir.basic_block_mut(entry).terminator = Terminator::GotoIf {
+            condition,
+            if_true,
+            if_false,
+        };
+        return_void(&mut ir, if_true);
+        return_void(&mut ir, if_false);
+
+        let passes = PassManager::for_level(OptimizationLevel::Full);
+        passes.run(&env, &mut ir);
+
+        assert_eq!(ir.basic_blocks.len(), 2);
+        assert!(ir.basic_block(BasicBlockId::ENTRY).flow.is_empty());
+        let target = goto_target(&ir, BasicBlockId::ENTRY);
+        assert_eq!(ir.basic_block(target).label, "if_true");
+        for pass in &passes.passes {
+            assert!(
+                !pass.run(&env, &mut ir),
+                "{} still changes the IR after the pipeline has finished",
+                pass.name()
+            );
+        }
+    }
+
+    struct AlwaysChanges(Rc<Cell<usize>>);
+
+    impl Pass for AlwaysChanges {
+        fn name(&self) -> &'static str {
+            "always_changes"
+        }
+
+        fn run(&self, _env: &Environment, _ir: &mut Ir) -> bool {
+            self.0.set(self.0.get() + 1);
+            true
+        }
+    }
+
+    #[test]
+    fn pass_manager_gives_up_after_max_iterations() {
+        let runs = Rc::new(Cell::new(0));
+        let mut passes = PassManager::new();
+        passes.add(AlwaysChanges(Rc::clone(&runs)));
+        passes.run(&Environment::new(), &mut Ir::new());
+        assert_eq!(runs.get(), PassManager::MAX_ITERATIONS);
+    }
+
+    #[test]
+    fn optimization_levels_are_cumulative() {
+        assert!(PassManager::for_level(OptimizationLevel::None).is_empty());
+        assert_eq!(
+            PassManager::for_level(OptimizationLevel::Basic)
+                .passes
+                .len(),
+            3
+        );
+        assert_eq!(
+            PassManager::for_level(OptimizationLevel::Full).passes.len(),
+            5
+        );
+    }
+}
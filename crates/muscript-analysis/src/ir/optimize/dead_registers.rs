@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+
+use crate::{
+    ir::{Ir, NodeKind, RegisterId},
+    Environment,
+};
+
+use super::Pass;
+
+/// Removes registers that are never read by any sink or terminator.
+///
+/// Since registers are only ever evaluated when a sink or terminator needs their value, removing
+/// unused ones is always safe, even if they would have side effects.
+pub struct DeadRegisters;
+
+impl Pass for DeadRegisters {
+    fn name(&self) -> &'static str {
+        "dead_registers"
+    }
+
+    fn run(&self, _env: &Environment, ir: &mut Ir) -> bool {
+        let mut live = HashSet::new();
+        let mut worklist: Vec<RegisterId> = vec![];
+        for basic_block in &ir.basic_blocks {
+            for &node_id in &basic_block.flow {
+                if let NodeKind::Sink(sink) = &ir.node(node_id).kind {
+                    sink.visit_registers(|register_id| worklist.push(register_id));
+                }
+            }
+            basic_block
+                .terminator
+                .visit_registers(|register_id| worklist.push(register_id));
+        }
+        while let Some(register_id) = worklist.pop() {
+            if live.insert(register_id) {
+                ir.register(register_id)
+                    .value
+                    .visit_registers(|register_id| worklist.push(register_id));
+            }
+        }
+
+        let mut changed = false;
+        for basic_block_id in ir.basic_block_ids() {
+            let mut flow = std::mem::take(&mut ir.basic_block_mut(basic_block_id).flow);
+            let len_before = flow.len();
+            flow.retain(|&node_id| match ir.node_register_id(node_id) {
+                Some(register_id) => live.contains(&register_id),
+                None => true,
+            });
+            changed |= flow.len() != len_before;
+            ir.basic_block_mut(basic_block_id).flow = flow;
+        }
+        changed
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    function::FunctionImplementation,
+    ir::{interpret::Constant, natives, Ir, NodeKind, Value},
+    Environment,
+};
+
+use super::Pass;
+
+/// Replaces calls to pure operators whose arguments are all constants with the result of the call.
+pub struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "constant_folding"
+    }
+
+    fn run(&self, env: &Environment, ir: &mut Ir) -> bool {
+        let mut changed = false;
+        for basic_block_id in ir.basic_block_ids() {
+            // Registers can only refer to registers that come before them, so a single forward
+            // sweep is enough to fold whole expression trees.
+            for i in 0..ir.basic_block(basic_block_id).flow.len() {
+                let node_id = ir.basic_block(basic_block_id).flow[i];
+                let NodeKind::Register(register) = &ir.node(node_id).kind else {
+                    continue;
+                };
+                let Value::CallFinal {
+                    function,
+                    arguments,
+                } = &register.value
+                else {
+                    continue;
+                };
+                let FunctionImplementation::Opcode(opcode) =
+                    env.get_function(*function).implementation
+                else {
+                    continue;
+                };
+                let Some(arguments) = arguments
+                    .iter()
+                    .map(|&argument| Constant::from_value(&ir.register(argument).value))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    continue;
+                };
+                if let Some(result) = natives::eval_pure(opcode, &arguments) {
+                    if let NodeKind::Register(register) = &mut ir.node_mut(node_id).kind {
+                        register.value = result.to_value();
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{
+    ir::{BasicBlockId, Ir, Terminator, Value},
+    Environment,
+};
+
+use super::Pass;
+
+/// Shortens chains of jumps through empty blocks, and turns conditional jumps on constant
+/// conditions into unconditional ones.
+///
+/// A block is considered part of a chain if it does nothing but `goto` another block.
+pub struct JumpThreading;
+
+impl Pass for JumpThreading {
+    fn name(&self) -> &'static str {
+        "jump_threading"
+    }
+
+    fn run(&self, _env: &Environment, ir: &mut Ir) -> bool {
+        let mut changed = false;
+        for basic_block_id in ir.basic_block_ids() {
+            if let Terminator::GotoIf {
+                condition,
+                if_true,
+                if_false,
+            } = ir.basic_block(basic_block_id).terminator
+            {
+                if let Value::Bool(condition) = ir.register(condition).value {
+                    let target = if condition { if_true } else { if_false };
+                    ir.basic_block_mut(basic_block_id).terminator = Terminator::Goto(target);
+                    changed = true;
+                }
+            }
+
+            let mut terminator = ir.basic_block(basic_block_id).terminator.clone();
+            terminator.visit_successors_mut(|target| {
+                let threaded = thread(ir, *target);
+                if threaded != *target {
+                    *target = threaded;
+                    changed = true;
+                }
+            });
+            ir.basic_block_mut(basic_block_id).terminator = terminator;
+        }
+        changed
+    }
+}
+
+/// Follows the chain of trivial `goto`s starting at `target` and returns the block the chain
+/// ends at.
+fn thread(ir: &Ir, mut target: BasicBlockId) -> BasicBlockId {
+    // Chains can loop back on themselves (as is the case with eg. `while (true) {}`), so we need
+    // to cap the number of steps taken.
+    for _ in 0..ir.basic_blocks.len() {
+        let basic_block = ir.basic_block(target);
+        match basic_block.terminator {
+            Terminator::Goto(next) if basic_block.flow.is_empty() && next != target => {
+                target = next
+            }
+            _ => break,
+        }
+    }
+    target
+}
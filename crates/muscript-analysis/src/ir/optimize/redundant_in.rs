@@ -0,0 +1,39 @@
+use crate::{
+    ir::{Ir, Value},
+    Environment,
+};
+
+use super::Pass;
+
+/// Removes `in` values whose context is `self`, since they are equivalent to just performing
+/// the action.
+pub struct RedundantIn;
+
+impl Pass for RedundantIn {
+    fn name(&self) -> &'static str {
+        "redundant_in"
+    }
+
+    fn run(&self, _env: &Environment, ir: &mut Ir) -> bool {
+        let mut replacements = vec![];
+        for basic_block in &ir.basic_blocks {
+            for &node_id in &basic_block.flow {
+                let Some(register_id) = ir.node_register_id(node_id) else {
+                    continue;
+                };
+                if let &Value::In { context, action } = &ir.register(register_id).value {
+                    if let Value::This = ir.register(context).value {
+                        replacements.push((register_id, action));
+                    }
+                }
+            }
+        }
+
+        // The `in` registers themselves are left for dead register elimination to clean up.
+        let mut changed = false;
+        for (register_id, action) in replacements {
+            changed |= ir.replace_register_uses(register_id, action);
+        }
+        changed
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    ir::{BasicBlockId, Ir},
+    Environment,
+};
+
+use super::Pass;
+
+/// Removes basic blocks that cannot be reached from the entry point.
+pub struct UnreachableBlocks;
+
+impl Pass for UnreachableBlocks {
+    fn name(&self) -> &'static str {
+        "unreachable_blocks"
+    }
+
+    fn run(&self, _env: &Environment, ir: &mut Ir) -> bool {
+        if ir.basic_blocks.is_empty() {
+            return false;
+        }
+
+        let mut reachable = vec![false; ir.basic_blocks.len()];
+        let mut worklist = vec![BasicBlockId::ENTRY];
+        while let Some(basic_block_id) = worklist.pop() {
+            let index = basic_block_id.0 as usize;
+            if !reachable[index] {
+                reachable[index] = true;
+                ir.basic_block(basic_block_id)
+                    .terminator
+                    .visit_successors(|successor| worklist.push(successor));
+            }
+        }
+
+        if reachable.iter().all(|&x| x) {
+            return false;
+        }
+
+        // Since the entry block is always reachable, it keeps its ID.
+        let mut new_ids = Vec::with_capacity(reachable.len());
+        let mut next_id = 0;
+        for &is_reachable in &reachable {
+            new_ids.push(BasicBlockId(next_id));
+            if is_reachable {
+                next_id += 1;
+            }
+        }
+
+        let basic_blocks = std::mem::take(&mut ir.basic_blocks);
+        ir.basic_blocks = basic_blocks
+            .into_iter()
+            .zip(&reachable)
+            .filter(|(_, &is_reachable)| is_reachable)
+            .map(|(mut basic_block, _)| {
+                basic_block
+                    .terminator
+                    .visit_successors_mut(|successor| *successor = new_ids[successor.0 as usize]);
+                basic_block
+            })
+            .collect();
+
+        true
+    }
+}
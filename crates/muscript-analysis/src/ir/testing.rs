@@ -0,0 +1,51 @@
+//! Helpers for building IR by hand in tests.
+
+use muscript_lexer::token::TokenSpan;
+use muscript_syntax::cst::{DocComment, ItemName};
+
+use crate::{
+    function::{Function, FunctionFlags, FunctionImplementation, FunctionKind},
+    ClassId, Environment, FunctionId, TypeId,
+};
+
+use super::{BasicBlock, BasicBlockId, Ir, RegisterId, Sink, Terminator, Value};
+
+/// Registers a final operator implemented by the given opcode, as declared in `Object`.
+pub fn opcode_function(env: &mut Environment, opcode: u16, return_ty: TypeId) -> FunctionId {
+    env.register_function(Function {
+        class_id: ClassId::OBJECT,
+        mangled_name: format!("Opcode{opcode}"),
+        name: ItemName {
+            span: TokenSpan::Empty,
+        },
+        return_ty,
+        params: vec![],
+        flags: FunctionFlags::FINAL,
+        kind: FunctionKind::InfixOperator,
+        implementation: FunctionImplementation::Opcode(opcode),
+        doc: DocComment::default(),
+    })
+}
+
+pub fn block(ir: &mut Ir, label: &'static str) -> BasicBlockId {
+    ir.create_basic_block(BasicBlock::new(label, TokenSpan::Empty))
+}
+
+/// Creates a register and appends it to the flow of the given block.
+pub fn register(ir: &mut Ir, block: BasicBlockId, ty: TypeId, value: Value) -> RegisterId {
+    let register_id = ir.create_register(TokenSpan::Empty, "r", ty, value);
+    ir.basic_block_mut(block).flow.push(register_id.into());
+    register_id
+}
+
+/// Creates a sink and appends it to the flow of the given block.
+pub fn sink(ir: &mut Ir, block: BasicBlockId, sink: Sink) {
+    let node_id = ir.create_sink(TokenSpan::Empty, sink);
+    ir.basic_block_mut(block).flow.push(node_id);
+}
+
+/// Ends the given block with a `return` of nothing.
+pub fn return_void(ir: &mut Ir, block: BasicBlockId) {
+    let void = register(ir, block, TypeId::VOID, Value::Void);
+    ir.basic_block_mut(block).terminator = Terminator::Return(void);
+}
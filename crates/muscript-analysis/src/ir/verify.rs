@@ -0,0 +1,472 @@
+//! IR verifier.
+//!
+//! Analysis and optimization passes are expected to always produce well-formed IR. The verifier
+//! checks that this is actually the case, and reports any violations as internal compiler errors.
+
+use std::collections::HashMap;
+
+use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label};
+use muscript_lexer::token::{Token, TokenSpan};
+
+use crate::{diagnostics::notes, Environment, TypeId};
+
+use super::{BasicBlockId, Ir, NodeId, NodeKind, RegisterId, Sink, Terminator};
+
+/// Checks that the IR is well-formed. Emits a bug diagnostic for every violation found and returns
+/// whether the IR passed verification.
+///
+/// The following properties are checked:
+/// - every register is defined before it's used, in a basic block that dominates the use
+/// - every reachable basic block ends with a terminator other than [`Terminator::Unreachable`]
+/// - [`Sink::Store`]s only ever store into places (see [`Ir::is_place`])
+/// - [`Terminator::GotoIf`] conditions are `Bool`s
+pub fn verify(env: &Environment, ir: &Ir, diagnostics: &mut dyn DiagnosticSink<Token>) -> bool {
+    let mut verifier = Verifier {
+        env,
+        ir,
+        diagnostics,
+        ok: true,
+    };
+    verifier.verify();
+    verifier.ok
+}
+
+struct Verifier<'a> {
+    env: &'a Environment,
+    ir: &'a Ir,
+    diagnostics: &'a mut dyn DiagnosticSink<Token>,
+    ok: bool,
+}
+
+/// Where a register is defined: the basic block, and the index of the register within
+/// the block's flow.
+type Definition = (BasicBlockId, usize);
+
+impl<'a> Verifier<'a> {
+    fn emit(&mut self, span: TokenSpan, message: String) {
+        self.ok = false;
+        self.diagnostics.emit(
+            Diagnostic::bug(format!("malformed IR: {message}"))
+                .with_label(Label::primary(&span, ""))
+                .with_note(notes::BUG),
+        );
+    }
+
+    fn verify(&mut self) {
+        if self.ir.basic_blocks.is_empty() {
+            self.emit(TokenSpan::Empty, "IR does not have an entry block".into());
+            return;
+        }
+        if !self.verify_node_references() {
+            // The rest of the checks assume all IDs are valid, so we can't continue.
+            return;
+        }
+
+        let reachable = self.reachable_blocks();
+        let dominators = self.dominators(&reachable);
+
+        let mut definitions = HashMap::new();
+        for basic_block_id in self.ir.basic_block_ids() {
+            if !reachable[basic_block_id.0 as usize] {
+                continue;
+            }
+            for (i, &node_id) in self.ir.basic_block(basic_block_id).flow.iter().enumerate() {
+                if let Some(register_id) = self.ir.node_register_id(node_id) {
+                    definitions.insert(register_id, (basic_block_id, i));
+                }
+            }
+        }
+
+        for basic_block_id in self.ir.basic_block_ids() {
+            if !reachable[basic_block_id.0 as usize] {
+                continue;
+            }
+            let basic_block = self.ir.basic_block(basic_block_id);
+
+            for (i, &node_id) in basic_block.flow.iter().enumerate() {
+                let node = self.ir.node(node_id);
+                let mut uses = vec![];
+                node.kind
+                    .visit_registers(|register_id| uses.push(register_id));
+                for register_id in uses {
+                    self.verify_use(
+                        &definitions,
+                        &dominators,
+                        register_id,
+                        (basic_block_id, i),
+                        node.span,
+                    );
+                }
+
+                if let NodeKind::Sink(Sink::Store(lvalue, _)) = node.kind {
+                    if !self.ir.is_place(lvalue) {
+                        self.emit(
+                            node.span,
+                            format!("`{}` is stored into, but is not a place", self.name(lvalue)),
+                        );
+                    }
+                }
+            }
+
+            let mut uses = vec![];
+            basic_block
+                .terminator
+                .visit_registers(|register_id| uses.push(register_id));
+            for register_id in uses {
+                self.verify_use(
+                    &definitions,
+                    &dominators,
+                    register_id,
+                    (basic_block_id, basic_block.flow.len()),
+                    basic_block.span,
+                );
+            }
+
+            match basic_block.terminator {
+                Terminator::Unreachable => self.emit(
+                    basic_block.span,
+                    format!(
+                        "basic block `{}_{}` is reachable, but is terminated with `unreachable`",
+                        basic_block.label, basic_block_id.0
+                    ),
+                ),
+                Terminator::GotoIf { condition, .. } => {
+                    let ty = self.ir.register(condition).ty;
+                    if ty != TypeId::BOOL && ty != TypeId::ERROR {
+                        self.emit(
+                            basic_block.span,
+                            format!(
+                                "condition `{}` must be a `Bool`, but it is `{}`",
+                                self.name(condition),
+                                self.env.type_name(ty)
+                            ),
+                        );
+                    }
+                }
+                Terminator::Goto(_) | Terminator::Return(_) => (),
+            }
+        }
+    }
+
+    /// Checks that all register and basic block IDs point to what they're supposed to point to.
+    fn verify_node_references(&mut self) -> bool {
+        let mut ok = true;
+        for basic_block_id in self.ir.basic_block_ids() {
+            let basic_block = self.ir.basic_block(basic_block_id);
+            for &node_id in &basic_block.flow {
+                if node_id.0 as usize >= self.ir.nodes.len() {
+                    self.emit(
+                        basic_block.span,
+                        format!("node {} does not exist", node_id.0),
+                    );
+                    ok = false;
+                    continue;
+                }
+                let node = self.ir.node(node_id);
+                let mut uses = vec![];
+                node.kind
+                    .visit_registers(|register_id| uses.push(register_id));
+                for register_id in uses {
+                    ok &= self.verify_register_reference(register_id, node.span);
+                }
+            }
+
+            let mut uses = vec![];
+            basic_block
+                .terminator
+                .visit_registers(|register_id| uses.push(register_id));
+            for register_id in uses {
+                ok &= self.verify_register_reference(register_id, basic_block.span);
+            }
+
+            let mut successors = vec![];
+            basic_block
+                .terminator
+                .visit_successors(|successor| successors.push(successor));
+            for successor in successors {
+                if successor.0 as usize >= self.ir.basic_blocks.len() {
+                    self.emit(
+                        basic_block.span,
+                        format!("jump to basic block {}, which does not exist", successor.0),
+                    );
+                    ok = false;
+                }
+            }
+        }
+        ok
+    }
+
+    fn verify_register_reference(&mut self, register_id: RegisterId, span: TokenSpan) -> bool {
+        let node_id = NodeId::from(register_id);
+        if node_id.0 as usize >= self.ir.nodes.len() {
+            self.emit(span, format!("register {} does not exist", node_id.0));
+            false
+        } else if self.ir.node_register_id(node_id).is_none() {
+            self.emit(
+                span,
+                format!("node {} is used as a register, but is a sink", node_id.0),
+            );
+            false
+        } else {
+            true
+        }
+    }
+
+    fn verify_use(
+        &mut self,
+        definitions: &HashMap<RegisterId, Definition>,
+        dominators: &[Vec<bool>],
+        register_id: RegisterId,
+        (use_block, use_index): Definition,
+        span: TokenSpan,
+    ) {
+        match definitions.get(&register_id) {
+            None => self.emit(
+                span,
+                format!(
+                    "`{}` is used, but not defined in any reachable basic block",
+                    self.name(register_id)
+                ),
+            ),
+            Some(&(def_block, def_index)) => {
+                let defined_before_use = if def_block == use_block {
+                    def_index < use_index
+                } else {
+                    dominators[use_block.0 as usize][def_block.0 as usize]
+                };
+                if !defined_before_use {
+                    self.emit(
+                        span,
+                        format!(
+                            "`{}` is used before it is defined (it is defined in `{}_{}`, which does not dominate the use)",
+                            self.name(register_id),
+                            self.ir.basic_block(def_block).label,
+                            def_block.0,
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn reachable_blocks(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.ir.basic_blocks.len()];
+        let mut worklist = vec![BasicBlockId::ENTRY];
+        while let Some(basic_block_id) = worklist.pop() {
+            let index = basic_block_id.0 as usize;
+            if !reachable[index] {
+                reachable[index] = true;
+                self.ir
+                    .basic_block(basic_block_id)
+                    .terminator
+                    .visit_successors(|successor| worklist.push(successor));
+            }
+        }
+        reachable
+    }
+
+    /// Computes the set of dominators of each reachable block. `dominators[b][d]` is `true` if `d`
+    /// dominates `b`.
+    fn dominators(&self, reachable: &[bool]) -> Vec<Vec<bool>> {
+        let n = self.ir.basic_blocks.len();
+        let mut predecessors = vec![vec![]; n];
+        for basic_block_id in self.ir.basic_block_ids() {
+            if reachable[basic_block_id.0 as usize] {
+                self.ir
+                    .basic_block(basic_block_id)
+                    .terminator
+                    .visit_successors(|successor| {
+                        predecessors[successor.0 as usize].push(basic_block_id.0 as usize)
+                    });
+            }
+        }
+
+        let mut dominators = vec![vec![true; n]; n];
+        dominators[0] = vec![false; n];
+        dominators[0][0] = true;
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 1..n {
+                if !reachable[b] {
+                    continue;
+                }
+                let mut new = vec![true; n];
+                for &p in &predecessors[b] {
+                    for (d, is_dominator) in new.iter_mut().enumerate() {
+                        *is_dominator &= dominators[p][d];
+                    }
+                }
+                new[b] = true;
+                if new != dominators[b] {
+                    dominators[b] = new;
+                    changed = true;
+                }
+            }
+        }
+        dominators
+    }
+
+    fn name(&self, register_id: RegisterId) -> String {
+        format!("%{}_{}", self.ir.register(register_id).name, register_id.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use muscript_foundation::errors::{codes, Diagnostic};
+    use muscript_lexer::token::{Token, TokenSpan};
+
+    use crate::{
+        ir::{
+            testing::{block, register, return_void, sink},
+            Ir, Sink, Terminator, Value,
+        },
+        Environment, TypeId,
+    };
+
+    use super::verify;
+
+    /// Verifies the IR and returns the messages of all diagnostics, checking that each of them is
+    /// reported as a bug.
+    fn verify_messages(ir: &Ir) -> Vec<String> {
+        let env = Environment::new();
+        let mut diagnostics: Vec<Diagnostic<Token>> = vec![];
+        let ok = verify(&env, ir, &mut diagnostics);
+        assert_eq!(ok, diagnostics.is_empty());
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.code.as_deref(), Some(codes::M0000));
+                diagnostic.message
+            })
+            .collect()
+    }
+
+    fn assert_single_error(ir: &Ir, expected: &str) {
+        let messages = verify_messages(ir);
+        assert_eq!(messages.len(), 1, "{messages:#?}");
+        assert!(
+            messages[0].contains(expected),
+            "expected {expected:?} in {:?}",
+            messages[0]
+        );
+    }
+
+    #[test]
+    fn well_formed_ir_passes() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let if_true = block(&mut ir, "if_true");
+        let if_false = block(&mut ir, "if_false");
+        let condition = register(&mut ir, entry, TypeId::BOOL, Value::Bool(true));
+        ir.basic_block_mut(entry).terminator = Terminator::GotoIf {
+            condition,
+            if_true,
+            if_false,
+        };
+        // Uses of registers from a dominating block are fine.
+        sink(&mut ir, if_true, Sink::Discard(condition));
+        return_void(&mut ir, if_true);
+        return_void(&mut ir, if_false);
+
+        assert_eq!(verify_messages(&ir), Vec::<String>::new());
+    }
+
+    #[test]
+    fn use_in_non_dominated_block() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let if_true = block(&mut ir, "if_true");
+        let if_false = block(&mut ir, "if_false");
+        let condition = register(&mut ir, entry, TypeId::BOOL, Value::Bool(true));
+        ir.basic_block_mut(entry).terminator = Terminator::GotoIf {
+            condition,
+            if_true,
+            if_false,
+        };
+        let value = register(&mut ir, if_true, TypeId::INT, Value::Int(1));
+        return_void(&mut ir, if_true);
+        // `if_true` does not dominate `if_false`, so `value` may not be used there.
+        ir.basic_block_mut(if_false).terminator = Terminator::Return(value);
+
+        assert_single_error(&ir, "does not dominate the use");
+    }
+
+    #[test]
+    fn use_before_definition() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let value = ir.create_register(TokenSpan::Empty, "value", TypeId::INT, Value::Int(1));
+        sink(&mut ir, entry, Sink::Discard(value));
+        ir.basic_block_mut(entry).flow.push(value.into());
+        return_void(&mut ir, entry);
+
+        assert_single_error(&ir, "is used before it is defined");
+    }
+
+    #[test]
+    fn reachable_unreachable_terminator() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let next = block(&mut ir, "next");
+        ir.basic_block_mut(entry).terminator = Terminator::Goto(next);
+
+        assert_single_error(&ir, "basic block `next_1` is reachable");
+    }
+
+    #[test]
+    fn unreachable_blocks_may_be_left_unterminated() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        block(&mut ir, "dead");
+        return_void(&mut ir, entry);
+
+        assert_eq!(verify_messages(&ir), Vec::<String>::new());
+    }
+
+    #[test]
+    fn store_into_non_place() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let lvalue = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        let rvalue = register(&mut ir, entry, TypeId::INT, Value::Int(2));
+        sink(&mut ir, entry, Sink::Store(lvalue, rvalue));
+        return_void(&mut ir, entry);
+
+        assert_single_error(&ir, "is stored into, but is not a place");
+    }
+
+    #[test]
+    fn non_bool_condition() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let if_true = block(&mut ir, "if_true");
+        let if_false = block(&mut ir, "if_false");
+        let condition = register(&mut ir, entry, TypeId::INT, Value::Int(1));
+        ir.basic_block_mut(entry).terminator = Terminator::GotoIf {
+            condition,
+            if_true,
+            if_false,
+        };
+        return_void(&mut ir, if_true);
+        return_void(&mut ir, if_false);
+
+        assert_single_error(&ir, "must be a `Bool`, but it is `Int`");
+    }
+
+    #[test]
+    fn error_typed_condition_is_not_reported_again() {
+        let mut ir = Ir::new();
+        let entry = block(&mut ir, "entry");
+        let condition = register(&mut ir, entry, TypeId::ERROR, Value::Void);
+        ir.basic_block_mut(entry).terminator = Terminator::GotoIf {
+            condition,
+            if_true: entry,
+            if_false: entry,
+        };
+
+        assert_eq!(verify_messages(&ir), Vec::<String>::new());
+    }
+}
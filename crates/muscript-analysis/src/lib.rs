@@ -6,6 +6,7 @@ pub mod ir;
 mod package;
 pub mod partition;
 mod source;
+mod suggestions;
 pub mod type_system;
 
 pub use environment::*;
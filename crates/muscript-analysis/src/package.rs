@@ -1,9 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use muscript_foundation::errors::pipe_all_diagnostics_into;
 use tracing::info_span;
 
-use crate::{environment::ClassId, CompileError, Compiler, FunctionId, VarId};
+use crate::{
+    environment::ClassId,
+    ir::{self, optimize::PassManager, NodeKind, Value},
+    type_system::Type,
+    CompileError, Compiler, Environment, FunctionId, TypeId, VarId,
+};
 
 #[derive(Debug, Clone)]
 pub struct Package {
@@ -53,4 +58,97 @@ impl Package {
 
         Ok(Self { classes })
     }
+
+    /// Returns the other classes each class of the package made use of: through the types of its
+    /// variables, functions, and expressions, and through the functions and objects its code
+    /// refers to.
+    ///
+    /// This includes classes that are only referred to indirectly, such as the class of an object
+    /// returned by a function call whose fields are then accessed.
+    pub fn class_references(&self, env: &Environment) -> HashMap<ClassId, HashSet<ClassId>> {
+        self.classes
+            .iter()
+            .map(|(&class_id, class)| {
+                let mut references = HashSet::new();
+                for &var_id in &class.vars {
+                    add_type_references(env, env.get_var(var_id).ty, &mut references);
+                }
+                for &function_id in &class.functions {
+                    let function = env.get_function(function_id);
+                    add_type_references(env, function.return_ty, &mut references);
+                    for param in &function.params {
+                        add_type_references(env, env.get_var(param.var).ty, &mut references);
+                    }
+                    let Some(ir) = env.get_function_ir(function_id) else {
+                        continue;
+                    };
+                    for &var_id in &ir.locals {
+                        add_type_references(env, env.get_var(var_id).ty, &mut references);
+                    }
+                    for node in &ir.nodes {
+                        let NodeKind::Register(register) = &node.kind else {
+                            continue;
+                        };
+                        add_type_references(env, register.ty, &mut references);
+                        match register.value {
+                            Value::CallFinal { function, .. } => {
+                                references.insert(env.get_function(function).class_id);
+                            }
+                            Value::Object { class, .. } => {
+                                references.insert(class);
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                references.remove(&class_id);
+                (class_id, references)
+            })
+            .collect()
+    }
+
+    /// Runs the given optimization passes over the IR of every function in the package.
+    pub fn optimize(&self, env: &mut Environment, passes: &PassManager) {
+        let _span = info_span!("optimize_package").entered();
+
+        if passes.is_empty() {
+            return;
+        }
+        for class in self.classes.values() {
+            for &function_id in &class.functions {
+                env.optimize_function_ir(function_id, passes);
+            }
+        }
+    }
+
+    /// Runs the IR verifier over every function in the package. Returns whether all IR passed
+    /// verification; violations are reported as diagnostics.
+    pub fn verify(&self, env: &mut Environment) -> bool {
+        let _span = info_span!("verify_package").entered();
+
+        let mut diagnostics = vec![];
+        let mut ok = true;
+        for class in self.classes.values() {
+            for &function_id in &class.functions {
+                if let Some(ir) = env.get_function_ir(function_id) {
+                    ok &= ir::verify(env, ir, &mut diagnostics);
+                }
+            }
+        }
+        pipe_all_diagnostics_into(env, diagnostics);
+        ok
+    }
+}
+
+fn add_type_references(env: &Environment, ty: TypeId, references: &mut HashSet<ClassId>) {
+    match *env.get_type(ty) {
+        Type::Error | Type::Void | Type::Primitive(_) => (),
+        Type::Array(element) => add_type_references(env, element, references),
+        Type::Object(class_id) | Type::Class(class_id) => {
+            references.insert(class_id);
+        }
+        Type::Struct { outer } | Type::Enum { outer } => {
+            references.insert(outer);
+        }
+    }
 }
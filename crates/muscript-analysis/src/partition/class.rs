@@ -5,8 +5,9 @@ mod support;
 use indexmap::IndexMap;
 use indoc::indoc;
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label, Note, NoteKind},
+    errors::{codes, Diagnostic, DiagnosticSink, Label, Note, NoteKind},
     ident::CaseInsensitive,
+    source_arena::{Relocate, Relocation},
     span::Spanned,
 };
 use muscript_lexer::{
@@ -15,8 +16,9 @@ use muscript_lexer::{
 };
 use muscript_syntax::{
     cst::{self, NamedItem, TypeOrDef, VarDef},
-    token, Spanned,
+    token, Relocate, Spanned,
 };
+use serde::{Deserialize, Serialize};
 use tracing::info_span;
 
 use crate::{
@@ -34,12 +36,13 @@ pub use structs::*;
 ///
 /// Note that a class may be composed of many partitions, in case the class is declared as
 /// `partial`. That's because a single partition corresponds to a single file.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UntypedClassPartition {
     pub kind: cst::ClassKind,
     pub name: token::Ident,
     pub extends: Option<token::Ident>,
     pub within: Option<token::Ident>,
+    pub doc: cst::DocComment,
 
     // We use IndexMaps so as to preserve the original declaration order, which is important
     // because we don't want our error messages to jump around the file. Instead we want them to go
@@ -54,7 +57,30 @@ pub struct UntypedClassPartition {
     // NOTE: cpptext is omitted because MuScript does not support exporting C++ headers.
 }
 
-#[derive(Debug, Clone, Spanned)]
+impl Relocate for UntypedClassPartition {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.name.relocate(relocation)?;
+        self.extends.relocate(relocation)?;
+        self.within.relocate(relocation)?;
+        self.doc.relocate(relocation)?;
+        self.vars
+            .values_mut()
+            .try_for_each(|var| var.relocate(relocation))?;
+        self.functions
+            .values_mut()
+            .try_for_each(|function| function.relocate(relocation))?;
+        self.types
+            .values_mut()
+            .try_for_each(|ty| ty.relocate(relocation))?;
+        self.states
+            .values_mut()
+            .try_for_each(|state| state.relocate(relocation))?;
+        self.default_properties.relocate(relocation)?;
+        self.replication.relocate(relocation)
+    }
+}
+
+#[derive(Debug, Clone, Spanned, Relocate, Serialize, Deserialize)]
 pub struct ItemSingleVar {
     pub var: cst::KVar,
     pub editor: Option<cst::VarEditor>,
@@ -62,18 +88,20 @@ pub struct ItemSingleVar {
     pub ty: cst::Type,
     pub variable: VarDef,
     pub semi: token::Semi,
+    pub doc: cst::DocComment,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Relocate, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
 pub enum VarCst {
     Const(cst::ItemConst),
     Var(ItemSingleVar),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Relocate, Serialize, Deserialize)]
 pub enum TypeCst {
     Struct(UntypedStruct),
-    Enum(cst::EnumDef),
+    Enum(cst::ItemEnum),
     // Not sure if states belong to the same namespace; AFAIK they're only ever referred to by name
     // (as in name literal) but needs verification.
 }
@@ -120,7 +148,11 @@ impl UntypedClassPartition {
                                 diagnostics,
                                 sources,
                                 &mut types,
-                                TypeCst::Enum(enum_def),
+                                TypeCst::Enum(cst::ItemEnum {
+                                    def: enum_def,
+                                    semi: None,
+                                    doc: cst::DocComment::default(),
+                                }),
                             );
                         }
                         Some(InlineTypeDef::Struct(struct_def)) => {
@@ -129,6 +161,7 @@ impl UntypedClassPartition {
                                 sources,
                                 &mut types,
                                 struct_def,
+                                cst::DocComment::default(),
                             );
                             Self::add_to_scope(
                                 diagnostics,
@@ -157,23 +190,23 @@ impl UntypedClassPartition {
                     );
                 }
                 cst::Item::Struct(item_struct) => {
-                    let untyped_struct =
-                        UntypedStruct::from_cst(diagnostics, sources, &mut types, item_struct.def);
-                    Self::add_to_scope(
+                    let untyped_struct = UntypedStruct::from_cst(
                         diagnostics,
                         sources,
                         &mut types,
-                        TypeCst::Struct(untyped_struct),
+                        item_struct.def,
+                        item_struct.doc,
                     );
-                }
-                cst::Item::Enum(item_enum) => {
                     Self::add_to_scope(
                         diagnostics,
                         sources,
                         &mut types,
-                        TypeCst::Enum(item_enum.def),
+                        TypeCst::Struct(untyped_struct),
                     );
                 }
+                cst::Item::Enum(item_enum) => {
+                    Self::add_to_scope(diagnostics, sources, &mut types, TypeCst::Enum(item_enum));
+                }
                 cst::Item::State(item_state) => {
                     Self::add_to_scope(diagnostics, sources, &mut states, item_state);
                 }
@@ -183,6 +216,7 @@ impl UntypedClassPartition {
                 cst::Item::StructDefaultProperties(item_struct_default_properties) => diagnostics
                     .emit(
                         Diagnostic::error("`structdefaultproperties` may only appear in structs")
+                            .with_code(codes::M0415)
                             .with_label(Label::primary(
                                 &item_struct_default_properties.keyword,
                                 "",
@@ -193,11 +227,13 @@ impl UntypedClassPartition {
                 }
                 cst::Item::CppText(item_cpp_text) => diagnostics.emit(
                     Diagnostic::warning("`cpptext` item is ignored")
+                        .with_code(codes::M0413)
                         .with_label(Label::primary(&item_cpp_text, ""))
                         .with_note(notes::CPP_UNSUPPORTED),
                 ),
                 cst::Item::StructCppText(item_struct_cpp_text) => diagnostics.emit(
                     Diagnostic::error("`structcpptext` may only appear in structs")
+                        .with_code(codes::M0415)
                         .with_label(Label::primary(&item_struct_cpp_text.cpptext, "")),
                 ),
                 cst::Item::Stmt(stmt) => {
@@ -213,7 +249,7 @@ impl UntypedClassPartition {
                 let path = &x.parent_class.components;
                 if path.len() > 1 {
                     diagnostics.emit(
-                        Diagnostic::error("parent class cannot be a path")
+                        Diagnostic::error("parent class cannot be a path").with_code(codes::M0419)
                             .with_label(Label::primary(&path[1], ""))
                             .with_note("help: paths `A.B` are used to refer to items declared within classes, not classes themselves")
                             .with_note(format!("note: assuming you meant to use `{}` as the parent class", sources.source(&path[0])))
@@ -222,6 +258,7 @@ impl UntypedClassPartition {
                 path[0]
             }),
             within: class.within.map(|x| x.outer_class),
+            doc: class.doc,
             vars,
             functions,
             types,
@@ -278,6 +315,7 @@ impl UntypedClassPartition {
         let name_first = sources.source(&span_first);
 
         let mut diagnostic = Diagnostic::error(format!("redefinition of `{name_first}`"))
+            .with_code(codes::M0418)
             .with_label(Label::primary(&span_first, "first defined here"))
             .with_label(Label::primary(&span_re, "redefined here"));
 
@@ -352,6 +390,7 @@ impl ItemSingleVar {
             },
             variable: single,
             semi: var.semi,
+            doc: var.doc.clone(),
         })
     }
 }
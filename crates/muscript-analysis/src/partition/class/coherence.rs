@@ -1,6 +1,6 @@
 use indexmap::IndexMap;
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     ident::CaseInsensitive,
     source::SourceFileSet,
 };
@@ -83,7 +83,7 @@ impl UntypedClassPartition {
         sources: &SourceFileSet,
         class_sources: &ClassSources,
     ) {
-        let Some(first_source_file) = class_sources.source_files.get(0) else {
+        let Some(first_source_file) = class_sources.source_files.first() else {
             // This can happen if all the class's source files failed to parse.
             return;
         };
@@ -101,7 +101,8 @@ impl UntypedClassPartition {
 
         if !conflicting.is_empty() {
             let mut diagnostic =
-                Diagnostic::error("redefinition of class across different packages");
+                Diagnostic::error("redefinition of class across different packages")
+                    .with_code(codes::M0417);
             conflicting.insert(0, 0);
             for i in conflicting {
                 let conflicting_cst = &class_sources.source_files[i].parsed;
@@ -1,8 +1,9 @@
 use indexmap::IndexMap;
 use indoc::indoc;
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
     ident::CaseInsensitive,
+    source_arena::{Relocate, Relocation},
     span::Spanned,
 };
 use muscript_lexer::{
@@ -10,12 +11,13 @@ use muscript_lexer::{
     token::{Token, TokenSpan},
 };
 use muscript_syntax::{cst, token::Ident};
+use serde::{Deserialize, Serialize};
 
 use crate::diagnostics::{self, notes, unnecessary_semicolon};
 
 use super::{InlineTypeDef, ItemSingleVar, TypeCst, UntypedClassPartition};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UntypedStruct {
     pub name: Ident,
     pub extends: Option<cst::Path>,
@@ -25,6 +27,20 @@ pub struct UntypedStruct {
     // structdefaultproperties is normalized to regular defaultproperties, because requiring the
     // extra `struct` word is just silly.
     pub default_properties: Option<cst::ItemDefaultProperties>,
+
+    pub doc: cst::DocComment,
+}
+
+impl Relocate for UntypedStruct {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.name.relocate(relocation)?;
+        self.extends.relocate(relocation)?;
+        self.vars
+            .values_mut()
+            .try_for_each(|var| var.relocate(relocation))?;
+        self.default_properties.relocate(relocation)?;
+        self.doc.relocate(relocation)
+    }
 }
 
 /// # Conversion from CST
@@ -34,6 +50,7 @@ impl UntypedStruct {
         sources: &LexedSources<'_>,
         types: &mut IndexMap<CaseInsensitive<String>, TypeCst>,
         cst: cst::StructDef,
+        doc: cst::DocComment,
     ) -> Self {
         let mut vars = IndexMap::new();
         let mut default_properties = None;
@@ -53,12 +70,21 @@ impl UntypedStruct {
                                 diagnostics,
                                 sources,
                                 types,
-                                TypeCst::Enum(enum_def),
+                                TypeCst::Enum(cst::ItemEnum {
+                                    def: enum_def,
+                                    semi: None,
+                                    doc: cst::DocComment::default(),
+                                }),
                             );
                         }
                         Some(InlineTypeDef::Struct(struct_def)) => {
-                            let untyped_struct =
-                                UntypedStruct::from_cst(diagnostics, sources, types, struct_def);
+                            let untyped_struct = UntypedStruct::from_cst(
+                                diagnostics,
+                                sources,
+                                types,
+                                struct_def,
+                                cst::DocComment::default(),
+                            );
                             UntypedClassPartition::add_to_scope(
                                 diagnostics,
                                 sources,
@@ -153,6 +179,7 @@ impl UntypedStruct {
                 ),
                 item @ (cst::Item::CppText(_) | cst::Item::StructCppText(_)) => diagnostics.emit(
                     Diagnostic::warning("`cpptext` item is ignored")
+                        .with_code(codes::M0413)
                         .with_label(Label::primary(&item, ""))
                         .with_note(notes::CPP_UNSUPPORTED),
                 ),
@@ -167,12 +194,14 @@ impl UntypedStruct {
             extends: cst.extends.map(|x| x.parent_class),
             vars,
             default_properties,
+            doc,
         }
     }
 }
 
 fn item_may_not_appear_in_struct(span: TokenSpan, message: &str) -> Diagnostic<Token> {
     Diagnostic::error(message)
+        .with_code(codes::M0414)
         .with_label(Label::primary(&span, ""))
         .with_note("note: structs may only contain `var`s and `defaultproperties`")
 }
@@ -1,4 +1,4 @@
-use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label};
+use muscript_foundation::errors::{codes, Diagnostic, DiagnosticSink, Label};
 use muscript_lexer::token::Token;
 
 use super::UntypedClassPartition;
@@ -12,6 +12,7 @@ impl UntypedClassPartition {
         if let Some(within) = self.within {
             diagnostics.emit(
                 Diagnostic::error("`within` is not yet supported by the compiler")
+                    .with_code(codes::M0416)
                     .with_label(Label::primary(&within, "")),
             );
         }
@@ -19,6 +20,7 @@ impl UntypedClassPartition {
         for state in self.states.values() {
             diagnostics.emit(
                 Diagnostic::error("states are not yet supported by the compiler")
+                    .with_code(codes::M0416)
                     .with_label(Label::primary(state, "")),
             );
         }
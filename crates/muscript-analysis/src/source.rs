@@ -1,7 +1,12 @@
 use muscript_foundation::{errors::DiagnosticSink, source::SourceFileId};
-use muscript_lexer::{sources::OwnedSources, token::Token};
+use muscript_lexer::{
+    sources::{LexedSources, OwnedSources},
+    token::Token,
+};
 use muscript_syntax::cst;
 
+use crate::partition::UntypedClassPartition;
+
 /// Collection of source files for a class.
 #[derive(Debug, Clone)]
 pub struct ClassSources {
@@ -20,7 +25,13 @@ pub trait CompilerInput {
     /// Returns whether a class with the given name exists.
     fn class_exists(&self, class_name: &str) -> bool;
 
-    /// Returns the source file IDs of a class.
+    /// Returns the names of all classes that exist. Used for suggesting similar names when a class
+    /// cannot be found.
+    fn class_names(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+
+    /// Returns the source file IDs of a class. The source code of the files may not have been
+    /// loaded yet if the class has not been requested with [`CompilerInput::parsed_class_sources`]
+    /// or [`CompilerInput::cached_class_partitions`].
     ///
     /// In case `None` is returned, a class with the given name does not exist.
     fn class_source_ids(&self, class_name: &str) -> Option<Vec<SourceFileId>>;
@@ -29,12 +40,41 @@ pub trait CompilerInput {
     ///
     /// In case `None` is returned, a class with the given name does not exist.
     ///
-    /// `diagnostics` should be filled in with any errors that occur during parsing. Files that
-    /// irrecoverably fail to parse should not be included in the output.
+    /// This is the point at which the class's source code is needed, so implementations may defer
+    /// loading it until then, using
+    /// [`SourceFileSet::load`][muscript_foundation::source::SourceFileSet::load].
+    ///
+    /// `diagnostics` should be filled in with any errors that occur during loading or parsing.
+    /// Files that cannot be loaded or irrecoverably fail to parse should not be included in the
+    /// output.
     fn parsed_class_sources(
         &self,
         sources: &mut OwnedSources<'_>,
         class_name: &str,
         diagnostics: &mut dyn DiagnosticSink<Token>,
     ) -> Option<ClassSources>;
+
+    /// Returns the class's partitions saved by an earlier compilation, loading any tokens they
+    /// refer to into `sources`. Lint levels set by the class's sources should be passed on to
+    /// `diagnostics`.
+    ///
+    /// Returning `None` makes the compiler parse and partition the class's sources as usual.
+    fn cached_class_partitions(
+        &self,
+        _sources: &mut OwnedSources<'_>,
+        _class_name: &str,
+        _diagnostics: &mut dyn DiagnosticSink<Token>,
+    ) -> Option<Vec<UntypedClassPartition>> {
+        None
+    }
+
+    /// Called after a class has been parsed and partitioned without emitting any diagnostics,
+    /// such that the partitions can be saved for later compilations.
+    fn save_class_partitions(
+        &self,
+        _sources: &LexedSources<'_>,
+        _class_name: &str,
+        _partitions: &[UntypedClassPartition],
+    ) {
+    }
 }
@@ -0,0 +1,270 @@
+//! "Did you mean" suggestions for names that could not be found.
+
+use std::collections::HashSet;
+
+use muscript_foundation::{
+    errors::{Applicability, Note},
+    ident::CaseInsensitive,
+    span::Spanned,
+};
+use muscript_lexer::token::Token;
+use muscript_syntax::cst;
+
+use crate::{function::builder::FunctionBuilder, partition::TypeCst, ClassId, Compiler};
+
+/// Computes the edit distance between two names, ignoring case.
+///
+/// This is the optimal string alignment distance, which counts insertions, deletions,
+/// substitutions, and transpositions of adjacent characters as single edits.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let b: Vec<char> = b.chars().map(|c| c.to_ascii_lowercase()).collect();
+
+    // Only three rows of the full matrix are needed at any given time.
+    let mut previous_previous = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(previous_previous[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut previous_previous, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Finds the candidate most similar to `name`, if any of them is similar enough to be considered
+/// a likely typo.
+pub fn closest_name<'c>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'c str>,
+) -> Option<&'c str> {
+    // Allow roughly one typo per three characters, so that short names don't end up matching
+    // completely unrelated names.
+    let max_distance = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .filter(|candidate| !candidate.eq_ignore_ascii_case(name))
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// # Suggestions
+impl<'a> Compiler<'a> {
+    /// Produces a note suggesting to replace `name` with the most similar of the `candidates`.
+    ///
+    /// `what` describes the kind of item that's being suggested, eg. `"type"`.
+    pub(crate) fn did_you_mean<'c>(
+        &self,
+        span: &impl Spanned<Token>,
+        name: &str,
+        what: &str,
+        candidates: impl IntoIterator<Item = &'c str>,
+    ) -> Option<Note> {
+        closest_name(name, candidates).map(|candidate| {
+            Note::from((
+                format!("help: a {what} with a similar name exists"),
+                self.sources
+                    .replacement_suggestion(span, candidate, Applicability::MaybeIncorrect),
+            ))
+        })
+    }
+
+    /// Returns the class followed by all of its super classes.
+    ///
+    /// Unlike [`Compiler::super_class_id`], this does not emit any diagnostics when a super class
+    /// cannot be found, since it's only used to gather names for suggestions. For the same reason,
+    /// the hierarchy stops at the first class whose partitions have not been loaded yet. Looking up
+    /// the name that could not be found has already loaded every class it had to search, and
+    /// loading more just to produce a suggestion would be wasted work.
+    fn class_hierarchy(&mut self, class_id: ClassId) -> Vec<ClassId> {
+        let mut hierarchy = vec![];
+        let mut visited = HashSet::new();
+        let mut next = Some(class_id);
+        while let Some(class_id) = next.take() {
+            if !visited.insert(class_id) {
+                break;
+            }
+            let Some(partitions) = self.env.untyped_class_partitions(class_id) else {
+                break;
+            };
+            hierarchy.push(class_id);
+            let super_class_name = partitions
+                .iter()
+                .find_map(|partition| partition.extends)
+                .map(|extends| self.sources.source(&extends).to_owned());
+            if let Some(super_class_name) = super_class_name {
+                if self.input.class_exists(&super_class_name) {
+                    next = Some(self.env.get_or_create_class(&super_class_name));
+                }
+            }
+        }
+        hierarchy
+    }
+
+    /// Returns the names of all types that can be referred to from within the given class, except
+    /// for class names, which are available from [`CompilerInput::class_names`].
+    ///
+    /// [`CompilerInput::class_names`]: crate::CompilerInput::class_names
+    pub(crate) fn type_names_in_scope(&mut self, class_id: ClassId) -> Vec<String> {
+        let mut names: Vec<String> = ["Bool", "Byte", "Int", "Float", "String", "Name"]
+            .into_iter()
+            .chain(["Array", "Class"])
+            .map(String::from)
+            .collect();
+        for class_id in self.class_hierarchy(class_id) {
+            if let Some(partitions) = self.env.untyped_class_partitions(class_id) {
+                names.extend(
+                    partitions
+                        .iter()
+                        .flat_map(|partition| partition.types.keys().map(|ci| (**ci).clone())),
+                );
+            }
+        }
+        names
+    }
+
+    /// Returns the names of all class variables, constants, and enum values that can be referred to
+    /// from within the given class.
+    pub(crate) fn var_names_in_scope(&mut self, class_id: ClassId) -> Vec<String> {
+        let mut names = vec![];
+        for class_id in self.class_hierarchy(class_id) {
+            names.extend_from_slice(self.all_var_names(class_id));
+            if let Some(partitions) = self.env.untyped_class_partitions(class_id) {
+                for partition in partitions {
+                    for type_cst in partition.types.values() {
+                        if let TypeCst::Enum(item_enum) = type_cst {
+                            names.extend(
+                                item_enum
+                                    .def
+                                    .variants
+                                    .iter()
+                                    .map(|variant| self.sources.source(&variant.name).to_owned()),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    /// Returns the names of all variables that can be referred to from within a function body,
+    /// including locals and parameters.
+    pub(crate) fn value_names_in_scope(&mut self, builder: &FunctionBuilder) -> Vec<String> {
+        let mut names: Vec<String> = builder.local_names().map(String::from).collect();
+        names.extend(self.var_names_in_scope(builder.class_id));
+        names
+    }
+
+    /// Returns the names of all functions that can be called from within the given class.
+    /// Operators are not included, since they cannot be called by name.
+    pub(crate) fn function_names_in_scope(&mut self, class_id: ClassId) -> Vec<String> {
+        let mut names = vec![];
+        for class_id in self.class_hierarchy(class_id) {
+            if let Some(partitions) = self.env.untyped_class_partitions(class_id) {
+                names.extend(partitions.iter().flat_map(|partition| {
+                    partition
+                        .functions
+                        .iter()
+                        .filter(|(_, function)| {
+                            matches!(
+                                function.kind,
+                                cst::FunctionKind::Function(_)
+                                    | cst::FunctionKind::Event(_)
+                                    | cst::FunctionKind::Delegate(_)
+                            )
+                        })
+                        .map(|(name, _)| (**name).clone())
+                }));
+            }
+        }
+        names
+    }
+
+    /// Returns whether a callable function with the given name exists in the class.
+    pub(crate) fn function_name_exists(&mut self, class_id: ClassId, name: &str) -> bool {
+        self.function_names_in_scope(class_id)
+            .iter()
+            .any(|function_name| {
+                CaseInsensitive::new_ref(function_name) == CaseInsensitive::new_ref(name)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("Total", "Total"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        // Insertion, deletion, substitution.
+        assert_eq!(edit_distance("Totl", "Total"), 1);
+        assert_eq!(edit_distance("Counterr", "Counter"), 1);
+        assert_eq!(edit_distance("Strng", "Stung"), 1);
+    }
+
+    #[test]
+    fn edit_distance_ignores_case() {
+        assert_eq!(edit_distance("counter", "COUNTER"), 0);
+        assert_eq!(edit_distance("totl", "Total"), 1);
+    }
+
+    #[test]
+    fn edit_distance_counts_transpositions_as_one_edit() {
+        assert_eq!(edit_distance("Totla", "Total"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+        // Plain Levenshtein distance would count this as two substitutions.
+        assert_eq!(edit_distance("Lenght", "Length"), 1);
+        // Optimal string alignment does not edit a substring more than once.
+        assert_eq!(edit_distance("CA", "ABC"), 3);
+    }
+
+    #[test]
+    fn closest_name_picks_the_most_similar_candidate() {
+        let candidates = ["Count", "Counter", "Countess"];
+        assert_eq!(closest_name("Countre", candidates), Some("Counter"));
+        // Ties are broken in favor of the candidate that comes first.
+        assert_eq!(closest_name("Countr", candidates), Some("Count"));
+        assert_eq!(closest_name("Totla", ["Total", "Trouble"]), Some("Total"));
+    }
+
+    #[test]
+    fn closest_name_skips_exact_matches() {
+        // A name that differs only in case is the same name, so it cannot be a typo of itself.
+        assert_eq!(closest_name("total", ["Total"]), None);
+        assert_eq!(closest_name("total", ["Total", "Totals"]), Some("Totals"));
+    }
+
+    #[test]
+    fn closest_name_allows_one_edit_per_three_characters() {
+        // Names of up to five characters allow a single edit.
+        assert_eq!(closest_name("ab", ["ac"]), Some("ac"));
+        assert_eq!(closest_name("abcde", ["abcxy"]), None);
+        assert_eq!(closest_name("abcde", ["abcdx"]), Some("abcdx"));
+        // Six characters allow two.
+        assert_eq!(closest_name("abcdef", ["abcdxy"]), Some("abcdxy"));
+        assert_eq!(closest_name("abcdef", ["abcxyz"]), None);
+        // Very short names still allow one edit, but not more.
+        assert_eq!(closest_name("a", ["b"]), Some("b"));
+        assert_eq!(closest_name("a", ["bc"]), None);
+    }
+
+    #[test]
+    fn closest_name_without_candidates() {
+        assert_eq!(closest_name("Total", []), None);
+    }
+}
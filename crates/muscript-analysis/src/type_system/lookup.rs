@@ -1,5 +1,5 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Applicability, Diagnostic, DiagnosticSink, Label},
     ident::CaseInsensitive,
     span::Spanned,
 };
@@ -56,13 +56,25 @@ impl<'a> Compiler<'a> {
             _ => (),
         }
 
-        // TODO: Emit a more helpful diagnostic pointing out possible typos.
+        let suggestion = if let [type_name_ident] = &ty.path.components[..] {
+            let type_name = self.sources.source(type_name_ident);
+            let candidates = self.type_names_in_scope(scope);
+            let candidates = candidates
+                .iter()
+                .map(String::as_str)
+                .chain(self.input.class_names());
+            self.did_you_mean(type_name_ident, type_name, "type", candidates)
+        } else {
+            None
+        };
         self.env.emit(
             Diagnostic::error(format!(
                 "cannot find type `{}` in this scope",
                 ty.path.pretty_print(&self.sources.as_borrowed())
             ))
-            .with_label(Label::primary(&ty.path, "")),
+            .with_code(codes::M0409)
+            .with_label(Label::primary(&ty.path, ""))
+            .with_optional_note(suggestion),
         );
         ERROR_RESULT
     }
@@ -71,6 +83,7 @@ impl<'a> Compiler<'a> {
         if let Some(generic) = &ty.generic {
             self.env.emit(
                 Diagnostic::error("use of generic arguments on non-generic type")
+                    .with_code(codes::M0410)
                     .with_label(Label::primary(generic, ""))
                     .with_label(Label::secondary(&ty.path, "this type is not generic"))
                     .with_note(
@@ -99,6 +112,7 @@ impl<'a> Compiler<'a> {
                         "`Array` expects a single generic argument `<T>`, but got {}",
                         generic.args.len()
                     ))
+                    .with_code(codes::M0411)
                     .with_label(Label::primary(generic, "")),
                 );
                 ERROR_RESULT
@@ -106,10 +120,15 @@ impl<'a> Compiler<'a> {
         } else {
             self.env.emit(
                 Diagnostic::error("`Array` expects one generic argument `<T>`")
+                    .with_code(codes::M0411)
                     .with_label(Label::primary(&ty.path, ""))
                     .with_note((
                         "help: try giving the array a type of element to store",
-                        self.sources.replacement_suggestion(ty, "Array<Int>"),
+                        self.sources.replacement_suggestion(
+                            ty,
+                            "Array<Int>",
+                            Applicability::HasPlaceholders,
+                        ),
                     )),
             );
             ERROR_RESULT
@@ -125,10 +144,13 @@ impl<'a> Compiler<'a> {
                     if let &Type::Object(class_id) = self.env.get_type(inner_id) {
                         (class_id, inner_id)
                     } else {
-                        self.env.emit(Diagnostic::error(format!(
-                            "`{}` is not a class",
-                            self.env.type_name(inner_id)
-                        )));
+                        self.env.emit(
+                            Diagnostic::error(format!(
+                                "`{}` is not a class",
+                                self.env.type_name(inner_id)
+                            ))
+                            .with_code(codes::M0412),
+                        );
                         return TypeId::ERROR;
                     }
                 }
@@ -138,6 +160,7 @@ impl<'a> Compiler<'a> {
                             "`Class` expects a single generic argument `<T>`, but got {}",
                             generic.args.len()
                         ))
+                        .with_code(codes::M0411)
                         .with_label(Label::primary(generic, "")),
                     );
                     return TypeId::ERROR;
@@ -156,11 +179,15 @@ impl<'a> Compiler<'a> {
     fn generics_not_allowed(&mut self, ty: &cst::Type, generic: &cst::Generic, type_name: &str) {
         self.env.emit(
             Diagnostic::error("only `Array` and `Class` may use generics")
+                .with_code(codes::M0410)
                 .with_label(Label::primary(generic, ""))
                 .with_note((
                     "help: remove the generic parameters",
-                    self.sources
-                        .replacement_suggestion(ty, type_name.to_string()),
+                    self.sources.replacement_suggestion(
+                        ty,
+                        type_name.to_string(),
+                        Applicability::MachineApplicable,
+                    ),
                 )),
         )
     }
@@ -3,6 +3,9 @@
 //! The error reporting in MuScript is largely inspired by the Rust compiler, though arguably it's a
 //! lot simpler.
 
+pub mod codes;
+pub mod lints;
+mod output;
 mod sink;
 
 use std::ops::Range;
@@ -11,6 +14,7 @@ use codespan_reporting::{
     term,
     term::termcolor::{ColorChoice, StandardStream},
 };
+use serde::Serialize;
 
 use crate::{
     source::{SourceFileId, SourceFileSet},
@@ -18,6 +22,7 @@ use crate::{
     span::{Span, Spanned},
 };
 
+pub use output::*;
 pub use sink::*;
 
 /// Trait for types which span across a range of source characters.
@@ -25,7 +30,8 @@ pub trait SourceRange {
     fn source_range(&self) -> Range<usize>;
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LabelStyle {
     /// Labels that describe the primary cause of a diagnostic.
     Primary,
@@ -93,6 +99,42 @@ impl<T> Label<T> {
     }
 }
 
+impl<T> Label<T>
+where
+    T: SourceRange,
+{
+    /// Resolves the label's span to a file and a range of bytes within it. Returns [`None`] if
+    /// the span is empty.
+    pub fn source_range(
+        &self,
+        source_arena: &SourceArena<T>,
+    ) -> Option<(SourceFileId, Range<usize>)> {
+        match self.span {
+            Span::Empty => None,
+            Span::Spanning { start, end } => {
+                let start_range = source_arena.element(start).source_range();
+                let end_range = source_arena.element(end).source_range();
+                Some((
+                    source_arena.source_file_id(start),
+                    start_range.start.min(end_range.start)..start_range.end.max(end_range.end),
+                ))
+            }
+        }
+    }
+}
+
+/// How confident the compiler is that a [`ReplacementSuggestion`] does what the user intended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Applicability {
+    /// The suggestion is definitely correct and can be applied automatically, eg. by `--fix`.
+    MachineApplicable,
+    /// The suggestion may be what the user wants, but it should be reviewed before applying.
+    MaybeIncorrect,
+    /// The suggestion contains placeholders which the user has to fill in.
+    HasPlaceholders,
+}
+
 /// Suggestion for what to replace a span with that might make the diagnostic go away.
 #[derive(Debug, Clone)]
 pub struct ReplacementSuggestion {
@@ -102,10 +144,13 @@ pub struct ReplacementSuggestion {
     pub span: Range<usize>,
     /// The replacement string.
     pub replacement: String,
+    /// Whether the suggestion can be applied without human review.
+    pub applicability: Applicability,
 }
 
 /// The type of a note.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NoteKind {
     Normal,
     Debug,
@@ -172,7 +217,8 @@ impl From<(&str, Option<ReplacementSuggestion>)> for Note {
 }
 
 /// Diagnostic severity.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     /// A help message.
     Help,
@@ -186,6 +232,19 @@ pub enum Severity {
     Bug,
 }
 
+impl Severity {
+    /// The name of the severity, as displayed in front of diagnostic messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Help => "help",
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Bug => "bug",
+        }
+    }
+}
+
 /// Diagnostic describing a problem encountered within the code.
 #[derive(Debug)]
 pub struct Diagnostic<T> {
@@ -240,8 +299,10 @@ impl<T> Diagnostic<T> {
     /// Note that unlike other severities, since this may be triggered by an actual bug
     /// (ie. an unhandled external error,) the message passed in may be anything that can be
     /// [`Display`][std::fmt::Display]ed as text.
+    ///
+    /// All bugs share the code [`codes::M0000`].
     pub fn bug(error: impl ToString) -> Self {
-        Self::new(Severity::Bug, error.to_string())
+        Self::new(Severity::Bug, error.to_string()).with_code(codes::M0000)
     }
 
     /// Creates a new error-level diagnostic with the given message.
@@ -295,6 +356,20 @@ impl<T> Diagnostic<T> {
         self.children.push(child);
         self
     }
+
+    /// Replaces the spans of all labels of the diagnostic and its children, eg. after the elements
+    /// they point to were moved to a different arena.
+    pub fn map_spans(mut self, f: &mut impl FnMut(Span<T>) -> Span<T>) -> Self {
+        for label in &mut self.labels {
+            label.span = f(label.span);
+        }
+        self.children = self
+            .children
+            .into_iter()
+            .map(|child| child.map_spans(f))
+            .collect();
+        self
+    }
 }
 
 impl<T> Diagnostic<T>
@@ -338,9 +413,9 @@ where
             labels: self
                 .labels
                 .iter()
-                .filter_map(|label| match label.span {
-                    Span::Empty => None,
-                    Span::Spanning { start, end } => Some(codespan_reporting::diagnostic::Label {
+                .filter_map(|label| {
+                    let (file_id, range) = label.source_range(source_arena)?;
+                    Some(codespan_reporting::diagnostic::Label {
                         style: match label.style {
                             LabelStyle::Primary => {
                                 codespan_reporting::diagnostic::LabelStyle::Primary
@@ -349,15 +424,10 @@ where
                                 codespan_reporting::diagnostic::LabelStyle::Secondary
                             }
                         },
-                        file_id: source_arena.source_file_id(start),
-                        range: {
-                            let start_range = source_arena.element(start).source_range();
-                            let end_range = source_arena.element(end).source_range();
-                            start_range.start.min(end_range.start)
-                                ..start_range.end.max(end_range.end)
-                        },
+                        file_id,
+                        range,
                         message: label.message.clone(),
-                    }),
+                    })
                 })
                 .collect(),
             notes: self
@@ -0,0 +1,160 @@
+//! Stable codes identifying the kinds of diagnostics the compiler can emit, along with long-form
+//! explanations of each of them.
+//!
+//! Codes are grouped by the stage of compilation that emits them:
+//! - `M0000` is reserved for internal compiler errors (bugs).
+//! - `M0001`–`M0099` are emitted by the lexer.
+//! - `M0100`–`M0199` are emitted by the preprocessor.
+//! - `M0200`–`M0399` are emitted by the parser.
+//! - `M0400` and above are emitted during semantic analysis.
+//!
+//! Once assigned, a code should never be reused for a different kind of diagnostic, since users
+//! may refer to it in scripts, lint configuration, or bug reports.
+//!
+//! Each code's explanation lives in a Markdown file named after the code, in the `codes`
+//! directory next to this module.
+
+macro_rules! error_codes {
+    ($($code:ident),* $(,)?) => {
+        $(
+            pub const $code: &str = stringify!($code);
+        )*
+
+        /// All error codes along with their explanations, sorted by code.
+        pub static EXPLANATIONS: &[(&str, &str)] = &[
+            $(
+                (
+                    stringify!($code),
+                    include_str!(concat!("codes/", stringify!($code), ".md")),
+                ),
+            )*
+        ];
+    };
+}
+
+error_codes! {
+    M0000,
+    // Lexer
+    M0001, M0002, M0003, M0004, M0005, M0006, M0007, M0008,
+    // Preprocessor
+    M0100, M0101, M0102, M0103, M0104, M0105, M0106, M0107, M0108, M0109, M0110, M0111,
+    // Parser
+    M0200, M0201, M0202, M0203, M0204, M0205, M0206, M0207, M0208, M0209, M0210, M0211, M0212,
+    M0213, M0214, M0215, M0216, M0217, M0218, M0219, M0220, M0221, M0222,
+    // Analysis
+    M0400, M0401, M0402, M0403, M0404, M0405, M0406, M0407, M0408, M0409, M0410, M0411, M0412,
+    M0413, M0414, M0415, M0416, M0417, M0418, M0419, M0420, M0421, M0422, M0423, M0424, M0425,
+    M0426, M0427, M0428, M0429, M0430, M0431, M0432, M0433, M0434, M0435, M0436, M0437, M0438,
+    M0439, M0440,
+}
+
+/// Returns the long-form explanation of the given error code. The code is matched
+/// case-insensitively, and the leading `M` and zeros may be omitted, so `M0427`, `m427`, and `427`
+/// all refer to the same code.
+pub fn explanation(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    let number: u16 = code.strip_prefix(['M', 'm']).unwrap_or(code).parse().ok()?;
+    EXPLANATIONS
+        .iter()
+        .find(|(known, _)| known[1..].parse() == Ok(number))
+        .map(|&(_, explanation)| explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, fs, path::Path};
+
+    use super::{explanation, EXPLANATIONS};
+
+    /// Returns the codes the Rust sources of the crates in the workspace refer to, either as
+    /// `codes::M0000` or as a `"M0000"` string literal.
+    fn codes_in_sources() -> BTreeSet<String> {
+        fn visit(dir: &Path, codes: &mut BTreeSet<String>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    visit(&path, codes);
+                } else if path.extension().is_some_and(|extension| extension == "rs")
+                    && !path.ends_with("errors/codes.rs")
+                {
+                    let source = fs::read_to_string(&path).unwrap();
+                    for prefix in ["codes::", "\""] {
+                        for (i, _) in source.match_indices(prefix) {
+                            let code = source.get(i + prefix.len()..i + prefix.len() + 5);
+                            if let Some(code) = code.filter(|code| {
+                                code.starts_with('M')
+                                    && code[1..].bytes().all(|b| b.is_ascii_digit())
+                            }) {
+                                codes.insert(code.to_owned());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let crates = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let mut codes = BTreeSet::new();
+        for entry in fs::read_dir(crates).unwrap() {
+            let src = entry.unwrap().path().join("src");
+            if src.is_dir() {
+                visit(&src, &mut codes);
+            }
+        }
+        codes
+    }
+
+    #[test]
+    fn codes_are_unique_and_sorted() {
+        for pair in EXPLANATIONS.windows(2) {
+            assert!(
+                pair[0].0 < pair[1].0,
+                "{} is not before {}",
+                pair[0].0,
+                pair[1].0
+            );
+        }
+    }
+
+    #[test]
+    fn emitted_codes_are_registered() {
+        let codes = codes_in_sources();
+        assert!(codes.contains("M0427"), "no codes found in the sources");
+        let unregistered: Vec<_> = codes
+            .iter()
+            .filter(|code| !EXPLANATIONS.iter().any(|(known, _)| known == code))
+            .collect();
+        assert!(
+            unregistered.is_empty(),
+            "unregistered codes: {unregistered:?}"
+        );
+    }
+
+    #[test]
+    fn every_explanation_belongs_to_a_code() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/errors/codes");
+        let files: BTreeSet<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        let expected: BTreeSet<_> = EXPLANATIONS
+            .iter()
+            .map(|(code, _)| format!("{code}.md"))
+            .collect();
+        assert_eq!(files, expected);
+        for (code, explanation) in EXPLANATIONS {
+            assert!(!explanation.trim().is_empty(), "{code} has no explanation");
+        }
+    }
+
+    #[test]
+    fn explanations_are_looked_up_leniently() {
+        let expected = explanation("M0427").unwrap();
+        for code in ["m0427", "M427", "427", " 0427 "] {
+            assert_eq!(explanation(code), Some(expected), "{code}");
+        }
+        for code in ["M9999", "X0427", "M", ""] {
+            assert_eq!(explanation(code), None, "{code}");
+        }
+    }
+}
@@ -0,0 +1,473 @@
+//! Lints - warnings whose level can be configured by the user.
+//!
+//! Every lint is identified by its name (such as `unused_variable`) and the error code of the
+//! diagnostics it emits. Lint levels can be set globally (from the command line or the project's
+//! configuration file), or within a span of source code using special comments. The levels are
+//! applied to diagnostics by [`LintLevels::apply`], which should be called by diagnostic sinks
+//! before storing any diagnostics.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{
+    errors::{codes, Diagnostic, LabelStyle, Severity},
+    span::Span,
+};
+
+/// What to do with diagnostics emitted by a lint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintLevel {
+    /// Diagnostics are discarded.
+    Allow,
+    /// Diagnostics are reported as warnings.
+    Warn,
+    /// Diagnostics are reported as errors, failing the compilation.
+    Deny,
+}
+
+impl LintLevel {
+    pub fn name(self) -> &'static str {
+        match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+        }
+    }
+}
+
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            _ => Err(format!(
+                "unknown lint level `{s}` (expected `allow`, `warn`, or `deny`)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A lint known to the compiler.
+#[derive(Debug)]
+pub struct Lint {
+    /// The name used to refer to the lint in lint levels.
+    pub name: &'static str,
+    /// The code of diagnostics emitted by the lint.
+    pub code: &'static str,
+    /// The level used when the user does not specify one.
+    pub default_level: LintLevel,
+    /// Short description of what the lint reports.
+    pub description: &'static str,
+}
+
+/// All lints known to the compiler.
+pub static LINTS: &[Lint] = &[
+    Lint {
+        name: "invalid_lint_directive",
+        code: codes::M0007,
+        default_level: LintLevel::Warn,
+        description: "lint directive comments that cannot be understood",
+    },
+    Lint {
+        name: "ignored_include",
+        code: codes::M0107,
+        default_level: LintLevel::Warn,
+        description: "`include directives naming files that cannot be found",
+    },
+    Lint {
+        name: "ignored_specifier",
+        code: codes::M0401,
+        default_level: LintLevel::Warn,
+        description: "specifiers which are accepted but have no effect",
+    },
+    Lint {
+        name: "repeated_specifier",
+        code: codes::M0402,
+        default_level: LintLevel::Warn,
+        description: "specifiers repeated within a single declaration",
+    },
+    Lint {
+        name: "ignored_cpptext",
+        code: codes::M0413,
+        default_level: LintLevel::Warn,
+        description: "`cpptext` and `structcpptext` blocks, which have no effect",
+    },
+    Lint {
+        name: "unnecessary_semicolon",
+        code: codes::M0423,
+        default_level: LintLevel::Warn,
+        description: "empty statements and extra semicolons after declarations",
+    },
+    Lint {
+        name: "possibly_uninitialized",
+        code: codes::M0439,
+        default_level: LintLevel::Warn,
+        description: "local variables that may be read before being assigned",
+    },
+    Lint {
+        name: "unused_variable",
+        code: codes::M0440,
+        default_level: LintLevel::Warn,
+        description: "variables and parameters that are never read",
+    },
+];
+
+/// Returns the lint emitting diagnostics with the given code.
+pub fn lint_by_code(code: &str) -> Option<&'static Lint> {
+    LINTS.iter().find(|lint| lint.code == code)
+}
+
+/// What a lint level is set for.
+#[derive(Debug, Clone, Copy)]
+pub enum LintSelector {
+    /// All lints; written as `warnings`.
+    Warnings,
+    /// A single lint, referred to either by its name or its code.
+    Lint(&'static Lint),
+}
+
+impl FromStr for LintSelector {
+    type Err = String;
+
+    /// Parses a lint selector. Dashes in lint names may be used in place of underscores, and
+    /// codes are matched case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().replace('-', "_");
+        if name == "warnings" {
+            return Ok(LintSelector::Warnings);
+        }
+        LINTS
+            .iter()
+            .find(|lint| lint.name == name || lint.code.eq_ignore_ascii_case(&name))
+            .map(LintSelector::Lint)
+            .ok_or_else(|| format!("unknown lint `{s}`"))
+    }
+}
+
+/// A set of lint levels coming from a single place, such as the command line or a comment.
+///
+/// Levels set for individual lints take precedence over the level set for all `warnings`.
+#[derive(Debug, Clone, Default)]
+pub struct LintSettings {
+    warnings: Option<LintLevel>,
+    lints: HashMap<&'static str, LintLevel>,
+}
+
+impl LintSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, selector: LintSelector, level: LintLevel) {
+        match selector {
+            LintSelector::Warnings => self.warnings = Some(level),
+            LintSelector::Lint(lint) => {
+                self.lints.insert(lint.name, level);
+            }
+        }
+    }
+
+    pub fn level(&self, lint: &Lint) -> Option<LintLevel> {
+        self.lints.get(lint.name).copied().or(self.warnings)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_none() && self.lints.is_empty()
+    }
+}
+
+/// Lint settings that apply within a span of source code.
+#[derive(Debug)]
+pub struct LintScope<T> {
+    pub span: Span<T>,
+    pub settings: LintSettings,
+}
+
+/// All lint levels in effect during compilation.
+#[derive(Debug)]
+pub struct LintLevels<T> {
+    /// Settings applying to all code. Later entries take precedence over earlier ones.
+    pub global: Vec<LintSettings>,
+    /// Settings applying to spans of code. The innermost scope takes precedence over outer scopes
+    /// and global settings.
+    pub scopes: Vec<LintScope<T>>,
+}
+
+impl<T> Default for LintLevels<T> {
+    fn default() -> Self {
+        Self {
+            global: vec![],
+            scopes: vec![],
+        }
+    }
+}
+
+impl<T> LintLevels<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the level of the lint at the given source code location.
+    pub fn level(&self, lint: &Lint, span: Span<T>) -> LintLevel {
+        self.scoped_level(lint, span)
+            .or_else(|| {
+                self.global
+                    .iter()
+                    .rev()
+                    .find_map(|settings| settings.level(lint))
+            })
+            .unwrap_or(lint.default_level)
+    }
+
+    fn scoped_level(&self, lint: &Lint, span: Span<T>) -> Option<LintLevel> {
+        let Span::Spanning { start, end } = span else {
+            return None;
+        };
+        let mut innermost = None;
+        for scope in &self.scopes {
+            let Span::Spanning {
+                start: scope_start,
+                end: scope_end,
+            } = scope.span
+            else {
+                continue;
+            };
+            if scope_start <= start && end <= scope_end {
+                if let Some(level) = scope.settings.level(lint) {
+                    let width = scope_end.index.get() - scope_start.index.get();
+                    // Ties go to the scope added last, such that a later comment overrides an
+                    // earlier one placed before the same code.
+                    if innermost.is_none_or(|(innermost_width, _)| width <= innermost_width) {
+                        innermost = Some((width, level));
+                    }
+                }
+            }
+        }
+        innermost.map(|(_, level)| level)
+    }
+
+    /// Applies lint levels to a diagnostic. Returns [`None`] if the diagnostic should be discarded.
+    ///
+    /// Diagnostics whose code does not belong to a lint are returned as is.
+    pub fn apply(&self, mut diagnostic: Diagnostic<T>) -> Option<Diagnostic<T>> {
+        let Some(lint) = diagnostic.code.as_deref().and_then(lint_by_code) else {
+            return Some(diagnostic);
+        };
+        let span = diagnostic
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .or_else(|| diagnostic.labels.first())
+            .map(|label| label.span)
+            .unwrap_or(Span::Empty);
+        match self.level(lint, span) {
+            LintLevel::Allow => None,
+            LintLevel::Warn => Some(diagnostic),
+            LintLevel::Deny => {
+                diagnostic.severity = diagnostic.severity.max(Severity::Error);
+                diagnostic
+                    .notes
+                    .push(format!("note: the `{}` lint is set to `deny`", lint.name).into());
+                Some(diagnostic)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::{
+        lint_by_code, LintLevel, LintLevels, LintScope, LintSelector, LintSettings, LINTS,
+    };
+    use crate::{
+        errors::{codes, Diagnostic, Label, Severity},
+        source::{SourceFile, SourceFileSet},
+        source_arena::{SourceArena, SourceId},
+        span::Span,
+    };
+
+    struct Element;
+
+    /// Returns the IDs of ten elements of a single source file.
+    fn elements() -> Vec<SourceId<Element>> {
+        let mut files = SourceFileSet::new();
+        let file = files.add(SourceFile::new(
+            Arc::from("Test"),
+            "Test.uc".to_owned(),
+            PathBuf::from("Test.uc"),
+            Arc::from(""),
+        ));
+        let mut arena = SourceArena::new();
+        let mut builder = arena.build_source_file(file);
+        (0..10).map(|_| builder.push(Element)).collect()
+    }
+
+    fn settings(levels: &[(&str, LintLevel)]) -> LintSettings {
+        let mut settings = LintSettings::new();
+        for &(selector, level) in levels {
+            settings.set(selector.parse().unwrap(), level);
+        }
+        settings
+    }
+
+    fn lint(name: &str) -> &'static super::Lint {
+        LINTS.iter().find(|lint| lint.name == name).unwrap()
+    }
+
+    #[test]
+    fn selectors() {
+        let unused = lint("unused_variable");
+        for selector in ["unused_variable", "unused-variable", " M0440", "m0440"] {
+            assert!(
+                matches!(selector.parse(), Ok(LintSelector::Lint(lint)) if std::ptr::eq(lint, unused)),
+                "{selector}"
+            );
+        }
+        assert!(matches!("warnings".parse(), Ok(LintSelector::Warnings)));
+        assert_eq!(
+            "unused_variables".parse::<LintSelector>().unwrap_err(),
+            "unknown lint `unused_variables`"
+        );
+    }
+
+    #[test]
+    fn individual_lints_take_precedence_over_warnings() {
+        let unused = lint("unused_variable");
+        let uninitialized = lint("possibly_uninitialized");
+        for levels in [
+            [
+                ("warnings", LintLevel::Deny),
+                ("unused_variable", LintLevel::Allow),
+            ],
+            [
+                ("unused_variable", LintLevel::Allow),
+                ("warnings", LintLevel::Deny),
+            ],
+        ] {
+            let settings = settings(&levels);
+            assert_eq!(settings.level(unused), Some(LintLevel::Allow));
+            assert_eq!(settings.level(uninitialized), Some(LintLevel::Deny));
+        }
+        assert_eq!(LintSettings::new().level(unused), None);
+    }
+
+    #[test]
+    fn later_global_settings_take_precedence() {
+        let ids = elements();
+        let unused = lint("unused_variable");
+        let uninitialized = lint("possibly_uninitialized");
+        let span = Span::single(ids[0]);
+
+        let mut levels = LintLevels::new();
+        assert_eq!(levels.level(unused, span), unused.default_level);
+        // The project file, followed by the command line.
+        levels.global.push(settings(&[
+            ("unused_variable", LintLevel::Deny),
+            ("possibly_uninitialized", LintLevel::Deny),
+        ]));
+        levels
+            .global
+            .push(settings(&[("unused_variable", LintLevel::Allow)]));
+        assert_eq!(levels.level(unused, span), LintLevel::Allow);
+        assert_eq!(levels.level(uninitialized, span), LintLevel::Deny);
+    }
+
+    #[test]
+    fn innermost_scope_takes_precedence() {
+        let ids = elements();
+        let unused = lint("unused_variable");
+
+        let mut levels = LintLevels::new();
+        levels
+            .global
+            .push(settings(&[("warnings", LintLevel::Deny)]));
+        // A class, a function within it, and a statement within the function.
+        levels.scopes.push(LintScope {
+            span: Span::spanning(ids[1], ids[9]),
+            settings: settings(&[("unused_variable", LintLevel::Allow)]),
+        });
+        levels.scopes.push(LintScope {
+            span: Span::spanning(ids[2], ids[6]),
+            settings: settings(&[("warnings", LintLevel::Warn)]),
+        });
+        levels.scopes.push(LintScope {
+            span: Span::spanning(ids[3], ids[4]),
+            settings: settings(&[("possibly_uninitialized", LintLevel::Allow)]),
+        });
+
+        assert_eq!(levels.level(unused, Span::single(ids[0])), LintLevel::Deny);
+        assert_eq!(levels.level(unused, Span::single(ids[1])), LintLevel::Allow);
+        assert_eq!(levels.level(unused, Span::single(ids[2])), LintLevel::Warn);
+        // The statement's scope does not set a level for the lint, so the function's is used.
+        assert_eq!(levels.level(unused, Span::single(ids[3])), LintLevel::Warn);
+        // Spans must be entirely within a scope for it to apply.
+        assert_eq!(
+            levels.level(unused, Span::spanning(ids[5], ids[7])),
+            LintLevel::Allow
+        );
+        assert_eq!(levels.level(unused, Span::Empty), LintLevel::Deny);
+    }
+
+    #[test]
+    fn later_scope_wins_ties() {
+        let ids = elements();
+        let unused = lint("unused_variable");
+
+        let mut levels = LintLevels::new();
+        for level in [LintLevel::Deny, LintLevel::Allow] {
+            levels.scopes.push(LintScope {
+                span: Span::spanning(ids[2], ids[4]),
+                settings: settings(&[("unused_variable", level)]),
+            });
+        }
+        assert_eq!(levels.level(unused, Span::single(ids[3])), LintLevel::Allow);
+    }
+
+    #[test]
+    fn apply() {
+        let ids = elements();
+        let mut levels = LintLevels::new();
+        levels.global.push(settings(&[
+            ("warnings", LintLevel::Deny),
+            ("unnecessary_semicolon", LintLevel::Allow),
+        ]));
+        let warning = |code: &str| {
+            Diagnostic::warning("warning")
+                .with_code(code)
+                .with_label(Label::secondary(&Span::single(ids[0]), ""))
+                .with_label(Label::primary(&Span::single(ids[5]), ""))
+        };
+
+        assert!(levels.apply(warning(codes::M0423)).is_none());
+
+        let denied = levels.apply(warning(codes::M0440)).unwrap();
+        assert_eq!(denied.severity, Severity::Error);
+        assert_eq!(
+            denied.notes.last().unwrap().text,
+            "note: the `unused_variable` lint is set to `deny`"
+        );
+
+        // Diagnostics which are not lints are not affected.
+        assert!(lint_by_code(codes::M0427).is_none());
+        let not_a_lint = levels.apply(warning(codes::M0427)).unwrap();
+        assert_eq!(not_a_lint.severity, Severity::Warning);
+        assert!(not_a_lint.notes.is_empty());
+
+        // The level is looked up at the primary label.
+        levels.scopes.push(LintScope {
+            span: Span::single(ids[5]),
+            settings: settings(&[("unused_variable", LintLevel::Allow)]),
+        });
+        assert!(levels.apply(warning(codes::M0440)).is_none());
+    }
+}
@@ -0,0 +1,581 @@
+//! Output formats for diagnostics other than the codespan-style terminal rendering: a condensed
+//! single-line format, and machine-readable JSON and SARIF for tools consuming diagnostics.
+
+use std::{ops::Range, path::Path};
+
+use codespan_reporting::files::Files;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    errors::{
+        Applicability, Diagnostic, DiagnosticConfig, LabelStyle, NoteKind, ReplacementSuggestion,
+        Severity, SourceRange,
+    },
+    source::{SourceFileId, SourceFileSet},
+    source_arena::SourceArena,
+};
+
+/// Position within a source file. Both lines and columns start at 1, and columns are counted in
+/// Unicode scalar values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Range of a source file, resolved to both bytes and lines and columns.
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonLocation {
+    /// The file's pretty name, as shown in terminal output.
+    pub file: String,
+    /// The full path to the file.
+    pub path: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonLabel {
+    pub style: LabelStyle,
+    #[serde(flatten)]
+    pub location: JsonLocation,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonSuggestion {
+    #[serde(flatten)]
+    pub location: JsonLocation,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonNote {
+    pub kind: NoteKind,
+    pub text: String,
+    pub suggestion: Option<JsonSuggestion>,
+}
+
+/// A [`Diagnostic`] with all of its spans resolved, ready to be serialized.
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonDiagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub labels: Vec<JsonLabel>,
+    pub notes: Vec<JsonNote>,
+    pub children: Vec<JsonDiagnostic>,
+}
+
+impl JsonLocation {
+    fn resolve(files: &SourceFileSet, file: SourceFileId, range: Range<usize>) -> Option<Self> {
+        let line_column = |byte_index| {
+            files
+                .location(file, byte_index)
+                .ok()
+                .map(|location| LineColumn {
+                    line: location.line_number,
+                    column: location.column_number,
+                })
+        };
+        let source_file = files.get(file);
+        Some(Self {
+            file: source_file.filename.clone(),
+            path: source_file.full_path.to_string_lossy().into_owned(),
+            start: line_column(range.start)?,
+            end: line_column(range.end)?,
+            byte_start: range.start,
+            byte_end: range.end,
+        })
+    }
+}
+
+impl JsonSuggestion {
+    fn resolve(files: &SourceFileSet, suggestion: &ReplacementSuggestion) -> Option<Self> {
+        Some(Self {
+            location: JsonLocation::resolve(files, suggestion.file, suggestion.span.clone())?,
+            replacement: suggestion.replacement.clone(),
+            applicability: suggestion.applicability,
+        })
+    }
+}
+
+impl<T> Diagnostic<T>
+where
+    T: SourceRange,
+{
+    /// Resolves the diagnostic's spans to files, lines, and columns, so that it can be serialized.
+    ///
+    /// Labels with empty spans are dropped, as are debug notes unless the config asks for them.
+    pub fn to_json(
+        &self,
+        files: &SourceFileSet,
+        source_arena: &SourceArena<T>,
+        config: &DiagnosticConfig,
+    ) -> JsonDiagnostic {
+        JsonDiagnostic {
+            severity: self.severity,
+            code: self.code.clone(),
+            message: self.message.clone(),
+            labels: self
+                .labels
+                .iter()
+                .filter_map(|label| {
+                    let (file, range) = label.source_range(source_arena)?;
+                    Some(JsonLabel {
+                        style: label.style,
+                        location: JsonLocation::resolve(files, file, range)?,
+                        message: label.message.clone(),
+                    })
+                })
+                .collect(),
+            notes: self
+                .notes
+                .iter()
+                .filter(|&note| note.kind != NoteKind::Debug || config.show_debug_info)
+                .map(|note| JsonNote {
+                    kind: note.kind,
+                    text: note.text.clone(),
+                    suggestion: note
+                        .suggestion
+                        .as_ref()
+                        .and_then(|suggestion| JsonSuggestion::resolve(files, suggestion)),
+                })
+                .collect(),
+            children: self
+                .children
+                .iter()
+                .map(|child| child.to_json(files, source_arena, config))
+                .collect(),
+        }
+    }
+}
+
+impl JsonDiagnostic {
+    /// Location of the first primary label, which is where the diagnostic is reported in
+    /// condensed output formats.
+    pub fn primary_location(&self) -> Option<&JsonLocation> {
+        self.labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .or_else(|| self.labels.first())
+            .map(|label| &label.location)
+    }
+
+    /// Renders the diagnostic in the condensed `file:line:column: severity[code]: message`
+    /// format, one line for the diagnostic itself and one for each of its children.
+    pub fn to_short(&self) -> String {
+        let mut short = String::new();
+        self.write_short(&mut short);
+        short
+    }
+
+    fn write_short(&self, out: &mut String) {
+        if let Some(location) = self.primary_location() {
+            out.push_str(&format!(
+                "{}:{}:{}: ",
+                location.file, location.start.line, location.start.column
+            ));
+        }
+        out.push_str(self.severity.name());
+        if let Some(code) = &self.code {
+            out.push_str(&format!("[{code}]"));
+        }
+        out.push_str(": ");
+        out.push_str(&self.message);
+        out.push('\n');
+        for child in &self.children {
+            child.write_short(out);
+        }
+    }
+}
+
+/// Builds a [SARIF](https://sarifweb.azurewebsites.net/) log containing a single run of the
+/// compiler, reporting all the given diagnostics.
+///
+/// SARIF does not support nesting results, so children are folded into their parent
+/// diagnostic's related locations and fixes.
+pub fn sarif_log(diagnostics: &[JsonDiagnostic]) -> Value {
+    let mut rule_ids: Vec<&str> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| diagnostic.code.as_deref())
+        .collect();
+    rule_ids.sort_unstable();
+    rule_ids.dedup();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "muscript",
+                    "informationUri": "https://github.com/abyteintime/stitchkit",
+                    "rules": rule_ids
+                        .iter()
+                        .map(|id| json!({ "id": id }))
+                        .collect::<Vec<_>>(),
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": diagnostics.iter().map(sarif_result).collect::<Vec<_>>(),
+        }],
+    })
+}
+
+fn sarif_result(diagnostic: &JsonDiagnostic) -> Value {
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push('\n');
+        message.push_str(&note.text);
+    }
+
+    let mut related_locations = vec![];
+    let mut fixes = vec![];
+    collect_related(diagnostic, None, &mut related_locations, &mut fixes);
+
+    let mut result = json!({
+        "level": match diagnostic.severity {
+            Severity::Bug | Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note | Severity::Help => "note",
+        },
+        "message": { "text": message },
+        "locations": diagnostic
+            .labels
+            .iter()
+            .filter(|label| label.style == LabelStyle::Primary)
+            .map(|label| sarif_location(&label.location, &label.message))
+            .collect::<Vec<_>>(),
+        "relatedLocations": related_locations,
+        "fixes": fixes,
+    });
+    if let Some(code) = &diagnostic.code {
+        result["ruleId"] = json!(code);
+    }
+    result
+}
+
+fn collect_related(
+    diagnostic: &JsonDiagnostic,
+    child_message: Option<&str>,
+    related_locations: &mut Vec<Value>,
+    fixes: &mut Vec<Value>,
+) {
+    for label in &diagnostic.labels {
+        // Primary labels of the top-level diagnostic are reported as its main locations.
+        if child_message.is_none() && label.style == LabelStyle::Primary {
+            continue;
+        }
+        let message = match child_message {
+            Some(child_message) if label.message.is_empty() => child_message.to_owned(),
+            Some(child_message) => format!("{child_message}: {}", label.message),
+            None => label.message.clone(),
+        };
+        related_locations.push(sarif_location(&label.location, &message));
+    }
+    for note in &diagnostic.notes {
+        if let Some(suggestion) = &note.suggestion {
+            fixes.push(json!({
+                "description": { "text": note.text },
+                "artifactChanges": [{
+                    "artifactLocation": { "uri": file_uri(&suggestion.location.path) },
+                    "replacements": [{
+                        "deletedRegion": sarif_region(&suggestion.location),
+                        "insertedContent": { "text": suggestion.replacement },
+                    }],
+                }],
+            }));
+        }
+    }
+    for child in &diagnostic.children {
+        collect_related(child, Some(&child.message), related_locations, fixes);
+    }
+}
+
+fn sarif_location(location: &JsonLocation, message: &str) -> Value {
+    let mut sarif_location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": file_uri(&location.path) },
+            "region": sarif_region(location),
+        },
+    });
+    if !message.is_empty() {
+        sarif_location["message"] = json!({ "text": message });
+    }
+    sarif_location
+}
+
+fn sarif_region(location: &JsonLocation) -> Value {
+    json!({
+        "startLine": location.start.line,
+        "startColumn": location.start.column,
+        "endLine": location.end.line,
+        "endColumn": location.end.column,
+        "byteOffset": location.byte_start,
+        "byteLength": location.byte_end - location.byte_start,
+    })
+}
+
+/// Converts a path to a `file` URI, or to a relative URI reference if the path is relative.
+fn file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    if path.as_bytes().get(1) == Some(&b':') {
+        // Absolute Windows paths need an extra slash, as in `file:///C:/...`. The colon after the
+        // drive letter is the only character that is not percent-encoded.
+        let (drive, rest) = path.split_at(2);
+        format!("file:///{drive}{}", percent_encode_path(rest))
+    } else if Path::new(&path).is_absolute() {
+        format!("file://{}", percent_encode_path(&path))
+    } else {
+        percent_encode_path(&path)
+    }
+}
+
+/// Percent-encodes all bytes of the path other than slashes and the characters RFC 3986 calls
+/// unreserved, such that spaces and characters like `#` or `?` are not mistaken for parts of the
+/// URI's syntax.
+fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for &byte in path.as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Range, path::PathBuf, sync::Arc};
+
+    use serde_json::json;
+
+    use super::{file_uri, sarif_log};
+    use crate::{
+        errors::{
+            Applicability, Diagnostic, DiagnosticConfig, Label, Note, NoteKind,
+            ReplacementSuggestion, SourceRange,
+        },
+        source::{SourceFile, SourceFileSet},
+        source_arena::SourceArena,
+        span::Span,
+    };
+
+    struct Element(Range<usize>);
+
+    impl SourceRange for Element {
+        fn source_range(&self) -> Range<usize> {
+            self.0.clone()
+        }
+    }
+
+    const SOURCE: &str = "local int Cöunt;\nCount = 1;\n";
+
+    /// Resolves a diagnostic with a primary and a secondary label, a note with a suggestion, a
+    /// debug note, and a child, reported in a file whose path needs percent-encoding.
+    fn resolved(config: &DiagnosticConfig) -> super::JsonDiagnostic {
+        let mut files = SourceFileSet::new();
+        let file = files.add(SourceFile::new(
+            Arc::from("MyMod"),
+            "MyMod/Classes/Test.uc".to_owned(),
+            PathBuf::from("/mods/My Mod #2/Classes/Test.uc"),
+            Arc::from(SOURCE),
+        ));
+        let mut arena = SourceArena::new();
+        let mut builder = arena.build_source_file(file);
+        // `Cöunt` in the declaration, and `Count` in the assignment.
+        let declaration = builder.push(Element(10..16));
+        let usage = builder.push(Element(18..23));
+        builder.finish();
+
+        let diagnostic = Diagnostic::error("cannot find variable `Count`")
+            .with_code("M0102")
+            .with_label(Label::primary(&Span::single(usage), "not found"))
+            .with_label(Label::secondary(&Span::single(declaration), ""))
+            .with_note((
+                "help: did you mean `Cöunt`?",
+                ReplacementSuggestion {
+                    file,
+                    span: 18..23,
+                    replacement: "Cöunt".to_owned(),
+                    applicability: Applicability::MaybeIncorrect,
+                },
+            ))
+            .with_note(Note {
+                kind: NoteKind::Debug,
+                text: "debug: looked in 1 scope".to_owned(),
+                suggestion: None,
+            })
+            .with_child(
+                Diagnostic::note("variable declared here")
+                    .with_label(Label::primary(&Span::single(declaration), "")),
+            );
+        diagnostic.to_json(&files, &arena, config)
+    }
+
+    #[test]
+    fn json() {
+        let diagnostic = resolved(&DiagnosticConfig {
+            show_debug_info: false,
+        });
+        let location = |byte_start, byte_end, start_column, end_column, line| {
+            json!({
+                "file": "MyMod/Classes/Test.uc",
+                "path": "/mods/My Mod #2/Classes/Test.uc",
+                "byte_start": byte_start,
+                "byte_end": byte_end,
+                "start": { "line": line, "column": start_column },
+                "end": { "line": line, "column": end_column },
+            })
+        };
+        let with = |mut location: serde_json::Value, fields: serde_json::Value| {
+            for (key, value) in fields.as_object().unwrap() {
+                location[key] = value.clone();
+            }
+            location
+        };
+        assert_eq!(
+            serde_json::to_value(&diagnostic).unwrap(),
+            json!({
+                "severity": "error",
+                "code": "M0102",
+                "message": "cannot find variable `Count`",
+                "labels": [
+                    with(
+                        location(18, 23, 1, 6, 2),
+                        json!({ "style": "primary", "message": "not found" }),
+                    ),
+                    with(
+                        location(10, 16, 11, 16, 1),
+                        json!({ "style": "secondary", "message": "" }),
+                    ),
+                ],
+                "notes": [{
+                    "kind": "normal",
+                    "text": "help: did you mean `Cöunt`?",
+                    "suggestion": with(
+                        location(18, 23, 1, 6, 2),
+                        json!({ "replacement": "Cöunt", "applicability": "maybe_incorrect" }),
+                    ),
+                }],
+                "children": [{
+                    "severity": "note",
+                    "code": null,
+                    "message": "variable declared here",
+                    "labels": [with(
+                        location(10, 16, 11, 16, 1),
+                        json!({ "style": "primary", "message": "" }),
+                    )],
+                    "notes": [],
+                    "children": [],
+                }],
+            })
+        );
+
+        let with_debug_info = resolved(&DiagnosticConfig {
+            show_debug_info: true,
+        });
+        assert_eq!(with_debug_info.notes.len(), 2);
+        assert_eq!(with_debug_info.notes[1].text, "debug: looked in 1 scope");
+    }
+
+    #[test]
+    fn short() {
+        let diagnostic = resolved(&DiagnosticConfig {
+            show_debug_info: false,
+        });
+        assert_eq!(
+            diagnostic.to_short(),
+            "\
+MyMod/Classes/Test.uc:2:1: error[M0102]: cannot find variable `Count`
+MyMod/Classes/Test.uc:1:11: note: variable declared here
+"
+        );
+    }
+
+    #[test]
+    fn sarif() {
+        let diagnostic = resolved(&DiagnosticConfig {
+            show_debug_info: false,
+        });
+        let uri = "file:///mods/My%20Mod%20%232/Classes/Test.uc";
+        let region = |byte_offset, byte_length, start_column, end_column, line| {
+            json!({
+                "startLine": line,
+                "startColumn": start_column,
+                "endLine": line,
+                "endColumn": end_column,
+                "byteOffset": byte_offset,
+                "byteLength": byte_length,
+            })
+        };
+        let log = sarif_log(&[diagnostic]);
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(
+            log["runs"][0]["tool"]["driver"]["rules"],
+            json!([{ "id": "M0102" }])
+        );
+        assert_eq!(
+            log["runs"][0]["results"],
+            json!([{
+                "ruleId": "M0102",
+                "level": "error",
+                "message": {
+                    "text": "cannot find variable `Count`\nhelp: did you mean `Cöunt`?",
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                        "region": region(18, 5, 1, 6, 2),
+                    },
+                    "message": { "text": "not found" },
+                }],
+                "relatedLocations": [
+                    {
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": uri },
+                            "region": region(10, 6, 11, 16, 1),
+                        },
+                    },
+                    {
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": uri },
+                            "region": region(10, 6, 11, 16, 1),
+                        },
+                        "message": { "text": "variable declared here" },
+                    },
+                ],
+                "fixes": [{
+                    "description": { "text": "help: did you mean `Cöunt`?" },
+                    "artifactChanges": [{
+                        "artifactLocation": { "uri": uri },
+                        "replacements": [{
+                            "deletedRegion": region(18, 5, 1, 6, 2),
+                            "insertedContent": { "text": "Cöunt" },
+                        }],
+                    }],
+                }],
+            }])
+        );
+    }
+
+    #[test]
+    fn file_uris_are_percent_encoded() {
+        assert_eq!(
+            file_uri("/home/user/My Mod/Classes/A#B.uc"),
+            "file:///home/user/My%20Mod/Classes/A%23B.uc"
+        );
+        assert_eq!(
+            file_uri("C:\\UDK\\Development\\Src\\Mod (old)\\Classes\\Ä.uc"),
+            "file:///C:/UDK/Development/Src/Mod%20%28old%29/Classes/%C3%84.uc"
+        );
+        assert_eq!(file_uri("Classes/50%?.uc"), "Classes/50%25%3F.uc");
+    }
+}
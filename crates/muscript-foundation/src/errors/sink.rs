@@ -1,10 +1,15 @@
 use tracing::warn;
 
-use crate::errors::Diagnostic;
+use crate::errors::{lints::LintScope, Diagnostic};
 
 /// Diagnostic sink - anything that can collect diagnostics for later display.
 pub trait DiagnosticSink<T> {
     fn emit(&mut self, diagnostic: Diagnostic<T>);
+
+    /// Sets lint levels within a span of source code, for diagnostics emitted from then on.
+    ///
+    /// Sinks which do not filter diagnostics by lint level may ignore this.
+    fn set_lint_levels(&mut self, _scope: LintScope<T>) {}
 }
 
 impl<T> DiagnosticSink<T> for () {
@@ -31,6 +36,52 @@ impl<T> DiagnosticSink<T> for Option<Diagnostic<T>> {
     }
 }
 
+/// Sink which records diagnostics and lint levels in the order they were emitted, so that they can
+/// be replayed into another sink later. This is useful for collecting diagnostics on other threads
+/// without making their order depend on thread scheduling.
+#[derive(Debug)]
+pub struct RecordingSink<T> {
+    events: Vec<SinkEvent<T>>,
+}
+
+#[derive(Debug)]
+enum SinkEvent<T> {
+    Emit(Diagnostic<T>),
+    SetLintLevels(LintScope<T>),
+}
+
+impl<T> RecordingSink<T> {
+    pub fn new() -> Self {
+        Self { events: vec![] }
+    }
+
+    /// Emits all recorded diagnostics and lint levels into `sink`, in their original order.
+    pub fn replay_into(self, sink: &mut dyn DiagnosticSink<T>) {
+        for event in self.events {
+            match event {
+                SinkEvent::Emit(diagnostic) => sink.emit(diagnostic),
+                SinkEvent::SetLintLevels(scope) => sink.set_lint_levels(scope),
+            }
+        }
+    }
+}
+
+impl<T> Default for RecordingSink<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> DiagnosticSink<T> for RecordingSink<T> {
+    fn emit(&mut self, diagnostic: Diagnostic<T>) {
+        self.events.push(SinkEvent::Emit(diagnostic));
+    }
+
+    fn set_lint_levels(&mut self, scope: LintScope<T>) {
+        self.events.push(SinkEvent::SetLintLevels(scope));
+    }
+}
+
 pub fn pipe_all_diagnostics_into<T, I>(sink: &mut dyn DiagnosticSink<T>, source: I)
 where
     I: IntoIterator<Item = Diagnostic<T>>,
@@ -7,12 +7,37 @@ use std::{
 };
 
 use ref_cast::RefCast;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// ASCII-insensitive identifier.
 #[derive(Clone, Copy, RefCast)]
 #[repr(transparent)]
 pub struct CaseInsensitive<S: ?Sized>(S);
 
+impl<S> Serialize for CaseInsensitive<S>
+where
+    S: ?Sized + Serialize,
+{
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, S> Deserialize<'de> for CaseInsensitive<S>
+where
+    S: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        S::deserialize(deserializer).map(Self)
+    }
+}
+
 impl<S> CaseInsensitive<S> {
     pub fn new(inner: S) -> Self {
         Self(inner)
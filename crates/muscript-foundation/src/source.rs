@@ -2,7 +2,7 @@
 
 use std::{
     path::{Path, PathBuf},
-    rc::Rc,
+    sync::{Arc, OnceLock},
 };
 
 use codespan_reporting::files::Files;
@@ -12,29 +12,73 @@ use thiserror::Error;
 #[derive(Debug, Clone)]
 pub struct SourceFile {
     /// The package this source file belongs to.
-    pub package: Rc<str>,
+    pub package: Arc<str>,
     /// The source file's pretty name.
     pub filename: String,
     /// The full path to the source file.
     pub full_path: PathBuf,
-    /// The source code.
-    pub source: Rc<str>,
 
+    text: OnceLock<SourceText>,
+}
+
+#[derive(Debug, Clone)]
+struct SourceText {
+    source: Arc<str>,
     line_starts: Vec<usize>,
 }
 
+impl SourceText {
+    fn new(source: Arc<str>) -> Self {
+        Self {
+            line_starts: codespan_reporting::files::line_starts(&source).collect(),
+            source,
+        }
+    }
+}
+
 impl SourceFile {
     /// Creates a new [`SourceFile`].
-    pub fn new(package: Rc<str>, filename: String, full_path: PathBuf, source: Rc<str>) -> Self {
+    pub fn new(package: Arc<str>, filename: String, full_path: PathBuf, source: Arc<str>) -> Self {
         Self {
             package,
             filename,
             full_path,
-            line_starts: codespan_reporting::files::line_starts(&source).collect(),
-            source,
+            text: OnceLock::from(SourceText::new(source)),
         }
     }
 
+    /// Creates a new [`SourceFile`] whose source code is not known yet. It has to be loaded with
+    /// [`SourceFileSet::load`] before it's used.
+    pub fn unloaded(package: Arc<str>, filename: String, full_path: PathBuf) -> Self {
+        Self {
+            package,
+            filename,
+            full_path,
+            text: OnceLock::new(),
+        }
+    }
+
+    /// Returns whether the source code of the file has been loaded.
+    pub fn is_loaded(&self) -> bool {
+        self.text.get().is_some()
+    }
+
+    /// Returns the source code.
+    ///
+    /// # Panics
+    ///
+    /// If the source code has not been loaded yet.
+    pub fn source(&self) -> &Arc<str> {
+        &self.text().source
+    }
+
+    #[track_caller]
+    fn text(&self) -> &SourceText {
+        self.text
+            .get()
+            .unwrap_or_else(|| panic!("source file {} was not loaded", self.filename))
+    }
+
     /// Returns the name of the class this source file declares, or [`Err`] if the filename does not
     /// contain a class name or contains invalid UTF-8 characters.
     pub fn class_name(&self) -> Result<&str, ClassNameError<'_>> {
@@ -54,16 +98,17 @@ impl SourceFile {
     fn line_start(&self, line_index: usize) -> Result<usize, codespan_reporting::files::Error> {
         use std::cmp::Ordering;
 
-        match line_index.cmp(&self.line_starts.len()) {
-            Ordering::Less => Ok(self
+        let text = self.text();
+        match line_index.cmp(&text.line_starts.len()) {
+            Ordering::Less => Ok(text
                 .line_starts
                 .get(line_index)
                 .cloned()
                 .expect("failed despite previous check")),
-            Ordering::Equal => Ok(self.source.len()),
+            Ordering::Equal => Ok(text.source.len()),
             Ordering::Greater => Err(codespan_reporting::files::Error::LineTooLarge {
                 given: line_index,
-                max: self.line_starts.len() - 1,
+                max: text.line_starts.len() - 1,
             }),
         }
     }
@@ -104,6 +149,23 @@ impl SourceFileSet {
         &self.source_files[file.0]
     }
 
+    /// Replaces the source code of a file, eg. after it's been edited.
+    ///
+    /// Note that any tokens lexed from the old source code will no longer refer to the
+    /// correct ranges of the file.
+    pub fn replace_source(&mut self, file: SourceFileId, source: Arc<str>) {
+        self.source_files[file.0].text = OnceLock::from(SourceText::new(source));
+    }
+
+    /// Loads the source code of a file added with [`SourceFile::unloaded`]. Does nothing if the
+    /// file's source code is already loaded.
+    ///
+    /// Unlike [`SourceFileSet::replace_source`], this only needs shared access to the set, so
+    /// that files can be loaded on demand while the set is borrowed by the compiler.
+    pub fn load(&self, file: SourceFileId, source: Arc<str>) {
+        _ = self.source_files[file.0].text.set(SourceText::new(source));
+    }
+
     pub fn len(&self) -> usize {
         self.source_files.len()
     }
@@ -122,7 +184,7 @@ impl SourceFileSet {
     }
 
     pub fn source(&self, file: SourceFileId) -> &str {
-        &self.get(file).source
+        self.get(file).source()
     }
 }
 
@@ -139,7 +201,7 @@ impl<'f> Files<'f> for SourceFileSet {
         &'f self,
         id: Self::FileId,
     ) -> Result<Self::Source, codespan_reporting::files::Error> {
-        Ok(&self.source_files[id.0].source)
+        Ok(self.source_files[id.0].source())
     }
 
     fn line_index(
@@ -148,6 +210,7 @@ impl<'f> Files<'f> for SourceFileSet {
         byte_index: usize,
     ) -> Result<usize, codespan_reporting::files::Error> {
         Ok(self.source_files[id.0]
+            .text()
             .line_starts
             .binary_search(&byte_index)
             .unwrap_or_else(|next_line| next_line - 1))
@@ -5,6 +5,8 @@ use std::{
     num::NonZeroU32,
 };
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 use crate::{source::SourceFileId, span::Span};
 
 /// ID of an element within a [`SourceArena<T>`].
@@ -19,6 +21,9 @@ pub struct SourceId<T> {
 #[derive(Debug, Clone)]
 pub struct SourceArena<T> {
     source_file_id_mapping: Vec<(SourceId<T>, SourceFileId)>,
+    /// Starts of the entries of `source_file_id_mapping` added by
+    /// [`SourceArena::build_synthesized`], in ascending order.
+    synthesized_starts: Vec<SourceId<T>>,
     elements: Vec<T>,
 }
 
@@ -26,6 +31,7 @@ impl<T> SourceArena<T> {
     pub fn new() -> Self {
         Self {
             source_file_id_mapping: vec![],
+            synthesized_starts: vec![],
             elements: vec![],
         }
     }
@@ -38,7 +44,7 @@ impl<T> SourceArena<T> {
         }
     }
 
-    pub fn build_source_file(&mut self, source_file_id: SourceFileId) -> SourceArenaBuilder<T> {
+    pub fn build_source_file(&mut self, source_file_id: SourceFileId) -> SourceArenaBuilder<'_, T> {
         let start = self.current_element_id();
         self.source_file_id_mapping.push((start, source_file_id));
         SourceArenaBuilder {
@@ -47,10 +53,96 @@ impl<T> SourceArena<T> {
         }
     }
 
+    /// Starts building elements which were synthesized from elements of the given source file,
+    /// rather than read from the file itself. These belong to the file as far as
+    /// [`SourceArena::source_file_id`] is concerned, but are not part of the span returned by
+    /// [`SourceArena::source_file_span`].
+    pub fn build_synthesized(&mut self, source_file_id: SourceFileId) -> SourceArenaBuilder<'_, T> {
+        let start = self.current_element_id();
+        self.synthesized_starts.push(start);
+        self.build_source_file(source_file_id)
+    }
+
+    /// Returns the number of elements in the arena, including those of files that were added to
+    /// it more than once.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
     pub fn element(&self, id: SourceId<T>) -> &T {
         &self.elements[(u32::from(id.index) - 1) as usize]
     }
 
+    /// Returns all elements within the span.
+    pub fn elements_in(&self, span: Span<T>) -> &[T] {
+        match span {
+            Span::Empty => &[],
+            Span::Spanning { start, end } => {
+                &self.elements[(start.index.get() - 1) as usize..end.index.get() as usize]
+            }
+        }
+    }
+
+    /// Returns the span of all elements of the given source file. If the file was added to the
+    /// arena more than once, returns the span of the elements added last.
+    pub fn source_file_span(&self, source_file_id: SourceFileId) -> Span<T> {
+        let Some(i) = self
+            .source_file_id_mapping
+            .iter()
+            .rposition(|&(start, id)| {
+                id == source_file_id && self.synthesized_starts.binary_search(&start).is_err()
+            })
+        else {
+            return Span::Empty;
+        };
+        let start = self.source_file_id_mapping[i].0;
+        let end_index = match self.source_file_id_mapping.get(i + 1) {
+            Some(&(next_start, _)) => next_start.index.get() - 1,
+            None => self.elements.len() as u32,
+        };
+        match NonZeroU32::new(end_index) {
+            Some(end) if end >= start.index => Span::spanning(start, SourceId::new(end)),
+            _ => Span::Empty,
+        }
+    }
+
+    /// Moves all elements of `other` to the end of this arena, which lets source files be built
+    /// into separate arenas (eg. on different threads) and merged afterwards. Returns the
+    /// [`Relocation`] mapping IDs from `other` onto their new IDs in this arena.
+    pub fn append(&mut self, other: SourceArena<T>) -> Relocation {
+        let offset = self.elements.len() as u32;
+        let mut relocation = Relocation::new();
+        if !other.elements.is_empty() {
+            relocation
+                .ranges
+                .push((1, offset + 1, other.elements.len() as u32));
+        }
+        self.source_file_id_mapping
+            .extend(
+                other
+                    .source_file_id_mapping
+                    .into_iter()
+                    .map(|(start, source_file_id)| {
+                        (
+                            SourceId::new(start.index.saturating_add(offset)),
+                            source_file_id,
+                        )
+                    }),
+            );
+        self.synthesized_starts.extend(
+            other
+                .synthesized_starts
+                .into_iter()
+                .map(|start| SourceId::new(start.index.saturating_add(offset))),
+        );
+        self.elements.extend(other.elements);
+        relocation
+    }
+
     pub fn source_file_id(&self, id: SourceId<T>) -> SourceFileId {
         match self
             .source_file_id_mapping
@@ -154,3 +246,237 @@ impl<T> Hash for SourceId<T> {
         self.index.hash(state);
     }
 }
+
+impl<T> Serialize for SourceId<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.index.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for SourceId<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        NonZeroU32::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Maps [`SourceId`]s saved by an earlier compilation onto the IDs the same elements have in the
+/// current arena, where they may have been allocated at different positions.
+///
+/// Values deserialized from an earlier compilation hold the IDs they were saved with, and must be
+/// brought up to date with [`Relocate::relocate`] before they're used.
+#[derive(Debug, Clone, Default)]
+pub struct Relocation {
+    /// Tuples of `(old_start, new_start, len)`.
+    ranges: Vec<(u32, u32, u32)>,
+}
+
+impl Relocation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares that the elements previously spanning `old` now span `new`. Returns [`None`] if
+    /// the spans have different lengths, in which case the elements cannot be the same.
+    pub fn add<T>(&mut self, old: Span<T>, new: Span<T>) -> Option<()> {
+        match (old, new) {
+            (Span::Empty, Span::Empty) => Some(()),
+            (
+                Span::Spanning {
+                    start: old_start,
+                    end: old_end,
+                },
+                Span::Spanning {
+                    start: new_start,
+                    end: new_end,
+                },
+            ) => {
+                let len = old_end.index.get() - old_start.index.get() + 1;
+                if new_end.index.get() - new_start.index.get() + 1 != len {
+                    return None;
+                }
+                self.ranges
+                    .push((old_start.index.get(), new_start.index.get(), len));
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    fn relocate(&self, index: NonZeroU32) -> Option<NonZeroU32> {
+        let index = index.get();
+        self.ranges
+            .iter()
+            .find(|&&(old_start, _, len)| index >= old_start && index - old_start < len)
+            .and_then(|&(old_start, new_start, _)| NonZeroU32::new(new_start + (index - old_start)))
+    }
+
+    /// Relocates a single ID. Returns [`None`] if it does not lie within any of the relocated
+    /// spans.
+    pub fn relocate_id<T>(&self, id: SourceId<T>) -> Option<SourceId<T>> {
+        self.relocate(id.index).map(SourceId::new)
+    }
+
+    /// Relocates both ends of a span.
+    pub fn relocate_span<T>(&self, span: Span<T>) -> Option<Span<T>> {
+        match span {
+            Span::Empty => Some(Span::Empty),
+            Span::Spanning { start, end } => Some(Span::spanning(
+                self.relocate_id(start)?,
+                self.relocate_id(end)?,
+            )),
+        }
+    }
+}
+
+/// Values which refer to elements of a [`SourceArena`] by ID, and whose IDs can be relocated.
+pub trait Relocate {
+    /// Relocates every ID within the value. Returns [`None`] if any of them does not lie within
+    /// the relocated spans, in which case the value is left partially relocated and must not be
+    /// used.
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()>;
+}
+
+impl<T> Relocate for SourceId<T> {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        *self = relocation.relocate_id(*self)?;
+        Some(())
+    }
+}
+
+impl<T> Relocate for Span<T> {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        *self = relocation.relocate_span(*self)?;
+        Some(())
+    }
+}
+
+impl<T> Relocate for Option<T>
+where
+    T: Relocate,
+{
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        match self {
+            Some(value) => value.relocate(relocation),
+            None => Some(()),
+        }
+    }
+}
+
+impl<T> Relocate for Box<T>
+where
+    T: Relocate + ?Sized,
+{
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        (**self).relocate(relocation)
+    }
+}
+
+impl<T> Relocate for [T]
+where
+    T: Relocate,
+{
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.iter_mut()
+            .try_for_each(|element| element.relocate(relocation))
+    }
+}
+
+impl<T> Relocate for Vec<T>
+where
+    T: Relocate,
+{
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.as_mut_slice().relocate(relocation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::{Relocate, SourceArena};
+    use crate::{
+        source::{SourceFile, SourceFileId, SourceFileSet},
+        span::Span,
+    };
+
+    fn add_file(files: &mut SourceFileSet, filename: &str) -> SourceFileId {
+        files.add(SourceFile::new(
+            Arc::from("Test"),
+            filename.to_owned(),
+            PathBuf::from(filename),
+            Arc::from(""),
+        ))
+    }
+
+    fn build(
+        arena: &mut SourceArena<&'static str>,
+        file: SourceFileId,
+        words: &[&'static str],
+    ) -> Span<&'static str> {
+        let mut builder = arena.build_source_file(file);
+        for word in words {
+            builder.push(word);
+        }
+        builder.finish()
+    }
+
+    fn text(arena: &SourceArena<&'static str>, span: Span<&'static str>) -> String {
+        arena.elements_in(span).join(" ")
+    }
+
+    #[test]
+    fn appended_spans_are_relocated() {
+        let mut files = SourceFileSet::new();
+        let a = add_file(&mut files, "A.uc");
+        let b = add_file(&mut files, "B.uc");
+        let c = add_file(&mut files, "C.uc");
+
+        let mut arena = SourceArena::new();
+        build(&mut arena, a, &["class", "A", ";"]);
+
+        // Built separately, as if on another thread.
+        let mut other = SourceArena::new();
+        let b_span = build(&mut other, b, &["class", "B", ";"]);
+        let mut synthesized = other.build_synthesized(b);
+        synthesized.push("synthesized");
+        synthesized.finish();
+        let c_span = build(&mut other, c, &["var", "int", "X", ";"]);
+        let Span::Spanning { start: var, end } = c_span else {
+            unreachable!()
+        };
+        let int_x = Span::spanning(var.successor(), end.predecessor().unwrap());
+        let texts: Vec<_> = [b_span, c_span, int_x]
+            .iter()
+            .map(|&span| text(&other, span))
+            .collect();
+
+        let relocation = arena.append(other);
+        let mut relocated = vec![b_span, c_span, int_x];
+        relocated.relocate(&relocation).unwrap();
+        for ((span, relocated), text_before) in
+            [b_span, c_span, int_x].iter().zip(&relocated).zip(&texts)
+        {
+            assert_ne!(span, relocated);
+            assert_eq!(&text(&arena, *relocated), text_before);
+        }
+        assert_eq!(texts, ["class B ;", "var int X ;", "int X"]);
+
+        assert_eq!(text(&arena, arena.source_file_span(a)), "class A ;");
+        assert_eq!(arena.source_file_span(b), relocated[0]);
+        assert_eq!(arena.source_file_span(c), relocated[1]);
+        let synthesized = relocated[0].end().unwrap().successor();
+        assert_eq!(*arena.element(synthesized), "synthesized");
+        assert_eq!(arena.source_file_id(synthesized), b);
+        assert_eq!(arena.source_file_id(relocated[2].start().unwrap()), c);
+
+        // IDs past the end of the appended arena were never part of it.
+        assert_eq!(relocation.relocate_id(end.successor()), None);
+    }
+}
@@ -1,8 +1,12 @@
 use std::{fmt, ops::Deref};
 
+use serde::{Deserialize, Serialize};
+
 use crate::source_arena::SourceId;
 
 /// Represents a span of elements within the source code.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub enum Span<T> {
     Empty,
     Spanning {
@@ -39,11 +43,12 @@ impl<T> Span<T> {
         Self::Spanning { start, end }
     }
 
+    /// Returns the span of `count` elements, starting at `start`.
     pub fn spanning_len(start: SourceId<T>, count: u32) -> Self {
         if count > 0 {
             Self::Spanning {
                 start,
-                end: SourceId::new(start.index.saturating_add(count)),
+                end: SourceId::new(start.index.saturating_add(count - 1)),
             }
         } else {
             Self::Empty
@@ -142,3 +147,22 @@ where
             .unwrap_or(Span::Empty)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::Span;
+    use crate::source_arena::SourceId;
+
+    fn id(index: u32) -> SourceId<()> {
+        SourceId::new(NonZeroU32::new(index).unwrap())
+    }
+
+    #[test]
+    fn spanning_len() {
+        assert_eq!(Span::spanning_len(id(3), 0), Span::Empty);
+        assert_eq!(Span::spanning_len(id(3), 1), Span::single(id(3)));
+        assert_eq!(Span::spanning_len(id(3), 4), Span::spanning(id(3), id(6)));
+    }
+}
@@ -1,9 +1,9 @@
-use std::{collections::HashMap, ops::Range, rc::Rc};
+use std::{collections::HashMap, ops::Range, sync::Arc};
 
 use muscript_foundation::{
-    errors::{Diagnostic, Label, ReplacementSuggestion},
+    errors::{codes, Applicability, Diagnostic, Label, ReplacementSuggestion},
     source::SourceFileId,
-    source_arena::SourceArenaBuilder,
+    source_arena::{Relocation, SourceArenaBuilder},
     span::Span,
 };
 
@@ -14,7 +14,7 @@ pub struct Lexer<'a> {
     pub token_arena: SourceArenaBuilder<'a, Token>,
 
     pub file: SourceFileId,
-    pub input: Rc<str>,
+    pub input: Arc<str>,
     pub position: SourceLocation,
 
     pub errors: &'a mut LexerErrors,
@@ -33,6 +33,22 @@ impl LexerErrors {
     pub fn set(&mut self, token: TokenId, error: Diagnostic<Token>) {
         self.errors.insert(token, error);
     }
+
+    /// Moves all errors from `other` into this set. The tokens `other` refers to must have been
+    /// moved to this set's token arena with the given relocation.
+    pub fn append(&mut self, other: LexerErrors, relocation: &Relocation) {
+        let relocate_span = &mut |span| {
+            relocation
+                .relocate_span(span)
+                .expect("lexer errors must only refer to relocated tokens")
+        };
+        for (token, error) in other.errors {
+            let token = relocation
+                .relocate_id(token)
+                .expect("lexer errors must only refer to relocated tokens");
+            self.errors.insert(token, error.map_spans(relocate_span));
+        }
+    }
 }
 
 // Unnecessary casts are allowed because `SourceLocation` may not end up being a `usize` if we
@@ -42,7 +58,7 @@ impl<'a> Lexer<'a> {
     pub fn new(
         token_arena: SourceArenaBuilder<'a, Token>,
         file: SourceFileId,
-        input: Rc<str>,
+        input: Arc<str>,
         errors: &'a mut LexerErrors,
     ) -> Self {
         Self {
@@ -133,6 +149,7 @@ impl<'a> Lexer<'a> {
                                 Diagnostic::error(
                                     "block comment does not have a matching '*/' terminator",
                                 )
+                                .with_code(codes::M0001)
                                 .with_label(Label::primary(
                                     &Span::single(comment_start),
                                     "the comment starts here",
@@ -191,7 +208,7 @@ impl<'a> Lexer<'a> {
                             self.create_token(TokenKind::Error, self.range(exponent_start));
                         self.errors.set(exponent, Diagnostic::error(
                             "'e' in float literal with scientific notation must be followed by an exponent number",
-                        )
+                        ).with_code(codes::M0002)
                         .with_label(Label::primary(
                             &Span::single(exponent),
                             "scientific notation used here",
@@ -241,6 +258,7 @@ impl<'a> Lexer<'a> {
                 Diagnostic::error(
                     "number literal must not be immediately followed by an identifier",
                 )
+                .with_code(codes::M0003)
                 .with_label(Label::secondary(
                     &Span::single(literal),
                     "number literal occurs here...",
@@ -259,6 +277,7 @@ impl<'a> Lexer<'a> {
                             &self.input[start as usize..ident_start as usize],
                             &self.input[ident_start as usize..ident_end as usize]
                         ),
+                        applicability: Applicability::MachineApplicable,
                     },
                 )),
             );
@@ -289,6 +308,7 @@ impl<'a> Lexer<'a> {
                 self.errors.set(
                     unterminated,
                     Diagnostic::error("string literal does not have a closing quote `\"`")
+                        .with_code(codes::M0004)
                         .with_label(Label::primary(
                             &Span::single(quote),
                             "the string starts here",
@@ -311,6 +331,7 @@ impl<'a> Lexer<'a> {
                 self.errors.set(
                     unterminated,
                     Diagnostic::error("name does not have a closing quote `'`")
+                        .with_code(codes::M0005)
                         .with_label(Label::primary(&Span::single(quote), "the name starts here")),
                 );
                 return unterminated;
@@ -432,6 +453,7 @@ impl<'a> Lexer<'a> {
                         self.errors.set(
                             unrecognized_character,
                             Diagnostic::error(format!("unrecognized character: {unknown:?}"))
+                                .with_code(codes::M0006)
                                 .with_label(Label::primary(
                                     &Span::single(unrecognized_character),
                                     "this character is not valid syntax",
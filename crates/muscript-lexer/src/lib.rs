@@ -1,4 +1,5 @@
 mod lexer;
+pub mod lint_directives;
 pub mod sliced_tokens;
 pub mod sources;
 pub mod token;
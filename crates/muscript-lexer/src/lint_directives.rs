@@ -0,0 +1,356 @@
+//! Comments setting lint levels for the code that follows them, such as:
+//!
+//! ```text
+//! // muscript: allow(unused_variable) deny(possibly_uninitialized)
+//! ```
+//!
+//! A directive placed before the `class` declaration applies to the entire file; otherwise it
+//! applies to the item (such as a function) or statement that directly follows it.
+//!
+//! Directives are found by scanning lexed tokens, before the file is preprocessed and parsed,
+//! such that diagnostics emitted by all compilation stages can respect them.
+
+use muscript_foundation::{
+    errors::{
+        codes,
+        lints::{LintLevel, LintScope, LintSelector, LintSettings},
+        pipe_all_diagnostics_into, Diagnostic, DiagnosticSink, Label,
+    },
+    source_arena::SourceArena,
+    span::Span,
+};
+
+use crate::{
+    token::{Token, TokenId, TokenKind, TokenSpan},
+    token_stream::{Channel, TokenSpanCursor, TokenStream},
+};
+
+struct CodeToken<'a> {
+    id: TokenId,
+    kind: TokenKind,
+    text: &'a str,
+}
+
+impl CodeToken<'_> {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Ident && self.text.eq_ignore_ascii_case(keyword)
+    }
+}
+
+/// Finds lint directives among the tokens of a single source file, and sets the lint levels
+/// they specify in the diagnostic sink.
+pub fn apply_lint_directives(
+    token_arena: &SourceArena<Token>,
+    source: &str,
+    tokens: TokenSpan,
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) {
+    let Some(mut cursor) = TokenSpanCursor::new(token_arena, tokens) else {
+        return;
+    };
+
+    let mut code = vec![];
+    // Problems with directives are reported after all of them are applied, so that they can be
+    // allowed by other directives, too.
+    let mut directive_diagnostics = vec![];
+    // Each directive is stored along with the index of the first code token following it.
+    let mut directives = vec![];
+    loop {
+        let token = cursor.next();
+        if token.kind == TokenKind::EndOfFile {
+            break;
+        }
+        let text = &source[token_arena.element(token.id).source_range.clone()];
+        if token.kind == TokenKind::Comment {
+            if let Some(settings) = parse_directive(token.id, text, &mut directive_diagnostics) {
+                directives.push((token.id, code.len(), settings));
+            }
+        } else if token.kind.channel() == Channel::CODE {
+            code.push(CodeToken {
+                id: token.id,
+                kind: token.kind,
+                text,
+            });
+        }
+    }
+
+    for (comment, start, settings) in directives {
+        if let Some(span) = directive_scope(&code, start) {
+            diagnostics.set_lint_levels(LintScope { span, settings });
+        } else {
+            directive_diagnostics.push(
+                Diagnostic::warning("lint directive does not apply to any code")
+                    .with_code(codes::M0007)
+                    .with_label(Label::primary(&Span::single(comment), ""))
+                    .with_note("note: lint directives apply to the class, item, or statement following them"),
+            );
+        }
+    }
+    pipe_all_diagnostics_into(diagnostics, directive_diagnostics);
+}
+
+fn parse_directive(
+    comment: TokenId,
+    text: &str,
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) -> Option<LintSettings> {
+    let text = if let Some(block) = text.strip_prefix("/*") {
+        block.strip_suffix("*/").unwrap_or(block)
+    } else {
+        text.strip_prefix("//").unwrap_or(text)
+    };
+    let mut rest = text.trim().strip_prefix("muscript:")?.trim_start();
+
+    let mut settings = LintSettings::new();
+    while !rest.is_empty() {
+        let Some((level, lints, after_lints)) = rest.split_once('(').and_then(|(level, rest)| {
+            let (lints, after_lints) = rest.split_once(')')?;
+            Some((level.trim().parse::<LintLevel>().ok()?, lints, after_lints))
+        }) else {
+            diagnostics.emit(
+                Diagnostic::warning("malformed lint directive")
+                    .with_code(codes::M0007)
+                    .with_label(Label::primary(&Span::single(comment), ""))
+                    .with_note(
+                        "note: lint directives are written like `muscript: allow(lint, ...) warn(...) deny(...)`",
+                    ),
+            );
+            return None;
+        };
+        for lint in lints
+            .split(',')
+            .map(str::trim)
+            .filter(|lint| !lint.is_empty())
+        {
+            match lint.parse::<LintSelector>() {
+                Ok(selector) => settings.set(selector, level),
+                Err(message) => diagnostics.emit(
+                    Diagnostic::warning(message)
+                        .with_code(codes::M0007)
+                        .with_label(Label::primary(&Span::single(comment), ""))
+                        .with_note(
+                            "note: run `muscript --explain M0007` to learn about lint directives",
+                        ),
+                ),
+            }
+        }
+        rest = after_lints.trim_start();
+    }
+    (!settings.is_empty()).then_some(settings)
+}
+
+/// Returns the span of the class, item, or statement beginning at `code[start]`.
+fn directive_scope(code: &[CodeToken<'_>], start: usize) -> Option<TokenSpan> {
+    let first = code.get(start)?;
+    if first.is_keyword("class") || first.is_keyword("interface") {
+        return Some(Span::spanning(first.id, code.last()?.id));
+    }
+
+    let mut depth = 0_usize;
+    let mut in_until_condition = false;
+    let mut index = start;
+    while let Some(token) = code.get(index) {
+        let ends_construct = match token.kind {
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                depth += 1;
+                false
+            }
+            // A brace closing the block the directive is in.
+            TokenKind::RightBrace if depth == 0 => {
+                return (index > start).then(|| Span::spanning(first.id, code[index - 1].id));
+            }
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
+                depth = depth.saturating_sub(1);
+                depth == 0
+                    && (token.kind == TokenKind::RightBrace
+                        || std::mem::take(&mut in_until_condition))
+            }
+            TokenKind::Semi => depth == 0,
+            _ => false,
+        };
+        if ends_construct {
+            match code.get(index + 1) {
+                // `if` statements continue with their `else` branch, and `do` statements with
+                // their `until` condition.
+                Some(next) if next.is_keyword("else") => (),
+                Some(next) if next.is_keyword("until") => in_until_condition = true,
+                // Declarations such as `struct` and `enum` end with an extra semicolon.
+                Some(next) if next.kind == TokenKind::Semi && token.kind != TokenKind::Semi => {
+                    return Some(Span::spanning(first.id, next.id));
+                }
+                _ => return Some(Span::spanning(first.id, token.id)),
+            }
+        }
+        index += 1;
+    }
+    code.last().map(|last| Span::spanning(first.id, last.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use muscript_foundation::{
+        errors::{
+            lints::{LintLevel, LintScope, LINTS},
+            Diagnostic, DiagnosticSink,
+        },
+        source::{SourceFile, SourceFileSet},
+        source_arena::SourceArena,
+    };
+
+    use super::apply_lint_directives;
+    use crate::{
+        lexer::{Lexer, LexerErrors},
+        token::{Token, TokenSpan},
+    };
+
+    #[derive(Default)]
+    struct Sink {
+        diagnostics: Vec<Diagnostic<Token>>,
+        scopes: Vec<LintScope<Token>>,
+    }
+
+    impl DiagnosticSink<Token> for Sink {
+        fn emit(&mut self, diagnostic: Diagnostic<Token>) {
+            self.diagnostics.push(diagnostic);
+        }
+
+        fn set_lint_levels(&mut self, scope: LintScope<Token>) {
+            self.scopes.push(scope);
+        }
+    }
+
+    const SOURCE: &str = "\
+// muscript: allow(unused_variable)
+class Test extends Object;
+
+// muscript: deny(warnings) warn(possibly-uninitialized)
+function Test()
+{
+    local int X;
+    // muscript: allow(M0439)
+    if (X == 0) { X = 1; } else { X = 2; }
+    X = 3;
+    // muscript: allow(warnings)
+}
+
+// muscript is not a directive.
+// muscript: allow(unused_variable
+// muscript: forbid(unused_variable)
+/* muscript: allow(unknown_lint, unused_variable) */
+var int Y;
+";
+
+    /// The level of each lint in [`LINTS`], as set by a single directive.
+    type Levels = Vec<Option<LintLevel>>;
+
+    /// Returns the scope of each directive as `(source text, levels)`, along with the messages of
+    /// the diagnostics reported.
+    fn directives(source: &str) -> (Vec<(String, Levels)>, Vec<String>) {
+        let mut files = SourceFileSet::new();
+        let file = files.add(SourceFile::new(
+            Arc::from("Test"),
+            "Test.uc".to_owned(),
+            PathBuf::from("Test.uc"),
+            Arc::from(source),
+        ));
+        let mut token_arena = SourceArena::new();
+        let mut errors = LexerErrors::default();
+        let tokens = Lexer::new(
+            token_arena.build_source_file(file),
+            file,
+            Arc::from(source),
+            &mut errors,
+        )
+        .lex();
+
+        let mut sink = Sink::default();
+        apply_lint_directives(&token_arena, source, tokens, &mut sink);
+
+        let text = |span: TokenSpan| {
+            let tokens = token_arena.elements_in(span);
+            let start = tokens.first().unwrap().source_range.start;
+            let end = tokens.last().unwrap().source_range.end;
+            source[start..end].to_owned()
+        };
+        let scopes = sink
+            .scopes
+            .iter()
+            .map(|scope| {
+                (
+                    text(scope.span),
+                    LINTS
+                        .iter()
+                        .map(|lint| scope.settings.level(lint))
+                        .collect(),
+                )
+            })
+            .collect();
+        let messages = sink
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        (scopes, messages)
+    }
+
+    fn levels(levels: &[(&str, LintLevel)], warnings: Option<LintLevel>) -> Levels {
+        LINTS
+            .iter()
+            .map(|lint| {
+                levels
+                    .iter()
+                    .find(|&&(name, _)| name == lint.name)
+                    .map(|&(_, level)| level)
+                    .or(warnings)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn directives_are_scoped_to_the_following_code() {
+        let (scopes, _) = directives(SOURCE);
+        let texts: Vec<_> = scopes.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts.len(), 4);
+        assert!(texts[0].starts_with("class Test extends Object;"));
+        assert!(texts[0].ends_with("var int Y;"));
+        assert!(texts[1].starts_with("function Test()\n{"));
+        assert!(texts[1].ends_with("X = 3;\n    // muscript: allow(warnings)\n}"));
+        assert_eq!(texts[2], "if (X == 0) { X = 1; } else { X = 2; }");
+        assert_eq!(texts[3], "var int Y;");
+    }
+
+    #[test]
+    fn directives_set_levels() {
+        let (scopes, _) = directives(SOURCE);
+        let levels_set: Vec<_> = scopes.into_iter().map(|(_, levels)| levels).collect();
+        assert_eq!(
+            levels_set,
+            [
+                levels(&[("unused_variable", LintLevel::Allow)], None),
+                levels(
+                    &[("possibly_uninitialized", LintLevel::Warn)],
+                    Some(LintLevel::Deny)
+                ),
+                levels(&[("possibly_uninitialized", LintLevel::Allow)], None),
+                // The unknown lint is skipped, but the rest of the directive still applies.
+                levels(&[("unused_variable", LintLevel::Allow)], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_directives_are_reported() {
+        let (_, messages) = directives(SOURCE);
+        assert_eq!(
+            messages,
+            [
+                "malformed lint directive",
+                "malformed lint directive",
+                "unknown lint `unknown_lint`",
+                "lint directive does not apply to any code",
+            ]
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use muscript_foundation::source_arena::SourceArena;
+use muscript_foundation::{
+    errors::{Diagnostic, Label, LabelStyle},
+    source_arena::{Relocate, Relocation, SourceArena},
+};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     token::{AnyToken, Token, TokenId, TokenKind, TokenSpan},
@@ -8,7 +12,7 @@ use crate::{
 /// A single element of [`SlicedTokens`].
 /// This contains a bit more information than [`TokenSpan`], because the empty token slice contains
 /// a source token the slice was constructed from.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum TokenSlice {
     Empty { source: TokenId },
     Span { start: TokenId, end: TokenId },
@@ -37,10 +41,84 @@ impl TokenSlice {
     }
 }
 
+impl Relocate for TokenSlice {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        match self {
+            TokenSlice::Empty { source } => source.relocate(relocation),
+            TokenSlice::Span { start, end } => {
+                start.relocate(relocation)?;
+                end.relocate(relocation)
+            }
+        }
+    }
+}
+
+/// A file whose tokens were spliced into [`SlicedTokens`] by an `` `include `` directive.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Inclusion {
+    /// Tokens of the included file.
+    pub file: TokenSpan,
+    /// The `` `include `` directive the file was included by.
+    pub directive: TokenSpan,
+}
+
+impl Relocate for Inclusion {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.file.relocate(relocation)?;
+        self.directive.relocate(relocation)
+    }
+}
+
+/// An expansion of a macro, whose tokens were spliced into [`SlicedTokens`] by the preprocessor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expansion {
+    /// Name of the expanded macro.
+    pub macro_name: String,
+    /// The invocation the macro was expanded from.
+    pub invocation: TokenSpan,
+    /// Index of the expansion the invocation itself is a part of.
+    pub parent: Option<u32>,
+}
+
+impl Relocate for Expansion {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.invocation.relocate(relocation)
+    }
+}
+
+/// A token synthesized by the preprocessor, which is yet to be added to the token arena by
+/// [`OwnedSources::synthesize`][crate::sources::OwnedSources::synthesize].
+#[derive(Debug, Clone)]
+pub struct PendingToken {
+    /// Index of the slice standing in for the token until it is synthesized.
+    pub slice_index: usize,
+    /// The token this token was synthesized from.
+    pub source: TokenId,
+    pub kind: TokenKind,
+    pub text: String,
+}
+
 /// Sliced tokens - the data structure and [`TokenStream`] output by the processor.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SlicedTokens {
     slices: Vec<TokenSlice>,
+    /// Index of the expansion each slice came from, if any.
+    slice_expansions: Vec<Option<u32>>,
+    inclusions: Vec<Inclusion>,
+    expansions: Vec<Expansion>,
+    #[serde(skip)]
+    current_expansion: Option<u32>,
+    #[serde(skip)]
+    pending: Vec<PendingToken>,
+}
+
+/// Pending tokens are not relocated, as they're not serialized either.
+impl Relocate for SlicedTokens {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.slices.relocate(relocation)?;
+        self.inclusions.relocate(relocation)?;
+        self.expansions.relocate(relocation)
+    }
 }
 
 impl SlicedTokens {
@@ -48,17 +126,23 @@ impl SlicedTokens {
         Self::default()
     }
 
+    /// Pushes a slice, which is considered part of the current expansion.
     pub fn push_slice(&mut self, slice: TokenSlice) {
         self.slices.push(slice);
+        self.slice_expansions.push(self.current_expansion);
     }
 
+    /// Pushes a single token, which is considered part of the current expansion.
     pub fn push_token(&mut self, token: TokenId) {
+        let same_expansion = self.slice_expansions.last() == Some(&self.current_expansion);
         match &mut self.slices[..] {
-            [.., TokenSlice::Span { start: _, end }] if end.successor() == token => {
+            [.., TokenSlice::Span { start: _, end }]
+                if end.successor() == token && same_expansion =>
+            {
                 *end = token;
             }
             _ => {
-                self.slices.push(TokenSlice::Span {
+                self.push_slice(TokenSlice::Span {
                     start: token,
                     end: token,
                 });
@@ -66,6 +150,102 @@ impl SlicedTokens {
         }
     }
 
+    /// Pushes a token with the given text, synthesized from the `source` token. Until the token is
+    /// synthesized, the `source` token stands in for it.
+    pub fn push_synthesized(&mut self, source: TokenId, kind: TokenKind, text: String) {
+        self.pending.push(PendingToken {
+            slice_index: self.slices.len(),
+            source,
+            kind,
+            text,
+        });
+        self.push_slice(TokenSlice::Span {
+            start: source,
+            end: source,
+        });
+    }
+
+    /// Returns the last token pushed, unless it was synthesized or the last slice is empty.
+    pub fn last_token(&self) -> Option<TokenId> {
+        let is_pending = self
+            .pending
+            .last()
+            .is_some_and(|pending| pending.slice_index + 1 == self.slices.len());
+        match self.slices.last() {
+            Some(&TokenSlice::Span { end, .. }) if !is_pending => Some(end),
+            _ => None,
+        }
+    }
+
+    /// Removes the token returned by [`SlicedTokens::last_token`].
+    pub fn pop_token(&mut self) -> Option<TokenId> {
+        let token = self.last_token()?;
+        match self.slices.last_mut() {
+            Some(TokenSlice::Span { start, end }) if start != end => {
+                *end = end.predecessor().expect("end > start");
+            }
+            _ => {
+                self.slices.pop();
+                self.slice_expansions.pop();
+            }
+        }
+        Some(token)
+    }
+
+    pub fn take_pending(&mut self) -> Vec<PendingToken> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn set_slice(&mut self, index: usize, slice: TokenSlice) {
+        self.slices[index] = slice;
+    }
+
+    /// Records that a file was included, so that diagnostics pointing into it can say where
+    /// it was included from.
+    pub fn push_inclusion(&mut self, inclusion: Inclusion) {
+        self.inclusions.push(inclusion);
+    }
+
+    pub fn inclusions(&self) -> &[Inclusion] {
+        &self.inclusions
+    }
+
+    pub fn extend_inclusions(&mut self, inclusions: &[Inclusion]) {
+        self.inclusions.extend_from_slice(inclusions);
+    }
+
+    /// Starts an expansion of a macro. Slices pushed until the matching
+    /// [`SlicedTokens::end_expansion`] are considered to come from the expansion.
+    pub fn begin_expansion(&mut self, macro_name: String, invocation: TokenSpan) {
+        self.expansions.push(Expansion {
+            macro_name,
+            invocation,
+            parent: self.current_expansion,
+        });
+        self.current_expansion = Some(self.expansions.len() as u32 - 1);
+    }
+
+    pub fn end_expansion(&mut self) {
+        self.current_expansion = self
+            .current_expansion
+            .and_then(|index| self.expansions[index as usize].parent);
+    }
+
+    pub fn expansions(&self) -> &[Expansion] {
+        &self.expansions
+    }
+
+    /// Copies expansions from the stream these tokens are being split off of. This must be done
+    /// before any tokens are pushed, so that the indices of the expansions stay the same.
+    pub fn extend_expansions(&mut self, expansions: &[Expansion]) {
+        self.expansions.extend_from_slice(expansions);
+    }
+
+    /// Sets the expansion tokens pushed from now on are considered part of.
+    pub fn set_current_expansion(&mut self, expansion: Option<u32>) {
+        self.current_expansion = expansion;
+    }
+
     pub fn stream<'a>(
         &'a self,
         token_arena: &'a SourceArena<Token>,
@@ -74,7 +254,7 @@ impl SlicedTokens {
             token_arena,
             sliced_tokens: self,
             slice_index: 0,
-            cursor: self.slices.get(0)?.start(),
+            cursor: self.slices.first()?.start(),
         })
     }
 }
@@ -140,6 +320,101 @@ impl<'a> TokenStream for SlicedTokenStream<'a> {
     fn set_position(&mut self, position: Self::Position) {
         (self.slice_index, self.cursor) = position;
     }
+
+    fn contextualize_diagnostic(&self, mut diagnostic: Diagnostic<Token>) -> Diagnostic<Token> {
+        let Some(token) = diagnostic
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .and_then(|label| label.span.start())
+        else {
+            return diagnostic;
+        };
+
+        // Tokens coming from macro definitions can appear in the stream more than once, so the
+        // expansion the diagnostic refers to is assumed to be the closest one before the
+        // stream's current position.
+        let slices = &self.sliced_tokens.slices;
+        let current = (self.slice_index as usize).min(slices.len());
+        let contains_token = |&i: &usize| {
+            let slice = &slices[i];
+            (slice.start()..=slice.end()).contains(&token)
+        };
+        let slice_index = (0..current)
+            .rev()
+            .find(contains_token)
+            .or_else(|| (current..slices.len()).find(contains_token));
+
+        let mut outermost = token;
+        let mut expansion = slice_index.and_then(|i| self.sliced_tokens.slice_expansions[i]);
+        while let Some(index) = expansion {
+            let Some(info) = self.sliced_tokens.expansions.get(index as usize) else {
+                break;
+            };
+            diagnostic = diagnostic.with_child(
+                Diagnostic::note(format!("in this expansion of macro `{}`", info.macro_name))
+                    .with_label(Label::primary(&info.invocation, "")),
+            );
+            outermost = info.invocation.start().unwrap_or(outermost);
+            expansion = info.parent;
+        }
+
+        included_from(self.sliced_tokens.inclusions(), outermost, diagnostic)
+    }
+
+    fn inclusions(&self) -> &[Inclusion] {
+        self.sliced_tokens.inclusions()
+    }
+
+    fn expansions(&self) -> &[Expansion] {
+        self.sliced_tokens.expansions()
+    }
+
+    fn previous_expansion(&self) -> Option<u32> {
+        // The previous token is in the current slice if the cursor is past its start, otherwise
+        // it's in the slice before.
+        let index = match self.sliced_tokens.slices.get(self.slice_index as usize) {
+            Some(&TokenSlice::Span { start, .. }) if self.cursor > start => self.slice_index,
+            _ => self.slice_index.checked_sub(1)?,
+        };
+        self.sliced_tokens
+            .slice_expansions
+            .get(index as usize)
+            .copied()
+            .flatten()
+    }
+}
+
+/// Adds notes pointing to the `` `include `` directives the token was included through, innermost
+/// first.
+fn included_from(
+    inclusions: &[Inclusion],
+    token: TokenId,
+    mut diagnostic: Diagnostic<Token>,
+) -> Diagnostic<Token> {
+    let mut token = Some(token);
+    // Each file can only be included once on the way to another (cycles are rejected by the
+    // preprocessor,) so the chain can never be longer than the list of inclusions.
+    for _ in 0..inclusions.len() {
+        let Some(id) = token else {
+            break;
+        };
+        let Some(inclusion) = inclusions.iter().find(|inclusion| {
+            inclusion
+                .file
+                .start()
+                .zip(inclusion.file.end())
+                .is_some_and(|(start, end)| (start..=end).contains(&id))
+        }) else {
+            break;
+        };
+        diagnostic = diagnostic.with_child(
+            Diagnostic::note("included from here")
+                .with_label(Label::primary(&inclusion.directive, "")),
+        );
+        token = inclusion.directive.start();
+    }
+    diagnostic
 }
 
 #[cfg(test)]
@@ -1,22 +1,54 @@
-use std::ops::Range;
+use std::{collections::HashMap, ops::Range};
 
 use muscript_foundation::{
-    errors::ReplacementSuggestion,
+    errors::{Applicability, ReplacementSuggestion},
     source::SourceFileSet,
     source_arena::SourceArena,
     span::{Span, Spanned},
 };
 
 use crate::{
+    sliced_tokens::{SlicedTokens, TokenSlice},
     token::{Token, TokenId},
     LexerErrors,
 };
 
+/// Text of tokens synthesized by the preprocessor, such as string literals with macros expanded
+/// inside them.
+///
+/// Synthesized tokens live in the token arena like any other token, but their source range is
+/// that of the token they were synthesized from, so that diagnostics pointing at them are reported
+/// at the original token.
+#[derive(Debug, Clone, Default)]
+pub struct SynthesizedTokens {
+    text: HashMap<TokenId, String>,
+}
+
+impl SynthesizedTokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Returns the text of the token, if it was synthesized.
+    pub fn text(&self, token: TokenId) -> Option<&str> {
+        self.text.get(&token).map(|text| text.as_str())
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct LexedSources<'a> {
     pub source_file_set: &'a SourceFileSet,
     pub token_arena: &'a SourceArena<Token>,
     pub lexer_errors: &'a LexerErrors,
+    pub synthesized_tokens: &'a SynthesizedTokens,
 }
 
 impl<'a> LexedSources<'a> {
@@ -31,10 +63,17 @@ impl<'a> LexedSources<'a> {
         }
     }
 
+    /// Returns the source code of the tokens. The text of a single synthesized token is its
+    /// synthesized text; spans of more than one token always return the text they were read from.
     pub fn source(&self, tokens: &impl Spanned<Token>) -> &'a str {
         match tokens.span() {
             Span::Empty => "",
             Span::Spanning { start, end } => {
+                if start == end {
+                    if let Some(text) = self.synthesized_tokens.text(start) {
+                        return text;
+                    }
+                }
                 let source_file_id = self.token_arena.source_file_id(start);
                 let start = self.token_arena.element(start);
                 let end = self.token_arena.element(end);
@@ -48,6 +87,7 @@ impl<'a> LexedSources<'a> {
         &self,
         tokens: &impl Spanned<Token>,
         replacement: impl Into<String>,
+        applicability: Applicability,
     ) -> Option<ReplacementSuggestion> {
         match tokens.span() {
             Span::Empty => None,
@@ -59,6 +99,7 @@ impl<'a> LexedSources<'a> {
                     file: source_file_id,
                     span: start.source_range.start..end.source_range.end,
                     replacement: replacement.into(),
+                    applicability,
                 })
             }
         }
@@ -90,6 +131,7 @@ pub struct OwnedSources<'a> {
     pub source_file_set: &'a SourceFileSet,
     pub token_arena: SourceArena<Token>,
     pub lexer_errors: LexerErrors,
+    pub synthesized_tokens: SynthesizedTokens,
 }
 
 impl<'a> OwnedSources<'a> {
@@ -98,6 +140,26 @@ impl<'a> OwnedSources<'a> {
             source_file_set: self.source_file_set,
             token_arena: &self.token_arena,
             lexer_errors: &self.lexer_errors,
+            synthesized_tokens: &self.synthesized_tokens,
+        }
+    }
+
+    /// Adds the tokens synthesized while preprocessing `tokens` to the token arena, and makes
+    /// `tokens` refer to them.
+    pub fn synthesize(&mut self, tokens: &mut SlicedTokens) {
+        for pending in tokens.take_pending() {
+            let source = self.token_arena.element(pending.source);
+            let token = Token {
+                kind: pending.kind,
+                source_range: source.source_range.clone(),
+            };
+            let source_file_id = self.token_arena.source_file_id(pending.source);
+            let id = self
+                .token_arena
+                .build_synthesized(source_file_id)
+                .push(token);
+            self.synthesized_tokens.text.insert(id, pending.text);
+            tokens.set_slice(pending.slice_index, TokenSlice::Span { start: id, end: id });
         }
     }
 
@@ -105,6 +167,8 @@ impl<'a> OwnedSources<'a> {
         self.as_borrowed().source_range(tokens)
     }
 
+    /// Returns the source code the tokens were read from. Unlike [`LexedSources::source`], this
+    /// does not return the text of synthesized tokens, since it does not live for `'a`.
     pub fn source(&self, tokens: &impl Spanned<Token>) -> &'a str {
         // Needs to be copy-pasted from LexedSources' implementation rather than calling
         // .as_borrowed().source() because otherwise the borrow checker sees that the returned string
@@ -125,8 +189,9 @@ impl<'a> OwnedSources<'a> {
         &self,
         tokens: &impl Spanned<Token>,
         replacement: impl Into<String>,
+        applicability: Applicability,
     ) -> Option<ReplacementSuggestion> {
         self.as_borrowed()
-            .replacement_suggestion(tokens, replacement)
+            .replacement_suggestion(tokens, replacement, applicability)
     }
 }
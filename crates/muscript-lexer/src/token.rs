@@ -1,15 +1,16 @@
 use muscript_foundation::{
     errors::SourceRange,
-    source_arena::SourceId,
+    source_arena::{Relocate, Relocation, SourceId},
     span::{Span, Spanned},
 };
+use serde::{Deserialize, Serialize};
 use std::{fmt, ops::Range};
 
 use crate::token_stream::Channel;
 
 pub type SourceLocation = usize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Token {
     pub kind: TokenKind,
     pub source_range: Range<usize>,
@@ -102,7 +103,7 @@ macro_rules! expand_tokens {
 
 macro_rules! token_kind_enum {
     ($($name:tt = $pretty_name:tt),* $(,)?) => {
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
         pub enum TokenKind {
             $($name),*
         }
@@ -157,12 +158,18 @@ impl TokenKind {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AnyToken {
     pub kind: TokenKind,
     pub id: TokenId,
 }
 
+impl Relocate for AnyToken {
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.id.relocate(relocation)
+    }
+}
+
 impl fmt::Debug for AnyToken {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}({:?})", self.kind, self.id)
@@ -1,7 +1,10 @@
 use bitflags::bitflags;
 use muscript_foundation::{errors::Diagnostic, source_arena::SourceArena, span::Span};
 
-use crate::token::{AnyToken, TokenId, TokenKind, TokenSpan};
+use crate::{
+    sliced_tokens::{Expansion, Inclusion},
+    token::{AnyToken, TokenId, TokenKind, TokenSpan},
+};
 
 use super::token::Token;
 
@@ -51,6 +54,24 @@ pub trait TokenStream {
     fn contextualize_diagnostic(&self, diagnostic: Diagnostic<Token>) -> Diagnostic<Token> {
         diagnostic
     }
+
+    /// Files included into the stream by the preprocessor, which streams split off of this one
+    /// should carry along.
+    fn inclusions(&self) -> &[Inclusion] {
+        &[]
+    }
+
+    /// Macro expansions the stream's tokens came from, which streams split off of this one should
+    /// carry along.
+    fn expansions(&self) -> &[Expansion] {
+        &[]
+    }
+
+    /// Index of the macro expansion the token returned by the last call to `next` came from,
+    /// within [`TokenStream::expansions`].
+    fn previous_expansion(&self) -> Option<u32> {
+        None
+    }
 }
 
 impl<T> TokenStream for &mut T
@@ -74,6 +95,18 @@ where
     fn set_position(&mut self, position: Self::Position) {
         <T as TokenStream>::set_position(self, position)
     }
+
+    fn inclusions(&self) -> &[Inclusion] {
+        <T as TokenStream>::inclusions(self)
+    }
+
+    fn expansions(&self) -> &[Expansion] {
+        <T as TokenStream>::expansions(self)
+    }
+
+    fn previous_expansion(&self) -> Option<u32> {
+        <T as TokenStream>::previous_expansion(self)
+    }
 }
 
 /// [`std::io::Cursor`] but for [`TokenSpan`]s. Turns a [`TokenSpan`] into a [`TokenStream`].
@@ -0,0 +1,177 @@
+//! Conversions between MuScript and LSP data types.
+
+use std::{collections::HashMap, ops::Range};
+
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, Url,
+};
+use muscript_foundation::{
+    errors::{Diagnostic, LabelStyle, NoteKind, Severity, SourceRange},
+    source::SourceFileId,
+    span::Span,
+};
+use muscript_lexer::token::{Token, TokenSpan};
+
+use crate::workspace::Workspace;
+
+/// Converts between byte offsets and LSP positions, which are expressed in lines and UTF-16 code
+/// units.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let character = self.text[line_start..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let mut utf16_offset = 0;
+        for (i, c) in self.text[line_start..line_end].char_indices() {
+            if utf16_offset >= position.character as usize {
+                return line_start + i;
+            }
+            utf16_offset += c.len_utf16();
+        }
+        line_end
+    }
+
+    pub fn range(&self, range: Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(self.position(range.start), self.position(range.end))
+    }
+}
+
+impl Workspace {
+    pub fn url(&self, file: SourceFileId) -> Option<Url> {
+        Url::from_file_path(&self.session.source_file_set.get(file).full_path).ok()
+    }
+
+    /// Returns the file and byte range a token span covers.
+    pub fn span_range(&self, span: TokenSpan) -> Option<(SourceFileId, Range<usize>)> {
+        match span {
+            Span::Empty => None,
+            Span::Spanning { start, end } => {
+                let token_arena = &self.session.token_arena;
+                let file = token_arena.source_file_id(start);
+                let start_range = token_arena.element(start).source_range();
+                // Spans can cross file boundaries when macros are involved, in which case the
+                // end token is meaningless within the start token's file.
+                let range = if token_arena.source_file_id(end) == file {
+                    let end_range = token_arena.element(end).source_range();
+                    start_range.start.min(end_range.start)..start_range.end.max(end_range.end)
+                } else {
+                    start_range
+                };
+                Some((file, range))
+            }
+        }
+    }
+
+    pub fn location(&self, span: TokenSpan) -> Option<Location> {
+        let (file, range) = self.span_range(span)?;
+        let line_index = LineIndex::new(self.session.source_file_set.source(file));
+        Some(Location::new(self.url(file)?, line_index.range(range)))
+    }
+
+    /// Converts the diagnostics emitted during the last analysis to LSP diagnostics, grouped by the
+    /// file they should be shown in.
+    pub fn lsp_diagnostics(&self) -> HashMap<SourceFileId, Vec<lsp_types::Diagnostic>> {
+        let mut diagnostics: HashMap<_, Vec<_>> = HashMap::new();
+        for diagnostic in &self.analysis.env.diagnostics {
+            if let Some((file, lsp_diagnostic)) = self.lsp_diagnostic(diagnostic) {
+                diagnostics.entry(file).or_default().push(lsp_diagnostic);
+            }
+        }
+        diagnostics
+    }
+
+    fn lsp_diagnostic(
+        &self,
+        diagnostic: &Diagnostic<Token>,
+    ) -> Option<(SourceFileId, lsp_types::Diagnostic)> {
+        let primary_label = diagnostic
+            .labels
+            .iter()
+            .filter(|label| label.span != Span::Empty)
+            .min_by_key(|label| label.style != LabelStyle::Primary)?;
+        let (file, range) = self.span_range(primary_label.span)?;
+        let line_index = LineIndex::new(self.session.source_file_set.source(file));
+
+        let mut message = diagnostic.message.clone();
+        if !primary_label.message.is_empty() {
+            message.push('\n');
+            message.push_str(&primary_label.message);
+        }
+        for note in &diagnostic.notes {
+            if note.kind == NoteKind::Debug {
+                continue;
+            }
+            message.push('\n');
+            message.push_str(&note.text);
+            if let Some(suggestion) = &note.suggestion {
+                message.push_str(&format!(": `{}`", suggestion.replacement));
+            }
+        }
+
+        let secondary_labels = diagnostic
+            .labels
+            .iter()
+            .filter(|label| !std::ptr::eq(*label, primary_label))
+            .map(|label| (label.span, label.message.clone()));
+        let children = diagnostic.children.iter().filter_map(|child| {
+            child
+                .labels
+                .iter()
+                .find(|label| label.span != Span::Empty)
+                .map(|label| (label.span, child.message.clone()))
+        });
+        let related_information: Vec<_> = secondary_labels
+            .chain(children)
+            .filter_map(|(span, message)| {
+                Some(DiagnosticRelatedInformation {
+                    location: self.location(span)?,
+                    message,
+                })
+            })
+            .collect();
+
+        Some((
+            file,
+            lsp_types::Diagnostic {
+                range: line_index.range(range),
+                severity: Some(match diagnostic.severity {
+                    Severity::Help => DiagnosticSeverity::HINT,
+                    Severity::Note => DiagnosticSeverity::INFORMATION,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                    Severity::Error | Severity::Bug => DiagnosticSeverity::ERROR,
+                }),
+                code: diagnostic.code.clone().map(NumberOrString::String),
+                source: Some("muscript".into()),
+                message,
+                related_information: (!related_information.is_empty())
+                    .then_some(related_information),
+                ..Default::default()
+            },
+        ))
+    }
+}
@@ -0,0 +1,51 @@
+mod convert;
+mod query;
+mod server;
+mod workspace;
+
+use camino::Utf8PathBuf;
+use clap::Parser;
+use lsp_server::Connection;
+use tracing::{error, metadata::LevelFilter};
+use tracing_subscriber::EnvFilter;
+
+/// Language server for MuScript, speaking the Language Server Protocol over standard input and
+/// output.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Directory containing the package sources (one directory above `Classes`).
+    ///
+    /// If not provided, the workspace root sent by the client is used.
+    #[clap(long)]
+    package: Option<Utf8PathBuf>,
+
+    /// External source packages. At least `Core` should be provided here, either through this
+    /// flag or through the `sources` initialization option.
+    #[clap(short = 's', long)]
+    source: Vec<Utf8PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    // Standard output is occupied by the protocol, so logs must go to standard error.
+    tracing_subscriber::fmt()
+        .without_time()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::WARN.into())
+                .with_env_var("MUSCRIPT_LOG")
+                .from_env_lossy(),
+        )
+        .init();
+
+    let (connection, io_threads) = Connection::stdio();
+    if let Err(error) = server::run(connection, args) {
+        error!("{error:?}");
+        std::process::exit(1);
+    }
+    if let Err(error) = io_threads.join() {
+        error!("{error:?}");
+    }
+}
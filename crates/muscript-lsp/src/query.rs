@@ -0,0 +1,498 @@
+//! Semantic queries about the code, such as what a name refers to.
+
+use std::{collections::HashSet, fmt::Write, ops::Range};
+
+use muscript_analysis::{
+    class::VarKind,
+    function::{Function, FunctionKind, ParamFlags},
+    ir::{NodeKind, Value},
+    partition::{TypeCst, UntypedClassPartitionsExt},
+    ClassId, CompilerInput, Environment, FunctionId, TypeId, VarId,
+};
+use muscript_foundation::{ident::CaseInsensitive, source::SourceFileId, span::Spanned};
+use muscript_lexer::{sources::LexedSources, token::TokenSpan};
+use muscript_syntax::cst::NamedItem;
+
+use crate::workspace::Workspace;
+
+/// Something a piece of code refers to.
+#[derive(Debug, Clone)]
+pub enum Symbol {
+    /// A local variable or parameter of a function.
+    Local {
+        function_id: FunctionId,
+        var_id: VarId,
+    },
+    /// A class variable or constant.
+    Var(VarId),
+    Function(FunctionId),
+    Class(ClassId),
+    /// A struct or enum.
+    Type {
+        kind: &'static str,
+        name: String,
+        span: TokenSpan,
+    },
+    /// An expression that does not refer to anything in particular, but still has a type.
+    Expr(TypeId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Local,
+    Var,
+    Const,
+    Function,
+}
+
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+impl Workspace {
+    /// Finds the symbol at the given byte offset in a file. Also returns the byte range of the code
+    /// referring to the symbol.
+    pub fn symbol_at(
+        &mut self,
+        file: SourceFileId,
+        offset: usize,
+    ) -> Option<(Range<usize>, Symbol)> {
+        let class_id = self.class_of_file(file)?;
+
+        let smallest_register = self.smallest_register_at(class_id, file, offset);
+        if let Some((ref range, function_id, ref value, _)) = smallest_register {
+            let symbol = match *value {
+                Value::Local(var_id) => Some(Symbol::Local {
+                    function_id,
+                    var_id,
+                }),
+                Value::Field(var_id) => Some(Symbol::Var(var_id)),
+                Value::CallFinal { function, .. } => Some(Symbol::Function(function)),
+                Value::This => Some(Symbol::Class(class_id)),
+                _ => None,
+            };
+            if let Some(symbol) = symbol {
+                return Some((range.clone(), symbol));
+            }
+        }
+
+        // Not everything ends up in the IR; declarations, types, and constants (which are inlined)
+        // need to be looked up by name.
+        if let Some(found) = self.symbol_by_name(class_id, file, offset) {
+            return Some(found);
+        }
+
+        smallest_register.map(|(range, _, _, ty)| (range, Symbol::Expr(ty)))
+    }
+
+    /// Returns the span of the symbol's declaration.
+    pub fn definition(&mut self, symbol: &Symbol) -> Option<TokenSpan> {
+        let env = &self.analysis.env;
+        match *symbol {
+            Symbol::Local { var_id, .. } | Symbol::Var(var_id) => {
+                Some(env.get_var(var_id).name.span())
+            }
+            Symbol::Function(function_id) => Some(env.get_function(function_id).name.span()),
+            Symbol::Class(class_id) => self.with_compiler(|compiler| {
+                compiler
+                    .untyped_class_partitions(class_id)?
+                    .first()
+                    .map(|partition| partition.name.span())
+            }),
+            Symbol::Type { span, .. } => Some(span),
+            Symbol::Expr(_) => None,
+        }
+    }
+
+    /// Returns a short, code-like description of the symbol.
+    pub fn describe(&mut self, symbol: &Symbol) -> String {
+        let sources = self.sources();
+        let env = &self.analysis.env;
+        match *symbol {
+            Symbol::Local {
+                function_id,
+                var_id,
+            } => {
+                let is_param = env
+                    .get_function(function_id)
+                    .params
+                    .iter()
+                    .any(|param| param.var == var_id);
+                let var = env.get_var(var_id);
+                format!(
+                    "{} {} {}",
+                    if is_param { "param" } else { "local" },
+                    env.type_name(var.ty),
+                    sources.source(&var.name)
+                )
+            }
+            Symbol::Var(var_id) => var_signature(env, &sources, var_id),
+            Symbol::Function(function_id) => {
+                function_signature(env, &sources, env.get_function(function_id))
+            }
+            Symbol::Class(class_id) => {
+                let class_name = env.class_name(class_id).to_owned();
+                let extends = env
+                    .untyped_class_partitions(class_id)
+                    .and_then(|partitions| {
+                        partitions.iter().find_map(|partition| partition.extends)
+                    })
+                    .map(|extends| sources.source(&extends));
+                match extends {
+                    Some(extends) => format!("class {class_name} extends {extends}"),
+                    None => format!("class {class_name}"),
+                }
+            }
+            Symbol::Type { kind, ref name, .. } => format!("{kind} {name}"),
+            Symbol::Expr(ty) => env.type_name(ty).to_string(),
+        }
+    }
+
+    /// Returns the doc comment attached to the symbol's declaration, if there is one.
+    pub fn documentation(&mut self, symbol: &Symbol) -> Option<String> {
+        let sources = self.sources();
+        let env = &self.analysis.env;
+        let doc = match *symbol {
+            Symbol::Var(var_id) => &env.get_var(var_id).doc,
+            Symbol::Function(function_id) => &env.get_function(function_id).doc,
+            _ => return None,
+        };
+        doc.text(&sources)
+    }
+
+    /// Lists the variables and functions that can be referred to at the given byte offset.
+    pub fn completions(&mut self, file: SourceFileId, offset: usize) -> Vec<Completion> {
+        let Some(class_id) = self.class_of_file(file) else {
+            return vec![];
+        };
+
+        let mut completions = vec![];
+        if let Some(function_id) = self.enclosing_function(class_id, file, offset) {
+            let env = &self.analysis.env;
+            let sources = self.sources();
+            let params = env
+                .get_function(function_id)
+                .params
+                .iter()
+                .map(|param| param.var);
+            let locals = env
+                .get_function_ir(function_id)
+                .into_iter()
+                .flat_map(|ir| ir.locals.iter().copied());
+            for var_id in params.chain(locals) {
+                let var = env.get_var(var_id);
+                completions.push(Completion {
+                    label: sources.source(&var.name).to_owned(),
+                    kind: CompletionKind::Local,
+                    detail: env.type_name(var.ty).to_string(),
+                });
+            }
+        }
+
+        self.with_compiler(|compiler| {
+            let mut visited = HashSet::new();
+            let mut next = Some(class_id);
+            while let Some(class_id) = next.take() {
+                if !visited.insert(class_id) {
+                    break;
+                }
+                for var_id in compiler.class_vars(class_id) {
+                    let var = compiler.env.get_var(var_id);
+                    completions.push(Completion {
+                        label: compiler.sources.source(&var.name).to_owned(),
+                        kind: match var.kind {
+                            VarKind::Var(_) => CompletionKind::Var,
+                            VarKind::Const(_) => CompletionKind::Const,
+                        },
+                        detail: var_signature(
+                            compiler.env,
+                            &compiler.sources.as_borrowed(),
+                            var_id,
+                        ),
+                    });
+                }
+                for function_id in compiler.class_functions(class_id) {
+                    let function = compiler.env.get_function(function_id);
+                    // Operators cannot be referred to by name.
+                    if matches!(
+                        function.kind,
+                        FunctionKind::Function | FunctionKind::Event | FunctionKind::Delegate
+                    ) {
+                        completions.push(Completion {
+                            label: compiler.sources.source(&function.name).to_owned(),
+                            kind: CompletionKind::Function,
+                            detail: function_signature(
+                                compiler.env,
+                                &compiler.sources.as_borrowed(),
+                                function,
+                            ),
+                        });
+                    }
+                }
+                next = compiler.super_class_id(class_id);
+            }
+        });
+
+        // Items declared closer to the completion point shadow those declared further away.
+        let mut seen = HashSet::new();
+        completions
+            .retain(|completion| seen.insert(CaseInsensitive::new(completion.label.clone())));
+        completions
+    }
+
+    fn class_of_file(&mut self, file: SourceFileId) -> Option<ClassId> {
+        let class_name = self
+            .session
+            .source_file_set
+            .get(file)
+            .class_name()
+            .ok()?
+            .to_owned();
+        self.session
+            .input
+            .class_exists(&class_name)
+            .then(|| self.analysis.env.get_or_create_class(&class_name))
+    }
+
+    fn functions_of_class(&self, class_id: ClassId) -> Vec<FunctionId> {
+        self.analysis
+            .package
+            .as_ref()
+            .and_then(|package| package.classes.get(&class_id))
+            .map(|class| class.functions.clone())
+            .unwrap_or_default()
+    }
+
+    fn contains(&self, span: TokenSpan, file: SourceFileId, offset: usize) -> Option<Range<usize>> {
+        self.span_range(span)
+            .filter(|(span_file, range)| {
+                *span_file == file && range.start <= offset && offset <= range.end
+            })
+            .map(|(_, range)| range)
+    }
+
+    /// Finds the function whose body contains the given byte offset.
+    fn enclosing_function(
+        &self,
+        class_id: ClassId,
+        file: SourceFileId,
+        offset: usize,
+    ) -> Option<FunctionId> {
+        self.functions_of_class(class_id)
+            .into_iter()
+            .find(|&function_id| {
+                self.analysis
+                    .env
+                    .get_function_ir(function_id)
+                    .and_then(|ir| ir.basic_blocks.first())
+                    .and_then(|entry| self.contains(entry.span, file, offset))
+                    .is_some()
+            })
+    }
+
+    /// Finds the smallest IR register produced by code at the given byte offset.
+    fn smallest_register_at(
+        &self,
+        class_id: ClassId,
+        file: SourceFileId,
+        offset: usize,
+    ) -> Option<(Range<usize>, FunctionId, Value, TypeId)> {
+        let mut smallest: Option<(Range<usize>, FunctionId, Value, TypeId)> = None;
+        for function_id in self.functions_of_class(class_id) {
+            let Some(ir) = self.analysis.env.get_function_ir(function_id) else {
+                continue;
+            };
+            for node in &ir.nodes {
+                let NodeKind::Register(register) = &node.kind else {
+                    continue;
+                };
+                let Some(range) = self.contains(node.span, file, offset) else {
+                    continue;
+                };
+                if smallest
+                    .as_ref()
+                    .is_none_or(|(smallest, ..)| range.len() < smallest.len())
+                {
+                    smallest = Some((range, function_id, register.value.clone(), register.ty));
+                }
+            }
+        }
+        smallest
+    }
+
+    fn symbol_by_name(
+        &mut self,
+        class_id: ClassId,
+        file: SourceFileId,
+        offset: usize,
+    ) -> Option<(Range<usize>, Symbol)> {
+        let range = word_at(self.session.source_file_set.source(file), offset)?;
+        let name = self.session.source_file_set.source(file)[range.clone()].to_owned();
+
+        // Declarations refer to themselves.
+        let env = &self.analysis.env;
+        for function_id in self.functions_of_class(class_id) {
+            let function = env.get_function(function_id);
+            if self.contains(function.name.span(), file, offset).is_some() {
+                return Some((range, Symbol::Function(function_id)));
+            }
+            let params = function.params.iter().map(|param| param.var);
+            let locals = env
+                .get_function_ir(function_id)
+                .into_iter()
+                .flat_map(|ir| ir.locals.iter().copied());
+            for var_id in params.chain(locals) {
+                if self
+                    .contains(env.get_var(var_id).name.span(), file, offset)
+                    .is_some()
+                {
+                    return Some((
+                        range,
+                        Symbol::Local {
+                            function_id,
+                            var_id,
+                        },
+                    ));
+                }
+            }
+        }
+
+        if let Some(function_id) = self.enclosing_function(class_id, file, offset) {
+            let function = env.get_function(function_id);
+            let params = function.params.iter().map(|param| param.var);
+            let locals = env
+                .get_function_ir(function_id)
+                .into_iter()
+                .flat_map(|ir| ir.locals.iter().copied());
+            let sources = self.sources();
+            for var_id in params.chain(locals) {
+                if sources
+                    .source(&env.get_var(var_id).name)
+                    .eq_ignore_ascii_case(&name)
+                {
+                    return Some((
+                        range,
+                        Symbol::Local {
+                            function_id,
+                            var_id,
+                        },
+                    ));
+                }
+            }
+        }
+
+        let symbol = self.with_compiler(|compiler| {
+            if let Some(var_id) = compiler.lookup_class_var(class_id, &name) {
+                return Some(Symbol::Var(var_id));
+            }
+            if let Some(function_id) = compiler.lookup_function(class_id, &name) {
+                return Some(Symbol::Function(function_id));
+            }
+
+            let mut visited = HashSet::new();
+            let mut next = Some(class_id);
+            while let Some(class_id) = next.take() {
+                if !visited.insert(class_id) {
+                    break;
+                }
+                // Load the partitions first, so that they can be borrowed alongside the sources.
+                compiler.untyped_class_partitions(class_id);
+                if let Some(partitions) = compiler.env.untyped_class_partitions(class_id) {
+                    if let Some(type_cst) = partitions.find_type(&name) {
+                        return Some(Symbol::Type {
+                            kind: match type_cst {
+                                TypeCst::Struct(_) => "struct",
+                                TypeCst::Enum(_) => "enum",
+                            },
+                            name: compiler.sources.source(&type_cst.name()).to_owned(),
+                            span: type_cst.name().span(),
+                        });
+                    }
+                }
+                next = compiler.super_class_id(class_id);
+            }
+
+            if compiler.input.class_exists(&name) {
+                return Some(Symbol::Class(compiler.env.get_or_create_class(&name)));
+            }
+            None
+        })?;
+        Some((range, symbol))
+    }
+}
+
+/// Finds the identifier at or right before the given byte offset.
+fn word_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    let is_ident_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let offset = offset.min(text.len());
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_ident_char(c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..]
+        .char_indices()
+        .find(|&(_, c)| !is_ident_char(c))
+        .map_or(text.len(), |(i, _)| offset + i);
+    (start < end && !text[start..].starts_with(|c: char| c.is_ascii_digit())).then_some(start..end)
+}
+
+fn var_signature(env: &Environment, sources: &LexedSources<'_>, var_id: VarId) -> String {
+    let var = env.get_var(var_id);
+    let keyword = match var.kind {
+        VarKind::Var(_) => "var",
+        VarKind::Const(_) => "const",
+    };
+    format!(
+        "{keyword} {} {}",
+        env.type_name(var.ty),
+        sources.source(&var.name)
+    )
+}
+
+fn function_signature(
+    env: &Environment,
+    sources: &LexedSources<'_>,
+    function: &Function,
+) -> String {
+    let mut signature = String::from(match function.kind {
+        FunctionKind::Function => "function",
+        FunctionKind::Event => "event",
+        FunctionKind::Delegate => "delegate",
+        FunctionKind::PrefixOperator => "preoperator",
+        FunctionKind::PostfixOperator => "postoperator",
+        FunctionKind::InfixOperator => "operator",
+    });
+    if function.return_ty != TypeId::VOID {
+        _ = write!(signature, " {}", env.type_name(function.return_ty));
+    }
+    _ = write!(signature, " {}(", sources.source(&function.name));
+    for (i, param) in function.params.iter().enumerate() {
+        if i != 0 {
+            signature.push_str(", ");
+        }
+        for (flag, keyword) in [
+            (ParamFlags::OPTIONAL, "optional "),
+            (ParamFlags::OUT, "out "),
+            (ParamFlags::COERCE, "coerce "),
+            (ParamFlags::SKIP, "skip "),
+        ] {
+            if param.flags.contains(flag) {
+                signature.push_str(keyword);
+            }
+        }
+        let var = env.get_var(param.var);
+        _ = write!(
+            signature,
+            "{} {}",
+            env.type_name(var.ty),
+            sources.source(&var.name)
+        );
+    }
+    signature.push(')');
+    signature
+}
@@ -0,0 +1,341 @@
+//! The language server's main loop and request handlers.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics, ShowMessage,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Request as _},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, MarkupContent, MarkupKind, MessageType, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, ShowMessageParams, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use muscript_foundation::source::SourceFileId;
+use serde::Deserialize;
+use tracing::{error, info, info_span};
+
+use crate::{
+    convert::LineIndex,
+    query::{self, CompletionKind},
+    workspace::Workspace,
+    Args,
+};
+
+/// Options that can be passed by the client in the `initialize` request.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct InitializationOptions {
+    /// External source packages, in addition to those passed on the command line.
+    sources: Vec<PathBuf>,
+}
+
+pub struct Server {
+    connection: Connection,
+    workspace: Option<Workspace>,
+    /// Files which currently have diagnostics published for them, so that the diagnostics can be
+    /// cleared once they're fixed.
+    files_with_diagnostics: HashSet<Url>,
+}
+
+/// Runs the language server until the client asks it to exit.
+pub fn run(connection: Connection, args: Args) -> anyhow::Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".into()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    let mut server = Server {
+        connection,
+        workspace: None,
+        files_with_diagnostics: HashSet::new(),
+    };
+    if let Err(error) = server.open_workspace(args, params) {
+        error!("{error:?}");
+        server.show_error(format!("MuScript: cannot open workspace: {error:#}"))?;
+    }
+    server.main_loop()
+}
+
+impl Server {
+    fn open_workspace(&mut self, args: Args, params: InitializeParams) -> anyhow::Result<()> {
+        let package = match args.package {
+            Some(package) => package,
+            None => {
+                #[allow(deprecated)] // Not all clients support workspace folders.
+                let root_uri = params
+                    .workspace_folders
+                    .and_then(|folders| folders.into_iter().next())
+                    .map(|folder| folder.uri)
+                    .or(params.root_uri)
+                    .context("no package given on the command line, and no workspace is open")?;
+                url_to_utf8_path(&root_uri)?
+            }
+        };
+        let options: InitializationOptions = params
+            .initialization_options
+            .map(serde_json::from_value)
+            .transpose()
+            .context("invalid initialization options")?
+            .unwrap_or_default();
+        let mut sources = args.source;
+        for source in options.sources {
+            sources.push(
+                Utf8PathBuf::from_path_buf(source)
+                    .map_err(|path| anyhow::anyhow!("{path:?} is not valid UTF-8"))?,
+            );
+        }
+
+        info!(%package, ?sources, "opening workspace");
+        let mut workspace = Workspace::load(&package, &sources)?;
+        workspace.analyze();
+        self.workspace = Some(workspace);
+        self.publish_diagnostics()
+    }
+
+    fn main_loop(&mut self) -> anyhow::Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        let _span = info_span!("handle_request", method = request.method).entered();
+
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            GotoDefinition::METHOD => {
+                parse_params(request).and_then(|params| to_json(self.goto_definition(params)))
+            }
+            HoverRequest::METHOD => {
+                parse_params(request).and_then(|params| to_json(self.hover(params)))
+            }
+            Completion::METHOD => {
+                parse_params(request).and_then(|params| to_json(self.completion(params)))
+            }
+            _ => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request: {}", request.method),
+                )
+            }
+        };
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(error) => error_response(id, error),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> anyhow::Result<()> {
+        let _span = info_span!("handle_notification", method = notification.method).entered();
+
+        let changed = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.set_file_contents(&params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // With full document sync, the last change always contains the whole document.
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.set_file_contents(&params.text_document.uri, change.text),
+                    None => false,
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let path = params.text_document.uri.to_file_path().ok();
+                match (&mut self.workspace, path) {
+                    (Some(workspace), Some(path)) => workspace.reload_file(&path),
+                    _ => false,
+                }
+            }
+            _ => false,
+        };
+
+        if changed {
+            if let Some(workspace) = &mut self.workspace {
+                workspace.analyze();
+            }
+            self.publish_diagnostics()?;
+        }
+        Ok(())
+    }
+
+    fn set_file_contents(&mut self, uri: &Url, contents: String) -> bool {
+        match (&mut self.workspace, uri.to_file_path()) {
+            (Some(workspace), Ok(path)) => workspace.set_file_contents(&path, contents),
+            _ => false,
+        }
+    }
+
+    fn publish_diagnostics(&mut self) -> anyhow::Result<()> {
+        let Some(workspace) = &self.workspace else {
+            return Ok(());
+        };
+
+        let mut diagnostics_by_file = workspace.lsp_diagnostics();
+        // Diagnostics from external packages are only interesting if they're errors, since those
+        // can prevent the main package from compiling. Warnings in code the user does not control
+        // would just be noise.
+        for (&file, diagnostics) in &mut diagnostics_by_file {
+            if !workspace.is_in_main_package(file) {
+                diagnostics.retain(|diagnostic| {
+                    diagnostic.severity == Some(lsp_types::DiagnosticSeverity::ERROR)
+                });
+            }
+        }
+
+        let mut files_with_diagnostics = HashSet::new();
+        let mut notifications = vec![];
+        for (file, diagnostics) in diagnostics_by_file {
+            if diagnostics.is_empty() {
+                continue;
+            }
+            if let Some(uri) = workspace.url(file) {
+                files_with_diagnostics.insert(uri.clone());
+                notifications.push(PublishDiagnosticsParams::new(uri, diagnostics, None));
+            }
+        }
+        for uri in self
+            .files_with_diagnostics
+            .difference(&files_with_diagnostics)
+        {
+            notifications.push(PublishDiagnosticsParams::new(uri.clone(), vec![], None));
+        }
+        self.files_with_diagnostics = files_with_diagnostics;
+
+        for params in notifications {
+            self.connection
+                .sender
+                .send(Notification::new(PublishDiagnostics::METHOD.into(), params).into())?;
+        }
+        Ok(())
+    }
+
+    fn show_error(&self, message: String) -> anyhow::Result<()> {
+        self.connection.sender.send(
+            Notification::new(
+                ShowMessage::METHOD.into(),
+                ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    message,
+                },
+            )
+            .into(),
+        )?;
+        Ok(())
+    }
+
+    /// Resolves a document position to a file in the workspace and a byte offset within it.
+    fn locate(
+        &mut self,
+        position: &TextDocumentPositionParams,
+    ) -> Option<(&mut Workspace, SourceFileId, usize)> {
+        let workspace = self.workspace.as_mut()?;
+        let path = position.text_document.uri.to_file_path().ok()?;
+        let file = workspace.file_id(&path)?;
+        let offset = LineIndex::new(workspace.session.source_file_set.source(file))
+            .offset(position.position);
+        Some((workspace, file, offset))
+    }
+
+    fn goto_definition(&mut self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let (workspace, file, offset) = self.locate(&params.text_document_position_params)?;
+        let (_, symbol) = workspace.symbol_at(file, offset)?;
+        let span = workspace.definition(&symbol)?;
+        workspace.location(span).map(GotoDefinitionResponse::Scalar)
+    }
+
+    fn hover(&mut self, params: HoverParams) -> Option<Hover> {
+        let (workspace, file, offset) = self.locate(&params.text_document_position_params)?;
+        let (range, symbol) = workspace.symbol_at(file, offset)?;
+        let description = workspace.describe(&symbol);
+        let mut value = format!("```unrealscript\n{description}\n```");
+        if let Some(documentation) = workspace.documentation(&symbol) {
+            value.push_str("\n\n");
+            value.push_str(&documentation);
+        }
+        let range = LineIndex::new(workspace.session.source_file_set.source(file)).range(range);
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range),
+        })
+    }
+
+    fn completion(&mut self, params: CompletionParams) -> Option<CompletionResponse> {
+        let (workspace, file, offset) = self.locate(&params.text_document_position)?;
+        let items = workspace
+            .completions(file, offset)
+            .into_iter()
+            .map(|completion: query::Completion| CompletionItem {
+                label: completion.label,
+                kind: Some(match completion.kind {
+                    CompletionKind::Local => CompletionItemKind::VARIABLE,
+                    CompletionKind::Var => CompletionItemKind::FIELD,
+                    CompletionKind::Const => CompletionItemKind::CONSTANT,
+                    CompletionKind::Function => CompletionItemKind::FUNCTION,
+                }),
+                detail: Some(completion.detail),
+                ..Default::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+fn parse_params<P>(request: Request) -> anyhow::Result<P>
+where
+    P: serde::de::DeserializeOwned,
+{
+    serde_json::from_value(request.params)
+        .with_context(|| format!("invalid parameters for {}", request.method))
+}
+
+fn to_json(value: impl serde::Serialize) -> anyhow::Result<serde_json::Value> {
+    Ok(serde_json::to_value(value)?)
+}
+
+fn error_response(id: RequestId, error: anyhow::Error) -> Response {
+    Response::new_err(id, ErrorCode::InvalidParams as i32, format!("{error:#}"))
+}
+
+fn url_to_utf8_path(url: &Url) -> anyhow::Result<Utf8PathBuf> {
+    let path = url
+        .to_file_path()
+        .map_err(|_| anyhow::anyhow!("{url} is not a file path"))?;
+    Utf8PathBuf::from_path_buf(path).map_err(|path| anyhow::anyhow!("{path:?} is not valid UTF-8"))
+}
@@ -0,0 +1,199 @@
+//! Compiler state that is kept alive across edits.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use muscript::{
+    config::ProjectConfig,
+    files::{canonical_path, read_source_file},
+    session::{self, Compilation, Session},
+};
+use muscript_analysis::{Compiler, Environment, Package};
+use muscript_foundation::{errors::lints::LintSettings, source::SourceFileId};
+use muscript_lexer::sources::{LexedSources, OwnedSources};
+use tracing::{error, info_span};
+
+/// A package opened in the editor, along with all the external packages it depends on.
+///
+/// The session lives for as long as the workspace does; edits replace the contents of individual
+/// files within it, and only the classes declared by edited files are parsed again. Analysis
+/// results are kept around in between edits so that requests can be answered without recompiling
+/// anything.
+pub struct Workspace {
+    pub session: Session,
+    lint_settings: Vec<LintSettings>,
+    /// All source files of the workspace, by their canonical paths.
+    files_by_path: HashMap<PathBuf, SourceFileId>,
+    pub analysis: Analysis,
+}
+
+/// Results of analyzing the workspace's package.
+pub struct Analysis {
+    pub env: Environment,
+    pub package: Option<Package>,
+}
+
+impl Workspace {
+    /// Loads the package at `package`, configured by its project file if it has one, and the
+    /// external packages at `external_packages`. Note that this does not analyze anything yet.
+    pub fn load(package: &Utf8Path, external_packages: &[Utf8PathBuf]) -> anyhow::Result<Self> {
+        let _span = info_span!("load_workspace", %package).entered();
+
+        let config = ProjectConfig::load(package)?;
+        let lint_settings = vec![config.lint_settings()?];
+        let options = session::Options {
+            sources: external_packages.to_vec(),
+            ..Default::default()
+        };
+        let mut session = Session::load(&options, &config)?;
+        session.input.keep_partitions_in_memory();
+
+        let files_by_path = session
+            .source_file_set
+            .iter()
+            .map(|(id, source_file)| (canonical_path(&source_file.full_path), id))
+            .collect();
+        Ok(Self {
+            session,
+            lint_settings,
+            files_by_path,
+            analysis: Analysis {
+                env: Environment::new(),
+                package: None,
+            },
+        })
+    }
+
+    /// Returns the file at `path`, loading its source code if it has not been loaded yet.
+    pub fn file_id(&self, path: &Path) -> Option<SourceFileId> {
+        let id = self.files_by_path.get(&canonical_path(path)).copied()?;
+        let source_file_set = &self.session.source_file_set;
+        if !source_file_set.get(id).is_loaded() {
+            let source = Utf8Path::from_path(path)
+                .ok_or_else(|| anyhow::anyhow!("path contains invalid UTF-8"))
+                .and_then(read_source_file);
+            match source {
+                Ok(source) => source_file_set.load(id, Arc::from(source)),
+                Err(error) => {
+                    error!("cannot load {path:?}: {error:?}");
+                    return None;
+                }
+            }
+        }
+        Some(id)
+    }
+
+    pub fn is_in_main_package(&self, file: SourceFileId) -> bool {
+        self.session.main_package_source_file_ids.contains(&file)
+    }
+
+    /// Replaces the contents of the file at `path`. Files that are not yet part of the workspace
+    /// are added to it if they're class sources belonging to the main package.
+    ///
+    /// Returns whether the workspace has changed and needs to be reanalyzed.
+    pub fn set_file_contents(&mut self, path: &Path, contents: String) -> bool {
+        if let Some(id) = self.files_by_path.get(&canonical_path(path)).copied() {
+            let source_file_set = &mut self.session.source_file_set;
+            if source_file_set.get(id).is_loaded() && *source_file_set.source(id) == *contents {
+                return false;
+            }
+            source_file_set.replace_source(id, Arc::from(contents));
+            if self.session.include_file_ids.contains(&id) {
+                // Include files are preprocessed into every other file, so nothing that was
+                // parsed before can be reused.
+                self.session.discard_tokens();
+            } else if let Ok(class_name) = self.session.source_file_set.get(id).class_name() {
+                let class_name = class_name.to_owned();
+                self.session.input.forget_partitions(&class_name);
+            }
+            return true;
+        }
+
+        let Some(path) = Utf8Path::from_path(path) else {
+            return false;
+        };
+        let classes_dir = canonical_path(self.session.package.join("Classes").as_std_path());
+        if path.extension() != Some("uc")
+            || !canonical_path(path.as_std_path()).starts_with(classes_dir)
+        {
+            return false;
+        }
+        let id = self.session.add_main_package_file(path, contents);
+        self.files_by_path
+            .insert(canonical_path(path.as_std_path()), id);
+        true
+    }
+
+    /// Reverts the file at `path` to what's saved on disk.
+    ///
+    /// Returns whether the workspace has changed and needs to be reanalyzed.
+    pub fn reload_file(&mut self, path: &Path) -> bool {
+        let Some(utf8_path) = Utf8Path::from_path(path) else {
+            return false;
+        };
+        match read_source_file(utf8_path) {
+            Ok(contents) => self.set_file_contents(path, contents),
+            Err(error) => {
+                error!("cannot reload {path:?}: {error:?}");
+                false
+            }
+        }
+    }
+
+    /// Analyzes the main package. Classes of files that have not changed since the last analysis
+    /// are not parsed again.
+    pub fn analyze(&mut self) {
+        let _span = info_span!("analyze_workspace").entered();
+
+        // The previous analysis is replaced below, so nothing refers to the old tokens anymore.
+        if self.session.has_outdated_tokens() {
+            self.session.discard_tokens();
+        }
+        let class_names = self.session.main_class_names();
+        let Compilation { env, result } = self.session.compile(&self.lint_settings, &class_names);
+        self.analysis = Analysis {
+            env,
+            package: result.ok(),
+        };
+    }
+
+    pub fn sources(&self) -> LexedSources<'_> {
+        self.session.sources()
+    }
+
+    /// Runs `f` with a compiler operating on the results of the last analysis.
+    ///
+    /// Since analysis is lazy, answering a query may analyze parts of the code that haven't been
+    /// analyzed yet. Any diagnostics emitted while doing so are discarded, since they're not
+    /// relevant to the query.
+    pub fn with_compiler<R>(&mut self, f: impl FnOnce(&mut Compiler<'_>) -> R) -> R {
+        let session = &mut self.session;
+        let env = &mut self.analysis.env;
+        let mut sources = OwnedSources {
+            source_file_set: &session.source_file_set,
+            token_arena: std::mem::take(&mut session.token_arena),
+            lexer_errors: std::mem::take(&mut session.lexer_errors),
+            synthesized_tokens: std::mem::take(&mut session.synthesized_tokens),
+        };
+
+        let num_diagnostics = env.diagnostics.len();
+        let compiler = &mut Compiler {
+            sources: &mut sources,
+            env,
+            input: &session.input,
+        };
+        let result = f(compiler);
+        compiler.env.diagnostics.truncate(num_diagnostics);
+
+        // The compiler borrows the sources for as long as they live, so the only way to keep the
+        // lexed tokens around is to take them out through the compiler.
+        session.token_arena = std::mem::take(&mut compiler.sources.token_arena);
+        session.lexer_errors = std::mem::take(&mut compiler.sources.lexer_errors);
+        session.synthesized_tokens = std::mem::take(&mut compiler.sources.synthesized_tokens);
+        result
+    }
+}
@@ -0,0 +1,353 @@
+//! Drives the language server through a scripted client speaking LSP over standard I/O.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+const OBJECT_UC: &str = "\
+class Object;
+
+native(146) static final operator(20) int + ( int A, int B );
+";
+
+const TEST_UC: &str = "\
+class Test extends Object;
+
+var int Counter;
+/** Adds two numbers together. */
+function int Add(int A, int B)
+{
+    return A + B;
+}
+
+function Run()
+{
+    local int Total;
+    Total = Add(Counter, 2);
+    Counter = Totl;
+}
+";
+
+struct Client {
+    server: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    next_id: i64,
+}
+
+impl Client {
+    fn spawn(args: &[&Path]) -> Self {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_muscript-lsp"));
+        for arg in args {
+            command.arg("-s").arg(arg);
+        }
+        let mut server = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("cannot spawn language server");
+        let stdin = server.stdin.take().unwrap();
+        let stdout = server.stdout.take().unwrap();
+
+        let (sender, messages) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            while let Some(message) = read_message(&mut stdout) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            server,
+            stdin,
+            messages,
+            next_id: 0,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Sends a request and returns its result, skipping over any notifications in between.
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                assert!(message.get("error").is_none(), "request failed: {message}");
+                return message["result"].clone();
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Value {
+        self.messages
+            .recv_timeout(TIMEOUT)
+            .expect("language server did not respond in time")
+    }
+
+    /// Waits for diagnostics to be published for the file at `uri`.
+    fn diagnostics(&mut self, uri: &str) -> Vec<Value> {
+        loop {
+            let message = self.receive();
+            if message["method"] == "textDocument/publishDiagnostics"
+                && message["params"]["uri"] == uri
+            {
+                return message["params"]["diagnostics"].as_array().unwrap().clone();
+            }
+        }
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length: ") {
+            content_length = length.parse().ok();
+        }
+    }
+    let mut body = vec![0; content_length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn write_package(root: &Path, name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let classes = root.join(name).join("Classes");
+    std::fs::create_dir_all(&classes).unwrap();
+    for (filename, contents) in files {
+        std::fs::write(classes.join(filename), contents).unwrap();
+    }
+    root.join(name)
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.canonicalize().unwrap().display())
+}
+
+fn position(line: u32, character: u32, uri: &str) -> Value {
+    json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": line, "character": character },
+    })
+}
+
+#[test]
+fn scripted_session() {
+    let root = std::env::temp_dir().join(format!("muscript-lsp-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let core = write_package(&root, "Core", &[("Object.uc", OBJECT_UC)]);
+    let test = write_package(&root, "Test", &[("Test.uc", TEST_UC)]);
+    let test_uc = file_uri(&test.join("Classes/Test.uc"));
+
+    let mut client = Client::spawn(&[&core]);
+    client.request(
+        "initialize",
+        json!({ "processId": null, "rootUri": file_uri(&test), "capabilities": {} }),
+    );
+    client.notify("initialized", json!({}));
+
+    // Diagnostics are published as soon as the workspace is analyzed.
+    let diagnostics = client.diagnostics(&test_uc);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:#?}");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 13);
+    let message = diagnostics[0]["message"].as_str().unwrap();
+    assert!(message.contains("cannot find variable `Totl`"), "{message}");
+    assert!(message.contains("`Total`"), "{message}");
+
+    // `Add` in `Total = Add(Counter, 2);`
+    let definition = client.request("textDocument/definition", position(12, 13, &test_uc));
+    assert_eq!(definition["uri"], test_uc.as_str());
+    assert_eq!(definition["range"]["start"]["line"], 4);
+    assert_eq!(definition["range"]["start"]["character"], 13);
+
+    // Hovering over a function shows its doc comment.
+    let hover = client.request("textDocument/hover", position(12, 13, &test_uc));
+    let hover_text = hover["contents"]["value"].as_str().unwrap();
+    assert!(
+        hover_text.contains("Adds two numbers together."),
+        "{hover_text}"
+    );
+
+    // `Counter` in `Total = Add(Counter, 2);`
+    let hover = client.request("textDocument/hover", position(12, 18, &test_uc));
+    let hover_text = hover["contents"]["value"].as_str().unwrap();
+    assert!(hover_text.contains("var Int Counter"), "{hover_text}");
+
+    // `Total` on its own declaration.
+    let hover = client.request("textDocument/hover", position(11, 15, &test_uc));
+    let hover_text = hover["contents"]["value"].as_str().unwrap();
+    assert!(hover_text.contains("local Int Total"), "{hover_text}");
+
+    let completion = client.request("textDocument/completion", position(13, 4, &test_uc));
+    let labels: Vec<_> = completion
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap().to_owned())
+        .collect();
+    for expected in ["Total", "Counter", "Add", "Run"] {
+        assert!(
+            labels.iter().any(|label| label == expected),
+            "{expected} missing from {labels:?}"
+        );
+    }
+
+    // Fixing the typo clears the diagnostics.
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": test_uc, "languageId": "unrealscript", "version": 1, "text": TEST_UC },
+        }),
+    );
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": test_uc, "version": 2 },
+            "contentChanges": [{ "text": TEST_UC.replace("Totl", "Total") }],
+        }),
+    );
+    let diagnostics = client.diagnostics(&test_uc);
+    assert!(diagnostics.is_empty(), "{diagnostics:#?}");
+
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    let status = client.server.wait().unwrap();
+    assert!(status.success());
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn project_file_and_edits() {
+    let root =
+        std::env::temp_dir().join(format!("muscript-lsp-test-project-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    write_package(&root, "Core", &[("Object.uc", OBJECT_UC)]);
+    let test = write_package(
+        &root,
+        "Test",
+        &[(
+            "Test.uc",
+            "\
+class Test extends Object;
+
+function int Run()
+{
+    local int Unused;
+    return `ONE + 1;
+}
+",
+        )],
+    );
+    std::fs::write(
+        test.join("muscript.toml"),
+        "\
+[package]
+sources = [\"../Core\"]
+
+[definitions]
+ONE = \"1\"
+
+[lints]
+unused_variable = \"deny\"
+",
+    )
+    .unwrap();
+    let test_uc = file_uri(&test.join("Classes/Test.uc"));
+    let helper_uc = test.join("Classes/Helper.uc");
+
+    // External packages and definitions come from the project file, not the command line.
+    let mut client = Client::spawn(&[]);
+    client.request(
+        "initialize",
+        json!({ "processId": null, "rootUri": file_uri(&test), "capabilities": {} }),
+    );
+    client.notify("initialized", json!({}));
+
+    // The project file denies unused variables.
+    let diagnostics = client.diagnostics(&test_uc);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:#?}");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    let message = diagnostics[0]["message"].as_str().unwrap();
+    assert!(message.contains("unused variable `Unused`"), "{message}");
+
+    // Edits only reparse the edited class, which must not leave stale results for the others.
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": test_uc, "version": 2 },
+            "contentChanges": [{ "text": "\
+class Test extends Helper;
+
+function int Run()
+{
+    return Get() + `ONE;
+}
+" }],
+        }),
+    );
+    let diagnostics = client.diagnostics(&test_uc);
+    let message = diagnostics[0]["message"].as_str().unwrap();
+    assert!(
+        message.contains("class `Helper` does not exist"),
+        "{diagnostics:#?}"
+    );
+
+    // Opening a class that does not exist on disk yet adds it to the package.
+    std::fs::write(&helper_uc, "").unwrap();
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": file_uri(&helper_uc),
+                "languageId": "unrealscript",
+                "version": 1,
+                "text": "\
+class Helper extends Object;
+
+function int Get()
+{
+    return 2;
+}
+",
+            },
+        }),
+    );
+    let diagnostics = client.diagnostics(&test_uc);
+    assert!(diagnostics.is_empty(), "{diagnostics:#?}");
+
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    let status = client.server.wait().unwrap();
+    assert!(status.success());
+
+    let _ = std::fs::remove_dir_all(&root);
+}
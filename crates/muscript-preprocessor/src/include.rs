@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use muscript_foundation::{ident::CaseInsensitive, source::SourceFileId};
+
+/// Index of the files `` `include `` directives may refer to.
+///
+/// Files are registered under their path relative to the directory containing their package, such
+/// as `Engine/Classes/GameStats.uci`. Paths are compared case-insensitively, and either `/` or `\`
+/// may be used to separate their components.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeFiles {
+    files: HashMap<CaseInsensitive<String>, SourceFileId>,
+}
+
+impl IncludeFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a file under the given path, which should be relative to the directory
+    /// containing the file's package.
+    pub fn add(&mut self, path: &str, id: SourceFileId) {
+        if let Some(path) = normalize(path) {
+            self.files.insert(CaseInsensitive::new(path), id);
+        }
+    }
+
+    /// Unregisters a file, such as one that was deleted.
+    pub fn remove(&mut self, id: SourceFileId) {
+        self.files.retain(|_, &mut file| file != id);
+    }
+
+    /// Finds the file an `` `include `` directive refers to. `including_file` is the path of the
+    /// file containing the directive, in the same form as paths passed to [`IncludeFiles::add`].
+    ///
+    /// The path is looked up relative to the directory of the including file, then relative to
+    /// the root directory of its package, and finally relative to the directory containing all
+    /// packages.
+    pub fn resolve(&self, including_file: &str, path: &str) -> Option<SourceFileId> {
+        let including_file = normalize(including_file).unwrap_or_default();
+        let directory = including_file
+            .rsplit_once('/')
+            .map_or("", |(directory, _)| directory);
+        let package = including_file
+            .split_once('/')
+            .map_or("", |(package, _)| package);
+        for base in [directory, package, ""] {
+            let Some(candidate) = normalize(&format!("{base}/{path}")) else {
+                continue;
+            };
+            if let Some(&id) = self.files.get(CaseInsensitive::new_ref(&candidate)) {
+                return Some(id);
+            }
+        }
+        None
+    }
+}
+
+/// Turns a path into a canonical form with `/` as the separator and `.` and `..` components
+/// removed. Returns [`None`] if the path refers to something above its root.
+fn normalize(path: &str) -> Option<String> {
+    let mut components = vec![];
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop()?;
+            }
+            _ => components.push(component),
+        }
+    }
+    Some(components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use muscript_foundation::source::{SourceFile, SourceFileId, SourceFileSet};
+
+    use super::{normalize, IncludeFiles};
+
+    /// Registers a file under each of the given paths, and returns the index along with the files'
+    /// IDs. Files are only used as keys, so their contents don't matter.
+    fn include_files<const N: usize>(paths: [&str; N]) -> (IncludeFiles, [SourceFileId; N]) {
+        let mut set = SourceFileSet::new();
+        let mut includes = IncludeFiles::new();
+        let ids = paths.map(|path| {
+            let id = set.add(SourceFile::unloaded(
+                Arc::from("Test"),
+                path.to_owned(),
+                PathBuf::from(path),
+            ));
+            includes.add(path, id);
+            id
+        });
+        (includes, ids)
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(
+            normalize("Engine\\Classes\\X.uci").as_deref(),
+            Some("Engine/Classes/X.uci")
+        );
+        assert_eq!(
+            normalize("./Engine//Classes/./X.uci").as_deref(),
+            Some("Engine/Classes/X.uci")
+        );
+        assert_eq!(
+            normalize("Engine/Classes/../X.uci").as_deref(),
+            Some("Engine/X.uci")
+        );
+        assert_eq!(normalize("Engine/../../X.uci"), None);
+    }
+
+    #[test]
+    fn paths_resolve_relative_to_including_directory_then_package_then_root() {
+        let (includes, [in_directory, in_package, in_root]) =
+            include_files(["Test/Classes/Shared.uci", "Test/Shared.uci", "Shared.uci"]);
+        assert_eq!(
+            includes.resolve("Test/Classes/Test.uc", "Shared.uci"),
+            Some(in_directory)
+        );
+        assert_eq!(
+            includes.resolve("Test/Classes/Nested/Test.uci", "Shared.uci"),
+            Some(in_package)
+        );
+        assert_eq!(
+            includes.resolve("Other/Classes/Test.uc", "Shared.uci"),
+            Some(in_root)
+        );
+        assert_eq!(
+            includes.resolve("Test/Classes/Test.uc", "Missing.uci"),
+            None
+        );
+    }
+
+    #[test]
+    fn paths_are_case_insensitive_and_may_use_either_separator() {
+        let (includes, [id]) = include_files(["Engine\\Classes\\GameStats.uci"]);
+        assert_eq!(
+            includes.resolve("Test/Classes/Test.uc", "engine/classes/gamestats.UCI"),
+            Some(id)
+        );
+        assert_eq!(
+            includes.resolve("Test\\Classes\\Test.uc", "Engine\\Classes\\GameStats.uci"),
+            Some(id)
+        );
+    }
+
+    #[test]
+    fn parent_components_are_resolved() {
+        let (includes, [id]) = include_files(["Engine/Classes/GameStats.uci"]);
+        assert_eq!(
+            includes.resolve("Test/Classes/Test.uc", "../../Engine/Classes/GameStats.uci"),
+            Some(id)
+        );
+        assert_eq!(
+            includes.resolve("Engine/Classes/Nested/Test.uc", "../GameStats.uci"),
+            Some(id)
+        );
+        // Climbing above the directory containing all packages never finds anything.
+        assert_eq!(
+            includes.resolve(
+                "Test/Classes/Test.uc",
+                "../../../Engine/Classes/GameStats.uci"
+            ),
+            None
+        );
+    }
+}
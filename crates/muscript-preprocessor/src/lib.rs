@@ -1,17 +1,25 @@
-use std::{cmp::Ordering, collections::HashMap};
+mod include;
+mod mode;
+mod strings;
+
+use std::{cmp::Ordering, collections::HashMap, rc::Rc};
 
 use indoc::indoc;
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, lints::LintScope, Diagnostic, DiagnosticSink, Label},
     ident::CaseInsensitive,
+    source::SourceFileId,
 };
 use muscript_lexer::{
-    sliced_tokens::{SlicedTokens, TokenSlice},
+    sliced_tokens::{Inclusion, SlicedTokens, TokenSlice},
     sources::LexedSources,
-    token::{AnyToken, Token, TokenKind, TokenSpan},
-    token_stream::{TokenSpanCursor, TokenStream},
+    token::{AnyToken, Token, TokenId, TokenKind, TokenSpan},
+    token_stream::{Channel, TokenSpanCursor, TokenStream},
 };
 
+pub use include::IncludeFiles;
+pub use mode::{Mode, Modes};
+
 /// A map of definitions. These may be constructed externally, to provide the preprocessor with
 /// symbols such as `FINAL_RELEASE`.
 #[derive(Debug, Clone, Default)]
@@ -26,18 +34,36 @@ pub struct Definition {
     pub parameters: Option<Vec<String>>,
 }
 
+/// Arguments of the macro being expanded, bound to the names of its parameters.
+#[derive(Debug, Default)]
+struct Arguments {
+    definitions: Definitions,
+    /// Arguments of the macro whose expansion the invocation was part of. The arguments' own
+    /// tokens are preprocessed with these, since that's where they were written.
+    outer: Option<Rc<Arguments>>,
+}
+
 /// Preprocessor that sits between the lexer and the parser.
 ///
 /// Note that the MuScript preprocessor does not strive for 100% compatibility with the UnrealScript
 /// preprocessor, largely because that would require sacrificing a lot of MuScript's error reporting
 /// infrastructure. Therefore this preprocessor only really supports features on a best effort
-/// basis; only enough features are supported to compile the engine successfully.
+/// basis; only enough features are supported to compile the engine successfully. Code relying on
+/// the UnrealScript preprocessor's semantics can be preprocessed in [`Mode::Compatible`].
 ///
 /// In general MuScript's improved ergonomics should be preferred over abusing the preprocessor
 /// as is typical in UnrealScript programming.
 pub struct Preprocessor<'a> {
     global_definitions: &'a mut Definitions,
-    local_definitions: Definitions,
+    arguments: Rc<Arguments>,
+    includes: &'a IncludeFiles,
+    mode: Mode,
+    /// Files currently being included, outermost first, starting with the file being
+    /// preprocessed. Used to reject include cycles.
+    include_stack: Vec<SourceFileId>,
+    /// The `` ` `` of the outermost macro invocation being expanded, which `` `__LINE__`` and
+    /// `` `__FILE__`` refer to.
+    outermost_invocation: Option<TokenId>,
     sources: LexedSources<'a>,
     tokens: TokenSpanCursor<'a>,
     diagnostics: &'a mut dyn DiagnosticSink<Token>,
@@ -55,6 +81,7 @@ struct If {
 impl<'a> Preprocessor<'a> {
     pub fn new(
         definitions: &'a mut Definitions,
+        includes: &'a IncludeFiles,
         sources: LexedSources<'a>,
         in_tokens: TokenSpanCursor<'a>,
         out_tokens: &'a mut SlicedTokens,
@@ -62,7 +89,11 @@ impl<'a> Preprocessor<'a> {
     ) -> Self {
         Self {
             global_definitions: definitions,
-            local_definitions: Definitions::default(),
+            arguments: Rc::default(),
+            includes,
+            mode: Mode::default(),
+            include_stack: vec![sources.token_arena.source_file_id(in_tokens.position())],
+            outermost_invocation: None,
             sources,
             tokens: in_tokens,
             diagnostics,
@@ -72,6 +103,12 @@ impl<'a> Preprocessor<'a> {
         }
     }
 
+    /// Sets the mode directives are evaluated in. The default is [`Mode::MuScript`].
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     fn flush(&mut self) {
         if let TokenSpan::Spanning { start, end } = self.current_span {
             self.out_tokens.push_slice(TokenSlice::Span { start, end });
@@ -112,10 +149,14 @@ impl<'a> Preprocessor<'a> {
             let token = self.tokens.peek();
             match token.kind {
                 TokenKind::EndOfFile => {
-                    self.diagnostics
-                        .emit(Diagnostic::error("mismatched parenthesis").with_label(
-                            Label::primary(&left_paren, "this `(` does not have a matching `)`"),
-                        ));
+                    self.diagnostics.emit(
+                        Diagnostic::error("mismatched parenthesis")
+                            .with_code(codes::M0203)
+                            .with_label(Label::primary(
+                                &left_paren,
+                                "this `(` does not have a matching `)`",
+                            )),
+                    );
                     return (elements, self.tokens.next());
                 }
                 TokenKind::RightParen => {
@@ -133,6 +174,7 @@ impl<'a> Preprocessor<'a> {
                 _ => {
                     self.diagnostics.emit(
                         Diagnostic::error("`,` or `)` expected")
+                            .with_code(codes::M0204)
                             .with_label(Label::primary(&next_token, "")),
                     );
                     return (elements, next_token);
@@ -164,14 +206,24 @@ impl<'a> Preprocessor<'a> {
             _ if macro_name.eq_ignore_ascii_case("if") => self.parse_if(macro_name_ident),
             _ if macro_name.eq_ignore_ascii_case("else") => self.parse_else(macro_name_ident),
             _ if macro_name.eq_ignore_ascii_case("endif") => self.parse_endif(macro_name_ident),
-            _ if macro_name.eq_ignore_ascii_case("include") => self.parse_include(macro_name_ident),
-            _ => self.parse_user_macro(macro_name_ident),
+            _ if macro_name.eq_ignore_ascii_case("include") => self.parse_include(accent),
+            _ if macro_name.eq_ignore_ascii_case("__LINE__")
+                || macro_name.eq_ignore_ascii_case("__FILE__") =>
+            {
+                self.parse_builtin(accent, macro_name_ident)
+            }
+            // Braced macro names are those not immediately following the accent.
+            _ if self.mode == Mode::Compatible && accent.id.successor() != macro_name_ident.id => {
+                self.parse_glued_macro(accent, macro_name_ident)
+            }
+            _ => self.parse_user_macro(accent, macro_name_ident),
         }
     }
 
     fn parse_macro_name(&mut self) -> Option<AnyToken> {
         fn macro_name_expected(token: AnyToken) -> Diagnostic<Token> {
             Diagnostic::error("macro name expected")
+                .with_code(codes::M0100)
                 .with_label(Label::primary(&token, "identifier expected here"))
         }
 
@@ -179,15 +231,12 @@ impl<'a> Preprocessor<'a> {
         match name_or_left_brace.kind {
             TokenKind::Ident => Some(name_or_left_brace),
             TokenKind::LeftBrace => {
-                let Some(name) = self.expect_token(TokenKind::Ident, macro_name_expected) else {
-                    return None;
-                };
-                let Some(_right_brace) = self.expect_token(TokenKind::RightBrace, |token| {
+                let name = self.expect_token(TokenKind::Ident, macro_name_expected)?;
+                let _right_brace = self.expect_token(TokenKind::RightBrace, |token| {
                     Diagnostic::error("`}` expected after the braced macro name")
+                        .with_code(codes::M0100)
                         .with_label(Label::primary(&token, "`}` expected here"))
-                }) else {
-                    return None;
-                };
+                })?;
                 Some(name)
             }
             _ => {
@@ -201,6 +250,7 @@ impl<'a> Preprocessor<'a> {
     fn parse_define(&mut self) {
         let Some(macro_name_ident) = self.expect_token(TokenKind::Ident, |token| {
             Diagnostic::error("new macro name expected")
+                .with_code(codes::M0101)
                 .with_label(Label::primary(&token, "identifier expected here"))
         }) else {
             return;
@@ -211,6 +261,7 @@ impl<'a> Preprocessor<'a> {
             let (parameters, _) = self.parse_comma_separated(open, |preprocessor| {
                 let parameter = preprocessor.expect_token(TokenKind::Ident, |token| {
                     Diagnostic::error("macro argument name expected")
+                        .with_code(codes::M0101)
                         .with_label(Label::primary(&token, "identifier expected here"))
                 });
                 let name = preprocessor.sources.source(&parameter);
@@ -227,7 +278,7 @@ impl<'a> Preprocessor<'a> {
             match token.kind {
                 TokenKind::Backslash => {
                     self.expect_token(TokenKind::NewLine, |non_newline_token| {
-                        Diagnostic::error("newline expected after backslash `\\`")
+                        Diagnostic::error("newline expected after backslash `\\`").with_code(codes::M0101)
                             .with_label(Label::secondary(&token, "this backslash indicates the macro should carry over to the next line"))
                             .with_label(Label::primary(&non_newline_token, "this token is where a newline was expected"))
                     });
@@ -258,6 +309,7 @@ impl<'a> Preprocessor<'a> {
 
         let Some(_) = self.expect_token(TokenKind::LeftParen, |token| {
             Diagnostic::error("`(` expected")
+                .with_code(codes::M0102)
                 .with_label(Label::primary(&token, "`(` expected here"))
                 .with_note(NOTE)
         }) else {
@@ -265,6 +317,7 @@ impl<'a> Preprocessor<'a> {
         };
         let Some(macro_name) = self.expect_token(TokenKind::Ident, |token| {
             Diagnostic::error("missing name of macro to undefine")
+                .with_code(codes::M0102)
                 .with_label(Label::primary(&token, "identifier expected here"))
                 .with_note(NOTE)
         }) else {
@@ -272,6 +325,7 @@ impl<'a> Preprocessor<'a> {
         };
         let Some(_) = self.expect_token(TokenKind::RightParen, |token| {
             Diagnostic::error("`)` expected to close `undefine invocation")
+                .with_code(codes::M0102)
                 .with_label(Label::primary(&token, "`)` expected here"))
                 .with_note(NOTE)
         }) else {
@@ -303,6 +357,7 @@ impl<'a> Preprocessor<'a> {
 
         let Some(_) = self.expect_token(TokenKind::LeftParen, |token| {
             Diagnostic::error("`(` expected")
+                .with_code(codes::M0103)
                 .with_label(Label::primary(&token, "`(` expected here"))
                 .with_note(NOTE[not as usize])
         }) else {
@@ -310,6 +365,7 @@ impl<'a> Preprocessor<'a> {
         };
         let Some(macro_name_ident) = self.expect_token(TokenKind::Ident, |token| {
             Diagnostic::error("missing name of macro to check")
+                .with_code(codes::M0103)
                 .with_label(Label::primary(&token, "identifier expected here"))
                 .with_note(NOTE[not as usize])
         }) else {
@@ -322,6 +378,7 @@ impl<'a> Preprocessor<'a> {
                     "`)` expected to close `notdefined invocation",
                 ][not as usize],
             )
+            .with_code(codes::M0103)
             .with_label(Label::primary(&token, "`)` expected here"))
             .with_note(NOTE[not as usize])
         }) else {
@@ -336,10 +393,16 @@ impl<'a> Preprocessor<'a> {
         let emit_non_empty = if not { !is_defined } else { is_defined };
 
         if emit_non_empty {
-            self.out_tokens.push_slice(TokenSlice::Span {
-                start: accent.id,
-                end: right_paren.id,
-            });
+            match self.mode {
+                Mode::MuScript => self.out_tokens.push_slice(TokenSlice::Span {
+                    start: accent.id,
+                    end: right_paren.id,
+                }),
+                Mode::Compatible => {
+                    self.out_tokens
+                        .push_synthesized(accent.id, TokenKind::IntLit, "1".to_owned())
+                }
+            }
         } else {
             self.out_tokens
                 .push_slice(TokenSlice::Empty { source: accent.id });
@@ -349,6 +412,7 @@ impl<'a> Preprocessor<'a> {
     fn parse_if(&mut self, if_ident: AnyToken) {
         let Some(left_paren) = self.expect_token(TokenKind::LeftParen, |token| {
             Diagnostic::error("`(` expected after `if")
+                .with_code(codes::M0104)
                 .with_label(Label::primary(&token, "`(` expected here"))
         }) else {
             return;
@@ -374,9 +438,12 @@ impl<'a> Preprocessor<'a> {
                 }
                 TokenKind::EndOfFile => {
                     self.diagnostics.emit(
-                        Diagnostic::error("missing `)` to close `if condition").with_label(
-                            Label::primary(&left_paren, "this `(` does not have a matching `)`"),
-                        ),
+                        Diagnostic::error("missing `)` to close `if condition")
+                            .with_code(codes::M0203)
+                            .with_label(Label::primary(
+                                &left_paren,
+                                "this `(` does not have a matching `)`",
+                            )),
                     );
                     return;
                 }
@@ -395,32 +462,53 @@ impl<'a> Preprocessor<'a> {
             ) {
                 let condition_tokens = {
                     let mut condition_tokens = SlicedTokens::new();
+                    let include_stack = self.include_stack.clone();
                     let mut sub_preprocessor = Preprocessor::new(
                         self.global_definitions,
+                        self.includes,
                         self.sources,
                         cursor,
                         &mut condition_tokens,
                         self.diagnostics,
-                    );
+                    )
+                    .with_mode(self.mode);
+                    sub_preprocessor.include_stack = include_stack;
+                    sub_preprocessor.arguments = Rc::clone(&self.arguments);
+                    sub_preprocessor.outermost_invocation = self.outermost_invocation;
                     sub_preprocessor.preprocess();
                     condition_tokens
                 };
-                // We only need to check one token; if we have one, the part inside parentheses is
-                // not empty.
-                let condition_token = condition_tokens
-                    .stream(self.sources.token_arena)
-                    .map(|mut stream| stream.next());
-                matches!(
-                    condition_token,
+                match (self.mode, condition_tokens.stream(self.sources.token_arena)) {
                     // None can happen if the preprocessor didn't produce any tokens (which would be
                     // weird, but let's err on the side of caution.)
-                    None | Some(AnyToken {
-                        // EndOfFile can happen only in case of `if();
-                        // FailedExp happens if any macro inside expands to nothing.
-                        kind: TokenKind::EndOfFile | TokenKind::FailedExp,
-                        ..
-                    })
-                )
+                    (_, None) => true,
+                    // We only need to check one token; if we have one, the part inside parentheses
+                    // is not empty.
+                    (Mode::MuScript, Some(mut stream)) => matches!(
+                        stream.next(),
+                        AnyToken {
+                            // EndOfFile can happen only in case of `if();
+                            // FailedExp happens if any macro inside expands to nothing.
+                            kind: TokenKind::EndOfFile | TokenKind::FailedExp,
+                            ..
+                        }
+                    ),
+                    // UPP looks at the whole expanded text, so macros expanding to nothing do not
+                    // make the condition empty unless there's nothing else in it.
+                    (Mode::Compatible, Some(mut stream)) => loop {
+                        let token = stream.next();
+                        if token.kind == TokenKind::EndOfFile {
+                            break true;
+                        }
+                        if token
+                            .kind
+                            .channel()
+                            .intersects(Channel::CODE | Channel::ERROR)
+                        {
+                            break false;
+                        }
+                    },
+                }
             } else {
                 true
             }
@@ -439,6 +527,7 @@ impl<'a> Preprocessor<'a> {
                 |name| name.eq_ignore_ascii_case("else") || name.eq_ignore_ascii_case("endif"),
                 || {
                     Diagnostic::error("missing `else or `endif to close `if")
+                        .with_code(codes::M0105)
                         .with_label(Label::primary(&if_ident, "this `if is never closed"))
                 },
             ) else {
@@ -455,6 +544,7 @@ impl<'a> Preprocessor<'a> {
                     |name| name.eq_ignore_ascii_case("endif"),
                     || {
                         Diagnostic::error("missing `endif to close `else")
+                            .with_code(codes::M0105)
                             .with_label(Label::primary(&else_ident, "this `else is never closed"))
                             .with_label(Label::primary(&if_ident, "this is the `else's `if"))
                     },
@@ -465,6 +555,7 @@ impl<'a> Preprocessor<'a> {
         } else {
             self.diagnostics.emit(
                 Diagnostic::error("`else without a matching `if")
+                    .with_code(codes::M0106)
                     .with_label(Label::primary(&else_ident, "stray `else here")),
             );
         }
@@ -508,114 +599,353 @@ impl<'a> Preprocessor<'a> {
         if self.if_stack.pop().is_none() {
             self.diagnostics.emit(
                 Diagnostic::error("`endif without a matching `if")
+                    .with_code(codes::M0106)
                     .with_label(Label::primary(&endif_ident, "stray `endif here")),
             )
         }
     }
 
-    fn parse_include(&mut self, include: AnyToken) {
-        self.diagnostics.emit(
-            Diagnostic::warning("use of `include preprocessor directive")
-                .with_label(Label::primary(&include, ""))
-                .with_note("note: MuScript ignores `include directives because it processes files in the correct order automatically"),
-        );
-
+    fn parse_include(&mut self, accent: AnyToken) {
         let Some(left_paren) = self.expect_token(TokenKind::LeftParen, |token| {
             Diagnostic::error("`(` expected")
+                .with_code(codes::M0108)
                 .with_label(Label::primary(&token, "`(` expected here"))
         }) else {
             return;
         };
 
+        let mut path_span = TokenSpan::Empty;
         loop {
             let token = self.tokens.peek();
             match token.kind {
                 TokenKind::RightParen => break,
                 TokenKind::EndOfFile => {
                     self.diagnostics.emit(
-                        Diagnostic::error("missing `)` to close `include path").with_label(
-                            Label::primary(&left_paren, "this `(` does not have a matching `)`"),
-                        ),
+                        Diagnostic::error("missing `)` to close `include path")
+                            .with_code(codes::M0203)
+                            .with_label(Label::primary(
+                                &left_paren,
+                                "this `(` does not have a matching `)`",
+                            )),
                     );
                     return;
                 }
                 _ => {
-                    self.tokens.next();
+                    let token = self.tokens.next();
+                    path_span = path_span.join(&TokenSpan::single(token.id));
                 }
             }
         }
 
-        let Some(_right_paren) = self.expect_token(TokenKind::RightParen, |token| {
+        let Some(right_paren) = self.expect_token(TokenKind::RightParen, |token| {
             Diagnostic::error("`)` expected after `include path")
+                .with_code(codes::M0108)
                 .with_label(Label::primary(&token, "`)` expected here"))
         }) else {
             return;
         };
+
+        let directive = TokenSpan::spanning(accent.id, right_paren.id);
+        let path = self.sources.source(&path_span).trim();
+        if path.is_empty() {
+            self.diagnostics.emit(
+                Diagnostic::error("missing path of file to include")
+                    .with_code(codes::M0108)
+                    .with_label(Label::primary(&directive, "")),
+            );
+            return;
+        }
+
+        let including_file = self
+            .sources
+            .source_file_set
+            .get(self.sources.token_arena.source_file_id(accent.id));
+        let Some(included_file_id) = self.includes.resolve(&including_file.filename, path) else {
+            self.diagnostics.emit(
+                Diagnostic::warning(format!("cannot find include file `{path}`"))
+                    .with_code(codes::M0107)
+                    .with_label(Label::primary(&path_span, "no such include file"))
+                    .with_note(indoc! {"
+                        note: include files are looked up relative to the directory of the file
+                              including them, then the root directory of its package, and then
+                              the directory containing all packages
+                    "})
+                    .with_note("note: the `include directive is ignored"),
+            );
+            return;
+        };
+
+        let included_file = self.sources.source_file_set.get(included_file_id);
+        if self.include_stack.contains(&included_file_id) {
+            self.diagnostics.emit(
+                Diagnostic::error(format!("`{}` includes itself", included_file.filename))
+                    .with_code(codes::M0110)
+                    .with_label(Label::primary(
+                        &directive,
+                        "this includes a file that is already being included",
+                    )),
+            );
+            return;
+        }
+
+        // The included file's end of file token is left out, since the file containing the
+        // directive continues after it.
+        let file_span = match self.sources.token_arena.source_file_span(included_file_id) {
+            TokenSpan::Spanning { start, end }
+                if self.sources.token_arena.element(end).kind == TokenKind::EndOfFile =>
+            {
+                match end.predecessor() {
+                    Some(last) if last >= start => TokenSpan::spanning(start, last),
+                    _ => TokenSpan::Empty,
+                }
+            }
+            span => span,
+        };
+        self.out_tokens.push_inclusion(Inclusion {
+            file: file_span,
+            directive,
+        });
+
+        if let Some(tokens) = TokenSpanCursor::new(self.sources.token_arena, file_span) {
+            let mut include_stack = self.include_stack.clone();
+            include_stack.push(included_file_id);
+            let mut diagnostics = IncludedFrom {
+                inner: self.diagnostics,
+                directive,
+            };
+            let mut sub_preprocessor = Preprocessor::new(
+                self.global_definitions,
+                self.includes,
+                self.sources,
+                tokens,
+                self.out_tokens,
+                &mut diagnostics,
+            )
+            .with_mode(self.mode);
+            sub_preprocessor.include_stack = include_stack;
+            sub_preprocessor.preprocess();
+        }
+    }
+
+    /// Finds the argument of the macro being expanded which is bound to the parameter `name`.
+    fn argument(&self, name: &str) -> Option<&Definition> {
+        self.arguments
+            .definitions
+            .map
+            .get(CaseInsensitive::new_ref(name))
     }
 
     fn definition(&self, name: &str) -> Option<&Definition> {
-        self.local_definitions
+        self.global_definitions
             .map
             .get(CaseInsensitive::new_ref(name))
-            .or_else(|| {
-                self.global_definitions
-                    .map
-                    .get(CaseInsensitive::new_ref(name))
-            })
     }
 
-    fn parse_user_macro(&mut self, macro_name_ident: AnyToken) {
-        let mut arguments = if self.tokens.peek().kind == TokenKind::LeftParen {
-            let open = self.tokens.next();
-            let (arguments, _close) = self.parse_comma_separated(open, |preprocessor| {
-                let mut span = TokenSpan::Empty;
-                let mut nesting = 0;
-                loop {
-                    let token = preprocessor.tokens.peek();
-                    match token.kind {
-                        TokenKind::LeftParen => {
+    /// Returns the token `` `__LINE__`` and `` `__FILE__`` refer to when invoked at `token`, which
+    /// is the invocation of the outermost macro being expanded, if any.
+    fn builtin_origin(&self, token: TokenId) -> TokenId {
+        self.outermost_invocation.unwrap_or(token)
+    }
+
+    /// Returns the text of a builtin macro (`` `__LINE__`` or `` `__FILE__``) invoked at `origin`.
+    fn builtin_text(&self, name: &str, origin: TokenId) -> Option<String> {
+        let file = self.sources.token_arena.source_file_id(origin);
+        if name.eq_ignore_ascii_case("__LINE__") {
+            let start = self.sources.token_arena.element(origin).source_range.start;
+            let source = self.sources.source_file_set.source(file);
+            let line = source[..start].matches('\n').count() + 1;
+            Some(line.to_string())
+        } else if name.eq_ignore_ascii_case("__FILE__") {
+            let filename = &self.sources.source_file_set.get(file).filename;
+            Some(
+                filename
+                    .rsplit(['/', '\\'])
+                    .next()
+                    .unwrap_or(filename)
+                    .to_owned(),
+            )
+        } else {
+            None
+        }
+    }
+
+    fn parse_builtin(&mut self, accent: AnyToken, name_ident: AnyToken) {
+        let name = self.sources.source(&name_ident);
+        let text = self
+            .builtin_text(name, self.builtin_origin(accent.id))
+            .expect("parse_builtin must only be called for builtin macros");
+        if name.eq_ignore_ascii_case("__FILE__") {
+            self.out_tokens
+                .push_synthesized(accent.id, TokenKind::StringLit, format!("{text:?}"));
+        } else {
+            self.out_tokens
+                .push_synthesized(accent.id, TokenKind::IntLit, text);
+        }
+    }
+
+    /// Expands a braced macro invocation in [`Mode::Compatible`], gluing the text it expands to
+    /// together with the identifiers or numbers directly next to it, like UPP's text substitution
+    /// would.
+    ///
+    /// Only expansions which result in a single identifier or number are glued; anything else is
+    /// expanded as usual.
+    fn parse_glued_macro(&mut self, accent: AnyToken, macro_name_ident: AnyToken) {
+        let sources = self.sources;
+        let hugs = |left: TokenId, right: TokenId| {
+            left.successor() == right
+                && sources.token_arena.element(left).source_range.end
+                    == sources.token_arena.element(right).source_range.start
+        };
+        let is_word = |kind: TokenKind| matches!(kind, TokenKind::Ident | TokenKind::IntLit);
+
+        let macro_name = sources.source(&macro_name_ident);
+        let takes_arguments = self
+            .definition(macro_name)
+            .is_some_and(|definition| definition.parameters.is_some());
+        let right_brace = macro_name_ident.id.successor();
+        let prefix = self.out_tokens.last_token().filter(|&token| {
+            hugs(token, accent.id) && is_word(sources.token_arena.element(token).kind)
+        });
+        let next = self.tokens.peek();
+        let suffix = (hugs(right_brace, next.id) && is_word(next.kind)).then_some(next);
+        if takes_arguments
+            || self.argument(macro_name).is_some()
+            || (prefix.is_none() && suffix.is_none())
+        {
+            self.parse_user_macro(accent, macro_name_ident);
+            return;
+        }
+
+        let expansion = self.expand_text(&format!("`{macro_name}"), accent);
+        let expansion = expansion.trim();
+        let prefix_text = prefix.map_or("", |token| sources.source(&TokenSpan::single(token)));
+        let suffix_text = suffix.map_or("", |token| sources.source(&token));
+        let word = format!("{prefix_text}{expansion}{suffix_text}");
+        let kind = if !word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            None
+        } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+            word.chars()
+                .all(|c| c.is_ascii_digit())
+                .then_some(TokenKind::IntLit)
+        } else {
+            Some(TokenKind::Ident)
+        };
+        let Some(kind) = kind else {
+            self.parse_user_macro(accent, macro_name_ident);
+            return;
+        };
+
+        if prefix.is_some() {
+            self.out_tokens.pop_token();
+        }
+        if suffix.is_some() {
+            _ = self.tokens.next();
+        }
+        let invocation = TokenSpan::spanning(accent.id, right_brace);
+        self.out_tokens
+            .begin_expansion(macro_name.to_owned(), invocation);
+        self.out_tokens.push_synthesized(accent.id, kind, word);
+        self.out_tokens.end_expansion();
+    }
+
+    fn parse_macro_arguments(&mut self) -> (Vec<Definition>, AnyToken) {
+        let open = self.tokens.next();
+        self.parse_comma_separated(open, |preprocessor| {
+            let mut span = TokenSpan::Empty;
+            let mut nesting = 0;
+            loop {
+                let token = preprocessor.tokens.peek();
+                match token.kind {
+                    TokenKind::LeftParen => {
+                        _ = preprocessor.tokens.next();
+                        nesting += 1;
+                    }
+                    TokenKind::RightParen => {
+                        if nesting > 0 {
                             _ = preprocessor.tokens.next();
-                            nesting += 1;
-                        }
-                        TokenKind::RightParen => {
-                            if nesting > 0 {
-                                _ = preprocessor.tokens.next();
-                                nesting -= 1;
-                            } else {
-                                break;
-                            }
-                        }
-                        TokenKind::Comma if nesting == 0 => break,
-                        TokenKind::EndOfFile => break,
-                        _ => {
-                            let token = preprocessor.tokens.next();
-                            span = span.join(&TokenSpan::single(token.id));
+                            nesting -= 1;
+                        } else {
+                            break;
                         }
                     }
+                    TokenKind::Comma if nesting == 0 => break,
+                    TokenKind::EndOfFile => break,
+                    _ => {
+                        let token = preprocessor.tokens.next();
+                        span = span.join(&TokenSpan::single(token.id));
+                    }
                 }
-                Definition {
-                    source_span: span,
-                    parameters: None,
-                }
-            });
+            }
+            Definition {
+                source_span: span,
+                parameters: None,
+            }
+        })
+    }
+
+    /// Expands an argument of the macro being expanded, in place of its parameter.
+    fn expand_argument(&mut self, source_span: TokenSpan) {
+        let Some(tokens) = TokenSpanCursor::new(self.sources.token_arena, source_span) else {
+            return;
+        };
+        let include_stack = self.include_stack.clone();
+        let outer = self.arguments.outer.clone().unwrap_or_default();
+        let mut sub_preprocessor = Preprocessor::new(
+            self.global_definitions,
+            self.includes,
+            self.sources,
+            tokens,
+            self.out_tokens,
+            self.diagnostics,
+        )
+        .with_mode(self.mode);
+        sub_preprocessor.include_stack = include_stack;
+        sub_preprocessor.arguments = outer;
+        sub_preprocessor.outermost_invocation = self.outermost_invocation;
+        sub_preprocessor.preprocess();
+    }
+
+    fn parse_user_macro(&mut self, accent: AnyToken, macro_name_ident: AnyToken) {
+        let macro_name = self.sources.source(&macro_name_ident);
+        // Parameters are substituted with their arguments as is, and whatever follows them is
+        // left alone.
+        if let Some(argument) = self.argument(macro_name) {
+            let source_span = argument.source_span;
+            self.expand_argument(source_span);
+            return;
+        }
+
+        // UPP only looks for arguments after macros which take them, so the parentheses after a
+        // macro without parameters are kept.
+        let takes_arguments = self
+            .definition(macro_name)
+            .map(|definition| definition.parameters.is_some());
+        let parse_arguments = self.tokens.peek().kind == TokenKind::LeftParen
+            && (self.mode == Mode::MuScript || takes_arguments == Some(true));
+
+        let mut invocation = TokenSpan::spanning(accent.id, macro_name_ident.id);
+        let mut arguments = if parse_arguments {
+            let (arguments, close) = self.parse_macro_arguments();
+            invocation = invocation.join(&TokenSpan::single(close.id));
             Some(arguments)
         } else {
             None
         };
 
-        let macro_name = self.sources.source(&macro_name_ident);
         if let Some(definition) = self.definition(macro_name) {
             if let Some(tokens) =
                 TokenSpanCursor::new(self.sources.token_arena, definition.source_span)
             {
                 let argument_count = arguments.as_ref().map(|list| list.len());
-                let parameter_count = definition.parameters.as_ref().map(|list| list.len());
+                let parameters = definition.parameters.clone();
+                let parameter_count = parameters.as_ref().map(|list| list.len());
                 match (parameter_count, argument_count) {
                     (None, Some(got)) => {
                         self.diagnostics.emit(
                             Diagnostic::error(format!(
                                 "macro expected no arguments, but {got} were provided"
                             ))
+                            .with_code(codes::M0109)
                             .with_label(Label::primary(&macro_name_ident, "")),
                         );
                         self.out_tokens.push_slice(TokenSlice::Empty {
@@ -628,6 +958,7 @@ impl<'a> Preprocessor<'a> {
                             Diagnostic::error(format!(
                                 "macro expected {expected} arguments, but none were provided"
                             ))
+                            .with_code(codes::M0109)
                             .with_label(Label::primary(&macro_name_ident, "")),
                         );
                         self.out_tokens.push_slice(TokenSlice::Empty {
@@ -663,15 +994,44 @@ impl<'a> Preprocessor<'a> {
                     }
                 }
 
+                // Bind the arguments to the macro's parameters, so that the macro's body can refer
+                // to them.
+                let bound = Arguments {
+                    definitions: Definitions {
+                        map: parameters
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(CaseInsensitive::new)
+                            .zip(arguments.unwrap_or_default())
+                            .collect(),
+                    },
+                    outer: Some(Rc::clone(&self.arguments)),
+                };
+
+                self.out_tokens
+                    .begin_expansion(macro_name.to_owned(), invocation);
+                let include_stack = self.include_stack.clone();
+                let outermost_invocation = self.builtin_origin(accent.id);
+                let mut diagnostics = ExpandedFrom {
+                    inner: self.diagnostics,
+                    macro_name,
+                    invocation,
+                };
                 let mut sub_preprocessor = Preprocessor::new(
                     self.global_definitions,
+                    self.includes,
                     self.sources,
                     tokens,
                     self.out_tokens,
-                    self.diagnostics,
-                );
+                    &mut diagnostics,
+                )
+                .with_mode(self.mode);
+                sub_preprocessor.include_stack = include_stack;
+                sub_preprocessor.arguments = Rc::new(bound);
+                sub_preprocessor.outermost_invocation = Some(outermost_invocation);
 
                 sub_preprocessor.preprocess();
+                self.out_tokens.end_expansion();
             } else {
                 // The macro is defined as empty; this is fine.
                 // This case does not result in a failed expansion.
@@ -692,9 +1052,15 @@ impl<'a> Preprocessor<'a> {
                     self.parse_macro_invocation(token);
                 }
                 TokenKind::EndOfFile => {
-                    self.current_span = self.current_span.join(&TokenSpan::single(token.id));
+                    // Cursors over spans which don't end with the end of their file (such as
+                    // macro bodies) report their last token as the end of file, which must not be
+                    // output a second time.
+                    if self.sources.token_arena.element(token.id).kind == TokenKind::EndOfFile {
+                        self.current_span = self.current_span.join(&TokenSpan::single(token.id));
+                    }
                     break;
                 }
+                TokenKind::StringLit => self.expand_string(token),
                 _ => self.current_span = self.current_span.join(&TokenSpan::single(token.id)),
             }
         }
@@ -704,3 +1070,45 @@ impl<'a> Preprocessor<'a> {
 
 #[derive(Debug, Clone, Copy)]
 struct EndOfFile;
+
+/// Sink for diagnostics emitted while preprocessing an included file, which points out where the
+/// file was included from.
+struct IncludedFrom<'a> {
+    inner: &'a mut dyn DiagnosticSink<Token>,
+    directive: TokenSpan,
+}
+
+impl<'a> DiagnosticSink<Token> for IncludedFrom<'a> {
+    fn emit(&mut self, diagnostic: Diagnostic<Token>) {
+        self.inner.emit(diagnostic.with_child(
+            Diagnostic::note("included from here").with_label(Label::primary(&self.directive, "")),
+        ));
+    }
+
+    fn set_lint_levels(&mut self, scope: LintScope<Token>) {
+        self.inner.set_lint_levels(scope);
+    }
+}
+
+/// Sink for diagnostics emitted while preprocessing the expansion of a macro, which points out
+/// where the macro was invoked.
+struct ExpandedFrom<'a, 's> {
+    inner: &'a mut dyn DiagnosticSink<Token>,
+    macro_name: &'s str,
+    invocation: TokenSpan,
+}
+
+impl<'a, 's> DiagnosticSink<Token> for ExpandedFrom<'a, 's> {
+    fn emit(&mut self, diagnostic: Diagnostic<Token>) {
+        self.inner.emit(
+            diagnostic.with_child(
+                Diagnostic::note(format!("in this expansion of macro `{}`", self.macro_name))
+                    .with_label(Label::primary(&self.invocation, "")),
+            ),
+        );
+    }
+
+    fn set_lint_levels(&mut self, scope: LintScope<Token>) {
+        self.inner.set_lint_levels(scope);
+    }
+}
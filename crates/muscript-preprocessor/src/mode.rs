@@ -0,0 +1,79 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use muscript_foundation::source::SourceFileId;
+
+/// How the preprocessor evaluates directives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// MuScript's own semantics, as described in `incompatibilities.md`.
+    #[default]
+    MuScript,
+    /// Replicates the semantics of the UnrealScript preprocessor where they differ from MuScript's,
+    /// so that code written for UPP preprocesses the same way:
+    ///
+    /// - `` `isdefined `` and `` `notdefined `` expand to `1` or nothing, and can thus be used
+    ///   outside of `` `if ``.
+    /// - `` `if `` is taken if its condition expands to anything at all, rather than only looking
+    ///   at what the condition starts with.
+    /// - `` `{MACRO} `` is glued together with identifiers and numbers directly next to it, so that
+    ///   `` Prefix`{MACRO}Suffix `` forms a single identifier.
+    Compatible,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::MuScript => "muscript",
+            Mode::Compatible => "compatible",
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "muscript" => Ok(Mode::MuScript),
+            "compatible" => Ok(Mode::Compatible),
+            _ => Err(format!(
+                "unknown preprocessor mode `{s}` (expected `muscript` or `compatible`)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The modes source files are preprocessed in. Since modes are selected per package, this is
+/// filled in as the package's files are added to the source file set.
+///
+/// Macros are expanded in the mode of the file being preprocessed, regardless of the mode of the
+/// file they were defined in.
+#[derive(Debug, Clone, Default)]
+pub struct Modes {
+    files: HashMap<SourceFileId, Mode>,
+}
+
+impl Modes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, file: SourceFileId, mode: Mode) {
+        if mode == Mode::default() {
+            self.files.remove(&file);
+        } else {
+            self.files.insert(file, mode);
+        }
+    }
+
+    /// Returns the mode of the file, which is [`Mode::MuScript`] unless set otherwise.
+    pub fn get(&self, file: SourceFileId) -> Mode {
+        self.files.get(&file).copied().unwrap_or_default()
+    }
+}
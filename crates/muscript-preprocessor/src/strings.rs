@@ -0,0 +1,315 @@
+//! Expansion of macros inside string literals.
+//!
+//! Unlike the rest of the preprocessor, which works on tokens, this works on the text of the
+//! string, since that's all there is to work with. The expanded string is synthesized as a new
+//! token, which diagnostics report at the original string.
+
+use std::{collections::HashMap, rc::Rc};
+
+use muscript_foundation::{
+    errors::{codes, Diagnostic, Label},
+    ident::CaseInsensitive,
+};
+use muscript_lexer::token::{AnyToken, TokenKind, TokenSpan};
+
+use crate::{Arguments, Preprocessor};
+
+/// How deep macros may expand into other macros inside a single string, before the expansion is
+/// assumed to be infinitely recursive.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+impl<'a> Preprocessor<'a> {
+    /// Expands the macros invoked inside the string literal `token`. Strings which don't invoke
+    /// any macros are passed through unchanged.
+    pub(crate) fn expand_string(&mut self, token: AnyToken) {
+        let text = self.sources.source(&token);
+        if !text.contains('`') {
+            self.current_span = self.current_span.join(&TokenSpan::single(token.id));
+            return;
+        }
+
+        self.flush();
+        let expanded = self.expand_text(text, token);
+        self.out_tokens
+            .push_synthesized(token.id, TokenKind::StringLit, expanded);
+    }
+
+    /// Expands the macros invoked inside `text`, which comes from the `origin` token. Diagnostics
+    /// are reported at the `origin`.
+    pub(crate) fn expand_text(&mut self, text: &str, origin: AnyToken) -> String {
+        let arguments = Rc::clone(&self.arguments);
+        let mut expander = StringExpander {
+            preprocessor: self,
+            token: origin,
+            if_stack: vec![],
+            too_deep: false,
+        };
+        let mut expanded = String::with_capacity(text.len());
+        expander.expand(text, Scope::Tokens(&arguments), 0, &mut expanded);
+        if !expander.if_stack.is_empty() {
+            self.diagnostics.emit(
+                Diagnostic::error("missing `endif inside string")
+                    .with_code(codes::M0105)
+                    .with_label(Label::primary(
+                        &origin,
+                        "an `if in this string is never closed",
+                    )),
+            );
+        }
+        expanded
+    }
+}
+
+/// Arguments visible to the text being expanded.
+#[derive(Clone, Copy)]
+enum Scope<'s> {
+    /// Arguments of the macro whose expansion the string is part of. Their text is expanded
+    /// as it is substituted.
+    Tokens(&'s Rc<Arguments>),
+    /// Arguments of a macro invoked inside the string, which are expanded before they're bound
+    /// to the macro's parameters.
+    Text(&'s HashMap<CaseInsensitive<String>, String>),
+}
+
+struct StringExpander<'p, 'a> {
+    preprocessor: &'p mut Preprocessor<'a>,
+    /// The string being expanded.
+    token: AnyToken,
+    if_stack: Vec<StringIf>,
+    /// Set once the expansion is found to be too deep, so that it's only reported once.
+    too_deep: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct StringIf {
+    condition: bool,
+    /// Whether the text around the `` `if `` was being output.
+    outer_active: bool,
+}
+
+impl<'p, 'a> StringExpander<'p, 'a> {
+    /// Returns whether text is currently being output, which is not the case inside the false
+    /// branch of an `` `if ``.
+    fn is_active(&self) -> bool {
+        self.if_stack
+            .last()
+            .is_none_or(|last_if| last_if.outer_active && last_if.condition)
+    }
+
+    fn push(&self, output: &mut String, text: &str) {
+        if self.is_active() {
+            output.push_str(text);
+        }
+    }
+
+    fn expand(&mut self, text: &str, scope: Scope<'_>, depth: usize, output: &mut String) {
+        if depth > MAX_EXPANSION_DEPTH {
+            if !self.too_deep {
+                self.preprocessor.diagnostics.emit(
+                    Diagnostic::error("macro expansion inside string is too deep")
+                        .with_code(codes::M0111)
+                        .with_label(Label::primary(&self.token, ""))
+                        .with_note(format!(
+                            "note: macros may expand into other macros at most {MAX_EXPANSION_DEPTH} times"
+                        ))
+                        .with_note("help: check whether any of the macros expands into itself"),
+                );
+                self.too_deep = true;
+            }
+            return;
+        }
+
+        let mut rest = text;
+        while let Some(accent) = rest.find('`') {
+            self.push(output, &rest[..accent]);
+            rest = &rest[accent + 1..];
+            let Some((name, after_name)) = split_macro_name(rest) else {
+                // A lone accent does not invoke anything.
+                self.push(output, "`");
+                continue;
+            };
+            rest = after_name;
+            let arguments = match split_arguments(rest) {
+                Some((arguments, after_arguments)) => {
+                    rest = after_arguments;
+                    Some(arguments)
+                }
+                None => None,
+            };
+            self.expand_macro(name, arguments, scope, depth, output);
+        }
+        self.push(output, rest);
+    }
+
+    fn expand_macro(
+        &mut self,
+        name: &str,
+        arguments: Option<Vec<&str>>,
+        scope: Scope<'_>,
+        depth: usize,
+        output: &mut String,
+    ) {
+        let argument = arguments
+            .as_ref()
+            .and_then(|arguments| arguments.first().copied())
+            .unwrap_or("");
+        match name {
+            _ if name.eq_ignore_ascii_case("if") => {
+                let outer_active = self.is_active();
+                let condition = outer_active && {
+                    let mut condition = String::new();
+                    self.expand(argument, scope, depth + 1, &mut condition);
+                    !condition.trim().is_empty()
+                };
+                self.if_stack.push(StringIf {
+                    condition,
+                    outer_active,
+                });
+            }
+            _ if name.eq_ignore_ascii_case("else") => match self.if_stack.last_mut() {
+                Some(last_if) => last_if.condition = !last_if.condition,
+                None => self.stray("`else without a matching `if"),
+            },
+            _ if name.eq_ignore_ascii_case("endif") => match self.if_stack.pop() {
+                Some(_) => (),
+                None => self.stray("`endif without a matching `if"),
+            },
+            _ if name.eq_ignore_ascii_case("isdefined")
+                || name.eq_ignore_ascii_case("notdefined") =>
+            {
+                let is_defined = self.preprocessor.definition(argument.trim()).is_some();
+                let not = name.eq_ignore_ascii_case("notdefined");
+                if is_defined != not {
+                    self.push(output, "1");
+                }
+            }
+            _ if self.is_active() => self.expand_user_macro(name, arguments, scope, depth, output),
+            _ => (),
+        }
+    }
+
+    fn expand_user_macro(
+        &mut self,
+        name: &str,
+        arguments: Option<Vec<&str>>,
+        scope: Scope<'_>,
+        depth: usize,
+        output: &mut String,
+    ) {
+        let sources = self.preprocessor.sources;
+        let key = CaseInsensitive::new_ref(name);
+        match scope {
+            Scope::Tokens(bound) => {
+                if let Some(argument) = bound.definitions.map.get(key) {
+                    let outer = bound.outer.clone().unwrap_or_default();
+                    let text = sources.source(&argument.source_span);
+                    self.expand(text, Scope::Tokens(&outer), depth + 1, output);
+                    return;
+                }
+            }
+            Scope::Text(bound) => {
+                if let Some(argument) = bound.get(key) {
+                    output.push_str(argument);
+                    return;
+                }
+            }
+        }
+
+        let origin = self.preprocessor.builtin_origin(self.token.id);
+        if let Some(text) = self.preprocessor.builtin_text(name, origin) {
+            output.push_str(&text);
+            return;
+        }
+
+        let Some(definition) = self.preprocessor.definition(name) else {
+            return;
+        };
+        // Line continuations are not part of the macro's text.
+        let body = sources
+            .source(&definition.source_span)
+            .replace("\\\r\n", "\n")
+            .replace("\\\n", "\n");
+        let parameters = definition.parameters.clone().unwrap_or_default();
+
+        let mut arguments: Vec<_> = arguments
+            .unwrap_or_default()
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        // Like with tokens, extra arguments are treated as one big argument.
+        if !parameters.is_empty() && arguments.len() > parameters.len() {
+            let last = arguments.split_off(parameters.len() - 1).join(",");
+            arguments.push(last);
+        }
+        self.expand_body(&body, &parameters, arguments, scope, depth, output);
+    }
+
+    /// Expands the body of a macro, with its parameters bound to `arguments` expanded in the
+    /// invocation's `scope`.
+    fn expand_body(
+        &mut self,
+        body: &str,
+        parameters: &[String],
+        arguments: Vec<String>,
+        scope: Scope<'_>,
+        depth: usize,
+        output: &mut String,
+    ) {
+        let mut bound = HashMap::new();
+        let mut arguments = arguments.into_iter();
+        for parameter in parameters {
+            let mut expanded = String::new();
+            if let Some(argument) = arguments.next() {
+                self.expand(argument.trim(), scope, depth + 1, &mut expanded);
+            }
+            bound.insert(CaseInsensitive::new(parameter.clone()), expanded);
+        }
+        self.expand(body.trim(), Scope::Text(&bound), depth + 1, output);
+    }
+
+    fn stray(&mut self, message: &str) {
+        self.preprocessor.diagnostics.emit(
+            Diagnostic::error(message)
+                .with_code(codes::M0106)
+                .with_label(Label::primary(&self.token, "inside this string")),
+        );
+    }
+}
+
+/// Splits a macro name, either bare or enclosed in braces, off the start of the text.
+fn split_macro_name(text: &str) -> Option<(&str, &str)> {
+    if let Some(braced) = text.strip_prefix('{') {
+        let (name, rest) = braced.split_once('}')?;
+        let name = name.trim();
+        return (!name.is_empty()).then_some((name, rest));
+    }
+    let length = text
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(text.len());
+    (length > 0).then(|| text.split_at(length))
+}
+
+/// Splits a parenthesized, comma-separated list of macro arguments off the start of the text.
+/// Returns [`None`] if the text does not start with a balanced argument list.
+fn split_arguments(text: &str) -> Option<(Vec<&str>, &str)> {
+    let inner = text.strip_prefix('(')?;
+    let mut arguments = vec![];
+    let mut nesting = 0_usize;
+    let mut argument_start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => nesting += 1,
+            ')' if nesting > 0 => nesting -= 1,
+            ')' => {
+                arguments.push(&inner[argument_start..i]);
+                return Some((arguments, &inner[i + 1..]));
+            }
+            ',' if nesting == 0 => {
+                arguments.push(&inner[argument_start..i]);
+                argument_start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    None
+}
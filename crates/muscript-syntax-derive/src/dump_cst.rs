@@ -0,0 +1,109 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Field, Ident, Item, ItemEnum, ItemStruct, LitStr};
+
+use crate::common::field_name;
+
+pub fn derive_dump_cst_impl(item: Item) -> syn::Result<TokenStream> {
+    match item {
+        Item::Struct(item) => Ok(for_struct(item)),
+        Item::Enum(item) => Ok(for_enum(item)),
+        _ => Err(syn::Error::new_spanned(
+            &item,
+            "DumpCst can only be derived for structs and enums",
+        )),
+    }
+}
+
+fn dump_cst_trait() -> TokenStream {
+    quote! { ::muscript_syntax::dump::DumpCst }
+}
+
+/// Generates code inserting the dump of a field into `fields`. `value` must evaluate to a
+/// reference to the field.
+fn insert_field(i: usize, field: &Field, value: TokenStream) -> TokenStream {
+    let dump_cst_trait = dump_cst_trait();
+    let name = field
+        .ident
+        .as_ref()
+        .map(|ident| ident.to_string())
+        .unwrap_or_else(|| i.to_string());
+    let ty = &field.ty;
+    quote! {
+        fields.insert(
+            ::std::string::String::from(#name),
+            <#ty as #dump_cst_trait>::dump_cst(#value, cx),
+        );
+    }
+}
+
+fn for_struct(item: ItemStruct) -> TokenStream {
+    let dump_cst_trait = dump_cst_trait();
+
+    let mut insert_fields = TokenStream::new();
+    for (i, field) in item.fields.iter().enumerate() {
+        let field_name = field_name(i, field);
+        insert_fields.extend(insert_field(i, field, quote! { &self.#field_name }));
+    }
+
+    let type_name = item.ident;
+    let kind = LitStr::new(&type_name.to_string(), type_name.span());
+    let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #dump_cst_trait for #type_name #type_generics #where_clause {
+            fn dump_cst(
+                &self,
+                cx: &mut ::muscript_syntax::dump::DumpContext<'_>,
+            ) -> ::muscript_syntax::dump::Value {
+                let mut fields = ::muscript_syntax::dump::Fields::new();
+                #insert_fields
+                cx.node(#kind, ::std::option::Option::None, self, fields)
+            }
+        }
+    }
+}
+
+fn for_enum(item: ItemEnum) -> TokenStream {
+    let dump_cst_trait = dump_cst_trait();
+
+    let type_name = item.ident;
+    let kind = LitStr::new(&type_name.to_string(), type_name.span());
+
+    let mut arms = TokenStream::new();
+    for variant in &item.variants {
+        let mut insert_fields = TokenStream::new();
+        let mut destructuring = TokenStream::new();
+        for (i, field) in variant.fields.iter().enumerate() {
+            let field_name = field_name(i, field);
+            let destructured_var_name = Ident::new(&format!("__dump_{i}"), type_name.span());
+            insert_fields.extend(insert_field(i, field, quote! { #destructured_var_name }));
+            destructuring.extend(quote! { #field_name: #destructured_var_name, });
+        }
+
+        let variant_name = &variant.ident;
+        let variant_kind = LitStr::new(&variant_name.to_string(), variant_name.span());
+        arms.extend(quote! {
+            Self::#variant_name { #destructuring } => {
+                let mut fields = ::muscript_syntax::dump::Fields::new();
+                #insert_fields
+                cx.node(#kind, ::std::option::Option::Some(#variant_kind), self, fields)
+            }
+        });
+    }
+
+    let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #dump_cst_trait for #type_name #type_generics #where_clause {
+            fn dump_cst(
+                &self,
+                cx: &mut ::muscript_syntax::dump::DumpContext<'_>,
+            ) -> ::muscript_syntax::dump::Value {
+                match self {
+                    #arms
+                }
+            }
+        }
+    }
+}
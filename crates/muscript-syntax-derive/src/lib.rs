@@ -1,8 +1,10 @@
 use syn::Item;
 
 mod common;
+mod dump_cst;
 mod parse;
 mod predictive_parse;
+mod relocate;
 mod spanned;
 
 #[proc_macro_derive(Parse, attributes(parse))]
@@ -31,3 +33,21 @@ pub fn derive_spanned(item: proc_macro::TokenStream) -> proc_macro::TokenStream
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
+
+#[proc_macro_derive(DumpCst)]
+pub fn derive_dump_cst(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(item as Item);
+
+    dump_cst::derive_dump_cst_impl(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Relocate)]
+pub fn derive_relocate(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(item as Item);
+
+    relocate::derive_relocate_impl(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
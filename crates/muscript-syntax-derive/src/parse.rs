@@ -1,4 +1,4 @@
-use darling::FromAttributes;
+use darling::{util::Flag, FromAttributes};
 use proc_macro2::{Ident, TokenStream};
 use quote::quote;
 use syn::{spanned::Spanned, Item, ItemEnum, ItemStruct, LitStr, Path};
@@ -87,7 +87,7 @@ fn for_enum(item: ItemEnum) -> syn::Result<TokenStream> {
             })
             .collect();
         let construct = quote! { #type_name::#variant_name { #constructor_fields } };
-        if !attrs.fallback {
+        if !attrs.fallback.is_present() {
             let guard = if let Some(keyword) = &attrs.keyword {
                 let keyword = LitStr::new(keyword, variant_name.span());
                 quote! { parser.next_matches_keyword(#keyword) }
@@ -155,8 +155,6 @@ struct ParseAttrs {
 #[derive(Debug, FromAttributes)]
 #[darling(attributes(parse))]
 pub(crate) struct ParseFieldAttrs {
-    #[darling(default)]
-    pub fallback: bool,
-    #[darling(default)]
+    pub fallback: Flag,
     pub keyword: Option<String>,
 }
@@ -0,0 +1,95 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Field, Ident, Item, ItemEnum, ItemStruct};
+
+use crate::common::field_name;
+
+pub fn derive_relocate_impl(item: Item) -> syn::Result<TokenStream> {
+    match item {
+        Item::Struct(item) => Ok(for_struct(item)),
+        Item::Enum(item) => Ok(for_enum(item)),
+        _ => Err(syn::Error::new_spanned(
+            &item,
+            "Relocate can only be derived for structs and enums",
+        )),
+    }
+}
+
+fn relocate_trait() -> TokenStream {
+    quote! { ::muscript_foundation::source_arena::Relocate }
+}
+
+/// Generates code relocating a field. `value` must evaluate to a mutable reference to the field.
+fn relocate_field(field: &Field, value: TokenStream) -> TokenStream {
+    let relocate_trait = relocate_trait();
+    let ty = &field.ty;
+    quote! {
+        <#ty as #relocate_trait>::relocate(#value, relocation)?;
+    }
+}
+
+fn for_struct(item: ItemStruct) -> TokenStream {
+    let relocate_trait = relocate_trait();
+
+    let mut relocate_fields = TokenStream::new();
+    for (i, field) in item.fields.iter().enumerate() {
+        let field_name = field_name(i, field);
+        relocate_fields.extend(relocate_field(field, quote! { &mut self.#field_name }));
+    }
+
+    let type_name = item.ident;
+    let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #relocate_trait for #type_name #type_generics #where_clause {
+            fn relocate(
+                &mut self,
+                relocation: &::muscript_foundation::source_arena::Relocation,
+            ) -> ::std::option::Option<()> {
+                #relocate_fields
+                ::std::option::Option::Some(())
+            }
+        }
+    }
+}
+
+fn for_enum(item: ItemEnum) -> TokenStream {
+    let relocate_trait = relocate_trait();
+
+    let type_name = item.ident;
+
+    let mut arms = TokenStream::new();
+    for variant in &item.variants {
+        let mut relocate_fields = TokenStream::new();
+        let mut destructuring = TokenStream::new();
+        for (i, field) in variant.fields.iter().enumerate() {
+            let field_name = field_name(i, field);
+            let destructured_var_name = Ident::new(&format!("__relocate_{i}"), type_name.span());
+            relocate_fields.extend(relocate_field(field, quote! { #destructured_var_name }));
+            destructuring.extend(quote! { #field_name: #destructured_var_name, });
+        }
+
+        let variant_name = &variant.ident;
+        arms.extend(quote! {
+            Self::#variant_name { #destructuring } => {
+                #relocate_fields
+            }
+        });
+    }
+
+    let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #relocate_trait for #type_name #type_generics #where_clause {
+            fn relocate(
+                &mut self,
+                relocation: &::muscript_foundation::source_arena::Relocation,
+            ) -> ::std::option::Option<()> {
+                match self {
+                    #arms
+                }
+                ::std::option::Option::Some(())
+            }
+        }
+    }
+}
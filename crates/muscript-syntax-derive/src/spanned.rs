@@ -64,7 +64,7 @@ fn for_enum(item: ItemEnum) -> syn::Result<TokenStream> {
     let spanned_trait = spanned_trait();
 
     let mut arms = TokenStream::new();
-    for (_, variant) in item.variants.iter().enumerate() {
+    for variant in &item.variants {
         let mut get_span = TokenStream::new();
         let mut destructuring = TokenStream::new();
         for (i, field) in variant.fields.iter().enumerate() {
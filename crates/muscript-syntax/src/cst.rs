@@ -1,6 +1,7 @@
 mod class;
 mod cppblob;
 pub mod default_properties;
+mod doc;
 mod expr;
 mod file;
 mod item;
@@ -12,6 +13,7 @@ mod types;
 
 pub use class::*;
 pub use cppblob::*;
+pub use doc::*;
 pub use expr::*;
 pub use file::*;
 pub use item::*;
@@ -1,15 +1,16 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     diagnostics::{labels, notes},
     list::TerminatedListErrorKind,
     token::{AnyToken, Ident, LeftParen, RightParen, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
-use super::{BoolLit, Path, SpecifierArgs};
+use super::{BoolLit, DocComment, Path, SpecifierArgs};
 
 keyword! {
     KPartial = "partial",
@@ -19,7 +20,9 @@ keyword! {
     KWithin = "within",
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "class_kind_error")]
 pub enum ClassKind {
     Class(KClass),
@@ -27,7 +30,7 @@ pub enum ClassKind {
     Interface(KInterface),
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Class {
     pub class: ClassKind,
     pub name: Ident,
@@ -35,21 +38,26 @@ pub struct Class {
     pub within: Option<Within>,
     pub specifiers: Vec<ClassSpecifier>,
     pub semi: Semi,
+    pub doc: DocComment,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct Extends {
     pub extends: KExtends,
     pub parent_class: Path,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct Within {
     pub within: KWithin,
     pub outer_class: Ident,
 }
 
-#[derive(Debug, Clone, Parse, Spanned)]
+#[derive(Debug, Clone, Parse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 #[parse(error = "specifier_error")]
 pub enum ClassSpecifier {
     #[parse(keyword = "abstract")]
@@ -108,9 +116,11 @@ pub enum ClassSpecifier {
 
 impl Parse for Class {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let doc = parser.parse()?;
         let class = parser.parse()?;
         let name = parser.parse_with_error(|parser, span| {
             Diagnostic::error("class name expected")
+                .with_code(codes::M0207)
                 .with_label(labels::invalid_identifier(span, &parser.sources))
                 .with_note(notes::IDENTIFIER_CHARS)
         })?;
@@ -120,9 +130,12 @@ impl Parse for Class {
             match error.kind {
                 TerminatedListErrorKind::Parse => (),
                 TerminatedListErrorKind::MissingTerminator => parser.emit_diagnostic(
-                    Diagnostic::error("missing `;` after class specifier list").with_label(
-                        Label::primary(&error.parse.span, "this was expected to be `;`"),
-                    ),
+                    Diagnostic::error("missing `;` after class specifier list")
+                        .with_code(codes::M0208)
+                        .with_label(Label::primary(
+                            &error.parse.span,
+                            "this was expected to be `;`",
+                        )),
                 ),
             }
             error.parse
@@ -134,12 +147,14 @@ impl Parse for Class {
             within,
             specifiers,
             semi,
+            doc,
         })
     }
 }
 
 fn class_kind_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("`class`, `partial class`, or `interface` expected")
+        .with_code(codes::M0209)
         .with_label(Label::primary(token, ""))
         .with_note("note: the file must start with the kind of type you're declaring")
 }
@@ -149,6 +164,7 @@ fn specifier_error(parser: &Parser<'_, impl TokenStream>, token: &AnyToken) -> D
         "unknown class specifier `{}`",
         parser.sources.source(token)
     ))
+    .with_code(codes::M0210)
     .with_label(Label::primary(token, "this specifier is not recognized"))
     .with_note("note: notable class specifiers include `placeable` and `abstract`")
 }
@@ -1,8 +1,11 @@
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
-use crate::{Braces, LazyBlock, Parse, PredictiveParse};
+use crate::{Braces, DumpCst, LazyBlock, Parse, PredictiveParse, Relocate};
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct CppBlob {
     pub blob: LazyBlock<Braces>,
 }
@@ -1,10 +1,11 @@
 use indoc::indoc;
 use muscript_foundation::{
-    errors::{Diagnostic, Label},
+    errors::{codes, Diagnostic, Label},
     span::Spanned,
 };
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     diagnostics::notes,
@@ -13,74 +14,90 @@ use crate::{
         Add, AnyToken, Assign, Dot, FailedExp, FloatLit, Ident, IntLit, LeftBrace, LeftBracket,
         LeftParen, NameLit, RightBrace, RightBracket, RightParen, Semi, StringLit, Sub,
     },
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 use super::Path;
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct DefaultPropertiesBlock {
     pub open: LeftBrace,
     pub properties: Vec<DefaultProperty>,
     pub close: RightBrace,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "default_property_error")]
 pub enum DefaultProperty {
     Subobject(Subobject),
     Value(Value),
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct Value {
     pub key: Key,
     pub action: ValueAction,
     pub semi: Option<Semi>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct Key {
     pub ident: Ident,
     pub index: Option<Index>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "index_error")]
 pub enum Index {
     Parens(LeftParen, IndexLit, RightParen),
     Brackets(LeftBracket, IndexLit, RightBracket),
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "index_lit_error")]
 pub enum IndexLit {
     Num(IntLit),
     Enum(Path),
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "value_action_error")]
 pub enum ValueAction {
     Assign(Assign, Lit),
     Call(Dot, Ident, Option<CallArg>),
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct CallArg {
     pub open: LeftParen,
     pub expr: Option<Lit>,
     pub close: RightParen,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "num_lit_error")]
 pub enum NumLit {
     Int(IntLit),
     Float(FloatLit),
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "lit_error")]
 pub enum Lit {
     FailedExp(FailedExp),
@@ -97,7 +114,9 @@ pub enum Lit {
 /// This was required in vanilla UnrealScript in order for the `defaultproperties` parser to ignore
 /// newlines within compound literals, but MuScript does not have such limitations; this exists
 /// solely for compatibility purposes.
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "braced_compound_error")]
 pub enum BracedCompound {
     Braced(LeftBrace, Compound, RightBrace),
@@ -108,14 +127,14 @@ pub enum BracedCompound {
 ///
 /// At the parsing stage they can be mixed freely, but semantic analysis rejects listings where
 /// both appear at the same time.
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Compound {
     pub open: LeftParen,
     pub elements: Vec<CompoundElement>,
     pub close: RightParen,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub enum CompoundElement {
     Lit(Lit),
     Field(Key, Assign, Lit),
@@ -127,7 +146,7 @@ keyword! {
     KObject = "object",
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Subobject {
     pub begin: KBegin,
     pub object1: KObject,
@@ -143,6 +162,7 @@ impl Parse for DefaultPropertiesBlock {
             if let TerminatedListErrorKind::MissingTerminator = error.kind {
                 parser.emit_diagnostic(
                     Diagnostic::error("missing `}` to close default properties block")
+                        .with_code(codes::M0203)
                         .with_label(Label::primary(&open, "this is where the block begins")),
                 );
             }
@@ -232,12 +252,12 @@ impl Parse for Subobject {
         let (properties, end) = parser.parse_terminated_list().map_err(|error| {
             if let TerminatedListErrorKind::MissingTerminator = error.kind {
                 parser.emit_diagnostic(
-                    Diagnostic::error("missing `end object` to end default subobject").with_label(
-                        Label::primary(
+                    Diagnostic::error("missing `end object` to end default subobject")
+                        .with_code(codes::M0203)
+                        .with_label(Label::primary(
                             &begin.span().join(&object1.span()),
                             "this `begin object` does not have a matching `end object`",
-                        ),
-                    ),
+                        )),
                 );
             }
             error.parse
@@ -255,6 +275,7 @@ impl Parse for Subobject {
 
 fn default_property_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("default property expected")
+        .with_code(codes::M0211)
         .with_label(Label::primary(
             token,
             "this token does not start a default property",
@@ -270,27 +291,31 @@ fn default_property_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) ->
 
 fn index_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("`(Index)` or `[Index]` expected")
+        .with_code(codes::M0211)
         .with_label(Label::primary(token, "array index expected here"))
 }
 
 fn index_lit_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("integer or enum index expected")
+        .with_code(codes::M0211)
         .with_label(Label::primary(token, "array index expected here"))
         .with_note("note: indices are integers `[1]`, or enums `[EXAMPLE_EnumValue]`")
 }
 
 fn value_action_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("`=` or `.Operation(Arg)` expected")
+        .with_code(codes::M0211)
         .with_label(Label::primary(token, "property action expected here"))
 }
 
 fn num_lit_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("number literal expected")
+        .with_code(codes::M0211)
         .with_label(Label::primary(token, "number literal expected here"))
 }
 
 fn lit_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
-    Diagnostic::error("default property literal expected")
+    Diagnostic::error("default property literal expected").with_code(codes::M0211)
         .with_label(Label::primary(
             token,
             "this token is not a supported literal",
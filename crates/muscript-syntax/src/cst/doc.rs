@@ -0,0 +1,115 @@
+use muscript_foundation::span::Spanned;
+use muscript_lexer::{
+    sources::LexedSources,
+    token::{Token, TokenId, TokenKind, TokenSpan},
+    token_stream::TokenStream,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dump::{DumpContext, Fields, Value},
+    DumpCst, Parse, ParseError, Parser, Relocate,
+};
+
+/// Documentation comments preceding an item: `/** .. */` blocks and `///` lines.
+///
+/// Comments are not seen by the rest of the parser, so doc comments do not contribute to the span
+/// of the item they're attached to. A blank line or a regular comment between a doc comment and
+/// the item detaches the doc comment.
+#[derive(Debug, Clone, Default, Relocate, Serialize, Deserialize)]
+pub struct DocComment {
+    pub comments: Vec<TokenId>,
+}
+
+impl DocComment {
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+
+    /// Returns the text of the doc comment, with comment markers and leading `*`s stripped.
+    pub fn text(&self, sources: &LexedSources<'_>) -> Option<String> {
+        let mut lines = vec![];
+        for &comment in &self.comments {
+            let source = sources.source(&TokenSpan::single(comment));
+            if let Some(line) = source.strip_prefix("///") {
+                lines.push(line.strip_prefix(' ').unwrap_or(line).trim_end());
+            } else if let Some(block) = source
+                .strip_prefix("/**")
+                .and_then(|block| block.strip_suffix("*/"))
+            {
+                for line in block.lines() {
+                    let line = line.trim();
+                    let line = line
+                        .strip_prefix('*')
+                        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+                        .unwrap_or(line);
+                    lines.push(line);
+                }
+            }
+        }
+
+        let first = lines.iter().position(|line| !line.is_empty())?;
+        let last = lines.iter().rposition(|line| !line.is_empty())?;
+        Some(lines[first..=last].join("\n"))
+    }
+}
+
+fn is_doc_comment(source: &str) -> bool {
+    (source.starts_with("///") && !source.starts_with("////"))
+        || (source.starts_with("/**") && !source.starts_with("/***") && source != "/**/")
+}
+
+impl Parse for DocComment {
+    /// Collects the doc comments before the next token of code. This never consumes any tokens,
+    /// so that the item can then be parsed as usual.
+    fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let position = parser.tokens.position();
+        let mut comments = vec![];
+        let mut new_lines = 0;
+        loop {
+            let token = parser.tokens.next();
+            match token.kind {
+                TokenKind::Comment => {
+                    new_lines = 0;
+                    if is_doc_comment(parser.sources.source(&token)) {
+                        comments.push(token.id);
+                    } else {
+                        // Regular comments separate doc comments from the item, so that eg. a
+                        // commented out item's documentation does not end up on the next one.
+                        comments.clear();
+                    }
+                }
+                TokenKind::NewLine => {
+                    new_lines += 1;
+                    // A blank line detaches the doc comments above it from the item.
+                    if new_lines > 1 {
+                        comments.clear();
+                    }
+                }
+                TokenKind::Backslash | TokenKind::FailedExp => (),
+                _ => break,
+            }
+        }
+        parser.tokens.set_position(position);
+        Ok(Self { comments })
+    }
+}
+
+impl Spanned<Token> for DocComment {
+    fn span(&self) -> TokenSpan {
+        TokenSpan::Empty
+    }
+}
+
+impl DumpCst for DocComment {
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        let comments = self
+            .comments
+            .iter()
+            .map(|&comment| cx.token("Comment", &TokenSpan::single(comment)))
+            .collect();
+        let mut fields = Fields::new();
+        fields.insert("comments".into(), Value::Array(comments));
+        cx.node("DocComment", None, self, fields)
+    }
+}
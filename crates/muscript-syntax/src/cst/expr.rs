@@ -1,9 +1,10 @@
 mod lit;
 
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
 use muscript_foundation::{
-    errors::{Diagnostic, Label, Note, NoteKind},
+    errors::{codes, Diagnostic, Label, Note, NoteKind},
     span::Spanned,
 };
 use muscript_lexer::{
@@ -19,12 +20,12 @@ use crate::{
         AnyToken, Assign, Colon, Dot, FailedExp, FloatLit, Ident, IntLit, Keyword, LeftBracket,
         LeftParen, NameLit, Question, RightBracket, RightParen, StringLit,
     },
-    Parse, ParseError, Parser,
+    DumpCst, Parse, ParseError, Parser, Relocate,
 };
 
 pub use lit::*;
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub enum Expr {
     Lit(Lit),
     Ident(Ident),
@@ -99,7 +100,7 @@ pub enum Expr {
     },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Spanned)]
+#[derive(Debug, Clone, PartialEq, Eq, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct InfixOperator {
     pub token: AnyToken,
     pub token2: Option<AnyToken>,
@@ -107,7 +108,7 @@ pub struct InfixOperator {
 }
 
 /// Optional function argument.
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub enum Arg {
     Provided(Expr),
     Omitted(
@@ -151,6 +152,7 @@ impl Expr {
                 let inner = Expr::precedence_parse(parser, Precedence::EXPR, false)?;
                 let close = parser.parse_with_error(|_, span| {
                     Diagnostic::error("missing `)` to close grouped expression")
+                        .with_code(codes::M0203)
                         .with_label(Label::primary(&span, "`)` expected here..."))
                         .with_label(Label::secondary(&token, "...to close this `(`"))
                 })?;
@@ -168,6 +170,7 @@ impl Expr {
                 // What is *really* happening is that we expect any ol' expression, but the user
                 // gave us something that isn't.
                 Diagnostic::error("expression expected")
+                    .with_code(codes::M0219)
                     .with_label(Label::primary(
                         &token,
                         "this token does not start an expression",
@@ -508,7 +511,7 @@ impl Precedence {
 /// Specialized version of [`Option<T>`] that's built for handling precedence levels.
 ///
 /// Unlike [`Option<u8>`], it compares correctly given UnrealScript's inverted precedence hierarchy.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Precedence {
     None,
     Some(u8),
@@ -1,18 +1,21 @@
 use indoc::indoc;
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     token::{AnyToken, FloatLit, IntLit, NameLit, StringLit},
-    Parse, Parser, PredictiveParse,
+    DumpCst, Parse, Parser, PredictiveParse, Relocate,
 };
 
 keyword!(KNone = "none");
 keyword!(KTrue = "true");
 keyword!(KFalse = "false");
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "bool_lit_error")]
 pub enum BoolLit {
     True(KTrue),
@@ -22,7 +25,9 @@ pub enum BoolLit {
 // NOTE: If you want to parse a literal, you actually probably want to use `Expr` instead.
 // This lets the user enjoy full expression syntax, as you can const-evaluate the expression
 // during semantic analysis. Also, negation `-` is not part of number literals, so beware!
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "lit_error")]
 pub enum Lit {
     None(KNone),
@@ -35,11 +40,13 @@ pub enum Lit {
 
 fn bool_lit_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("boolean `true` or `false` expected")
+        .with_code(codes::M0222)
         .with_label(Label::primary(token, "this token is not a boolean"))
 }
 
 fn lit_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("literal expected")
+        .with_code(codes::M0222)
         .with_label(Label::primary(token, "this token is not a literal"))
         .with_note(indoc!(
             r#"note: literals include
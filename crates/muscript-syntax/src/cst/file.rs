@@ -1,20 +1,22 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::token_stream::TokenStream;
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    diagnostics::notes, list::TerminatedListErrorKind, token::EndOfFile, Parse, ParseError, Parser,
+    diagnostics::notes, list::TerminatedListErrorKind, token::EndOfFile, DumpCst, Parse,
+    ParseError, Parser, Relocate,
 };
 
 use super::{Class, Item};
 
-#[derive(Debug, Clone, Spanned, Parse)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Parse, Serialize, Deserialize)]
 pub struct File {
     pub class: Class,
     pub bare: BareFile,
 }
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct BareFile {
     pub items: Vec<Item>,
     pub eof: EndOfFile,
@@ -27,6 +29,7 @@ impl Parse for BareFile {
                 TerminatedListErrorKind::Parse => (),
                 TerminatedListErrorKind::MissingTerminator => parser.emit_diagnostic(
                     Diagnostic::error("end of file expected after items")
+                        .with_code(codes::M0206)
                         .with_label(Label::primary(
                             &error.parse.span,
                             "this is where the file should end",
@@ -10,13 +10,14 @@ mod state;
 mod structs;
 mod var;
 
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     token::{AnyToken, Semi},
-    Parse, Parser,
+    DumpCst, Parse, Parser, Relocate,
 };
 
 pub use consts::*;
@@ -33,7 +34,7 @@ pub use var::*;
 
 use super::Stmt;
 
-#[derive(Debug, Clone, Parse, Spanned)]
+#[derive(Debug, Clone, Parse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 #[parse(error = "_item_error")]
 pub enum Item {
     Empty(Semi),
@@ -59,6 +60,7 @@ pub enum Item {
 
 fn _item_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("item expected")
+        .with_code(codes::M0220)
         .with_label(Label::primary(token, "this token does not start an item"))
         .with_note("help: notable types of items include `var`, `function`, `struct`, and `enum`")
 }
@@ -1,12 +1,14 @@
+use muscript_lexer::token_stream::TokenStream;
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    cst::{Expr, KConst},
+    cst::{DocComment, Expr, KConst},
     token::{Assign, Ident, Semi},
-    Parse, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemConst {
     pub kconst: KConst,
     // TODO: Alter the error here somehow to say "constant name expected"
@@ -14,4 +16,19 @@ pub struct ItemConst {
     pub equals: Assign,
     pub value: Expr,
     pub semi: Semi,
+    pub doc: DocComment,
+}
+
+impl Parse for ItemConst {
+    fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let doc = parser.parse()?;
+        Ok(Self {
+            kconst: parser.parse()?,
+            name: parser.parse()?,
+            equals: parser.parse()?,
+            value: parser.parse()?,
+            semi: parser.parse()?,
+            doc,
+        })
+    }
 }
@@ -1,17 +1,22 @@
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
-use crate::{cst::CppBlob, Parse, PredictiveParse};
+use crate::{cst::CppBlob, DumpCst, Parse, PredictiveParse, Relocate};
 
 keyword!(KCppText = "cpptext");
 keyword!(KStructCppText = "structcpptext");
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct ItemCppText {
     pub cpptext: KCppText,
     pub blob: CppBlob,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct ItemStructCppText {
     pub cpptext: KStructCppText,
     pub blob: CppBlob,
@@ -1,17 +1,24 @@
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
-use crate::{cst::default_properties::DefaultPropertiesBlock, Parse, PredictiveParse};
+use crate::{
+    cst::default_properties::DefaultPropertiesBlock, DumpCst, Parse, PredictiveParse, Relocate,
+};
 
 keyword!(KDefaultProperties = "defaultproperties");
 keyword!(KStructDefaultProperties = "structdefaultproperties");
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct ItemDefaultProperties {
     pub keyword: KDefaultProperties,
     pub block: DefaultPropertiesBlock,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct ItemStructDefaultProperties {
     pub keyword: KStructDefaultProperties,
     pub block: DefaultPropertiesBlock,
@@ -1,24 +1,26 @@
-use muscript_foundation::errors::Diagnostic;
+use muscript_foundation::errors::{codes, Diagnostic};
 use muscript_lexer::token_stream::TokenStream;
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    cst::Meta,
+    cst::{DocComment, Meta},
     diagnostics::{labels, notes},
     list::SeparatedListDiagnostics,
     token::{Ident, LeftBrace, RightBrace, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 keyword!(KEnum = "enum");
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemEnum {
     pub def: EnumDef,
     pub semi: Option<Semi>,
+    pub doc: DocComment,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct EnumDef {
     pub kenum: KEnum,
     pub name: Ident,
@@ -27,17 +29,31 @@ pub struct EnumDef {
     pub close: RightBrace,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct EnumVariant {
     pub name: Ident,
     pub meta: Option<Meta>,
 }
 
+impl Parse for ItemEnum {
+    fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let doc = parser.parse()?;
+        Ok(Self {
+            def: parser.parse()?,
+            semi: parser.parse()?,
+            doc,
+        })
+    }
+}
+
 impl Parse for EnumDef {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
         let kenum = parser.parse()?;
         let name = parser.parse_with_error(|parser, span| {
             Diagnostic::error("enum name expected")
+                .with_code(codes::M0207)
                 .with_label(labels::invalid_identifier(span, &parser.sources))
                 .with_note(notes::IDENTIFIER_CHARS)
         })?;
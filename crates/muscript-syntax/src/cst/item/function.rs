@@ -1,6 +1,6 @@
 use indoc::indoc;
 use muscript_foundation::{
-    errors::{Diagnostic, Label},
+    errors::{codes, Diagnostic, Label},
     span::Spanned,
 };
 use muscript_lexer::{
@@ -9,18 +9,20 @@ use muscript_lexer::{
     token_stream::TokenStream,
 };
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    cst::{Expr, KConst, Path, Type},
+    cst::{DocComment, Expr, KConst, Path, StmtList, Type},
     diagnostics::{labels, notes},
+    dump::{DumpContext, Fields, Value},
     list::SeparatedListDiagnostics,
     token::{AnyToken, Assign, Greater, Ident, IntLit, LeftParen, RightParen, Semi},
-    Braces, LazyBlock, Parse, ParseError, Parser, PredictiveParse,
+    Braces, DumpCst, LazyBlock, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 use super::{ItemName, VarArray};
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemFunction {
     pub pre_specifiers: Vec<FunctionSpecifier>,
     pub kind: FunctionKind,
@@ -30,9 +32,12 @@ pub struct ItemFunction {
     pub params: Params,
     pub kconst: Option<KConst>,
     pub body: Body,
+    pub doc: DocComment,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "function_specifier_error")]
 pub enum FunctionSpecifier {
     #[parse(keyword = "client")]
@@ -85,14 +90,18 @@ pub enum FunctionSpecifier {
     Virtual(Ident),
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct ParenInt {
     pub left: LeftParen,
     pub number: IntLit,
     pub right: RightParen,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "kind_error")]
 pub enum FunctionKind {
     #[parse(keyword = "function")]
@@ -109,14 +118,14 @@ pub enum FunctionKind {
     PostOperator(Ident),
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Params {
     pub open: LeftParen,
     pub params: Vec<Param>,
     pub close: RightParen,
 }
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Param {
     pub specifiers: Vec<ParamSpecifier>,
     pub ty: Type,
@@ -125,7 +134,9 @@ pub struct Param {
     pub default: Option<ParamDefault>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "param_specifier_error")]
 pub enum ParamSpecifier {
     #[parse(keyword = "coerce")]
@@ -142,23 +153,44 @@ pub enum ParamSpecifier {
     Skip(Ident),
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct ParamDefault {
     pub equals: Assign,
     pub value: Expr,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, Parse, PredictiveParse, Spanned, Relocate, Serialize, Deserialize)]
 #[parse(error = "body_error")]
 pub enum Body {
     Stub(Semi),
     Impl(LazyBlock<Braces>),
 }
 
+/// Function bodies are only parsed once they're needed, so dumping one parses its statements.
+impl DumpCst for Body {
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        let mut fields = Fields::new();
+        let variant = match self {
+            Body::Stub(semi) => {
+                fields.insert("0".into(), semi.dump_cst(cx));
+                "Stub"
+            }
+            Body::Impl(block) => {
+                fields.insert("0".into(), block.dump_cst_with_inner::<StmtList>(cx));
+                "Impl"
+            }
+        };
+        cx.node("Body", Some(variant), self, fields)
+    }
+}
+
 impl ItemFunction {
     fn parse_name(parser: &mut Parser<'_, impl TokenStream>) -> Result<Ident, ParseError> {
         parser.parse_with_error::<Ident>(|parser, span| {
             Diagnostic::error("function name expected")
+                .with_code(codes::M0207)
                 .with_label(labels::invalid_identifier(span, &parser.sources))
                 .with_note(notes::IDENTIFIER_CHARS)
         })
@@ -167,6 +199,7 @@ impl ItemFunction {
 
 impl Parse for ItemFunction {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let doc = parser.parse()?;
         let pre_specifiers = parser.parse_greedy_list()?;
         let function = parser.parse()?;
         let post_specifiers = parser.parse_greedy_list()?;
@@ -215,6 +248,7 @@ impl Parse for ItemFunction {
                             "`{}` is not an overloadable operator",
                             parser.sources.source(&operator)
                         ))
+                        .with_code(codes::M0213)
                         .with_label(Label::primary(&operator, "operator expected here"))
                         .with_note(indoc!(
                             r#"
@@ -270,6 +304,7 @@ impl Parse for ItemFunction {
             params,
             kconst,
             body,
+            doc,
         })
     }
 }
@@ -335,6 +370,7 @@ fn function_specifier_error(
         "unknown function specifier `{}`",
         parser.sources.source(token)
     ))
+    .with_code(codes::M0210)
     .with_label(Label::primary(token, "this specifier is not recognized"))
     .with_note("note: notable function specifiers include `static` and `final`")
 }
@@ -347,12 +383,14 @@ fn param_specifier_error(
         "unknown parameter specifier `{}`",
         parser.sources.source(token)
     ))
+    .with_code(codes::M0210)
     .with_label(Label::primary(token, "this specifier is not recognized"))
     .with_note("note: notable parameter specifiers include `optional` and `out`")
 }
 
 fn kind_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("`function`, `event`, `preoperator`, or `operator` expected")
+        .with_code(codes::M0214)
         .with_label(Label::primary(
             token,
             "this token does not start a function",
@@ -361,7 +399,7 @@ fn kind_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<
 }
 
 fn body_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
-    Diagnostic::error("function body `{ .. }` expected")
+    Diagnostic::error("function body `{ .. }` expected").with_code(codes::M0215)
         .with_label(Label::primary(token, "`{` expected here"))
         .with_note(
             "note: functions can also be stubbed out using `;`, but it's probably not what you want"
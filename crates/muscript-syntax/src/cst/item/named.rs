@@ -1,12 +1,15 @@
 use muscript_foundation::span::Spanned;
 use muscript_lexer::token::{Token, TokenSpan};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
+
+use crate::{DumpCst, Relocate};
 
 use super::{EnumDef, ItemConst, ItemEnum, ItemFunction, ItemState, ItemStruct, StructDef};
 
 /// Item names can be made out of multiple tokens (as is the case with eg. function names, which
 /// can be `+=` - two tokens.)
-#[derive(Debug, Clone, Copy, Spanned)]
+#[derive(Debug, Clone, Copy, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemName {
     pub span: TokenSpan,
 }
@@ -1,16 +1,17 @@
 use muscript_lexer::token_stream::TokenStream;
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     cst::{KIf, ParenExpr},
     list::SeparatedListDiagnostics,
     token::{Ident, LeftBrace, RightBrace, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 keyword!(KReplication = "replication");
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemReplication {
     pub replication: KReplication,
     pub open: LeftBrace,
@@ -18,7 +19,7 @@ pub struct ItemReplication {
     pub close: RightBrace,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct RepCondition {
     pub kif: KIf,
     pub cond: ParenExpr,
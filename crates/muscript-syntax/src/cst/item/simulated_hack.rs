@@ -1,30 +1,53 @@
 //! Somewhat of a hack to support parsing `simulated function` and `simulated state` predictively.
 
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{
     token::{AnyToken, Token},
     token_stream::TokenStream,
 };
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
-use crate::{cst::KSimulated, Parse, Parser, PredictiveParse};
+use crate::{
+    cst::{DocComment, KSimulated},
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
+};
 
 use super::{ItemFunction, ItemState};
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemSimulated {
     pub simulated: KSimulated,
     pub item: SimulatedItem,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "simulated_item_error")]
+#[allow(clippy::large_enum_variant)]
 pub enum SimulatedItem {
     Function(ItemFunction),
     State(ItemState),
 }
 
+impl Parse for ItemSimulated {
+    fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        // The doc comment goes before `simulated`, so it has to be parsed here rather than by the
+        // function or state.
+        let doc: DocComment = parser.parse()?;
+        let simulated = parser.parse()?;
+        let mut item = parser.parse()?;
+        match &mut item {
+            SimulatedItem::Function(function) => function.doc = doc,
+            SimulatedItem::State(state) => state.doc = doc,
+        }
+        Ok(Self { simulated, item })
+    }
+}
+
 fn simulated_item_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("function or state item expected after `simulated`")
+        .with_code(codes::M0216)
         .with_label(Label::primary(token, "function or state expected here"))
 }
@@ -1,12 +1,13 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{sources::LexedSources, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    cst::{Extends, KSimulated},
+    cst::{DocComment, Extends, KSimulated},
     list::{SeparatedListDiagnostics, TerminatedListErrorKind},
     token::{AnyToken, Ident, LeftBrace, RightBrace, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 use super::{Item, VarEditor};
@@ -17,7 +18,7 @@ keyword! {
     KIgnores = "ignores",
 }
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemState {
     pub simulated: Option<KSimulated>,
     pub auto: Option<KAuto>,
@@ -29,9 +30,10 @@ pub struct ItemState {
     pub ignores: Option<Ignores>,
     pub items: Vec<Item>,
     pub close: RightBrace,
+    pub doc: DocComment,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Ignores {
     pub ignores: KIgnores,
     pub events: Vec<Ident>,
@@ -40,6 +42,7 @@ pub struct Ignores {
 
 impl Parse for ItemState {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let doc = parser.parse()?;
         let auto = parser.parse()?;
         let state = parser.parse()?;
         let editor = parser.parse()?;
@@ -51,6 +54,7 @@ impl Parse for ItemState {
             if let TerminatedListErrorKind::MissingTerminator = error.kind {
                 parser.emit_diagnostic(
                     Diagnostic::error("missing `}` to close state body")
+                        .with_code(codes::M0203)
                         .with_label(Label::primary(&open, "this is where the state body begins")),
                 )
             }
@@ -67,6 +71,7 @@ impl Parse for ItemState {
             ignores,
             items,
             close,
+            doc,
         })
     }
 }
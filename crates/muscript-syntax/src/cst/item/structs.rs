@@ -1,27 +1,29 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    cst::{CppBlob, Extends},
+    cst::{CppBlob, DocComment, Extends},
     diagnostics::{labels, notes},
     list::TerminatedListErrorKind,
     token::{AnyToken, Ident, LeftBrace, RightBrace, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 use super::Item;
 
 keyword!(KStruct = "struct");
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemStruct {
     pub def: StructDef,
     // UX thing: MuScript considers the semicolon after `}` optional.
     pub semi: Option<Semi>,
+    pub doc: DocComment,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct StructDef {
     pub kstruct: KStruct,
     pub specifiers: Vec<StructSpecifier>,
@@ -33,6 +35,17 @@ pub struct StructDef {
     pub close: RightBrace,
 }
 
+impl Parse for ItemStruct {
+    fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let doc = parser.parse()?;
+        Ok(Self {
+            def: parser.parse()?,
+            semi: parser.parse()?,
+            doc,
+        })
+    }
+}
+
 impl Parse for StructDef {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
         let kstruct = parser.parse()?;
@@ -40,6 +53,7 @@ impl Parse for StructDef {
         let cpp_name = parser.parse()?;
         let name = parser.parse_with_error(|parser, span| {
             Diagnostic::error("struct name expected")
+                .with_code(codes::M0207)
                 .with_label(labels::invalid_identifier(span, &parser.sources))
                 .with_note(notes::IDENTIFIER_CHARS)
         })?;
@@ -48,9 +62,12 @@ impl Parse for StructDef {
         let (items, close) = parser.parse_terminated_list().map_err(|error| {
             if let TerminatedListErrorKind::MissingTerminator = error.kind {
                 parser.emit_diagnostic(
-                    Diagnostic::error("missing `}` to close struct body").with_label(
-                        Label::primary(&open, "this is where the struct body begins"),
-                    ),
+                    Diagnostic::error("missing `}` to close struct body")
+                        .with_code(codes::M0203)
+                        .with_label(Label::primary(
+                            &open,
+                            "this is where the struct body begins",
+                        )),
                 )
             }
             error.parse
@@ -68,7 +85,9 @@ impl Parse for StructDef {
     }
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "specifier_error")]
 pub enum StructSpecifier {
     #[parse(keyword = "export")]
@@ -88,6 +107,7 @@ fn specifier_error(parser: &Parser<'_, impl TokenStream>, token: &AnyToken) -> D
         "unknown struct specifier `{}`",
         parser.sources.source(token)
     ))
+    .with_code(codes::M0210)
     .with_label(Label::primary(token, "this specifier is not recognized"))
     .with_note("note: notable struct specifiers include `immutable` and `transient`")
 }
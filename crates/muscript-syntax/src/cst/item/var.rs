@@ -1,18 +1,19 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    cst::{CppBlob, Expr, Meta, TypeOrDef, TypeSpecifier},
+    cst::{CppBlob, DocComment, Expr, Meta, TypeOrDef, TypeSpecifier},
     diagnostics,
     list::SeparatedListDiagnostics,
     token::{AnyToken, Ident, LeftBracket, LeftParen, RightBracket, RightParen, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 keyword!(KVar = "var");
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct ItemVar {
     pub var: KVar,
     pub editor: Option<VarEditor>,
@@ -20,16 +21,19 @@ pub struct ItemVar {
     pub ty: TypeOrDef,
     pub variables: Vec<VarDef>,
     pub semi: Semi,
+    pub doc: DocComment,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct VarEditor {
     pub open: LeftParen,
     pub categories: Vec<Ident>,
     pub close: RightParen,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "specifier_error")]
 pub enum VarSpecifier {
     #[parse(keyword = "bitwise")]
@@ -105,6 +109,7 @@ pub enum VarSpecifier {
 
 impl Parse for ItemVar {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
+        let doc = parser.parse()?;
         let var = parser.parse()?;
         let editor = parser.parse()?;
         let specifiers = parser.parse_greedy_list()?;
@@ -119,6 +124,7 @@ impl Parse for ItemVar {
             ty,
             variables: names,
             semi,
+            doc,
         })
     }
 }
@@ -153,11 +159,14 @@ fn specifier_error(parser: &Parser<'_, impl TokenStream>, token: &AnyToken) -> D
         "unknown variable specifier `{}`",
         parser.sources.source(token)
     ))
+    .with_code(codes::M0210)
     .with_label(Label::primary(token, "this specifier is not recognized"))
     .with_note("note: notable variable specifiers include `const` and `transient`")
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct VarDef {
     pub name: Ident,
     pub array: Option<VarArray>,
@@ -165,7 +174,9 @@ pub struct VarDef {
     pub cpptype: Option<CppBlob>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct VarArray {
     pub open: LeftBracket,
     pub size: Expr,
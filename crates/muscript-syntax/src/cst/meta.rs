@@ -1,25 +1,26 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{
     token::{TokenKind, TokenSpan},
     token_stream::TokenStream,
 };
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     diagnostics::{labels, notes},
     list::SeparatedListDiagnostics,
     token::{Assign, BitOr, Greater, Ident, Less},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Meta {
     pub open: Less,
     pub pairs: Vec<MetaValue>,
     pub close: Greater,
 }
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub enum MetaValue {
     Switch(Ident),
     Pair(Ident, Assign, TokenSpan),
@@ -54,6 +55,7 @@ impl Parse for MetaValue {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
         let key: Ident = parser.parse_with_error(|parser, span| {
             Diagnostic::error("metadata key expected")
+                .with_code(codes::M0207)
                 .with_label(labels::invalid_identifier(span, &parser.sources))
                 .with_note(notes::IDENTIFIER_CHARS)
         })?;
@@ -68,6 +70,7 @@ impl Parse for MetaValue {
                             Diagnostic::error(
                                 "metadata pair does not have a `|` or `>` that would end it",
                             )
+                            .with_code(codes::M0221)
                             .with_label(Label::primary(
                                 &key,
                                 "this metadatum does not have an end",
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
 use muscript_lexer::{sources::LexedSources, token::TokenKind, token_stream::TokenStream};
@@ -5,10 +6,10 @@ use muscript_syntax_derive::Spanned;
 
 use crate::{
     token::{AnyToken, Ident},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Path {
     pub components: Vec<Ident>,
 }
@@ -2,11 +2,12 @@
 
 use muscript_lexer::token_stream::TokenStream;
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     list::SeparatedListDiagnostics,
     token::{LeftParen, RightParen},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 use super::Expr;
@@ -16,7 +17,7 @@ keyword! {
     KSimulated = "simulated",
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct SpecifierArgs {
     pub open: LeftParen,
     pub args: Vec<Expr>,
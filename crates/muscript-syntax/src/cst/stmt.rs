@@ -1,15 +1,16 @@
 mod control_flow;
 mod local;
 
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     diagnostics::notes,
     list::TerminatedListErrorKind,
     token::{AnyToken, EndOfFile, LeftBrace, RightBrace, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 pub use control_flow::*;
@@ -17,7 +18,7 @@ pub use local::*;
 
 use super::{Expr, Precedence};
 
-#[derive(Debug, Clone, Parse, Spanned)]
+#[derive(Debug, Clone, Parse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 #[parse(error = "_stmt_error")]
 pub enum Stmt {
     Empty(Semi),
@@ -41,13 +42,13 @@ pub enum Stmt {
     Expr(StmtExpr),
 }
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct StmtExpr {
     pub expr: Expr,
     pub semi: Option<Semi>,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Block {
     pub open: LeftBrace,
     pub stmts: Vec<Stmt>,
@@ -55,7 +56,7 @@ pub struct Block {
 }
 
 /// [`StmtList`] should be used to parse statements inside a [`LazyBlock`][crate::LazyBlock].
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct StmtList {
     pub stmts: Vec<Stmt>,
     pub eof: EndOfFile,
@@ -79,12 +80,14 @@ impl Parse for Block {
     fn parse(parser: &mut Parser<'_, impl TokenStream>) -> Result<Self, ParseError> {
         let open: LeftBrace = parser.parse_with_error(|_, span| {
             Diagnostic::error("block `{ .. }` expected")
+                .with_code(codes::M0217)
                 .with_label(Label::primary(&span, "`{` expected here"))
         })?;
         let (stmts, close) = parser.parse_terminated_list().map_err(|error| {
             if let TerminatedListErrorKind::MissingTerminator = error.kind {
                 parser.emit_diagnostic(
                     Diagnostic::error("missing `}` to close block")
+                        .with_code(codes::M0203)
                         .with_label(Label::primary(&open, "this is where the block begins")),
                 );
             }
@@ -101,6 +104,7 @@ impl Parse for StmtList {
                 TerminatedListErrorKind::Parse => (),
                 TerminatedListErrorKind::MissingTerminator => parser.emit_diagnostic(
                     Diagnostic::error("end of file expected after statements")
+                        .with_code(codes::M0206)
                         .with_label(Label::primary(
                             &error.parse.span,
                             "this is where the statements should have ended",
@@ -115,7 +119,7 @@ impl Parse for StmtList {
 }
 
 fn _stmt_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
-    Diagnostic::error("statement expected")
+    Diagnostic::error("statement expected").with_code(codes::M0218)
         .with_label(Label::primary(
             token,
             "this token does not start a statement",
@@ -1,11 +1,12 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{token::Token, token_stream::TokenStream};
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     cst::{Expr, Precedence},
     token::{AnyToken, Colon, LeftParen, RightParen, Semi},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 use super::{Block, Stmt};
@@ -26,14 +27,18 @@ keyword! {
     KContinue = "continue",
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct ParenExpr {
     pub open: LeftParen,
     pub expr: Expr,
     pub close: RightParen,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtIf {
     pub kif: KIf,
     pub cond: ParenExpr,
@@ -41,20 +46,26 @@ pub struct StmtIf {
     pub false_branch: Option<Else>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct Else {
     pub kelse: KElse,
     pub then: Box<Stmt>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtWhile {
     pub kwhile: KWhile,
     pub cond: ParenExpr,
     pub body: Box<Stmt>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtDo {
     pub kdo: KDo,
     pub block: Block,
@@ -62,7 +73,9 @@ pub struct StmtDo {
     pub cond: ParenExpr,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtFor {
     pub kfor: KFor,
 
@@ -77,34 +90,40 @@ pub struct StmtFor {
     pub body: Box<Stmt>,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct StmtForEach {
     pub foreach: KForEach,
     pub iterator: Expr,
     pub stmt: Box<Stmt>,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtSwitch {
     pub switch: KSwitch,
     pub value: ParenExpr,
     pub block: Block,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtCase {
     pub case: KCase,
     pub cond: Expr,
     pub colon: Colon,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtReturn {
     pub kreturn: KReturn,
     pub value: ReturnValue,
 }
 
-#[derive(Debug, Clone, Parse, Spanned)]
+#[derive(Debug, Clone, Parse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 #[parse(error = "_return_value_error")]
 pub enum ReturnValue {
     Nothing(Semi),
@@ -113,13 +132,17 @@ pub enum ReturnValue {
     Something(Expr, Semi),
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtBreak {
     pub kreturn: KBreak,
     pub semi: Semi,
 }
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 pub struct StmtContinue {
     pub kreturn: KContinue,
     pub semi: Semi,
@@ -140,5 +163,6 @@ impl Parse for StmtForEach {
 
 fn _return_value_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("return value or `;` expected")
+        .with_code(codes::M0205)
         .with_label(Label::primary(token, "return value expected here"))
 }
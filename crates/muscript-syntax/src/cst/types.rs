@@ -1,20 +1,23 @@
-use muscript_foundation::errors::{Diagnostic, Label};
+use muscript_foundation::errors::{codes, Diagnostic, Label};
 use muscript_lexer::{
     sources::LexedSources,
     token::{AnyToken, Token},
     token_stream::TokenStream,
 };
 use muscript_syntax_derive::Spanned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     list::SeparatedListDiagnostics,
     token::{Greater, Ident, Less},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 use super::{CppBlob, EnumDef, Path, StructDef};
 
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "type_or_def_error")]
 pub enum TypeOrDef {
     StructDef(StructDef),
@@ -23,7 +26,9 @@ pub enum TypeOrDef {
 }
 
 /// Some variable specifiers are attached to types within the engine source.
-#[derive(Debug, Clone, Parse, PredictiveParse, Spanned)]
+#[derive(
+    Debug, Clone, Parse, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize,
+)]
 #[parse(error = "specifier_error")]
 pub enum TypeSpecifier {
     #[parse(keyword = "const")]
@@ -34,7 +39,7 @@ pub enum TypeSpecifier {
     Transient(Ident),
 }
 
-#[derive(Debug, Clone, Spanned)]
+#[derive(Debug, Clone, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Type {
     pub specifiers: Vec<TypeSpecifier>,
     pub path: Path,
@@ -42,7 +47,7 @@ pub struct Type {
     pub cpptemplate: Option<CppBlob>,
 }
 
-#[derive(Debug, Clone, PredictiveParse, Spanned)]
+#[derive(Debug, Clone, PredictiveParse, Spanned, DumpCst, Relocate, Serialize, Deserialize)]
 pub struct Generic {
     pub less: Less,
     pub args: Vec<Type>,
@@ -109,10 +114,12 @@ fn specifier_error(parser: &Parser<'_, impl TokenStream>, token: &AnyToken) -> D
         "unknown type specifier `{}`",
         parser.sources.source(token)
     ))
+    .with_code(codes::M0210)
     .with_label(Label::primary(token, "this specifier is not recognized"))
 }
 
 fn type_or_def_error(_: &Parser<'_, impl TokenStream>, token: &AnyToken) -> Diagnostic<Token> {
     Diagnostic::error("type, struct definition, or enum definition expected")
+        .with_code(codes::M0212)
         .with_label(Label::primary(token, "type expected here"))
 }
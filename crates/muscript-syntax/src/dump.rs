@@ -0,0 +1,113 @@
+//! Conversion of syntax trees into JSON, for tools which cannot consume Rust types.
+//!
+//! Every node is dumped as an object with the node's `kind` (the name of its type), the `variant`
+//! for nodes that are enums, its `span` as a range of byte offsets into the source file, and its
+//! `fields` by name (or by index, for tuple-like nodes.) Tokens have their `text` in place of
+//! fields, and missing optional nodes as well as empty spans are `null`.
+
+use muscript_foundation::{errors::DiagnosticSink, span::Spanned};
+use muscript_lexer::{
+    sources::LexedSources,
+    token::{AnyToken, Token, TokenSpan},
+};
+use serde_json::json;
+
+pub use serde_json::Value;
+
+/// Fields of a node, by name.
+pub type Fields = serde_json::Map<String, Value>;
+
+/// Converts a syntax tree node into JSON.
+pub trait DumpCst {
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value;
+}
+
+pub struct DumpContext<'a> {
+    pub sources: LexedSources<'a>,
+    /// Receives diagnostics from parsing lazily parsed blocks, which are parsed while dumping.
+    pub diagnostics: &'a mut dyn DiagnosticSink<Token>,
+}
+
+impl<'a> DumpContext<'a> {
+    pub fn new(sources: LexedSources<'a>, diagnostics: &'a mut dyn DiagnosticSink<Token>) -> Self {
+        Self {
+            sources,
+            diagnostics,
+        }
+    }
+
+    pub fn span(&self, tokens: &impl Spanned<Token>) -> Value {
+        match self.sources.source_range(tokens) {
+            Some(range) => json!({ "start": range.start, "end": range.end }),
+            None => Value::Null,
+        }
+    }
+
+    pub fn node(
+        &self,
+        kind: &str,
+        variant: Option<&str>,
+        node: &impl Spanned<Token>,
+        fields: Fields,
+    ) -> Value {
+        let mut object = Fields::new();
+        object.insert("kind".into(), kind.into());
+        if let Some(variant) = variant {
+            object.insert("variant".into(), variant.into());
+        }
+        object.insert("span".into(), self.span(node));
+        object.insert("fields".into(), Value::Object(fields));
+        Value::Object(object)
+    }
+
+    pub fn token(&self, kind: &str, token: &impl Spanned<Token>) -> Value {
+        json!({
+            "kind": kind,
+            "span": self.span(token),
+            "text": self.sources.source(token),
+        })
+    }
+}
+
+impl<T> DumpCst for Option<T>
+where
+    T: DumpCst,
+{
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        match self {
+            Some(node) => node.dump_cst(cx),
+            None => Value::Null,
+        }
+    }
+}
+
+impl<T> DumpCst for Vec<T>
+where
+    T: DumpCst,
+{
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        Value::Array(self.iter().map(|node| node.dump_cst(cx)).collect())
+    }
+}
+
+impl<T> DumpCst for Box<T>
+where
+    T: DumpCst,
+{
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        T::dump_cst(self, cx)
+    }
+}
+
+impl DumpCst for AnyToken {
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        cx.token(&format!("{:?}", self.kind), self)
+    }
+}
+
+/// Spans stored in nodes are dumped as just the range of bytes they cover.
+impl DumpCst for TokenSpan {
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        cx.span(self)
+    }
+}
@@ -8,8 +8,10 @@ mod parsing;
 
 pub mod cst;
 pub mod diagnostics;
+pub mod dump;
 pub mod list;
 
+pub use dump::DumpCst;
 pub use parsing::*;
 
 pub use muscript_syntax_derive::*;
@@ -1,9 +1,10 @@
 //! Parsing of delimited, comma-separated lists.
 
+use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 
 use muscript_foundation::{
-    errors::{Diagnostic, Label},
+    errors::{codes, Diagnostic, Label},
     span::Span,
 };
 use muscript_lexer::{
@@ -179,7 +180,7 @@ pub struct TerminatedListError {
     pub parse: ParseError,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SeparatedListDiagnostics<'a> {
     /// ```text
     /// missing `right` to close [thing]
@@ -226,10 +227,12 @@ where
             SeparatedListErrorKind::Parse => (),
             SeparatedListErrorKind::MissingRight => self.emit_diagnostic(
                 Diagnostic::error(diagnostics.missing_right)
+                    .with_code(codes::M0203)
                     .with_label(Label::secondary(open, diagnostics.missing_right_label)),
             ),
             SeparatedListErrorKind::MissingSeparator => self.emit_diagnostic(
                 Diagnostic::error(diagnostics.missing_comma)
+                    .with_code(codes::M0204)
                     .with_label(Label::primary(
                         &error.parse.span,
                         diagnostics.missing_comma_token,
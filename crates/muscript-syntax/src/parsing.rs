@@ -1,7 +1,7 @@
 mod lazy;
 mod recovery;
 
-use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label, Note, NoteKind};
+use muscript_foundation::errors::{codes, Diagnostic, DiagnosticSink, Label, Note, NoteKind};
 use muscript_lexer::{
     sources::LexedSources,
     token::{AnyToken, Token, TokenKind, TokenSpan},
@@ -10,7 +10,6 @@ use muscript_lexer::{
 use tracing::warn;
 
 pub use lazy::*;
-pub use recovery::*;
 
 use crate::token::SingleToken;
 
@@ -48,7 +47,7 @@ impl<'a, T> Parser<'a, T> {
     pub fn sub<'b>(
         &'b mut self,
         diagnostics: Option<&'b mut dyn DiagnosticSink<Token>>,
-    ) -> Parser<'b, &mut T> {
+    ) -> Parser<'b, &'b mut T> {
         Parser {
             sources: self.sources,
             tokens: &mut self.tokens,
@@ -187,6 +186,7 @@ where
         Tok::try_from_token(token, &self.sources).map_err(|error| {
             self.emit_diagnostic(
                 Diagnostic::error(format!("{} expected", Tok::NAME))
+                    .with_code(codes::M0200)
                     .with_label(Label::primary(
                         &TokenSpan::single(error.token_id),
                         format!("{} expected here", Tok::NAME),
@@ -250,9 +250,8 @@ where
     where
         N: Parse,
     {
-        self.sub(Some(&mut ())).parse().map_err(|error| {
+        self.sub(Some(&mut ())).parse().inspect_err(|error| {
             self.emit_diagnostic(diagnostic(self, error.span));
-            error
         })
     }
 }
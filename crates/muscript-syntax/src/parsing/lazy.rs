@@ -1,5 +1,6 @@
 use muscript_foundation::{
-    errors::{Diagnostic, DiagnosticSink, Label},
+    errors::{codes, Diagnostic, DiagnosticSink, Label},
+    source_arena::{Relocate, Relocation},
     span::Spanned,
 };
 use muscript_lexer::{
@@ -8,10 +9,12 @@ use muscript_lexer::{
     token::{AnyToken, Token, TokenKind, TokenSpan},
     token_stream::TokenStream,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    dump::{DumpContext, Fields, Value},
     token::{LeftBrace, RightBrace, SingleToken},
-    Parse, ParseError, Parser, PredictiveParse,
+    DumpCst, Parse, ParseError, Parser, PredictiveParse, Relocate,
 };
 
 pub trait Delimiters {
@@ -24,7 +27,7 @@ pub trait Delimiters {
     fn close(&self) -> Self::Close;
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Relocate, Serialize, Deserialize)]
 pub struct Braces {
     pub open: LeftBrace,
     pub close: RightBrace,
@@ -47,12 +50,22 @@ impl Delimiters for Braces {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LazyBlock<D> {
     pub delimiters: D,
     pub inner: SlicedTokens,
 }
 
+impl<D> Relocate for LazyBlock<D>
+where
+    D: Relocate,
+{
+    fn relocate(&mut self, relocation: &Relocation) -> Option<()> {
+        self.delimiters.relocate(relocation)?;
+        self.inner.relocate(relocation)
+    }
+}
+
 impl<D> LazyBlock<D> {
     pub fn parse_inner<'a, P>(
         &self,
@@ -79,6 +92,8 @@ where
         let open_nesting_level = parser.nesting_level();
 
         let mut inner = SlicedTokens::new();
+        inner.extend_inclusions(parser.tokens.inclusions());
+        inner.extend_expansions(parser.tokens.expansions());
         let mut close = None;
 
         while parser.nesting_level() >= open_nesting_level {
@@ -93,6 +108,7 @@ where
                 parser.emit_diagnostic(missing_closing_delimiter::<D>(&open));
                 return Err(parser.make_error(TokenSpan::single(open.id())));
             }
+            inner.set_current_expansion(parser.tokens.previous_expansion());
             inner.push_token(token.id);
         }
 
@@ -117,6 +133,7 @@ where
         D::Close::NAME,
         D::Open::NAME
     ))
+    .with_code(codes::M0203)
     .with_label(Label::primary(
         open,
         format!("this {} is missing its closing delimiter", D::Open::NAME),
@@ -145,3 +162,48 @@ where
             .join(&self.delimiters.close().span())
     }
 }
+
+impl<D> LazyBlock<D>
+where
+    D: Delimiters,
+    D::Open: DumpCst,
+    D::Close: DumpCst,
+{
+    /// Dumps the block along with its contents, which are parsed as `P`. The contents are `null`
+    /// if they cannot be parsed.
+    pub fn dump_cst_with_inner<P>(&self, cx: &mut DumpContext<'_>) -> Value
+    where
+        P: Parse + DumpCst,
+    {
+        let inner = self.parse_inner::<P>(cx.sources, &mut *cx.diagnostics);
+        let mut fields = self.dump_delimiters(cx);
+        fields.insert(
+            "inner".into(),
+            match inner {
+                Ok(Some(inner)) => inner.dump_cst(cx),
+                Ok(None) | Err(_) => Value::Null,
+            },
+        );
+        cx.node("LazyBlock", None, self, fields)
+    }
+
+    fn dump_delimiters(&self, cx: &mut DumpContext<'_>) -> Fields {
+        let mut fields = Fields::new();
+        fields.insert("open".into(), self.delimiters.open().dump_cst(cx));
+        fields.insert("close".into(), self.delimiters.close().dump_cst(cx));
+        fields
+    }
+}
+
+/// Without knowing what the block contains, only its delimiters are dumped.
+impl<D> DumpCst for LazyBlock<D>
+where
+    D: Delimiters,
+    D::Open: DumpCst,
+    D::Close: DumpCst,
+{
+    fn dump_cst(&self, cx: &mut DumpContext<'_>) -> Value {
+        let fields = self.dump_delimiters(cx);
+        cx.node("LazyBlock", None, self, fields)
+    }
+}
@@ -63,7 +63,7 @@ pub struct TokenKindMismatch {
 macro_rules! strong_token_types {
     ($($name:tt = $pretty_name:tt),* $(,)?) => {
         $(
-            #[derive(Clone, Copy, PartialEq, Eq)]
+            #[derive(Clone, Copy, PartialEq, Eq, ::serde::Serialize, ::serde::Deserialize)]
             pub struct $name {
                 pub id: TokenId,
             }
@@ -89,6 +89,21 @@ macro_rules! strong_token_types {
                 }
             }
 
+            impl $crate::dump::DumpCst for $name {
+                fn dump_cst(&self, cx: &mut $crate::dump::DumpContext<'_>) -> $crate::dump::Value {
+                    cx.token(stringify!($name), self)
+                }
+            }
+
+            impl ::muscript_foundation::source_arena::Relocate for $name {
+                fn relocate(
+                    &mut self,
+                    relocation: &::muscript_foundation::source_arena::Relocation,
+                ) -> Option<()> {
+                    ::muscript_foundation::source_arena::Relocate::relocate(&mut self.id, relocation)
+                }
+            }
+
             impl SingleToken for $name {
                 const NAME: &'static str = $pretty_name;
                 const KIND: TokenKind = TokenKind::$name;
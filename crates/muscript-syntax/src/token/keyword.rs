@@ -16,7 +16,7 @@ pub trait Keyword: SingleToken {
 #[doc(hidden)]
 macro_rules! __keyword_impl {
     ($T:tt = $keyword:tt) => {
-        #[derive(Clone, Copy, PartialEq, Eq)]
+        #[derive(Clone, Copy, PartialEq, Eq, ::serde::Serialize, ::serde::Deserialize)]
         pub struct $T {
             pub id: muscript_lexer::token::TokenId,
         }
@@ -32,6 +32,15 @@ macro_rules! __keyword_impl {
             }
         }
 
+        impl ::muscript_foundation::source_arena::Relocate for $T {
+            fn relocate(
+                &mut self,
+                relocation: &::muscript_foundation::source_arena::Relocation,
+            ) -> ::std::option::Option<()> {
+                ::muscript_foundation::source_arena::Relocate::relocate(&mut self.id, relocation)
+            }
+        }
+
         impl $crate::token::SingleToken for $T {
             const NAME: &'static str = concat!("`", $keyword, "`");
             const KIND: muscript_lexer::token::TokenKind = muscript_lexer::token::TokenKind::Ident;
@@ -74,6 +83,12 @@ macro_rules! __keyword_impl {
             }
         }
 
+        impl $crate::dump::DumpCst for $T {
+            fn dump_cst(&self, cx: &mut $crate::dump::DumpContext<'_>) -> $crate::dump::Value {
+                cx.token(stringify!($T), self)
+            }
+        }
+
         impl $crate::parsing::Parse for $T {
             fn parse(
                 parser: &mut $crate::parsing::Parser<
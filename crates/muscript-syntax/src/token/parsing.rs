@@ -1,6 +1,6 @@
 use std::num::{IntErrorKind, ParseIntError};
 
-use muscript_foundation::errors::{Diagnostic, DiagnosticSink, Label};
+use muscript_foundation::errors::{codes, Diagnostic, DiagnosticSink, Label};
 use muscript_lexer::sources::LexedSources;
 
 use crate::diagnostics::notes;
@@ -20,7 +20,7 @@ impl IntLit {
             Err(error) => match error.kind() {
                 IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
                     diagnostics.emit(
-                        Diagnostic::error("integer does not fit within 32 bits")
+                        Diagnostic::error("integer does not fit within 32 bits").with_code(codes::M0201)
                             .with_label(Label::primary(self, ""))
                             .with_note(indoc::indoc! {"
                                 note: UnrealScript integers are 32 bit;
@@ -97,6 +97,7 @@ impl StringLit {
                     Some((_, 'n')) => result.push('\n'),
                     Some((_, other)) => diagnostics.emit(
                         Diagnostic::error(format!("invalid escape sequence: `\\{other}`"))
+                            .with_code(codes::M0202)
                             // TODO: As a result of refactoring the lexer, it is now impossible to
                             // subslice the string in an error message. Therefore StringLit tokens
                             // should probably be split up into StringBegin, StringData, StringEscape,
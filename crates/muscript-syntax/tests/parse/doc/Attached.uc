@@ -0,0 +1,17 @@
+/** A documented class. */
+class Attached extends Object;
+
+/// A documented variable.
+var int A;
+
+/**
+ * A documented enum.
+ */
+enum EAttached
+{
+    ATTACHED_A,
+};
+
+/// A documented function,
+/// spanning two lines.
+function F();
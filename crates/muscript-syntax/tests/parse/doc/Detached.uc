@@ -0,0 +1,18 @@
+/// Detached from the class by a blank line.
+
+class Detached extends Object;
+
+/** Detached from the variable by a blank line. */
+
+var int A;
+
+/// Detached from the variable by a regular comment.
+// A regular comment.
+var int B;
+
+/** Detached from the enum by a regular comment. */
+/* A regular block comment. */
+enum EDetached
+{
+    DETACHED_A,
+};
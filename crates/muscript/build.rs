@@ -0,0 +1,61 @@
+//! Computes the version of the on-disk cache's format, which is a hash of the sources of
+//! everything that shapes cache entries: the lexer, preprocessor, and parser producing them, the
+//! types they are serialized from, and the dependencies they're serialized with.
+//!
+//! bincode is not self-describing, so an entry saved by a compiler built from different sources
+//! cannot be told apart from a valid one and must never be loaded.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Paths relative to the workspace root whose contents affect the format of cache entries.
+const SCHEMA_PATHS: &[&str] = &[
+    "Cargo.lock",
+    "crates/muscript-foundation/src",
+    "crates/muscript-lexer/src",
+    "crates/muscript-preprocessor/src",
+    "crates/muscript-syntax/src",
+    "crates/muscript-syntax-derive/src",
+    "crates/muscript-analysis/src",
+    "crates/muscript/src/cache.rs",
+    "crates/muscript/src/parse.rs",
+];
+
+fn main() {
+    let workspace_root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+
+    let mut files = vec![];
+    for path in SCHEMA_PATHS {
+        let path = workspace_root.join(path);
+        println!("cargo:rerun-if-changed={}", path.display());
+        collect_files(&path, &mut files);
+    }
+    files.sort();
+
+    let mut hasher = blake3::Hasher::new();
+    for file in &files {
+        let relative_path = file.strip_prefix(&workspace_root).unwrap_or(file);
+        let contents = fs::read(file).expect("cannot read source file");
+        let relative_path = relative_path.to_string_lossy();
+        hasher.update(&(relative_path.len() as u64).to_le_bytes());
+        hasher.update(relative_path.as_bytes());
+        hasher.update(&(contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    println!(
+        "cargo:rustc-env=MUSCRIPT_CACHE_SCHEMA={}",
+        &hasher.finalize().to_hex()[..16]
+    );
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        for entry in fs::read_dir(path).expect("cannot read source directory") {
+            collect_files(&entry.expect("cannot read source directory").path(), files);
+        }
+    } else if path.is_file() {
+        files.push(path.to_owned());
+    }
+}
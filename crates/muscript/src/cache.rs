@@ -0,0 +1,653 @@
+//! On-disk cache of lexed and parsed sources of external packages.
+//!
+//! External packages (most notably the game's own) rarely change between compilations, yet they
+//! make up the bulk of the work done by the lexer and parser. The cache stores the tokens and
+//! [`cst::File`] of each external source file, as well as the untyped partitions of each external
+//! class, such that later compilations can load them instead of starting from scratch.
+//!
+//! Entries are keyed by a hash of the file's path, contents, and preprocessor mode, along with the
+//! contents of all include files, since those provide the macros the file is preprocessed with. Cached trees refer
+//! to tokens by their IDs, which are relocated to wherever the tokens end up in the current token
+//! arena when an entry is loaded.
+//!
+//! Entries are stored in a directory named after a hash of the sources of the compiler that saved
+//! them (see the build script), since their serialized form is not self-describing and changes
+//! along with the compiler.
+//!
+//! Only files which compiled without any diagnostics are cached, so loading an entry never hides
+//! a diagnostic that would be emitted otherwise.
+
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use muscript_analysis::partition::UntypedClassPartition;
+use muscript_foundation::{
+    errors::{lints::LintScope, Diagnostic, DiagnosticSink},
+    source::{SourceFileId, SourceFileSet},
+    source_arena::{Relocate, Relocation},
+};
+use muscript_lexer::{
+    lint_directives::apply_lint_directives,
+    sources::{LexedSources, OwnedSources},
+    token::{Token, TokenSpan},
+};
+use muscript_preprocessor::{Definitions, IncludeFiles, Modes};
+use muscript_syntax::cst;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::parse::{lex_source, parse_tokens};
+
+/// Hash of the compiler sources the format of cache entries depends on, computed by the build
+/// script.
+const SCHEMA: &str = env!("MUSCRIPT_CACHE_SCHEMA");
+
+/// Prefix of the names of directories holding cache entries of a particular compiler build.
+const VERSION_DIR_PREFIX: &str = "muscript-";
+
+pub struct Cache {
+    dir: PathBuf,
+    /// Hash of everything besides a file's own path and contents that affects how it is parsed.
+    environment: blake3::Hash,
+    include_file_ids: Vec<SourceFileId>,
+    modes: Modes,
+    /// Files which were parsed (or loaded from the cache) without any diagnostics, and may thus
+    /// have their class's partitions cached.
+    clean_files: RefCell<HashSet<SourceFileId>>,
+}
+
+/// Spans of the tokens referred to by a cache entry, as they were when the entry was saved.
+#[derive(Serialize, Deserialize)]
+struct EntryHeader {
+    spans: Vec<TokenSpan>,
+}
+
+impl Cache {
+    /// Returns the directory the cache is stored in when the user does not specify one.
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = if cfg!(windows) {
+            std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+        } else {
+            std::env::var_os("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        };
+        base.map(|base| base.join("muscript"))
+    }
+
+    /// Opens the cache in the given directory, for compiling with the given include files and
+    /// preprocessor modes.
+    ///
+    /// Entries saved by other builds of the compiler are removed.
+    pub fn open(
+        base_dir: &Path,
+        source_file_set: &SourceFileSet,
+        include_file_ids: &[SourceFileId],
+        modes: &Modes,
+    ) -> anyhow::Result<Self> {
+        let version_dir_name = format!("{VERSION_DIR_PREFIX}{SCHEMA}");
+        let dir = base_dir.join(&version_dir_name);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create cache directory {dir:?}"))?;
+        remove_outdated_versions(base_dir, &version_dir_name);
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(version_dir_name.as_bytes());
+        for &id in include_file_ids {
+            hasher.update(modes.get(id).name().as_bytes());
+            hash_source_file(&mut hasher, source_file_set, id);
+        }
+
+        Ok(Self {
+            dir,
+            environment: hasher.finalize(),
+            include_file_ids: include_file_ids.to_owned(),
+            modes: modes.clone(),
+            clean_files: RefCell::new(HashSet::new()),
+        })
+    }
+
+    fn file_key(&self, source_file_set: &SourceFileSet, id: SourceFileId) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.environment.as_bytes());
+        hasher.update(self.modes.get(id).name().as_bytes());
+        hash_source_file(&mut hasher, source_file_set, id);
+        hasher.finalize()
+    }
+
+    fn class_key(&self, source_file_set: &SourceFileSet, ids: &[SourceFileId]) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        for &id in ids {
+            hasher.update(self.file_key(source_file_set, id).as_bytes());
+        }
+        hasher.finalize()
+    }
+
+    fn entry_path(&self, key: blake3::Hash, extension: &str) -> PathBuf {
+        self.dir
+            .join(key.to_hex().as_str())
+            .with_extension(extension)
+    }
+
+    /// Lexes and parses a source file, or loads its tokens and CST from the cache.
+    pub fn parse_source(
+        &self,
+        sources: &mut OwnedSources<'_>,
+        definitions: &mut Definitions,
+        includes: &IncludeFiles,
+        id: SourceFileId,
+        diagnostics: &mut dyn DiagnosticSink<Token>,
+    ) -> Result<cst::File, muscript_syntax::ParseError> {
+        let key = self.file_key(sources.source_file_set, id);
+        let tokens_path = self.entry_path(key, "tokens");
+        let cst_path = self.entry_path(key, "cst");
+
+        if let (Some(tokens), Some(cst_bytes)) = (
+            read_entry::<Vec<Token>>(&tokens_path),
+            read_entry_bytes(&cst_path),
+        ) {
+            let token_span = push_tokens(sources, id, tokens, diagnostics);
+            match self.deserialize_relocated(sources, &[id], &cst_bytes) {
+                Some(file) => {
+                    debug!(?cst_path, "loaded CST from cache");
+                    self.clean_files.borrow_mut().insert(id);
+                    return Ok(file);
+                }
+                None => {
+                    remove_entry(&cst_path);
+                    return parse_tokens(
+                        sources,
+                        definitions,
+                        includes,
+                        &self.modes,
+                        token_span,
+                        diagnostics,
+                    );
+                }
+            }
+        }
+
+        let lexer_error_count = sources.lexer_errors.errors.len();
+        let synthesized_token_count = sources.synthesized_tokens.len();
+        let mut counting = CountingSink {
+            inner: diagnostics,
+            count: 0,
+        };
+        let token_span = lex_source(sources, id, &mut counting);
+        let result = parse_tokens::<cst::File>(
+            sources,
+            definitions,
+            includes,
+            &self.modes,
+            token_span,
+            &mut counting,
+        );
+        if let Ok(file) = &result {
+            // Tokens synthesized by the preprocessor live outside the file's token span, so CSTs
+            // referring to them cannot be relocated when loaded back from the cache.
+            if counting.count == 0
+                && sources.lexer_errors.errors.len() == lexer_error_count
+                && sources.synthesized_tokens.len() == synthesized_token_count
+            {
+                self.clean_files.borrow_mut().insert(id);
+                write_entry(&tokens_path, |writer| {
+                    bincode::serialize_into(writer, sources.token_arena.elements_in(token_span))
+                });
+                self.write_relocatable(&sources.as_borrowed(), &[id], &cst_path, file);
+            }
+        }
+        result
+    }
+
+    /// Loads the partitions of a class made up of the given files, along with the files' tokens.
+    pub fn class_partitions(
+        &self,
+        sources: &mut OwnedSources<'_>,
+        ids: &[SourceFileId],
+        diagnostics: &mut dyn DiagnosticSink<Token>,
+    ) -> Option<Vec<UntypedClassPartition>> {
+        let partitions_path =
+            self.entry_path(self.class_key(sources.source_file_set, ids), "partitions");
+        let partitions_bytes = read_entry_bytes(&partitions_path)?;
+        let all_tokens = ids
+            .iter()
+            .map(|&id| {
+                let key = self.file_key(sources.source_file_set, id);
+                read_entry::<Vec<Token>>(&self.entry_path(key, "tokens"))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        for (&id, tokens) in ids.iter().zip(all_tokens) {
+            push_tokens(sources, id, tokens, diagnostics);
+        }
+        let partitions = self.deserialize_relocated(sources, ids, &partitions_bytes);
+        if partitions.is_some() {
+            debug!(?partitions_path, "loaded class partitions from cache");
+            self.clean_files.borrow_mut().extend(ids);
+        } else {
+            remove_entry(&partitions_path);
+        }
+        partitions
+    }
+
+    /// Saves the partitions of a class made up of the given files, as long as all of the files
+    /// were parsed without diagnostics.
+    pub fn save_class_partitions(
+        &self,
+        sources: &LexedSources<'_>,
+        ids: &[SourceFileId],
+        partitions: &[UntypedClassPartition],
+    ) {
+        let clean_files = self.clean_files.borrow();
+        if ids.iter().all(|id| clean_files.contains(id)) {
+            let partitions_path =
+                self.entry_path(self.class_key(sources.source_file_set, ids), "partitions");
+            if !partitions_path.exists() {
+                self.write_relocatable(sources, ids, &partitions_path, partitions);
+            }
+        }
+    }
+
+    /// Returns the spans of the tokens of the given files and all include files, which are all
+    /// the tokens cached trees may refer to.
+    fn token_spans(&self, sources: &LexedSources<'_>, ids: &[SourceFileId]) -> Vec<TokenSpan> {
+        ids.iter()
+            .chain(&self.include_file_ids)
+            .map(|&id| sources.token_arena.source_file_span(id))
+            .collect()
+    }
+
+    fn write_relocatable<T>(
+        &self,
+        sources: &LexedSources<'_>,
+        ids: &[SourceFileId],
+        path: &Path,
+        value: &T,
+    ) where
+        T: Serialize + ?Sized,
+    {
+        let header = EntryHeader {
+            spans: self.token_spans(sources, ids),
+        };
+        write_entry(path, |writer| {
+            bincode::serialize_into(&mut *writer, &header)?;
+            bincode::serialize_into(writer, value)
+        });
+    }
+
+    fn deserialize_relocated<T>(
+        &self,
+        sources: &OwnedSources<'_>,
+        ids: &[SourceFileId],
+        bytes: &[u8],
+    ) -> Option<T>
+    where
+        T: DeserializeOwned + Relocate,
+    {
+        let mut reader = bytes;
+        let header: EntryHeader = bincode::deserialize_from(&mut reader).ok()?;
+        let spans = self.token_spans(&sources.as_borrowed(), ids);
+        if header.spans.len() != spans.len() {
+            return None;
+        }
+        let mut relocation = Relocation::new();
+        for (old, new) in header.spans.into_iter().zip(spans) {
+            relocation.add(old, new)?;
+        }
+        let mut value: T = bincode::deserialize_from(reader)
+            .map_err(|error| warn!("cannot deserialize cache entry: {error}"))
+            .ok()?;
+        value.relocate(&relocation)?;
+        Some(value)
+    }
+}
+
+/// Pushes cached tokens of a source file into the token arena, as if the file was lexed.
+fn push_tokens(
+    sources: &mut OwnedSources<'_>,
+    id: SourceFileId,
+    tokens: Vec<Token>,
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) -> TokenSpan {
+    let mut builder = sources.token_arena.build_source_file(id);
+    for token in tokens {
+        builder.push(token);
+    }
+    let token_span = builder.finish();
+    apply_lint_directives(
+        &sources.token_arena,
+        sources.source_file_set.get(id).source(),
+        token_span,
+        diagnostics,
+    );
+    token_span
+}
+
+fn hash_source_file(
+    hasher: &mut blake3::Hasher,
+    source_file_set: &SourceFileSet,
+    id: SourceFileId,
+) {
+    let source_file = source_file_set.get(id);
+    let path = source_file.full_path.to_string_lossy();
+    let source = source_file.source().as_bytes();
+    // Lengths are hashed along with the data so that moving bytes between the path and the
+    // source does not produce the same hash.
+    hasher.update(&(path.len() as u64).to_le_bytes());
+    hasher.update(path.as_bytes());
+    hasher.update(&(source.len() as u64).to_le_bytes());
+    hasher.update(source);
+}
+
+fn remove_outdated_versions(base_dir: &Path, current: &str) {
+    let Ok(entries) = fs::read_dir(base_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.starts_with(VERSION_DIR_PREFIX) && name != current && entry.path().is_dir() {
+            debug!(name, "removing outdated cache directory");
+            if let Err(error) = fs::remove_dir_all(entry.path()) {
+                warn!("cannot remove outdated cache directory {name}: {error}");
+            }
+        }
+    }
+}
+
+fn read_entry_bytes(path: &Path) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    match fs::File::open(path) {
+        Ok(file) => match BufReader::new(file).read_to_end(&mut bytes) {
+            Ok(_) => Some(bytes),
+            Err(error) => {
+                warn!("cannot read cache entry {path:?}: {error}");
+                None
+            }
+        },
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => {
+            warn!("cannot open cache entry {path:?}: {error}");
+            None
+        }
+    }
+}
+
+fn read_entry<T>(path: &Path) -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    let bytes = read_entry_bytes(path)?;
+    match bincode::deserialize(&bytes) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            warn!("removing corrupt cache entry {path:?}: {error}");
+            remove_entry(path);
+            None
+        }
+    }
+}
+
+/// Writes a cache entry such that other compiler processes never observe it half-written.
+fn write_entry(path: &Path, write: impl FnOnce(&mut BufWriter<fs::File>) -> bincode::Result<()>) {
+    let temp_path = path.with_extension(format!("tmp{}", std::process::id()));
+    let result = fs::File::create(&temp_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        })
+        .and_then(|()| Ok(fs::rename(&temp_path, path)?));
+    if let Err(error) = result {
+        warn!("cannot write cache entry {path:?}: {error}");
+        _ = fs::remove_file(&temp_path);
+    }
+}
+
+fn remove_entry(path: &Path) {
+    if let Err(error) = fs::remove_file(path) {
+        if error.kind() != io::ErrorKind::NotFound {
+            warn!("cannot remove cache entry {path:?}: {error}");
+        }
+    }
+}
+
+/// Forwards diagnostics to another sink, counting how many were emitted.
+struct CountingSink<'a> {
+    inner: &'a mut dyn DiagnosticSink<Token>,
+    count: usize,
+}
+
+impl DiagnosticSink<Token> for CountingSink<'_> {
+    fn emit(&mut self, diagnostic: Diagnostic<Token>) {
+        self.count += 1;
+        self.inner.emit(diagnostic);
+    }
+
+    fn set_lint_levels(&mut self, scope: LintScope<Token>) {
+        self.inner.set_lint_levels(scope);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, sync::Arc};
+
+    use muscript_analysis::partition::UntypedClassPartition;
+    use muscript_foundation::{
+        errors::Diagnostic,
+        source::{SourceFile, SourceFileId, SourceFileSet},
+        source_arena::SourceArena,
+    };
+    use muscript_lexer::{
+        sources::{OwnedSources, SynthesizedTokens},
+        token::{Token, TokenId},
+        LexerErrors,
+    };
+    use muscript_preprocessor::{Definitions, IncludeFiles, Modes};
+
+    use crate::parse::{lex_source, preprocess_include_files};
+
+    use super::Cache;
+
+    struct Fixture {
+        dir: PathBuf,
+        source_file_set: SourceFileSet,
+        padding: SourceFileId,
+        globals: SourceFileId,
+        class: SourceFileId,
+    }
+
+    impl Fixture {
+        fn new(test_name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "muscript-cache-test-{}-{test_name}",
+                std::process::id()
+            ));
+            _ = fs::remove_dir_all(&dir);
+
+            let mut source_file_set = SourceFileSet::new();
+            let mut add = |filename: &str, source: &str| {
+                source_file_set.add(SourceFile::new(
+                    Arc::from("Test"),
+                    filename.to_owned(),
+                    PathBuf::from("/Test/Classes").join(filename),
+                    Arc::from(source),
+                ))
+            };
+            let padding = add("Padding.uc", "class Padding extends Object;\n");
+            // The class's name comes from the include file, such that trees refer to its tokens,
+            // too.
+            let globals = add("Globals.uci", "`define NAME Test\n");
+            let class = add(
+                "Test.uc",
+                "class `NAME extends Object;\n\nvar int X;\n\nfunction F() { X = 1; }\n",
+            );
+            Self {
+                dir,
+                source_file_set,
+                padding,
+                globals,
+                class,
+            }
+        }
+
+        fn entries(&self, extension: &str) -> Vec<PathBuf> {
+            fs::read_dir(self.dir.read_dir().unwrap().next().unwrap().unwrap().path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().is_some_and(|e| e == extension))
+                .collect()
+        }
+
+        /// Opens the cache and runs `f` with fresh sources. When `padded`, another file is lexed
+        /// before anything else, such that all tokens end up at different positions in the token
+        /// arena than they would otherwise.
+        fn with_cache<R>(
+            &self,
+            padded: bool,
+            f: impl FnOnce(&Cache, &mut OwnedSources<'_>, &mut Definitions) -> R,
+        ) -> R {
+            let mut diagnostics: Vec<Diagnostic<Token>> = vec![];
+            let mut sources = OwnedSources {
+                source_file_set: &self.source_file_set,
+                token_arena: SourceArena::new(),
+                lexer_errors: LexerErrors::default(),
+                synthesized_tokens: SynthesizedTokens::new(),
+            };
+            if padded {
+                lex_source(&mut sources, self.padding, &mut diagnostics);
+            }
+            let mut definitions = Definitions::default();
+            let modes = Modes::new();
+            preprocess_include_files(
+                &mut sources,
+                &mut definitions,
+                &IncludeFiles::new(),
+                &modes,
+                &[self.globals],
+                &mut diagnostics,
+            );
+            let cache =
+                Cache::open(&self.dir, &self.source_file_set, &[self.globals], &modes).unwrap();
+            let result = f(&cache, &mut sources, &mut definitions);
+            assert!(diagnostics.is_empty(), "{diagnostics:#?}");
+            result
+        }
+
+        /// Parses the class through the cache, and returns the ID and source code of its name.
+        fn parse(&self, padded: bool) -> (TokenId, String) {
+            self.with_cache(padded, |cache, sources, definitions| {
+                let mut diagnostics: Vec<Diagnostic<Token>> = vec![];
+                let file = cache
+                    .parse_source(
+                        sources,
+                        definitions,
+                        &IncludeFiles::new(),
+                        self.class,
+                        &mut diagnostics,
+                    )
+                    .unwrap();
+                assert!(diagnostics.is_empty(), "{diagnostics:#?}");
+
+                let sources = sources.as_borrowed();
+                let partition = UntypedClassPartition::from_cst(&mut diagnostics, &sources, file);
+                let name = partition.name;
+                cache.save_class_partitions(&sources, &[self.class], &[partition]);
+                (name.id, sources.source(&name).to_owned())
+            })
+        }
+
+        /// Loads the class's partitions from the cache, and returns the ID and source code of the
+        /// class's name.
+        fn load_partitions(&self, padded: bool) -> Option<(TokenId, String)> {
+            self.with_cache(padded, |cache, sources, _| {
+                let mut diagnostics: Vec<Diagnostic<Token>> = vec![];
+                let partitions =
+                    cache.class_partitions(sources, &[self.class], &mut diagnostics)?;
+                assert!(diagnostics.is_empty(), "{diagnostics:#?}");
+                let name = partitions[0].name;
+                Some((name.id, sources.source(&name).to_owned()))
+            })
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn assert_single(paths: Vec<PathBuf>) -> PathBuf {
+        assert_eq!(paths.len(), 1, "{paths:?}");
+        paths.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn entries_are_saved_and_loaded() {
+        let fixture = Fixture::new("saved_and_loaded");
+        let (parsed_id, parsed_name) = fixture.parse(false);
+        assert_eq!(parsed_name, "Test");
+        let cst = assert_single(fixture.entries("cst"));
+        let partitions = assert_single(fixture.entries("partitions"));
+
+        let (loaded_id, loaded_name) = fixture.parse(false);
+        assert_eq!((loaded_id, loaded_name), (parsed_id, parsed_name));
+        let (loaded_id, loaded_name) = fixture.load_partitions(false).unwrap();
+        assert_eq!((loaded_id, loaded_name.as_str()), (parsed_id, "Test"));
+
+        // Entries which fail to load are removed, so them still being around means they loaded.
+        assert!(cst.exists() && partitions.exists());
+    }
+
+    #[test]
+    fn entries_are_relocated() {
+        let fixture = Fixture::new("relocated");
+        let (parsed_id, _) = fixture.parse(false);
+
+        let (loaded_id, loaded_name) = fixture.parse(true);
+        assert_ne!(loaded_id, parsed_id);
+        assert_eq!(loaded_name, "Test");
+        let (loaded_id, loaded_name) = fixture.load_partitions(true).unwrap();
+        assert_ne!(loaded_id, parsed_id);
+        assert_eq!(loaded_name, "Test");
+
+        // Entries which fail to load are removed, so them still being around means they loaded.
+        assert_single(fixture.entries("cst"));
+        assert_single(fixture.entries("partitions"));
+    }
+
+    #[test]
+    fn entries_are_invalidated_when_sources_change() {
+        let mut fixture = Fixture::new("invalidated");
+        fixture.parse(false);
+
+        fixture.source_file_set.replace_source(
+            fixture.class,
+            Arc::from("class `NAME extends Object;\n\nvar int Y;\n"),
+        );
+        assert!(fixture.load_partitions(false).is_none());
+        let (_, parsed_name) = fixture.parse(false);
+        assert_eq!(parsed_name, "Test");
+        assert_eq!(fixture.entries("cst").len(), 2);
+        assert_eq!(fixture.entries("partitions").len(), 2);
+
+        fixture
+            .source_file_set
+            .replace_source(fixture.globals, Arc::from("`define NAME Renamed\n"));
+        assert!(fixture.load_partitions(false).is_none());
+        let (_, parsed_name) = fixture.parse(false);
+        assert_eq!(parsed_name, "Renamed");
+        assert_eq!(fixture.entries("cst").len(), 3);
+    }
+}
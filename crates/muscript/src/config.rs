@@ -0,0 +1,634 @@
+//! Project configuration, read from the `muscript.toml` file placed in the package directory or
+//! next to it.
+//!
+//! ```toml
+//! [package]
+//! name = "MyMod"
+//! # Directory containing the package's `Classes`, relative to this file.
+//! path = "MyMod"
+//! # External source packages, in the order they'd be passed to `-s`.
+//! sources = ["Core", "Engine", "../Shared/SharedUtils"]
+//!
+//! [build]
+//! game = "C:/UDK/UDK-2013"
+//! output = "C:/UDK/UDK-2013/UDKGame/Script/MyMod.u"
+//!
+//! [definitions]
+//! DEBUG = ""
+//! MAX_PLAYERS = "16"
+//!
+//! # Build profiles, selected with `--profile`. `debug` and `release` exist even when not listed
+//! # here, and define `DEBUG` and `FINAL_RELEASE` respectively.
+//! [profiles.release.definitions]
+//! SHIPPING = ""
+//!
+//! [lints]
+//! unused_variable = "allow"
+//!
+//! # `muscript` (the default), or `compatible` to preprocess like the UnrealScript preprocessor.
+//! [preprocessor]
+//! mode = "muscript"
+//!
+//! # Modes of external source packages, by package name.
+//! [preprocessor.packages]
+//! SharedUtils = "compatible"
+//!
+//! # Options of `muscript fmt`.
+//! [format]
+//! indent_style = "spaces"
+//! indent_width = 4
+//! brace_style = "same_line"
+//! keyword_case = "lower"
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use camino::{Utf8Path, Utf8PathBuf};
+use muscript_foundation::errors::lints::{LintLevel, LintSelector, LintSettings};
+use muscript_preprocessor::Mode;
+use serde::Deserialize;
+
+use crate::{
+    files::get_package_name,
+    format::{self, BraceStyle, IndentStyle, KeywordCase},
+};
+
+/// Name of the project configuration file.
+pub const CONFIG_FILE_NAME: &str = "muscript.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub package: PackageConfig,
+    #[serde(default)]
+    pub build: BuildConfig,
+    /// Preprocessor definitions, as a map of macro names to the text they expand to. These are
+    /// visible to all include files and classes, as if they were `` `define``d before them.
+    #[serde(default)]
+    pub definitions: BTreeMap<String, String>,
+    /// Named sets of preprocessor definitions, only one of which is used at a time.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Lint levels, as a map of lint names (or `warnings`) to `allow`, `warn`, or `deny`.
+    #[serde(default)]
+    pub lints: HashMap<String, String>,
+    #[serde(default)]
+    pub preprocessor: PreprocessorConfig,
+    #[serde(default)]
+    pub format: FormatConfig,
+
+    /// Directory containing the configuration file. Relative paths are resolved against it.
+    #[serde(skip)]
+    dir: Utf8PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackageConfig {
+    /// Name of the package. Defaults to the name of the package directory.
+    pub name: Option<String>,
+    /// Directory containing the package's `Classes` directory. Defaults to the directory
+    /// containing the configuration file.
+    pub path: Option<Utf8PathBuf>,
+    /// External source packages the package depends on, in order. Each one is either a path, or
+    /// when a game directory is set, the name of a package in the game's `Development/Src`.
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+    /// The game's installation directory, which contains `Development/Src`.
+    pub game: Option<Utf8PathBuf>,
+    /// Where the compiled package should be written.
+    ///
+    /// Code generation is not implemented yet, so nothing is written here for now.
+    pub output: Option<Utf8PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreprocessorConfig {
+    /// Mode the package's own files are preprocessed in, `muscript` or `compatible`.
+    pub mode: Option<String>,
+    /// Modes of external source packages, as a map of package names to modes. Packages not listed
+    /// here are preprocessed in the `muscript` mode.
+    #[serde(default)]
+    pub packages: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FormatConfig {
+    /// `tabs` (the default) or `spaces`.
+    pub indent_style: Option<String>,
+    /// Number of spaces per level of indentation when indenting with spaces. Defaults to 4.
+    pub indent_width: Option<usize>,
+    /// `next_line` (the default) to put opening braces on their own line, `same_line` to put
+    /// them at the end of the line before, or `preserve` to leave them where they are.
+    pub brace_style: Option<String>,
+    /// `lower` (the default) to spell keywords in lowercase, or `preserve`.
+    pub keyword_case: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    /// Preprocessor definitions made in addition to the project's `[definitions]`.
+    #[serde(default)]
+    pub definitions: BTreeMap<String, String>,
+}
+
+/// Profiles that exist in every project, along with the macros they define.
+const BUILTIN_PROFILES: &[(&str, &str)] = &[("debug", "DEBUG"), ("release", "FINAL_RELEASE")];
+
+impl ProjectConfig {
+    /// Reads the configuration file in the given directory. Packages without a configuration file
+    /// use the default configuration.
+    pub fn load(dir: &Utf8Path) -> anyhow::Result<Self> {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self {
+                dir: dir.to_owned(),
+                ..Default::default()
+            });
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("cannot read {path}"))?;
+        Self::parse(&text, dir)
+    }
+
+    /// Parses the text of a configuration file placed in the given directory.
+    fn parse(text: &str, dir: &Utf8Path) -> anyhow::Result<Self> {
+        let path = dir.join(CONFIG_FILE_NAME);
+        let mut config: Self =
+            toml::from_str(text).with_context(|| format!("invalid configuration in {path}"))?;
+        config.dir = dir.to_owned();
+        config
+            .check_definitions()
+            .with_context(|| format!("in definitions of {path}"))?;
+        config
+            .check_preprocessor_modes()
+            .with_context(|| format!("in [preprocessor] of {path}"))?;
+        config
+            .format_options()
+            .with_context(|| format!("in [format] of {path}"))?;
+        Ok(config)
+    }
+
+    /// Returns whether a configuration file exists in the given directory.
+    pub fn exists_in(dir: &Utf8Path) -> bool {
+        dir.join(CONFIG_FILE_NAME).is_file()
+    }
+
+    /// Returns the path of the configuration file. The file may not exist.
+    pub fn path(&self) -> Utf8PathBuf {
+        self.dir.join(CONFIG_FILE_NAME)
+    }
+
+    /// Returns the directory containing the package's `Classes` directory.
+    pub fn package_dir(&self) -> Utf8PathBuf {
+        match &self.package.path {
+            Some(path) => self.dir.join(path),
+            None => self.dir.clone(),
+        }
+    }
+
+    /// Returns the name of the package.
+    pub fn package_name(&self) -> anyhow::Result<String> {
+        match &self.package.name {
+            Some(name) => Ok(name.clone()),
+            None => get_package_name(&self.package_dir()),
+        }
+    }
+
+    /// Resolves the directories of the external source packages, in order.
+    pub fn source_dirs(&self) -> Vec<Utf8PathBuf> {
+        self.package
+            .sources
+            .iter()
+            .map(|source| {
+                let is_package_name = !source.contains(['/', '\\']) && !source.starts_with('.');
+                match (&self.build.game, is_package_name) {
+                    (Some(game), true) => self.dir.join(game).join("Development/Src").join(source),
+                    _ => self.dir.join(source),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the path the compiled package should be written to, if one is configured.
+    pub fn output_path(&self) -> Option<Utf8PathBuf> {
+        self.build
+            .output
+            .as_ref()
+            .map(|output| self.dir.join(output))
+    }
+
+    /// Returns the definitions of the project, followed by those of the given profile.
+    pub fn definitions(&self, profile: Option<&str>) -> anyhow::Result<Vec<(&str, &str)>> {
+        let mut definitions: Vec<_> = self
+            .definitions
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        if let Some(profile) = profile {
+            let builtin = BUILTIN_PROFILES
+                .iter()
+                .find(|&&(builtin, _)| builtin == profile);
+            let configured = self.profiles.get(profile);
+            if builtin.is_none() && configured.is_none() {
+                let available: BTreeSet<_> = BUILTIN_PROFILES
+                    .iter()
+                    .map(|&(name, _)| name)
+                    .chain(self.profiles.keys().map(|name| name.as_str()))
+                    .collect();
+                let available: Vec<_> = available.into_iter().collect();
+                bail!(
+                    "unknown profile `{profile}` (available profiles: {})",
+                    available.join(", ")
+                );
+            }
+            definitions.extend(builtin.map(|&(_, name)| (name, "")));
+            definitions.extend(configured.into_iter().flat_map(|profile| {
+                profile
+                    .definitions
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str()))
+            }));
+        }
+        Ok(definitions)
+    }
+
+    fn check_definitions(&self) -> anyhow::Result<()> {
+        let names = self.definitions.keys().chain(
+            self.profiles
+                .values()
+                .flat_map(|profile| profile.definitions.keys()),
+        );
+        for name in names {
+            if !is_macro_name(name) {
+                bail!("{name:?} is not a valid macro name");
+            }
+        }
+        Ok(())
+    }
+
+    fn check_preprocessor_modes(&self) -> anyhow::Result<()> {
+        for mode in self
+            .preprocessor
+            .mode
+            .iter()
+            .chain(self.preprocessor.packages.values())
+        {
+            mode.parse::<Mode>().map_err(anyhow::Error::msg)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the preprocessor mode of the compiled package, or of the external package with the
+    /// given name. Package names are compared case-insensitively.
+    pub fn preprocessor_mode(&self, external_package: Option<&str>) -> Mode {
+        let mode = match external_package {
+            None => self.preprocessor.mode.as_ref(),
+            Some(package) => self
+                .preprocessor
+                .packages
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(package))
+                .map(|(_, mode)| mode),
+        };
+        // Modes are checked when the configuration is loaded.
+        mode.and_then(|mode| mode.parse().ok()).unwrap_or_default()
+    }
+
+    /// Parses the options of the formatter.
+    pub fn format_options(&self) -> anyhow::Result<format::Options> {
+        fn parse<T: FromStr<Err = String>>(
+            value: &Option<String>,
+            key: &str,
+        ) -> anyhow::Result<Option<T>> {
+            value
+                .as_deref()
+                .map(|value| value.parse().map_err(anyhow::Error::msg))
+                .transpose()
+                .with_context(|| format!("invalid {key}"))
+        }
+
+        let defaults = format::Options::default();
+        let format = &self.format;
+        Ok(format::Options {
+            indent_style: parse::<IndentStyle>(&format.indent_style, "indent_style")?
+                .unwrap_or(defaults.indent_style),
+            indent_width: format.indent_width.unwrap_or(defaults.indent_width),
+            brace_style: parse::<BraceStyle>(&format.brace_style, "brace_style")?
+                .unwrap_or(defaults.brace_style),
+            keyword_case: parse::<KeywordCase>(&format.keyword_case, "keyword_case")?
+                .unwrap_or(defaults.keyword_case),
+        })
+    }
+
+    /// Parses the configured lint levels.
+    pub fn lint_settings(&self) -> anyhow::Result<LintSettings> {
+        let mut settings = LintSettings::new();
+        for (lint, level) in &self.lints {
+            let selector = lint
+                .parse::<LintSelector>()
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("in [lints] of {CONFIG_FILE_NAME}"))?;
+            let level = level
+                .parse::<LintLevel>()
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("in [lints] of {CONFIG_FILE_NAME}"))?;
+            settings.set(selector, level);
+        }
+        Ok(settings)
+    }
+}
+
+fn is_macro_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A macro definition given on the command line, as `NAME` or `NAME=tokens`.
+#[derive(Debug, Clone)]
+pub struct Define {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for Define {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once('=').unwrap_or((s, ""));
+        Ok(Self {
+            name: parse_macro_name(name)?,
+            value: value.to_owned(),
+        })
+    }
+}
+
+/// Parses a macro name given on the command line.
+pub fn parse_macro_name(name: &str) -> Result<String, String> {
+    if is_macro_name(name) {
+        Ok(name.to_owned())
+    } else {
+        Err(format!("{name:?} is not a valid macro name"))
+    }
+}
+
+/// Generates the source code of an include file defining and then undefining the given macros,
+/// such that definitions made outside of source files can be handled by the preprocessor like any
+/// other.
+pub fn definitions_source<'a>(
+    definitions: impl IntoIterator<Item = (&'a str, &'a str)>,
+    undefinitions: impl IntoIterator<Item = &'a str>,
+) -> String {
+    let mut source = String::new();
+    for (name, value) in definitions {
+        // Newlines within the value have to be escaped, since a definition otherwise ends at the
+        // end of its line.
+        let value = value.replace("\r\n", "\n").replace('\n', "\\\n");
+        source.push_str(&format!("`define {name} {value}\n"));
+    }
+    for name in undefinitions {
+        source.push_str(&format!("`undefine({name})\n"));
+    }
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::{Utf8Path, Utf8PathBuf};
+    use muscript_foundation::{
+        errors::lints::{LintLevel, LintLevels, LintSettings, LINTS},
+        span::Span,
+    };
+    use muscript_preprocessor::Mode;
+
+    use super::{definitions_source, Define, ProjectConfig};
+
+    const DIR: &str = "/projects/MyMod";
+
+    fn parse(text: &str) -> ProjectConfig {
+        ProjectConfig::parse(text, Utf8Path::new(DIR)).unwrap()
+    }
+
+    fn parse_error(text: &str) -> String {
+        format!(
+            "{:#}",
+            ProjectConfig::parse(text, Utf8Path::new(DIR)).unwrap_err()
+        )
+    }
+
+    fn path(relative: &str) -> Utf8PathBuf {
+        Utf8Path::new(DIR).join(relative)
+    }
+
+    #[test]
+    fn empty_config_uses_defaults() {
+        let config = parse("");
+        assert_eq!(config.package_dir(), DIR);
+        assert_eq!(config.package_name().unwrap(), "MyMod");
+        assert_eq!(config.source_dirs(), Vec::<Utf8PathBuf>::new());
+        assert_eq!(config.output_path(), None);
+        assert_eq!(config.preprocessor_mode(None), Mode::MuScript);
+        assert!(config.lint_settings().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for (text, key) in [
+            ("[pakage]\nname = \"MyMod\"\n", "pakage"),
+            ("[package]\nsource = [\"Core\"]\n", "source"),
+            ("[preprocessor]\nmodes = \"compatible\"\n", "modes"),
+            ("[profiles.release]\ndefines = {}\n", "defines"),
+        ] {
+            let error = parse_error(text);
+            assert!(
+                error.contains(&format!("unknown field `{key}`")),
+                "{text:?}: {error}"
+            );
+            assert!(
+                error.starts_with("invalid configuration in /projects/MyMod/muscript.toml"),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for (text, message) in [
+            (
+                "[definitions]\n\"1ST\" = \"\"\n",
+                "\"1ST\" is not a valid macro name",
+            ),
+            (
+                "[preprocessor]\nmode = \"upp\"\n",
+                "unknown preprocessor mode",
+            ),
+            ("[format]\nbrace_style = \"k&r\"\n", "invalid brace_style"),
+        ] {
+            let error = parse_error(text);
+            assert!(error.contains(message), "{text:?}: {error}");
+        }
+
+        let config = parse("[lints]\nunused_variables = \"allow\"\n");
+        let error = format!("{:#}", config.lint_settings().unwrap_err());
+        assert!(error.contains("unknown lint `unused_variables`"), "{error}");
+    }
+
+    #[test]
+    fn paths_are_resolved_against_the_config_dir() {
+        let config = parse(
+            r#"
+            [package]
+            name = "Renamed"
+            path = "Src/MyMod"
+            sources = ["Core", "../Shared/SharedUtils", "/absolute/Engine"]
+
+            [build]
+            output = "Script/MyMod.u"
+            "#,
+        );
+        assert_eq!(config.package_dir(), path("Src/MyMod"));
+        assert_eq!(config.package_name().unwrap(), "Renamed");
+        assert_eq!(
+            config.source_dirs(),
+            [
+                path("Core"),
+                path("../Shared/SharedUtils"),
+                Utf8PathBuf::from("/absolute/Engine"),
+            ]
+        );
+        assert_eq!(config.output_path(), Some(path("Script/MyMod.u")));
+    }
+
+    #[test]
+    fn package_names_are_looked_up_in_the_game_dir() {
+        let config = parse(
+            r#"
+            [package]
+            sources = ["Core", "./Local"]
+
+            [build]
+            game = "UDK"
+            "#,
+        );
+        assert_eq!(
+            config.source_dirs(),
+            [path("UDK/Development/Src/Core"), path("./Local")]
+        );
+    }
+
+    #[test]
+    fn preprocessor_modes() {
+        let config = parse(
+            r#"
+            [preprocessor]
+            mode = "compatible"
+
+            [preprocessor.packages]
+            SharedUtils = "compatible"
+            Engine = "muscript"
+            "#,
+        );
+        assert_eq!(config.preprocessor_mode(None), Mode::Compatible);
+        assert_eq!(
+            config.preprocessor_mode(Some("sharedutils")),
+            Mode::Compatible
+        );
+        assert_eq!(config.preprocessor_mode(Some("Engine")), Mode::MuScript);
+        assert_eq!(config.preprocessor_mode(Some("Core")), Mode::MuScript);
+    }
+
+    #[test]
+    fn profiles_add_to_project_definitions() {
+        let config = parse(
+            r#"
+            [definitions]
+            MAX_PLAYERS = "16"
+
+            [profiles.release.definitions]
+            SHIPPING = ""
+
+            [profiles.demo.definitions]
+            MAX_PLAYERS = "4"
+            "#,
+        );
+        assert_eq!(config.definitions(None).unwrap(), [("MAX_PLAYERS", "16")]);
+        assert_eq!(
+            config.definitions(Some("release")).unwrap(),
+            [
+                ("MAX_PLAYERS", "16"),
+                ("FINAL_RELEASE", ""),
+                ("SHIPPING", "")
+            ]
+        );
+        assert_eq!(
+            config.definitions(Some("demo")).unwrap(),
+            [("MAX_PLAYERS", "16"), ("MAX_PLAYERS", "4")]
+        );
+        assert_eq!(
+            format!("{}", config.definitions(Some("beta")).unwrap_err()),
+            "unknown profile `beta` (available profiles: debug, demo, release)"
+        );
+    }
+
+    #[test]
+    fn command_line_settings_come_after_the_project_file() {
+        let config = parse(
+            r#"
+            [definitions]
+            MAX_PLAYERS = "16"
+            DEBUG_DRAW = ""
+
+            [lints]
+            warnings = "deny"
+            unused_variable = "allow"
+            "#,
+        );
+
+        // Later definitions replace earlier ones, and undefinitions are made last.
+        let defines: Vec<Define> = ["MAX_PLAYERS=32", "VERBOSE"]
+            .iter()
+            .map(|define| define.parse().unwrap())
+            .collect();
+        let mut definitions = config.definitions(Some("debug")).unwrap();
+        definitions.extend(
+            defines
+                .iter()
+                .map(|define| (define.name.as_str(), define.value.as_str())),
+        );
+        assert_eq!(
+            definitions_source(definitions, ["DEBUG_DRAW"]),
+            "`define DEBUG_DRAW \n\
+             `define MAX_PLAYERS 16\n\
+             `define DEBUG \n\
+             `define MAX_PLAYERS 32\n\
+             `define VERBOSE \n\
+             `undefine(DEBUG_DRAW)\n"
+        );
+
+        let mut command_line = LintSettings::new();
+        command_line.set("unused_variable".parse().unwrap(), LintLevel::Warn);
+        let mut levels = LintLevels::<()>::new();
+        levels.global = vec![config.lint_settings().unwrap(), command_line];
+        let level = |name: &str| {
+            let lint = LINTS.iter().find(|lint| lint.name == name).unwrap();
+            levels.level(lint, Span::Empty)
+        };
+        assert_eq!(level("unused_variable"), LintLevel::Warn);
+        assert_eq!(level("possibly_uninitialized"), LintLevel::Deny);
+    }
+}
@@ -0,0 +1,845 @@
+//! Generation of API reference documentation from the partitions of classes.
+//!
+//! Each class gets a page listing its constants, variables, structs, enums, functions, and states,
+//! along with their doc comments. An index page lays out the class hierarchy. Types appearing in
+//! declarations are linked to the pages documenting them.
+
+use std::collections::{HashMap, HashSet};
+
+use muscript_analysis::partition::{ItemSingleVar, TypeCst, UntypedClassPartition, VarCst};
+use muscript_foundation::{ident::CaseInsensitive, span::Spanned};
+use muscript_lexer::{sources::LexedSources, token::Token};
+use muscript_syntax::cst;
+
+/// Format of the generated pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "md",
+        }
+    }
+}
+
+/// A class to be documented.
+pub struct DocClass<'a> {
+    pub package: &'a str,
+    /// Partitions the class is made of. Partial classes have more than one.
+    pub partitions: &'a [UntypedClassPartition],
+}
+
+/// A generated page, to be written to the output directory.
+pub struct Page {
+    pub filename: String,
+    pub contents: String,
+}
+
+/// Generates the index page, followed by one page per class.
+pub fn generate(sources: &LexedSources<'_>, classes: &[DocClass<'_>], format: Format) -> Vec<Page> {
+    let mut classes: Vec<_> = classes
+        .iter()
+        .filter(|class| !class.partitions.is_empty())
+        .collect();
+    classes.sort_by_cached_key(|class| sources.source(&class.partitions[0].name).to_lowercase());
+
+    let generator = Generator::new(sources, &classes, format);
+    let mut pages = vec![Page {
+        filename: format!("index.{}", format.extension()),
+        contents: generator.index_page(),
+    }];
+    for class in 0..classes.len() {
+        pages.push(Page {
+            filename: generator.class_filename(class),
+            contents: generator.class_page(class),
+        });
+    }
+    pages
+}
+
+/// Block-level elements pages are made of.
+enum Block {
+    Heading {
+        level: u8,
+        anchor: Option<String>,
+        text: String,
+    },
+    /// A declaration, rendered as code.
+    Signature(Vec<Inline>),
+    /// Text of a doc comment.
+    Doc(String),
+    Paragraph(Vec<Inline>),
+    List(Vec<ListItem>),
+}
+
+enum Inline {
+    Text(String),
+    Code(String),
+    Link { text: String, href: String },
+}
+
+struct ListItem {
+    content: Vec<Inline>,
+    children: Vec<ListItem>,
+}
+
+/// Appends code to the inlines, merging it into the preceding code if there is one.
+fn push_code(inlines: &mut Vec<Inline>, code: &str) {
+    if let Some(Inline::Code(previous)) = inlines.last_mut() {
+        previous.push_str(code);
+    } else {
+        inlines.push(Inline::Code(code.to_owned()));
+    }
+}
+
+struct Generator<'a> {
+    sources: &'a LexedSources<'a>,
+    classes: &'a [&'a DocClass<'a>],
+    format: Format,
+    class_indices: HashMap<CaseInsensitive<String>, usize>,
+    /// Indices of the classes declaring structs and enums with a given name.
+    types: HashMap<CaseInsensitive<String>, Vec<usize>>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(sources: &'a LexedSources<'a>, classes: &'a [&'a DocClass<'a>], format: Format) -> Self {
+        let mut class_indices = HashMap::new();
+        let mut types: HashMap<_, Vec<_>> = HashMap::new();
+        for (i, class) in classes.iter().enumerate() {
+            class_indices.insert(
+                CaseInsensitive::new(sources.source(&class.partitions[0].name).to_owned()),
+                i,
+            );
+            for partition in class.partitions {
+                for name in partition.types.keys() {
+                    types.entry(name.clone()).or_default().push(i);
+                }
+            }
+        }
+        Self {
+            sources,
+            classes,
+            format,
+            class_indices,
+            types,
+        }
+    }
+
+    fn class_name(&self, class: usize) -> &'a str {
+        self.sources.source(&self.classes[class].partitions[0].name)
+    }
+
+    fn class_filename(&self, class: usize) -> String {
+        format!("{}.{}", self.class_name(class), self.format.extension())
+    }
+
+    fn parent_name(&self, class: usize) -> Option<&'a str> {
+        self.classes[class]
+            .partitions
+            .iter()
+            .find_map(|partition| partition.extends)
+            .map(|extends| self.sources.source(&extends))
+    }
+
+    fn find_class(&self, name: &str) -> Option<usize> {
+        self.class_indices
+            .get(CaseInsensitive::new_ref(name))
+            .copied()
+    }
+
+    /// Returns the class, followed by its documented ancestors.
+    fn class_chain(&self, class: usize) -> Vec<usize> {
+        let mut chain = vec![class];
+        let mut visited = HashSet::from([class]);
+        let mut current = class;
+        while let Some(parent) = self
+            .parent_name(current)
+            .and_then(|name| self.find_class(name))
+        {
+            if !visited.insert(parent) {
+                break;
+            }
+            chain.push(parent);
+            current = parent;
+        }
+        chain
+    }
+
+    fn subclasses(&self, class: usize) -> Vec<usize> {
+        (0..self.classes.len())
+            .filter(|&other| {
+                other != class
+                    && self
+                        .parent_name(other)
+                        .and_then(|name| self.find_class(name))
+                        == Some(class)
+            })
+            .collect()
+    }
+
+    fn class_link(&self, class: usize) -> Inline {
+        Inline::Link {
+            text: self.class_name(class).to_owned(),
+            href: self.class_filename(class),
+        }
+    }
+
+    /// Resolves a type name written in the given class to the page (and anchor) documenting it.
+    fn resolve(&self, class: usize, path: &cst::Path) -> Option<String> {
+        let type_anchor = |owner: usize, name: &str| {
+            let declares = self.classes[owner]
+                .partitions
+                .iter()
+                .any(|partition| partition.types.contains_key(CaseInsensitive::new_ref(name)));
+            declares.then(|| format!("{}#{}", self.class_filename(owner), anchor(name)))
+        };
+        match &path.components[..] {
+            [name] => {
+                let name = self.sources.source(name);
+                self.class_chain(class)
+                    .into_iter()
+                    .find_map(|owner| type_anchor(owner, name))
+                    .or_else(|| {
+                        self.find_class(name)
+                            .map(|class| self.class_filename(class))
+                    })
+                    .or_else(|| {
+                        let owner = *self.types.get(CaseInsensitive::new_ref(name))?.first()?;
+                        type_anchor(owner, name)
+                    })
+            }
+            [owner, name] => {
+                let owner = self.find_class(self.sources.source(owner))?;
+                type_anchor(owner, self.sources.source(name))
+            }
+            _ => None,
+        }
+    }
+
+    fn push_source(&self, inlines: &mut Vec<Inline>, node: &impl Spanned<Token>) {
+        push_code(inlines, self.sources.source(node));
+    }
+
+    fn push_type(&self, inlines: &mut Vec<Inline>, class: usize, ty: &cst::Type) {
+        for specifier in &ty.specifiers {
+            self.push_source(inlines, specifier);
+            push_code(inlines, " ");
+        }
+        let path = ty
+            .path
+            .components
+            .iter()
+            .map(|component| self.sources.source(component))
+            .collect::<Vec<_>>()
+            .join(".");
+        match self.resolve(class, &ty.path) {
+            Some(href) => inlines.push(Inline::Link { text: path, href }),
+            None => push_code(inlines, &path),
+        }
+        if let Some(generic) = &ty.generic {
+            push_code(inlines, "<");
+            for (i, arg) in generic.args.iter().enumerate() {
+                if i > 0 {
+                    push_code(inlines, ", ");
+                }
+                self.push_type(inlines, class, arg);
+            }
+            push_code(inlines, ">");
+        }
+    }
+
+    fn push_doc(&self, blocks: &mut Vec<Block>, doc: &cst::DocComment) {
+        if let Some(text) = doc.text(self.sources) {
+            blocks.push(Block::Doc(text));
+        }
+    }
+
+    fn index_page(&self) -> String {
+        let mut blocks = vec![Block::Heading {
+            level: 1,
+            anchor: None,
+            text: "Class hierarchy".to_owned(),
+        }];
+        let mut visited = HashSet::new();
+        let roots = (0..self.classes.len())
+            .filter(|&class| {
+                self.parent_name(class)
+                    .and_then(|name| self.find_class(name))
+                    .is_none()
+            })
+            .collect::<Vec<_>>();
+        let mut items = self.hierarchy(&roots, &mut visited);
+        // Classes extending each other in a cycle do not descend from any root.
+        let rest = (0..self.classes.len())
+            .filter(|class| !visited.contains(class))
+            .collect::<Vec<_>>();
+        items.extend(self.hierarchy(&rest, &mut visited));
+        blocks.push(Block::List(items));
+        self.render("Class hierarchy", None, &blocks)
+    }
+
+    fn hierarchy(&self, classes: &[usize], visited: &mut HashSet<usize>) -> Vec<ListItem> {
+        let mut items = vec![];
+        for &class in classes {
+            if !visited.insert(class) {
+                continue;
+            }
+            let mut content = vec![self.class_link(class)];
+            content.push(Inline::Text(format!(" ({})", self.classes[class].package)));
+            items.push(ListItem {
+                content,
+                children: self.hierarchy(&self.subclasses(class), visited),
+            });
+        }
+        items
+    }
+
+    fn class_page(&self, class: usize) -> String {
+        let name = self.class_name(class);
+        let partitions = self.classes[class].partitions;
+        let first = &partitions[0];
+        let mut blocks = vec![Block::Heading {
+            level: 1,
+            anchor: None,
+            text: name.to_owned(),
+        }];
+
+        let mut declaration = vec![];
+        self.push_source(&mut declaration, &first.kind);
+        push_code(&mut declaration, " ");
+        push_code(&mut declaration, name);
+        if let Some(parent) = self.parent_name(class) {
+            push_code(&mut declaration, " extends ");
+            match self.find_class(parent) {
+                Some(parent) => declaration.push(self.class_link(parent)),
+                None => push_code(&mut declaration, parent),
+            }
+        }
+        if let Some(within) = partitions.iter().find_map(|partition| partition.within) {
+            push_code(&mut declaration, " within ");
+            let within = self.sources.source(&within);
+            match self.find_class(within) {
+                Some(outer) => declaration.push(self.class_link(outer)),
+                None => push_code(&mut declaration, within),
+            }
+        }
+        blocks.push(Block::Signature(declaration));
+        blocks.push(Block::Paragraph(vec![
+            Inline::Text("Package: ".to_owned()),
+            Inline::Code(self.classes[class].package.to_owned()),
+        ]));
+
+        let chain = self.class_chain(class);
+        if chain.len() > 1 {
+            let mut inheritance = vec![Inline::Text("Inheritance: ".to_owned())];
+            for (i, &ancestor) in chain.iter().rev().enumerate() {
+                if i > 0 {
+                    inheritance.push(Inline::Text(" > ".to_owned()));
+                }
+                inheritance.push(self.class_link(ancestor));
+            }
+            blocks.push(Block::Paragraph(inheritance));
+        }
+        let subclasses = self.subclasses(class);
+        if !subclasses.is_empty() {
+            let mut inlines = vec![Inline::Text("Subclasses: ".to_owned())];
+            for (i, &subclass) in subclasses.iter().enumerate() {
+                if i > 0 {
+                    inlines.push(Inline::Text(", ".to_owned()));
+                }
+                inlines.push(self.class_link(subclass));
+            }
+            blocks.push(Block::Paragraph(inlines));
+        }
+        if let Some(partition) = partitions
+            .iter()
+            .find(|partition| !partition.doc.is_empty())
+        {
+            self.push_doc(&mut blocks, &partition.doc);
+        }
+
+        let consts = partitions.iter().flat_map(|partition| {
+            partition.vars.values().filter_map(|var| match var {
+                VarCst::Const(item_const) => Some(item_const),
+                VarCst::Var(_) => None,
+            })
+        });
+        self.section(&mut blocks, "Constants", consts, |blocks, item_const| {
+            let name = self.sources.source(&item_const.name);
+            let mut signature = vec![];
+            push_code(&mut signature, &format!("const {name} = "));
+            self.push_source(&mut signature, &item_const.value);
+            self.member(blocks, 3, name, false, signature, &item_const.doc);
+        });
+
+        let vars = partitions.iter().flat_map(|partition| {
+            partition.vars.values().filter_map(|var| match var {
+                VarCst::Var(item_var) => Some(item_var),
+                VarCst::Const(_) => None,
+            })
+        });
+        self.section(&mut blocks, "Variables", vars, |blocks, item_var| {
+            self.var(blocks, class, 3, item_var);
+        });
+
+        let types = || {
+            partitions
+                .iter()
+                .flat_map(|partition| partition.types.values())
+        };
+        let structs = types().filter_map(|ty| match ty {
+            TypeCst::Struct(untyped_struct) => Some(untyped_struct),
+            TypeCst::Enum(_) => None,
+        });
+        self.section(&mut blocks, "Structs", structs, |blocks, untyped_struct| {
+            let name = self.sources.source(&untyped_struct.name);
+            let mut signature = vec![];
+            push_code(&mut signature, &format!("struct {name}"));
+            if let Some(extends) = &untyped_struct.extends {
+                push_code(&mut signature, " extends ");
+                let text = self.sources.source(extends);
+                match self.resolve(class, extends) {
+                    Some(href) => signature.push(Inline::Link {
+                        text: text.to_owned(),
+                        href,
+                    }),
+                    None => push_code(&mut signature, text),
+                }
+            }
+            self.member(blocks, 3, name, true, signature, &untyped_struct.doc);
+            for field in untyped_struct.vars.values() {
+                self.var(blocks, class, 4, field);
+            }
+        });
+
+        let enums = types().filter_map(|ty| match ty {
+            TypeCst::Enum(item_enum) => Some(item_enum),
+            TypeCst::Struct(_) => None,
+        });
+        self.section(&mut blocks, "Enums", enums, |blocks, item_enum| {
+            let name = self.sources.source(&item_enum.def.name);
+            let signature = vec![Inline::Code(format!("enum {name}"))];
+            self.member(blocks, 3, name, true, signature, &item_enum.doc);
+            blocks.push(Block::List(
+                item_enum
+                    .def
+                    .variants
+                    .iter()
+                    .map(|variant| ListItem {
+                        content: vec![Inline::Code(self.sources.source(&variant.name).to_owned())],
+                        children: vec![],
+                    })
+                    .collect(),
+            ));
+        });
+
+        let functions = partitions
+            .iter()
+            .flat_map(|partition| partition.functions.values());
+        self.section(&mut blocks, "Functions", functions, |blocks, function| {
+            self.function(blocks, class, 3, function);
+        });
+
+        let states = partitions
+            .iter()
+            .flat_map(|partition| partition.states.values());
+        self.section(&mut blocks, "States", states, |blocks, state| {
+            self.state(blocks, class, state);
+        });
+
+        self.render(
+            name,
+            Some(&format!("index.{}", self.format.extension())),
+            &blocks,
+        )
+    }
+
+    /// Adds a section with the given title, unless there is nothing to put in it.
+    fn section<T>(
+        &self,
+        blocks: &mut Vec<Block>,
+        title: &str,
+        items: impl Iterator<Item = T>,
+        mut document: impl FnMut(&mut Vec<Block>, T),
+    ) {
+        let mut items = items.peekable();
+        if items.peek().is_none() {
+            return;
+        }
+        blocks.push(Block::Heading {
+            level: 2,
+            anchor: None,
+            text: title.to_owned(),
+        });
+        for item in items {
+            document(blocks, item);
+        }
+    }
+
+    /// Adds a heading, signature, and doc comment of a member of a class.
+    fn member(
+        &self,
+        blocks: &mut Vec<Block>,
+        level: u8,
+        name: &str,
+        is_type: bool,
+        signature: Vec<Inline>,
+        doc: &cst::DocComment,
+    ) {
+        blocks.push(Block::Heading {
+            level,
+            // Only types are linked to, so only they need stable anchors.
+            anchor: is_type.then(|| anchor(name)),
+            text: name.to_owned(),
+        });
+        blocks.push(Block::Signature(signature));
+        self.push_doc(blocks, doc);
+    }
+
+    fn var(&self, blocks: &mut Vec<Block>, class: usize, level: u8, item_var: &ItemSingleVar) {
+        let name = self.sources.source(&item_var.variable.name);
+        let mut signature = vec![];
+        push_code(&mut signature, "var");
+        if let Some(editor) = &item_var.editor {
+            self.push_source(&mut signature, editor);
+        }
+        for specifier in &item_var.specifiers {
+            push_code(&mut signature, " ");
+            self.push_source(&mut signature, specifier);
+        }
+        push_code(&mut signature, " ");
+        self.push_type(&mut signature, class, &item_var.ty);
+        push_code(&mut signature, " ");
+        push_code(&mut signature, name);
+        if let Some(array) = &item_var.variable.array {
+            self.push_source(&mut signature, array);
+        }
+        self.member(blocks, level, name, false, signature, &item_var.doc);
+    }
+
+    fn function(
+        &self,
+        blocks: &mut Vec<Block>,
+        class: usize,
+        level: u8,
+        function: &cst::ItemFunction,
+    ) {
+        let name = self.sources.source(&function.name);
+        let mut signature = vec![];
+        for specifier in &function.pre_specifiers {
+            self.push_source(&mut signature, specifier);
+            push_code(&mut signature, " ");
+        }
+        self.push_source(&mut signature, &function.kind);
+        for specifier in &function.post_specifiers {
+            push_code(&mut signature, " ");
+            self.push_source(&mut signature, specifier);
+        }
+        push_code(&mut signature, " ");
+        if let Some(return_ty) = &function.return_ty {
+            self.push_type(&mut signature, class, return_ty);
+            push_code(&mut signature, " ");
+        }
+        push_code(&mut signature, name);
+        push_code(&mut signature, "(");
+        for (i, param) in function.params.params.iter().enumerate() {
+            if i > 0 {
+                push_code(&mut signature, ", ");
+            }
+            for specifier in &param.specifiers {
+                self.push_source(&mut signature, specifier);
+                push_code(&mut signature, " ");
+            }
+            self.push_type(&mut signature, class, &param.ty);
+            push_code(&mut signature, " ");
+            self.push_source(&mut signature, &param.name);
+            if let Some(array) = &param.array {
+                self.push_source(&mut signature, array);
+            }
+            if let Some(default) = &param.default {
+                push_code(&mut signature, " = ");
+                self.push_source(&mut signature, &default.value);
+            }
+        }
+        push_code(&mut signature, ")");
+        if function.kconst.is_some() {
+            push_code(&mut signature, " const");
+        }
+        self.member(blocks, level, name, false, signature, &function.doc);
+    }
+
+    fn state(&self, blocks: &mut Vec<Block>, class: usize, state: &cst::ItemState) {
+        let name = self.sources.source(&state.name);
+        let mut signature = vec![];
+        if state.simulated.is_some() {
+            push_code(&mut signature, "simulated ");
+        }
+        if state.auto.is_some() {
+            push_code(&mut signature, "auto ");
+        }
+        push_code(&mut signature, "state");
+        if let Some(editor) = &state.editor {
+            self.push_source(&mut signature, editor);
+        }
+        push_code(&mut signature, " ");
+        push_code(&mut signature, name);
+        if let Some(extends) = &state.extends {
+            push_code(&mut signature, " ");
+            self.push_source(&mut signature, extends);
+        }
+        self.member(blocks, 3, name, false, signature, &state.doc);
+        if let Some(ignores) = &state.ignores {
+            let mut inlines = vec![Inline::Text("Ignores: ".to_owned())];
+            for (i, event) in ignores.events.iter().enumerate() {
+                if i > 0 {
+                    inlines.push(Inline::Text(", ".to_owned()));
+                }
+                inlines.push(Inline::Code(self.sources.source(event).to_owned()));
+            }
+            blocks.push(Block::Paragraph(inlines));
+        }
+        for item in &state.items {
+            match item {
+                cst::Item::Function(function)
+                | cst::Item::Simulated(cst::ItemSimulated {
+                    item: cst::SimulatedItem::Function(function),
+                    ..
+                }) => self.function(blocks, class, 4, function),
+                _ => (),
+            }
+        }
+    }
+
+    fn render(&self, title: &str, up: Option<&str>, blocks: &[Block]) -> String {
+        match self.format {
+            Format::Html => render_html(title, up, blocks),
+            Format::Markdown => render_markdown(up, blocks),
+        }
+    }
+}
+
+fn anchor(name: &str) -> String {
+    name.to_lowercase()
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; }
+pre { background: #f4f4f4; padding: 0.5em; overflow-x: auto; }
+.doc { white-space: pre-wrap; }
+";
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_html(title: &str, up: Option<&str>, blocks: &[Block]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n",
+        escape_html(title)
+    );
+    if let Some(up) = up {
+        html.push_str(&format!(
+            "<nav><a href=\"{}\">Index</a></nav>\n",
+            escape_html(up)
+        ));
+    }
+    for block in blocks {
+        match block {
+            Block::Heading {
+                level,
+                anchor,
+                text,
+            } => match anchor {
+                Some(anchor) => html.push_str(&format!(
+                    "<h{level} id=\"{}\">{}</h{level}>\n",
+                    escape_html(anchor),
+                    escape_html(text)
+                )),
+                None => html.push_str(&format!("<h{level}>{}</h{level}>\n", escape_html(text))),
+            },
+            Block::Signature(inlines) => {
+                html.push_str("<pre><code>");
+                push_html_inlines(&mut html, inlines, false);
+                html.push_str("</code></pre>\n");
+            }
+            Block::Doc(text) => {
+                for paragraph in text.split("\n\n") {
+                    html.push_str(&format!(
+                        "<p class=\"doc\">{}</p>\n",
+                        escape_html(paragraph.trim_matches('\n'))
+                    ));
+                }
+            }
+            Block::Paragraph(inlines) => {
+                html.push_str("<p>");
+                push_html_inlines(&mut html, inlines, true);
+                html.push_str("</p>\n");
+            }
+            Block::List(items) => push_html_list(&mut html, items),
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Inlines within signatures are already inside of `<code>`, so `code_tags` is false for them.
+fn push_html_inlines(html: &mut String, inlines: &[Inline], code_tags: bool) {
+    let code = |text: &str| {
+        if code_tags {
+            format!("<code>{}</code>", escape_html(text))
+        } else {
+            escape_html(text)
+        }
+    };
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => html.push_str(&escape_html(text)),
+            Inline::Code(text) => html.push_str(&code(text)),
+            Inline::Link { text, href } => html.push_str(&format!(
+                "<a href=\"{}\">{}</a>",
+                escape_html(href),
+                code(text)
+            )),
+        }
+    }
+}
+
+fn push_html_list(html: &mut String, items: &[ListItem]) {
+    html.push_str("<ul>\n");
+    for item in items {
+        html.push_str("<li>");
+        push_html_inlines(html, &item.content, true);
+        if !item.children.is_empty() {
+            html.push('\n');
+            push_html_list(html, &item.children);
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+}
+
+/// Wraps the text in a Markdown code span, using enough backticks to fit any backticks within.
+fn markdown_code(text: &str) -> String {
+    let mut longest_run = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest_run = longest_run.max(run);
+    }
+    let fence = "`".repeat(longest_run + 1);
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{fence} {text} {fence}")
+    } else {
+        format!("{fence}{text}{fence}")
+    }
+}
+
+/// Escapes `<` and `&` in doc comment text, so that Markdown renderers do not take them for HTML.
+/// Code spans are left alone, since they're displayed as is.
+fn escape_markdown_doc(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '`' => {
+                let fence_len = rest.len() - rest.trim_start_matches('`').len();
+                let (fence, after_fence) = rest.split_at(fence_len);
+                // A code span ends at the next run of backticks of the same length. Without one,
+                // the backticks are literal.
+                let mut search = 0;
+                let end = loop {
+                    let Some(start) = after_fence[search..].find(fence).map(|i| search + i) else {
+                        break None;
+                    };
+                    let run_len = after_fence[start..].len()
+                        - after_fence[start..].trim_start_matches('`').len();
+                    if run_len == fence_len {
+                        break Some(start + fence_len);
+                    }
+                    search = start + run_len;
+                };
+                let span_len = fence_len + end.unwrap_or(0);
+                escaped.push_str(&rest[..span_len]);
+                rest = &rest[span_len..];
+            }
+            '<' => {
+                escaped.push_str("&lt;");
+                rest = &rest[1..];
+            }
+            '&' => {
+                escaped.push_str("&amp;");
+                rest = &rest[1..];
+            }
+            _ => {
+                escaped.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    escaped
+}
+
+fn render_markdown(up: Option<&str>, blocks: &[Block]) -> String {
+    let mut markdown = String::new();
+    if let Some(up) = up {
+        markdown.push_str(&format!("[Index]({up})\n\n"));
+    }
+    for block in blocks {
+        match block {
+            Block::Heading { level, text, .. } => {
+                markdown.push_str(&format!("{} {text}\n\n", "#".repeat(usize::from(*level))));
+            }
+            Block::Signature(inlines) | Block::Paragraph(inlines) => {
+                push_markdown_inlines(&mut markdown, inlines);
+                markdown.push_str("\n\n");
+            }
+            Block::Doc(text) => {
+                markdown.push_str(&escape_markdown_doc(text));
+                markdown.push_str("\n\n");
+            }
+            Block::List(items) => {
+                push_markdown_list(&mut markdown, items, 0);
+                markdown.push('\n');
+            }
+        }
+    }
+    markdown
+}
+
+fn push_markdown_inlines(markdown: &mut String, inlines: &[Inline]) {
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => markdown.push_str(text),
+            Inline::Code(text) => markdown.push_str(&markdown_code(text)),
+            Inline::Link { text, href } => {
+                markdown.push_str(&format!("[{}]({href})", markdown_code(text)))
+            }
+        }
+    }
+}
+
+fn push_markdown_list(markdown: &mut String, items: &[ListItem], depth: usize) {
+    for item in items {
+        markdown.push_str(&"  ".repeat(depth));
+        markdown.push_str("- ");
+        push_markdown_inlines(markdown, &item.content);
+        markdown.push('\n');
+        push_markdown_list(markdown, &item.children, depth + 1);
+    }
+}
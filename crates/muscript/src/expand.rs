@@ -0,0 +1,165 @@
+//! Rendering of preprocessed tokens back into source code, to make it possible to see what the
+//! parser receives.
+
+use muscript_lexer::{
+    sliced_tokens::{Expansion, SlicedTokens},
+    sources::LexedSources,
+    token::{TokenId, TokenKind, TokenSpan},
+    token_stream::{Channel, TokenStream},
+};
+
+/// Renders preprocessed tokens as source code.
+///
+/// Tokens which were next to each other in their source file keep the whitespace that was between
+/// them, and the first token on each line keeps its indentation. Tokens spliced together by the
+/// preprocessor are separated by a single space.
+///
+/// With `annotate_expansions`, tokens that came from macro expansions are surrounded with comments
+/// naming the macro.
+pub fn preprocessed_source(
+    sources: LexedSources<'_>,
+    tokens: &SlicedTokens,
+    annotate_expansions: bool,
+) -> String {
+    let mut output = String::new();
+    let Some(mut stream) = tokens.stream(sources.token_arena) else {
+        return output;
+    };
+
+    let mut previous = None;
+    // Expansions surrounding the previous token, outermost first.
+    let mut open_expansions: Vec<u32> = vec![];
+    loop {
+        let token = stream.next();
+        if token.kind == TokenKind::EndOfFile {
+            break;
+        }
+        // Failed expansions (and `isdefined`s of undefined macros) do not produce any code.
+        if token.kind.channel() == Channel::MACRO {
+            continue;
+        }
+
+        let expansions = expansion_chain(tokens.expansions(), stream.previous_expansion());
+        let mut opened = &[][..];
+        if annotate_expansions {
+            let common = open_expansions
+                .iter()
+                .zip(&expansions)
+                .take_while(|(open, new)| open == new)
+                .count();
+            for &index in open_expansions[common..].iter().rev() {
+                push_end_of_expansion(&mut output, &tokens.expansions()[index as usize]);
+            }
+            open_expansions.truncate(common);
+            opened = &expansions[common..];
+        }
+
+        if token.kind != TokenKind::NewLine {
+            // Tokens coming from a macro are indented like the macro's invocation.
+            let line_start = expansions
+                .first()
+                .and_then(|&outermost| tokens.expansions()[outermost as usize].invocation.start())
+                .unwrap_or(token.id);
+            push_separator(&mut output, sources, previous, token.id, line_start);
+        }
+        for &index in opened {
+            let name = &tokens.expansions()[index as usize].macro_name;
+            output.push_str(&format!("/* `{name} */ "));
+            open_expansions.push(index);
+        }
+        output.push_str(sources.source(&token));
+        previous = Some(token.id);
+    }
+
+    for &index in open_expansions.iter().rev() {
+        push_end_of_expansion(&mut output, &tokens.expansions()[index as usize]);
+    }
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
+
+fn push_end_of_expansion(output: &mut String, expansion: &Expansion) {
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push(' ');
+    }
+    output.push_str(&format!("/* end of `{} */", expansion.macro_name));
+}
+
+/// Returns the indices of the expansion and the expansions it's nested in, outermost first.
+fn expansion_chain(expansions: &[Expansion], innermost: Option<u32>) -> Vec<u32> {
+    let mut chain = vec![];
+    let mut expansion = innermost;
+    while let Some(index) = expansion {
+        chain.push(index);
+        expansion = expansions
+            .get(index as usize)
+            .and_then(|expansion| expansion.parent);
+    }
+    chain.reverse();
+    chain
+}
+
+/// Pushes the whitespace that should go in between the previous token and the next one, which is
+/// the whitespace found before the token in its source file. `line_start` is the token whose
+/// indentation should be used if the token begins a new line.
+fn push_separator(
+    output: &mut String,
+    sources: LexedSources<'_>,
+    previous: Option<TokenId>,
+    token: TokenId,
+    line_start: TokenId,
+) {
+    if output.is_empty() || output.ends_with('\n') {
+        output.push_str(&indentation(sources, line_start));
+        return;
+    }
+
+    let whitespace = whitespace_before(sources, token);
+    let is_synthesized = |id| sources.synthesized_tokens.text(id).is_some();
+    let follows_previous = previous.is_some_and(|previous| {
+        previous.successor() == token && !is_synthesized(previous) && !is_synthesized(token)
+    });
+    if whitespace.is_empty() && !follows_previous {
+        // The tokens were not next to each other, so they must be kept apart if gluing them
+        // together would turn them into a single token.
+        let glues = |c: char| c.is_alphanumeric() || c == '_';
+        let text = sources.source(&TokenSpan::single(token));
+        if output.ends_with(glues) && text.starts_with(glues) {
+            output.push(' ');
+        }
+    } else {
+        // Line breaks are tokens of their own, so only the part after the last one is wanted.
+        output.push_str(&whitespace[whitespace.rfind('\n').map_or(0, |i| i + 1)..]);
+    }
+}
+
+/// Returns the whitespace in between the token and the token before it in its source file.
+fn whitespace_before(sources: LexedSources<'_>, token: TokenId) -> &str {
+    let file = sources.token_arena.source_file_id(token);
+    let source = sources.source_file_set.source(file);
+    let start = sources.token_arena.element(token).source_range.start;
+    let end_of_previous = token
+        .predecessor()
+        .filter(|&previous| sources.token_arena.source_file_id(previous) == file)
+        .map_or(0, |previous| {
+            sources.token_arena.element(previous).source_range.end
+        });
+    source
+        .get(end_of_previous..start)
+        .filter(|between| between.chars().all(char::is_whitespace))
+        .unwrap_or(" ")
+}
+
+/// Returns the whitespace the line containing the token begins with.
+fn indentation(sources: LexedSources<'_>, token: TokenId) -> String {
+    let file = sources.token_arena.source_file_id(token);
+    let source = sources.source_file_set.source(file);
+    let start = sources.token_arena.element(token).source_range.start;
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    source[line_start..start]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect()
+}
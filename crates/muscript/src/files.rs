@@ -0,0 +1,152 @@
+//! Discovery and loading of package source files.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use camino::{Utf8Path, Utf8PathBuf};
+use rayon::{prelude::*, ThreadPool};
+use tracing::warn;
+use walkdir::WalkDir;
+
+pub fn get_package_name(package: &Utf8Path) -> anyhow::Result<String> {
+    package
+        .file_name()
+        .ok_or_else(|| anyhow!("path {package:?} has no package name"))
+        .map(|package_name| package_name.to_owned())
+}
+
+#[derive(Debug, Default)]
+pub struct SourceFileListing {
+    pub source: Vec<Utf8PathBuf>,
+    pub include: Vec<Utf8PathBuf>,
+}
+
+pub fn list_source_files_in_package(package: &Utf8Path) -> anyhow::Result<SourceFileListing> {
+    let classes_dir = package.join("Classes");
+    if !classes_dir.is_dir() {
+        bail!("{classes_dir:?} is not a directory");
+    }
+
+    let mut listing = SourceFileListing::default();
+    for entry in WalkDir::new(classes_dir) {
+        let entry = entry?;
+        let path = entry.path();
+        if let Some(path) = Utf8Path::from_path(path) {
+            if path.is_file() {
+                match path.extension() {
+                    Some("uc") => listing.source.push(path.to_owned()),
+                    Some("uci") => listing.include.push(path.to_owned()),
+                    _ => (),
+                }
+            }
+        } else {
+            warn!("path contains invalid UTF-8: {path:?}");
+        }
+    }
+
+    // Special case for Globals.uci, which lives outside the Classes folder.
+    let globals_uci = package.join("Globals.uci");
+    if globals_uci.is_file() {
+        listing.include.push(globals_uci);
+    }
+    Ok(listing)
+}
+
+/// Text encoding of a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceEncoding {
+    Utf8,
+    Utf16BigEndian,
+    Utf16LittleEndian,
+}
+
+impl SourceEncoding {
+    /// Detects the encoding of a source file from its byte order mark. Files without one are
+    /// assumed to be UTF-8.
+    pub fn detect(source_bytes: &[u8]) -> Self {
+        if source_bytes.starts_with(&[0xFE, 0xFF]) {
+            Self::Utf16BigEndian
+        } else if source_bytes.starts_with(&[0xFF, 0xFE]) {
+            Self::Utf16LittleEndian
+        } else {
+            Self::Utf8
+        }
+    }
+
+    /// Encodes source code back into bytes, such that [`decode_source`] produces the same string.
+    pub fn encode(self, source: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => source.as_bytes().to_vec(),
+            Self::Utf16BigEndian => [0xFE, 0xFF]
+                .into_iter()
+                .chain(source.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect(),
+            Self::Utf16LittleEndian => [0xFF, 0xFE]
+                .into_iter()
+                .chain(source.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+        }
+    }
+}
+
+pub fn decode_source(source_bytes: Vec<u8>) -> anyhow::Result<(String, SourceEncoding)> {
+    let encoding = SourceEncoding::detect(&source_bytes);
+    let source = match encoding {
+        SourceEncoding::Utf16BigEndian => {
+            let words: Vec<_> = source_bytes
+                .chunks_exact(2)
+                .map(|arr| (arr[0] as u16) << 8 | arr[1] as u16)
+                .collect();
+            String::from_utf16(&words[1..]).context("encoding error in UTF-16 (big-endian) file")?
+        }
+        SourceEncoding::Utf16LittleEndian => {
+            let words: Vec<_> = source_bytes
+                .chunks_exact(2)
+                .map(|arr| (arr[0]) as u16 | (arr[1] as u16) << 8)
+                .collect();
+            String::from_utf16(&words[1..])
+                .context("encoding error in UTF-16 (little-endian) file")?
+        }
+        SourceEncoding::Utf8 => {
+            String::from_utf8(source_bytes).context("encoding error in UTF-8 file")?
+        }
+    };
+    Ok((source, encoding))
+}
+
+pub fn read_source_file(path: &Utf8Path) -> anyhow::Result<String> {
+    read_source_file_with_encoding(path).map(|(source, _)| source)
+}
+
+/// Reads source files on the thread pool. The sources are returned in the same order as the paths.
+pub fn read_source_files(
+    thread_pool: &ThreadPool,
+    paths: &[Utf8PathBuf],
+) -> anyhow::Result<Vec<String>> {
+    thread_pool.install(|| {
+        paths
+            .par_iter()
+            .map(|path| read_source_file(path))
+            .collect()
+    })
+}
+
+pub fn read_source_file_with_encoding(path: &Utf8Path) -> anyhow::Result<(String, SourceEncoding)> {
+    let source_bytes =
+        std::fs::read(path).with_context(|| format!("cannot read source file at {path:?}"))?;
+    decode_source(source_bytes)
+}
+
+pub fn pretty_file_name(package_root: &Utf8Path, source_file: &Utf8Path) -> String {
+    let package_root = package_root.parent().unwrap_or(package_root);
+    source_file
+        .strip_prefix(package_root)
+        .expect("source_file must start with package_root")
+        .to_string()
+}
+
+/// Canonicalizes the path, such that different paths to the same file compare equal. Paths that
+/// cannot be canonicalized (eg. because the file does not exist anymore) are returned as-is.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
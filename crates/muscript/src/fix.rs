@@ -0,0 +1,249 @@
+//! Automatic application of suggestions attached to diagnostics.
+
+use std::{collections::HashMap, ops::Range};
+
+use muscript_foundation::{
+    errors::{Applicability, Diagnostic},
+    source::SourceFileId,
+};
+use similar::TextDiff;
+
+/// A single replacement of a range of bytes in a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// Edits to be applied to source files, collected from diagnostics.
+#[derive(Debug, Default)]
+pub struct Fixes {
+    /// Non-overlapping edits for each file, sorted by their position in the file.
+    pub edits: HashMap<SourceFileId, Vec<Edit>>,
+    /// Edits that were left out because they overlap with another edit in the same file.
+    /// These may become applicable once the other edits are applied and the code is recompiled.
+    pub overlapping: Vec<(SourceFileId, Edit)>,
+}
+
+impl Fixes {
+    /// Collects all machine-applicable suggestions from the diagnostics (including their
+    /// children) which apply to files accepted by `include_file`.
+    pub fn collect<'a, T: 'a>(
+        diagnostics: impl IntoIterator<Item = &'a Diagnostic<T>>,
+        mut include_file: impl FnMut(SourceFileId) -> bool,
+    ) -> Self {
+        let mut candidates: HashMap<SourceFileId, Vec<Edit>> = HashMap::new();
+        // Diagnostics are visited in the order they were reported, parents before their children,
+        // such that the first of two conflicting suggestions is the one that gets applied.
+        let mut stack: Vec<_> = diagnostics.into_iter().collect();
+        stack.reverse();
+        while let Some(diagnostic) = stack.pop() {
+            for suggestion in diagnostic
+                .notes
+                .iter()
+                .filter_map(|note| note.suggestion.as_ref())
+            {
+                if suggestion.applicability == Applicability::MachineApplicable
+                    && include_file(suggestion.file)
+                {
+                    candidates.entry(suggestion.file).or_default().push(Edit {
+                        range: suggestion.span.clone(),
+                        replacement: suggestion.replacement.clone(),
+                    });
+                }
+            }
+            stack.extend(diagnostic.children.iter().rev());
+        }
+
+        let mut fixes = Self::default();
+        for (file, mut candidates) in candidates {
+            candidates.sort_by_key(|edit| (edit.range.start, edit.range.end));
+            // The same diagnostic can be reported more than once, eg. when a class is analyzed
+            // from multiple places.
+            candidates.dedup();
+
+            let mut edits: Vec<Edit> = vec![];
+            for edit in candidates {
+                let overlaps = edits.last().is_some_and(|last| {
+                    edit.range.start < last.range.end
+                        // Two insertions at the same position would have to be ordered somehow,
+                        // and there's no telling which order is correct.
+                        || (edit.range.start == last.range.start
+                            && edit.range.is_empty()
+                            && last.range.is_empty())
+                });
+                if overlaps {
+                    fixes.overlapping.push((file, edit));
+                } else {
+                    edits.push(edit);
+                }
+            }
+            fixes.edits.insert(file, edits);
+        }
+        fixes
+    }
+}
+
+/// Applies sorted, non-overlapping edits to the source code.
+pub fn apply_edits(source: &str, edits: &[Edit]) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut position = 0;
+    for edit in edits {
+        result.push_str(&source[position..edit.range.start]);
+        result.push_str(&edit.replacement);
+        position = edit.range.end;
+    }
+    result.push_str(&source[position..]);
+    result
+}
+
+/// Renders a unified diff between the old and new contents of the file named `filename`.
+pub fn unified_diff(filename: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("a/{filename}"), &format!("b/{filename}"))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Range, path::PathBuf, sync::Arc};
+
+    use muscript_foundation::{
+        errors::{Applicability, Diagnostic, ReplacementSuggestion},
+        source::{SourceFile, SourceFileId, SourceFileSet},
+    };
+
+    use crate::files::{decode_source, SourceEncoding};
+
+    use super::{apply_edits, Edit, Fixes};
+
+    /// Returns ids of two distinct files. Files are only used as keys, so their contents don't
+    /// matter.
+    fn files() -> (SourceFileId, SourceFileId) {
+        let mut set = SourceFileSet::new();
+        let mut add = |name: &str| {
+            set.add(SourceFile::unloaded(
+                Arc::from("Test"),
+                name.to_owned(),
+                PathBuf::from(name),
+            ))
+        };
+        (add("A.uc"), add("B.uc"))
+    }
+
+    fn suggest(
+        file: SourceFileId,
+        span: Range<usize>,
+        replacement: &str,
+        applicability: Applicability,
+    ) -> Diagnostic<()> {
+        Diagnostic::warning("warning").with_note((
+            "help: replace it",
+            ReplacementSuggestion {
+                file,
+                span,
+                replacement: replacement.to_owned(),
+                applicability,
+            },
+        ))
+    }
+
+    fn edit(range: Range<usize>, replacement: &str) -> Edit {
+        Edit {
+            range,
+            replacement: replacement.to_owned(),
+        }
+    }
+
+    #[test]
+    fn only_machine_applicable_suggestions_are_collected() {
+        let (a, _) = files();
+        let diagnostics = [
+            suggest(a, 0..1, "machine", Applicability::MachineApplicable),
+            suggest(a, 2..3, "maybe", Applicability::MaybeIncorrect),
+            suggest(a, 4..5, "placeholders", Applicability::HasPlaceholders),
+        ];
+        let fixes = Fixes::collect(&diagnostics, |_| true);
+        assert_eq!(fixes.edits[&a], [edit(0..1, "machine")]);
+        assert!(fixes.overlapping.is_empty());
+    }
+
+    #[test]
+    fn suggestions_of_children_and_included_files_only() {
+        let (a, b) = files();
+        let diagnostics = [Diagnostic::error("error")
+            .with_child(suggest(a, 1..2, "a", Applicability::MachineApplicable))
+            .with_child(suggest(b, 1..2, "b", Applicability::MachineApplicable))];
+        let fixes = Fixes::collect(&diagnostics, |file| file == a);
+        assert_eq!(fixes.edits.len(), 1);
+        assert_eq!(fixes.edits[&a], [edit(1..2, "a")]);
+    }
+
+    #[test]
+    fn overlapping_suggestions_are_set_aside() {
+        let (a, _) = files();
+        let diagnostics = [
+            suggest(a, 8..12, "later", Applicability::MachineApplicable),
+            suggest(a, 0..4, "first", Applicability::MachineApplicable),
+            // Overlaps with `first`.
+            suggest(a, 2..6, "overlap", Applicability::MachineApplicable),
+            // Touches `first` without overlapping it.
+            suggest(a, 4..4, "insert", Applicability::MachineApplicable),
+            // Two insertions at the same position cannot be ordered, so only the first is applied.
+            suggest(a, 4..4, "another", Applicability::MachineApplicable),
+            // Duplicates are applied once, and don't count as overlapping.
+            suggest(a, 8..12, "later", Applicability::MachineApplicable),
+        ];
+        let fixes = Fixes::collect(&diagnostics, |_| true);
+        assert_eq!(
+            fixes.edits[&a],
+            [
+                edit(0..4, "first"),
+                edit(4..4, "insert"),
+                edit(8..12, "later")
+            ]
+        );
+        assert!(fixes.overlapping.iter().all(|&(file, _)| file == a));
+        let mut overlapping: Vec<_> = fixes
+            .overlapping
+            .iter()
+            .map(|(_, edit)| edit.replacement.as_str())
+            .collect();
+        overlapping.sort();
+        assert_eq!(overlapping, ["another", "overlap"]);
+    }
+
+    #[test]
+    fn edits_apply_in_order() {
+        let source = "local int Unused, Used;";
+        let edits = [
+            edit(0..5, "LOCAL"),
+            edit(10..18, ""),
+            edit(23..23, " // ok"),
+        ];
+        assert_eq!(apply_edits(source, &edits), "LOCAL int Used; // ok");
+        assert_eq!(apply_edits(source, &[]), source);
+    }
+
+    #[test]
+    fn edits_survive_an_encoding_round_trip() {
+        for encoding in [
+            SourceEncoding::Utf8,
+            SourceEncoding::Utf16BigEndian,
+            SourceEncoding::Utf16LittleEndian,
+        ] {
+            let original = "var string Grüße, Unused;\n";
+            let (source, detected) = decode_source(encoding.encode(original)).unwrap();
+            assert_eq!(detected, encoding);
+            assert_eq!(source, original);
+
+            // Edit ranges are byte offsets into the decoded source, past a multi-byte character.
+            let start = source.find(", Unused").unwrap();
+            let fixed = apply_edits(&source, &[edit(start..start + 8, "")]);
+            let (reread, reread_encoding) = decode_source(encoding.encode(&fixed)).unwrap();
+            assert_eq!(reread_encoding, encoding);
+            assert_eq!(reread, "var string Grüße;\n");
+        }
+    }
+}
@@ -0,0 +1,1516 @@
+//! Source code formatter, which re-prints UnrealScript with consistent indentation, brace
+//! placement, spacing around operators, and keyword casing.
+//!
+//! The formatter works on the tokens of a file as they were lexed, before preprocessing, such that
+//! comments and preprocessor directives are kept: lines starting with a directive (and `#exec`
+//! lines) are only re-indented, and C++ code in `cpptext` blocks and `{..}` blobs is left as
+//! written. Line breaks are kept as well, except around braces, which are moved according to the
+//! [`BraceStyle`].
+
+use std::{borrow::Cow, fmt, ops::Range, str::FromStr, sync::Arc};
+
+use anyhow::bail;
+use muscript_foundation::source_arena::SourceArena;
+use muscript_lexer::{
+    sources::LexedSources,
+    token::{Token, TokenKind, TokenSpan},
+    Lexer, LexerErrors,
+};
+
+/// Declares an enum of formatting options which is read from the project configuration as one of
+/// the given names.
+macro_rules! option_enum {
+    (
+        $(#[$attr:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_attr:meta])* $variant:ident = $text:literal,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        pub enum $name {
+            $($(#[$variant_attr])* $variant,)*
+        }
+
+        impl $name {
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($name::$variant => $text,)*
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($text => Ok($name::$variant),)*
+                    _ => Err(format!(
+                        "unknown value `{s}` (expected {})",
+                        [$(concat!("`", $text, "`")),*].join(" or ")
+                    )),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+    };
+}
+
+option_enum! {
+    /// What each level of indentation is made of.
+    pub enum IndentStyle {
+        /// One tab per level.
+        #[default]
+        Tabs = "tabs",
+        /// [`Options::indent_width`] spaces per level.
+        Spaces = "spaces",
+    }
+}
+
+option_enum! {
+    /// Where the opening braces of blocks are placed.
+    pub enum BraceStyle {
+        /// On a line of their own, as in the engine's sources.
+        #[default]
+        NextLine = "next_line",
+        /// At the end of the line introducing the block. `else` is joined with the preceding `}`.
+        SameLine = "same_line",
+        /// Wherever they were written.
+        Preserve = "preserve",
+    }
+}
+
+option_enum! {
+    /// How keywords are spelled.
+    pub enum KeywordCase {
+        /// In lowercase, including `none`, `begin object`, and `end object`.
+        #[default]
+        Lower = "lower",
+        /// As written.
+        Preserve = "preserve",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub indent_style: IndentStyle,
+    /// Number of spaces per level of indentation, when indenting with spaces.
+    pub indent_width: usize,
+    pub brace_style: BraceStyle,
+    pub keyword_case: KeywordCase,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            indent_style: IndentStyle::default(),
+            indent_width: 4,
+            brace_style: BraceStyle::default(),
+            keyword_case: KeywordCase::default(),
+        }
+    }
+}
+
+impl Options {
+    fn indent(&self, output: &mut String, level: usize) {
+        match self.indent_style {
+            IndentStyle::Tabs => output.extend(std::iter::repeat_n('\t', level)),
+            IndentStyle::Spaces => {
+                output.extend(std::iter::repeat_n(' ', level * self.indent_width))
+            }
+        }
+    }
+}
+
+/// Formats a source file, given the tokens it was lexed into by
+/// [`lex_source`][crate::parse::lex_source].
+///
+/// The formatted code is lexed again and compared with the original tokens; an error is returned
+/// if anything but whitespace and the casing of keywords would change, since that would alter
+/// the meaning of the code.
+pub fn format_source(
+    sources: LexedSources<'_>,
+    token_span: TokenSpan,
+    options: &Options,
+) -> anyhow::Result<String> {
+    let Some(start) = token_span.start() else {
+        return Ok(String::new());
+    };
+    let source_file_id = sources.token_arena.source_file_id(start);
+    let source_file = sources.source_file_set.get(source_file_id);
+    let source = source_file.source();
+    let tokens = sources.token_arena.elements_in(token_span);
+    if tokens.iter().any(|token| token.kind == TokenKind::Error) {
+        bail!(
+            "{} contains characters that cannot be lexed",
+            source_file.filename
+        );
+    }
+
+    let formatted = format_tokens(source, tokens, options);
+
+    let mut token_arena = SourceArena::new();
+    let formatted_span = Lexer::new(
+        token_arena.build_source_file(source_file_id),
+        source_file_id,
+        Arc::from(formatted.as_str()),
+        &mut LexerErrors::default(),
+    )
+    .lex();
+    let significant = |source: &'_ str, tokens: &'_ [Token]| -> Vec<(TokenKind, String)> {
+        tokens
+            .iter()
+            .filter(|token| token.kind != TokenKind::NewLine)
+            .map(|token| {
+                let text = &source[token.source_range.clone()];
+                let text = match token.kind {
+                    TokenKind::Ident => text.to_ascii_lowercase(),
+                    TokenKind::Comment => text.trim_end().to_owned(),
+                    _ => text.to_owned(),
+                };
+                (token.kind, text)
+            })
+            .collect()
+    };
+    if significant(source, tokens)
+        != significant(&formatted, token_arena.elements_in(formatted_span))
+    {
+        bail!(
+            "formatting {} would change its meaning; this is a bug in the formatter",
+            source_file.filename
+        );
+    }
+
+    Ok(formatted)
+}
+
+/// Formats source code that was lexed into the given tokens.
+fn format_tokens(source: &str, tokens: &[Token], options: &Options) -> String {
+    let lines = split_lines(source, tokens);
+    let lines = place_braces(lines, options.brace_style);
+    let newline = if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    Printer::new(options, newline).print(&lines)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AtomKind {
+    Token(TokenKind),
+    /// A macro invocation or directive, `` `name `` or `` `{name} ``.
+    Macro,
+    /// A compound assignment operator, such as `+=`, lexed as two tokens.
+    CompoundAssign,
+    /// A `>>` or `>>>` operator, lexed as separate `>` tokens.
+    Shift,
+    /// The `<` and `>` surrounding the arguments of a generic type, such as `array<Int>`.
+    GenericOpen,
+    GenericClose,
+    /// C++ code, printed exactly as written.
+    Verbatim,
+}
+
+/// The unit of formatting: a single token, or several tokens which must stay together.
+#[derive(Debug, Clone)]
+struct Atom<'a> {
+    kind: AtomKind,
+    text: &'a str,
+    range: Range<usize>,
+    /// Whitespace between the atom and the preceding one on the same line, as written.
+    gap: &'a str,
+}
+
+impl Atom<'_> {
+    fn is(&self, kind: TokenKind) -> bool {
+        self.kind == AtomKind::Token(kind)
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        self.is(TokenKind::Ident) && self.text.eq_ignore_ascii_case(word)
+    }
+
+    fn is_any_word(&self, words: &[&str]) -> bool {
+        self.is(TokenKind::Ident) && is_any_of(self.text, words)
+    }
+}
+
+fn is_any_of(text: &str, words: &[&str]) -> bool {
+    words.iter().any(|word| text.eq_ignore_ascii_case(word))
+}
+
+/// Words which are keywords wherever they appear.
+const KEYWORDS: &[&str] = &[
+    "break",
+    "case",
+    "class",
+    "const",
+    "continue",
+    "cpptext",
+    "default",
+    "defaultproperties",
+    "delegate",
+    "do",
+    "else",
+    "enum",
+    "event",
+    "extends",
+    "false",
+    "for",
+    "foreach",
+    "function",
+    "goto",
+    "if",
+    "ignores",
+    "interface",
+    "local",
+    "new",
+    "none",
+    "operator",
+    "postoperator",
+    "preoperator",
+    "replication",
+    "return",
+    "self",
+    "state",
+    "static",
+    "struct",
+    "structcpptext",
+    "structdefaultproperties",
+    "super",
+    "switch",
+    "true",
+    "until",
+    "var",
+    "while",
+    "within",
+];
+
+/// Specifiers of classes, variables, functions, and parameters, which are only keywords in
+/// declarations.
+const SPECIFIERS: &[&str] = &[
+    "abstract",
+    "auto",
+    "client",
+    "coerce",
+    "collapsecategories",
+    "config",
+    "dependson",
+    "deprecated",
+    "dontcollapsecategories",
+    "duplicatetransient",
+    "editconst",
+    "editfixedsize",
+    "edithide",
+    "editinline",
+    "editinlinenew",
+    "editinlineuse",
+    "editoronly",
+    "edittextbox",
+    "exec",
+    "export",
+    "final",
+    "globalconfig",
+    "hidecategories",
+    "hidedropdown",
+    "immutable",
+    "immutablewhencooked",
+    "implements",
+    "init",
+    "input",
+    "instanced",
+    "interp",
+    "iterator",
+    "latent",
+    "localized",
+    "native",
+    "nativereplication",
+    "noclear",
+    "noexport",
+    "noexportheader",
+    "noimport",
+    "nontransactional",
+    "notforconsole",
+    "notplaceable",
+    "optional",
+    "out",
+    "perobjectconfig",
+    "perobjectlocalized",
+    "placeable",
+    "private",
+    "protected",
+    "protectedwrite",
+    "public",
+    "reliable",
+    "repnotify",
+    "repretry",
+    "server",
+    "showcategories",
+    "simulated",
+    "singular",
+    "skip",
+    "static",
+    "transient",
+    "unreliable",
+];
+
+/// Words that start declarations.
+const DECLARATIONS: &[&str] = &[
+    "class",
+    "const",
+    "delegate",
+    "enum",
+    "event",
+    "function",
+    "interface",
+    "operator",
+    "postoperator",
+    "preoperator",
+    "state",
+    "struct",
+    "var",
+];
+
+/// Words that introduce functions, whose bodies contain statements.
+const FUNCTIONS: &[&str] = &[
+    "delegate",
+    "event",
+    "function",
+    "operator",
+    "postoperator",
+    "preoperator",
+];
+
+/// Keywords of statements whose body may be a single statement without braces.
+const CONTROL_FLOW: &[&str] = &["do", "else", "for", "foreach", "if", "while"];
+
+/// Keywords after which an expression starts, rather than one ending.
+const EXPRESSION_KEYWORDS: &[&str] = &[
+    "case", "do", "else", "for", "foreach", "goto", "if", "local", "new", "return", "switch",
+    "until", "var", "while",
+];
+
+/// Keywords which are separated from the parenthesis following them.
+const SPACE_BEFORE_PAREN: &[&str] = &[
+    "case", "for", "foreach", "if", "new", "return", "switch", "until", "while",
+];
+
+/// Words that can be directly followed by the braces of a block on the same line.
+const BLOCK_INTRODUCERS: &[&str] = &[
+    "cpptext",
+    "defaultproperties",
+    "do",
+    "else",
+    "replication",
+    "structcpptext",
+    "structdefaultproperties",
+];
+
+/// Types which take generic arguments in angle brackets.
+const GENERIC_TYPES: &[&str] = &["array", "class", "delegate", "map"];
+
+const DIRECTIVES: &[&str] = &["define", "else", "endif", "if", "include", "undefine"];
+
+#[derive(Debug, Clone)]
+enum Line<'a> {
+    Blank,
+    /// A preprocessor directive or `#exec` line, printed as written apart from its indentation.
+    Directive(&'a str),
+    Code(Vec<Atom<'a>>),
+}
+
+/// Splits the tokens of a file into lines of atoms.
+fn split_lines<'a>(source: &'a str, tokens: &[Token]) -> Vec<Line<'a>> {
+    let mut lines = vec![];
+    let mut atoms: Vec<Atom<'a>> = vec![];
+    // Lines ending with a backslash (which continue `` `define``s) are joined with the next line.
+    let mut escaped = false;
+    let mut previous_end = None;
+    let mut generic_depth = 0;
+    // The last token of code before the current one, for finding `cpptext` blocks.
+    let mut last_code: Option<&Token> = None;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let adjacent = |j: usize| {
+            tokens
+                .get(j)
+                .filter(|next: &&Token| next.source_range.start == tokens[j - 1].source_range.end)
+        };
+        let mut kind = AtomKind::Token(token.kind);
+        let mut end = i;
+        match token.kind {
+            TokenKind::EndOfFile => break,
+            TokenKind::NewLine => {
+                if atoms
+                    .last()
+                    .is_some_and(|atom| atom.is(TokenKind::Backslash))
+                {
+                    escaped = true;
+                } else {
+                    lines.push(finish_line(source, std::mem::take(&mut atoms), escaped));
+                    escaped = false;
+                    previous_end = None;
+                    generic_depth = 0;
+                }
+                i += 1;
+                continue;
+            }
+            TokenKind::Accent => match adjacent(i + 1).map(|next| next.kind) {
+                Some(TokenKind::Ident) => {
+                    kind = AtomKind::Macro;
+                    end = i + 1;
+                }
+                Some(TokenKind::LeftBrace) => {
+                    if let Some(close) = (i + 2..tokens.len().min(i + 4))
+                        .find(|&j| tokens[j].kind == TokenKind::RightBrace)
+                    {
+                        kind = AtomKind::Macro;
+                        end = close;
+                    }
+                }
+                _ => (),
+            },
+            TokenKind::LeftBrace => {
+                let is_cpptext = last_code.is_some_and(|last| {
+                    last.kind == TokenKind::Ident
+                        && is_any_of(
+                            &source[last.source_range.clone()],
+                            &["cpptext", "structcpptext"],
+                        )
+                });
+                if let (true, Some(close)) = (is_cpptext, matching_brace(tokens, i, false)) {
+                    kind = AtomKind::Verbatim;
+                    end = close;
+                } else if !atoms
+                    .iter()
+                    .rev()
+                    .find(|atom| !atom.is(TokenKind::Comment))
+                    .is_none_or(|atom| {
+                        atom.is(TokenKind::RightParen)
+                            || atom.is(TokenKind::RightBrace)
+                            || atom.is_any_word(BLOCK_INTRODUCERS)
+                    })
+                {
+                    // Braces directly after a declaration's name or specifiers, closed on the same
+                    // line without any statements inside, hold C++ code.
+                    if let Some(close) = matching_brace(tokens, i, true) {
+                        if !tokens[i..close]
+                            .iter()
+                            .any(|token| token.kind == TokenKind::Semi)
+                        {
+                            kind = AtomKind::Verbatim;
+                            end = close;
+                        }
+                    }
+                }
+            }
+            TokenKind::Less => {
+                if generic_depth > 0
+                    || atoms
+                        .last()
+                        .is_some_and(|atom| atom.is_any_word(GENERIC_TYPES))
+                {
+                    kind = AtomKind::GenericOpen;
+                    generic_depth += 1;
+                }
+            }
+            TokenKind::Greater => {
+                if generic_depth > 0 {
+                    kind = AtomKind::GenericClose;
+                    generic_depth -= 1;
+                } else {
+                    while end < i + 2 && adjacent(end + 1).map(|next| next.kind) == Some(token.kind)
+                    {
+                        kind = AtomKind::Shift;
+                        end += 1;
+                    }
+                }
+            }
+            TokenKind::Semi => generic_depth = 0,
+            other => {
+                if other.can_be_compound_assignment()
+                    && adjacent(i + 1).map(|next| next.kind) == Some(TokenKind::Assign)
+                {
+                    kind = AtomKind::CompoundAssign;
+                    end = i + 1;
+                }
+            }
+        }
+
+        let mut range = token.source_range.start..tokens[end].source_range.end;
+        if token.kind == TokenKind::Comment {
+            // Line comments extend up to the line feed, so in files with CRLF line endings they
+            // include the carriage return.
+            range.end = range.start + source[range.clone()].trim_end().len();
+        }
+        atoms.push(Atom {
+            kind,
+            text: &source[range.clone()],
+            gap: previous_end.map_or("", |previous_end| &source[previous_end..range.start]),
+            range: range.clone(),
+        });
+        previous_end = Some(range.end);
+        if token.kind != TokenKind::Comment {
+            last_code = Some(&tokens[end]);
+        }
+        i = end + 1;
+    }
+    if !atoms.is_empty() {
+        lines.push(finish_line(source, atoms, escaped));
+    }
+    lines
+}
+
+/// Finds the brace closing the one at index `open`, optionally only looking at the same line.
+fn matching_brace(tokens: &[Token], open: usize, same_line: bool) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            TokenKind::NewLine if same_line => return None,
+            _ => (),
+        }
+    }
+    None
+}
+
+fn finish_line<'a>(source: &'a str, atoms: Vec<Atom<'a>>, escaped: bool) -> Line<'a> {
+    let (Some(first), Some(last)) = (atoms.first(), atoms.last()) else {
+        return Line::Blank;
+    };
+    let is_directive = first.is(TokenKind::Hash)
+        || (first.kind == AtomKind::Macro && is_any_of(first.text[1..].trim(), DIRECTIVES));
+    if is_directive || escaped {
+        Line::Directive(&source[first.range.start..last.range.end])
+    } else {
+        Line::Code(atoms)
+    }
+}
+
+/// Moves braces to where the brace style wants them, splitting and joining lines as necessary.
+fn place_braces(lines: Vec<Line<'_>>, style: BraceStyle) -> Vec<Line<'_>> {
+    if style == BraceStyle::Preserve {
+        return lines;
+    }
+
+    let mut output = vec![];
+    for line in lines {
+        let Line::Code(atoms) = line else {
+            output.push(line);
+            continue;
+        };
+        let mut segment = vec![];
+        let mut atoms = atoms.into_iter().peekable();
+        while let Some(atom) = atoms.next() {
+            let is_open = atom.is(TokenKind::LeftBrace);
+            let is_close = atom.is(TokenKind::RightBrace);
+            if ((is_open && style == BraceStyle::NextLine) || is_close) && !segment.is_empty() {
+                push_segment(&mut output, std::mem::take(&mut segment), style);
+            }
+            segment.push(atom);
+            let breaks_after = match atoms.peek() {
+                None => false,
+                Some(next) if is_open => !next.is(TokenKind::Comment),
+                Some(next) if is_close => {
+                    !(next.is(TokenKind::Semi)
+                        || next.is(TokenKind::Comma)
+                        || next.is(TokenKind::RightParen)
+                        || next.is(TokenKind::Comment)
+                        || next.is_word("until")
+                        || (next.is_word("else") && style == BraceStyle::SameLine))
+                }
+                Some(_) => false,
+            };
+            if breaks_after {
+                push_segment(&mut output, std::mem::take(&mut segment), style);
+            }
+        }
+        if !segment.is_empty() {
+            push_segment(&mut output, segment, style);
+        }
+    }
+    output
+}
+
+/// Adds a line split off by [`place_braces`] to the output, joining it with the previous line if
+/// the brace style calls for it.
+fn push_segment<'a>(output: &mut Vec<Line<'a>>, mut segment: Vec<Atom<'a>>, style: BraceStyle) {
+    segment[0].gap = "";
+    if style == BraceStyle::SameLine {
+        if let Some(Line::Code(previous)) = output.last_mut() {
+            let previous_last = previous.last().expect("code lines are never empty");
+            let joins_brace = segment[0].is(TokenKind::LeftBrace)
+                && !matches!(
+                    previous_last.kind,
+                    AtomKind::Token(
+                        TokenKind::Comment
+                            | TokenKind::Semi
+                            | TokenKind::LeftBrace
+                            | TokenKind::RightBrace
+                            | TokenKind::Colon
+                    ) | AtomKind::Macro
+                        | AtomKind::Verbatim
+                );
+            let joins_else = segment[0].is_word("else")
+                && previous.len() == 1
+                && previous_last.is(TokenKind::RightBrace);
+            if joins_brace || joins_else {
+                segment[0].gap = " ";
+                previous.extend(segment);
+                return;
+            }
+        }
+    }
+    output.push(Line::Code(segment));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    /// The body of a class-level declaration, such as a struct or state.
+    Declarations,
+    /// The body of a function, or of a statement within one.
+    Statements,
+    Switch,
+    Enum,
+    /// `defaultproperties` and `begin object` blocks, whose spacing is kept as written.
+    Defaults,
+}
+
+/// A block delimited by braces (or `begin object` and `end object`).
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    kind: FrameKind,
+    /// Level of indentation of the lines delimiting the block.
+    outer: usize,
+    /// Whether a `case` label was seen in a `switch` block, so that the lines following it need
+    /// to be indented.
+    in_case: bool,
+}
+
+impl Frame {
+    fn level(&self) -> usize {
+        self.outer + 1
+    }
+}
+
+/// The statement or declaration the printer is in.
+#[derive(Debug, Clone, Copy, Default)]
+struct Statement {
+    /// Levels of indentation added to the statement's lines, for bodies of `if`s and such which
+    /// are not surrounded by braces.
+    extra: usize,
+    is_declaration: bool,
+    is_class: bool,
+    is_operator: bool,
+    is_control_flow: bool,
+    /// Whether the parenthesized condition of a control flow statement has been closed, after
+    /// which its body begins.
+    has_header: bool,
+    /// Kind of the block opened by the next brace within the statement.
+    block: Option<FrameKind>,
+}
+
+/// State of an `` `if `` directive, which is needed to indent each of its branches the same.
+#[derive(Debug, Clone)]
+struct Conditional {
+    level: usize,
+    before: Vec<Frame>,
+    after_first_branch: Option<Vec<Frame>>,
+}
+
+struct Printer<'o> {
+    options: &'o Options,
+    newline: &'static str,
+    output: String,
+    frames: Vec<Frame>,
+    conditionals: Vec<Conditional>,
+    statement: Statement,
+    paren_depth: usize,
+    /// Whether the previous line of code left its statement unfinished.
+    previous_open: bool,
+    ternary_depth: usize,
+    blank_lines: usize,
+}
+
+impl<'o> Printer<'o> {
+    fn new(options: &'o Options, newline: &'static str) -> Self {
+        Self {
+            options,
+            newline,
+            output: String::new(),
+            frames: vec![],
+            conditionals: vec![],
+            statement: Statement::default(),
+            paren_depth: 0,
+            previous_open: false,
+            ternary_depth: 0,
+            blank_lines: 0,
+        }
+    }
+
+    fn print(mut self, lines: &[Line<'_>]) -> String {
+        for line in lines {
+            match line {
+                Line::Blank => self.blank_lines += 1,
+                Line::Directive(text) => {
+                    let level = self.directive(text);
+                    self.begin_line(level);
+                    self.output.push_str(text.trim_end());
+                }
+                Line::Code(atoms) => self.code(atoms),
+            }
+        }
+        if !self.output.is_empty() {
+            self.output.push_str(self.newline);
+        }
+        self.output
+    }
+
+    fn begin_line(&mut self, level: usize) {
+        if !self.output.is_empty() {
+            self.output.push_str(self.newline);
+            if self.blank_lines > 0 {
+                self.output.push_str(self.newline);
+            }
+        }
+        self.blank_lines = 0;
+        self.options.indent(&mut self.output, level);
+    }
+
+    fn frame_kind(&self) -> Option<FrameKind> {
+        self.frames.last().map(|frame| frame.kind)
+    }
+
+    /// Level of indentation of the lines within the innermost block.
+    fn block_level(&self) -> usize {
+        self.frames.last().map_or(0, |frame| {
+            frame.level() + usize::from(frame.kind == FrameKind::Switch && frame.in_case)
+        })
+    }
+
+    /// Keeps track of blocks across the branches of `` `if ``s, and returns the level the
+    /// directive should be indented at.
+    fn directive(&mut self, text: &str) -> usize {
+        let name = text
+            .strip_prefix('`')
+            .map(|name| {
+                let name = name.trim_start_matches('{');
+                let end = name
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(name.len());
+                name[..end].to_ascii_lowercase()
+            })
+            .unwrap_or_default();
+        let level = self.block_level();
+        match name.as_str() {
+            "if" => {
+                self.conditionals.push(Conditional {
+                    level,
+                    before: self.frames.clone(),
+                    after_first_branch: None,
+                });
+                level
+            }
+            "else" => match self.conditionals.last_mut() {
+                Some(conditional) => {
+                    conditional
+                        .after_first_branch
+                        .get_or_insert_with(|| self.frames.clone());
+                    self.frames = conditional.before.clone();
+                    conditional.level
+                }
+                None => level,
+            },
+            "endif" => match self.conditionals.pop() {
+                Some(conditional) => {
+                    if let Some(frames) = conditional.after_first_branch {
+                        self.frames = frames;
+                    }
+                    conditional.level
+                }
+                None => level,
+            },
+            _ => level,
+        }
+    }
+
+    fn code(&mut self, atoms: &[Atom<'_>]) {
+        let first = &atoms[0];
+        let is_comment = atoms.iter().all(|atom| atom.is(TokenKind::Comment));
+        let frame_kind = self.frame_kind();
+        let in_defaults = frame_kind == Some(FrameKind::Defaults);
+        let is_list = matches!(frame_kind, Some(FrameKind::Defaults | FrameKind::Enum));
+
+        let leading_closers = atoms
+            .iter()
+            .take_while(|atom| atom.is(TokenKind::RightParen) || atom.is(TokenKind::RightBracket))
+            .count();
+        let in_parens = self.paren_depth.saturating_sub(leading_closers) > 0;
+
+        // Find out which statement the line belongs to.
+        let continues = self.previous_open && !is_list;
+        let mut extra = 0;
+        if first.is(TokenKind::RightBrace) {
+            // Closing braces end their statement.
+        } else if first.is(TokenKind::LeftBrace)
+            || (first.kind == AtomKind::Verbatim && first.text.starts_with('{'))
+        {
+            extra = if continues { self.statement.extra } else { 0 };
+        } else if continues
+            && !in_parens
+            && self.statement.is_control_flow
+            && self.statement.has_header
+        {
+            extra = self.statement.extra + 1;
+        } else if continues || in_parens {
+            extra = self.statement.extra + usize::from(leading_closers == 0);
+        }
+        let starts_statement = !first.is(TokenKind::RightBrace) && !continues && !in_parens;
+        let starts_nested_statement = extra > self.statement.extra
+            && !in_parens
+            && self.statement.is_control_flow
+            && self.statement.has_header;
+        if !is_comment && (starts_statement || starts_nested_statement) {
+            self.start_statement(atoms, extra);
+        }
+
+        let is_case = frame_kind == Some(FrameKind::Switch)
+            && (first.is_word("case")
+                || (first.is_word("default")
+                    && atoms.get(1).is_some_and(|a| a.is(TokenKind::Colon))));
+        let is_label = matches!(
+            frame_kind,
+            Some(FrameKind::Statements | FrameKind::Declarations)
+        ) && atoms.len() >= 2
+            && first.is(TokenKind::Ident)
+            && !first.is_any_word(KEYWORDS)
+            && atoms[1].is(TokenKind::Colon)
+            && atoms[2..].iter().all(|atom| atom.is(TokenKind::Comment));
+        let is_end_object = in_defaults
+            && first.is_word("end")
+            && atoms.get(1).is_some_and(|a| a.is_word("object"));
+
+        let level = if first.is(TokenKind::RightBrace) || is_end_object {
+            self.frames.last().map_or(0, |frame| frame.outer)
+        } else if is_case {
+            let frame = self.frames.last_mut().expect("switch frame must exist");
+            frame.in_case = true;
+            frame.level()
+        } else if is_label {
+            self.block_level().saturating_sub(1)
+        } else {
+            self.block_level() + extra
+        };
+        self.begin_line(level);
+
+        let mut previous: Option<&Atom<'_>> = None;
+        // Whether the previous atom ended an operand, which tells binary operators apart from
+        // unary ones.
+        let mut previous_operand = false;
+        let mut previous_prefix = false;
+        let mut previous_binary = false;
+        for (i, atom) in atoms.iter().enumerate() {
+            let text = self.cased(atoms, i);
+            let role = self.role(atom, previous_operand);
+            if let Some(previous) = previous {
+                let keep_gap =
+                    in_defaults || (self.statement.is_operator && !atom.is(TokenKind::Comment));
+                let gap = if keep_gap {
+                    Cow::Borrowed(atom.gap)
+                } else {
+                    spacing(previous, atom, role, previous_prefix, previous_binary)
+                };
+                self.output.push_str(&gap);
+            }
+            self.output.push_str(&text);
+            self.track(atom);
+
+            if !atom.is(TokenKind::Comment) {
+                previous_prefix = role == Role::Prefix;
+                previous_binary = role == Role::Binary;
+                previous_operand = match atom.kind {
+                    AtomKind::Token(TokenKind::Ident) => !atom.is_any_word(EXPRESSION_KEYWORDS),
+                    AtomKind::Token(
+                        TokenKind::IntLit
+                        | TokenKind::FloatLit
+                        | TokenKind::StringLit
+                        | TokenKind::NameLit
+                        | TokenKind::RightParen
+                        | TokenKind::RightBracket,
+                    )
+                    | AtomKind::GenericClose
+                    | AtomKind::Macro
+                    | AtomKind::Verbatim => true,
+                    AtomKind::Token(TokenKind::Inc | TokenKind::Dec) => role == Role::Postfix,
+                    _ => false,
+                };
+            }
+            previous = Some(atom);
+        }
+
+        if is_end_object {
+            self.frames.pop();
+        } else if in_defaults
+            && first.is_word("begin")
+            && atoms.get(1).is_some_and(|a| a.is_word("object"))
+        {
+            self.frames.push(Frame {
+                kind: FrameKind::Defaults,
+                outer: level,
+                in_case: false,
+            });
+        }
+
+        if !is_comment {
+            let last = atoms
+                .iter()
+                .rev()
+                .find(|atom| !atom.is(TokenKind::Comment))
+                .expect("line is not only comments");
+            self.previous_open = !(first.kind == AtomKind::Macro
+                || is_label
+                || is_case
+                || matches!(
+                    last.kind,
+                    AtomKind::Token(
+                        TokenKind::Semi
+                            | TokenKind::LeftBrace
+                            | TokenKind::RightBrace
+                            | TokenKind::Colon
+                    ) | AtomKind::Macro
+                        | AtomKind::Verbatim
+                ));
+        }
+    }
+
+    fn start_statement(&mut self, atoms: &[Atom<'_>], extra: usize) {
+        let first = &atoms[0];
+        let in_statements = matches!(
+            self.frame_kind(),
+            Some(FrameKind::Statements | FrameKind::Switch)
+        );
+        let is_declaration = !in_statements
+            && (first.is_any_word(DECLARATIONS)
+                || (first.is_any_word(SPECIFIERS)
+                    && atoms.get(1).is_some_and(|next| {
+                        matches!(
+                            next.kind,
+                            AtomKind::Token(TokenKind::Ident | TokenKind::LeftParen)
+                                | AtomKind::Verbatim
+                        )
+                    })));
+        self.statement = Statement {
+            extra,
+            is_declaration,
+            is_class: is_declaration && first.is_any_word(&["class", "interface"]),
+            is_control_flow: first.is_any_word(CONTROL_FLOW),
+            has_header: first.is_any_word(&["do", "else"]),
+            ..Default::default()
+        };
+        self.paren_depth = 0;
+        self.ternary_depth = 0;
+    }
+
+    /// Updates the state of the printer after printing an atom.
+    fn track(&mut self, atom: &Atom<'_>) {
+        match atom.kind {
+            AtomKind::Token(TokenKind::LeftParen | TokenKind::LeftBracket) => {
+                self.paren_depth += 1;
+                if self.statement.is_control_flow && self.paren_depth == 1 {
+                    self.statement.has_header = false;
+                }
+            }
+            AtomKind::Token(TokenKind::RightParen | TokenKind::RightBracket) => {
+                self.paren_depth = self.paren_depth.saturating_sub(1);
+                if self.statement.is_control_flow && self.paren_depth == 0 {
+                    self.statement.has_header = true;
+                }
+            }
+            AtomKind::Token(TokenKind::Question) => self.ternary_depth += 1,
+            AtomKind::Token(TokenKind::Colon) => {
+                self.ternary_depth = self.ternary_depth.saturating_sub(1)
+            }
+            AtomKind::Token(TokenKind::Semi) if self.paren_depth == 0 => {
+                self.statement = Statement::default();
+            }
+            AtomKind::Token(TokenKind::LeftBrace) => {
+                let kind = self.statement.block.unwrap_or(match self.frame_kind() {
+                    Some(FrameKind::Statements | FrameKind::Switch) => FrameKind::Statements,
+                    Some(FrameKind::Defaults) => FrameKind::Defaults,
+                    _ => FrameKind::Declarations,
+                });
+                self.frames.push(Frame {
+                    kind,
+                    outer: self.block_level() + self.statement.extra,
+                    in_case: false,
+                });
+                self.statement = Statement::default();
+                self.paren_depth = 0;
+            }
+            AtomKind::Token(TokenKind::RightBrace) => {
+                self.frames.pop();
+                self.statement = Statement::default();
+                self.paren_depth = 0;
+            }
+            AtomKind::Token(TokenKind::Ident) if self.paren_depth == 0 => {
+                let block = if atom.is_word("switch") {
+                    Some(FrameKind::Switch)
+                } else if atom.is_word("enum") {
+                    Some(FrameKind::Enum)
+                } else if atom.is_any_word(&["defaultproperties", "structdefaultproperties"]) {
+                    Some(FrameKind::Defaults)
+                } else if atom.is_any_word(FUNCTIONS) && self.statement.is_declaration {
+                    Some(FrameKind::Statements)
+                } else {
+                    None
+                };
+                if block.is_some() {
+                    self.statement.block = block;
+                }
+                if self.statement.is_declaration
+                    && atom.is_any_word(&["operator", "preoperator", "postoperator"])
+                {
+                    self.statement.is_operator = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn role(&self, atom: &Atom<'_>, previous_operand: bool) -> Role {
+        match atom.kind {
+            AtomKind::CompoundAssign | AtomKind::Shift => Role::Binary,
+            AtomKind::Token(TokenKind::Not | TokenKind::BitNot) => Role::Prefix,
+            AtomKind::Token(TokenKind::Inc | TokenKind::Dec) if previous_operand => Role::Postfix,
+            AtomKind::Token(TokenKind::Inc | TokenKind::Dec) => Role::Prefix,
+            AtomKind::Token(TokenKind::Add | TokenKind::Sub) if !previous_operand => Role::Prefix,
+            AtomKind::Token(TokenKind::Colon) if self.ternary_depth == 0 => Role::Label,
+            AtomKind::Token(
+                TokenKind::Add
+                | TokenKind::Sub
+                | TokenKind::Mul
+                | TokenKind::Div
+                | TokenKind::Rem
+                | TokenKind::Pow
+                | TokenKind::Dollar
+                | TokenKind::At
+                | TokenKind::ShiftLeft
+                | TokenKind::ShiftRight
+                | TokenKind::TripleShiftRight
+                | TokenKind::BitAnd
+                | TokenKind::BitOr
+                | TokenKind::BitXor
+                | TokenKind::Equal
+                | TokenKind::NotEqual
+                | TokenKind::ApproxEqual
+                | TokenKind::Less
+                | TokenKind::Greater
+                | TokenKind::LessEqual
+                | TokenKind::GreaterEqual
+                | TokenKind::And
+                | TokenKind::Or
+                | TokenKind::Xor
+                | TokenKind::Assign
+                | TokenKind::Question
+                | TokenKind::Colon,
+            ) if previous_operand => Role::Binary,
+            _ => Role::Other,
+        }
+    }
+
+    /// Returns the text of an atom, with keywords spelled as configured.
+    fn cased<'a>(&self, atoms: &[Atom<'a>], i: usize) -> Cow<'a, str> {
+        let atom = &atoms[i];
+        if self.options.keyword_case == KeywordCase::Preserve || !atom.is(TokenKind::Ident) {
+            return Cow::Borrowed(atom.text);
+        }
+        let previous = i.checked_sub(1).map(|i| &atoms[i]);
+        let next = atoms.get(i + 1);
+        let is_object_keyword = |word: &Atom<'_>, object: Option<&Atom<'_>>| {
+            word.is_any_word(&["begin", "end"]) && object.is_some_and(|a| a.is_word("object"))
+        };
+
+        let is_keyword = if self.frame_kind() == Some(FrameKind::Defaults) {
+            atom.is_any_word(&["none", "true", "false"])
+                || (i == 0 && is_object_keyword(atom, next))
+                || (i == 1 && is_object_keyword(&atoms[0], Some(atom)))
+        } else if previous.is_some_and(|previous| previous.is(TokenKind::Dot)) {
+            // Members named like keywords, except for `Class.default.Variable` and such.
+            atom.is_any_word(&["const", "default", "static"])
+                && next.is_some_and(|next| next.is(TokenKind::Dot))
+        } else if atom.is_any_word(KEYWORDS) {
+            // `class<Object>` is a type.
+            !(atom.is_word("class") && next.is_some_and(|next| next.kind == AtomKind::GenericOpen))
+        } else if atom.is_any_word(SPECIFIERS) && self.statement.is_declaration {
+            if self.statement.is_class {
+                self.paren_depth == 0
+                    && !previous.is_some_and(|previous| {
+                        previous.is_any_word(&["class", "interface", "extends", "within"])
+                    })
+            } else {
+                let before_name = next.is_some_and(|next| {
+                    matches!(
+                        next.kind,
+                        AtomKind::Token(TokenKind::Ident | TokenKind::LeftParen)
+                            | AtomKind::Verbatim
+                    )
+                });
+                let after_specifier = previous.is_none_or(|previous| {
+                    matches!(
+                        previous.kind,
+                        AtomKind::Token(
+                            TokenKind::LeftParen | TokenKind::RightParen | TokenKind::Comma
+                        )
+                    ) || previous.is_any_word(SPECIFIERS)
+                        || previous.is_any_word(&["const", "state", "struct", "var"])
+                });
+                before_name && after_specifier
+            }
+        } else {
+            false
+        };
+        if is_keyword {
+            match atom.text.bytes().any(|b| b.is_ascii_uppercase()) {
+                true => Cow::Owned(atom.text.to_ascii_lowercase()),
+                false => Cow::Borrowed(atom.text),
+            }
+        } else {
+            Cow::Borrowed(atom.text)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Binary,
+    Prefix,
+    Postfix,
+    /// The colon after a `case` or label.
+    Label,
+    Other,
+}
+
+/// Returns the whitespace to put between two atoms on the same line.
+fn spacing<'a>(
+    previous: &Atom<'a>,
+    next: &Atom<'a>,
+    role: Role,
+    previous_prefix: bool,
+    previous_binary: bool,
+) -> Cow<'a, str> {
+    use TokenKind as T;
+
+    let space = Cow::Borrowed(" ");
+    let none = Cow::Borrowed("");
+    let as_written = if next.gap.is_empty() {
+        none.clone()
+    } else {
+        space.clone()
+    };
+    let is = |atom: &Atom<'_>, kinds: &[TokenKind]| kinds.iter().any(|&kind| atom.is(kind));
+    let is_opaque = |atom: &Atom<'_>| {
+        matches!(atom.kind, AtomKind::Macro | AtomKind::Verbatim)
+            || is(
+                atom,
+                &[T::Hash, T::Accent, T::Backslash, T::Generated, T::FailedExp],
+            )
+    };
+
+    if next.is(T::Comment) {
+        // Trailing comments are often aligned with each other.
+        return if next.gap.is_empty() {
+            space
+        } else {
+            Cow::Borrowed(next.gap)
+        };
+    }
+    if previous.is(T::Comment) || is_opaque(previous) || is_opaque(next) {
+        return as_written;
+    }
+    if is(next, &[T::Comma, T::Semi, T::RightParen, T::RightBracket])
+        || is(previous, &[T::LeftParen, T::LeftBracket])
+        || previous.is(T::Dot)
+        || next.is(T::Dot)
+        || previous_prefix
+        || role == Role::Postfix
+        || role == Role::Label
+        || previous.kind == AtomKind::GenericOpen
+        || next.kind == AtomKind::GenericOpen
+        || (next.kind == AtomKind::GenericClose && previous.kind != AtomKind::GenericClose)
+    {
+        return none;
+    }
+    if next.kind == AtomKind::GenericClose {
+        // `array<class<Actor> >`, since `>>` is a shift operator to other compilers.
+        return space;
+    }
+    if role == Role::Binary || previous_binary {
+        return space;
+    }
+    if next.is(T::LeftParen) {
+        return if (previous.is(T::Ident) && !previous.is_any_word(SPACE_BEFORE_PAREN))
+            || previous.kind == AtomKind::GenericClose
+        {
+            none
+        } else if is(previous, &[T::RightParen, T::StringLit, T::NameLit]) {
+            as_written
+        } else {
+            space
+        };
+    }
+    if next.is(T::LeftBracket) {
+        return if is(previous, &[T::Ident, T::RightParen, T::RightBracket])
+            || previous.kind == AtomKind::GenericClose
+        {
+            none
+        } else {
+            as_written
+        };
+    }
+    if previous.is(T::Ident) && next.is(T::NameLit) {
+        // `class'Actor'`
+        return as_written;
+    }
+    if is(
+        previous,
+        &[T::Comma, T::Semi, T::LeftBrace, T::RightBrace, T::Colon],
+    ) || is(next, &[T::LeftBrace, T::RightBrace])
+        || role == Role::Prefix
+    {
+        return space;
+    }
+    let is_word = |atom: &Atom<'_>| {
+        is(
+            atom,
+            &[T::Ident, T::IntLit, T::FloatLit, T::StringLit, T::NameLit],
+        )
+    };
+    if (is_word(previous)
+        || is(previous, &[T::RightParen, T::RightBracket])
+        || previous.kind == AtomKind::GenericClose)
+        && is_word(next)
+    {
+        return space;
+    }
+    as_written
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use muscript_foundation::{
+        source::{SourceFile, SourceFileSet},
+        source_arena::SourceArena,
+    };
+    use muscript_lexer::{
+        sources::{LexedSources, SynthesizedTokens},
+        Lexer, LexerErrors,
+    };
+
+    use super::{format_source, BraceStyle, Options};
+
+    fn format(source: &str, options: &Options) -> String {
+        let formatted = format_once(source, options);
+        assert_eq!(
+            format_once(&formatted, options),
+            formatted,
+            "formatting is not idempotent"
+        );
+        formatted
+    }
+
+    fn format_once(source: &str, options: &Options) -> String {
+        let mut source_file_set = SourceFileSet::new();
+        let id = source_file_set.add(SourceFile::new(
+            "Test".into(),
+            "Test.uc".into(),
+            "Test.uc".into(),
+            Arc::from(source),
+        ));
+        let mut token_arena = SourceArena::new();
+        let mut lexer_errors = LexerErrors::default();
+        let token_span = Lexer::new(
+            token_arena.build_source_file(id),
+            id,
+            Arc::from(source),
+            &mut lexer_errors,
+        )
+        .lex();
+        let sources = LexedSources {
+            source_file_set: &source_file_set,
+            token_arena: &token_arena,
+            lexer_errors: &lexer_errors,
+            synthesized_tokens: &SynthesizedTokens::new(),
+        };
+        format_source(sources, token_span, options).unwrap()
+    }
+
+    #[test]
+    fn indentation_and_braces() {
+        let source = "\
+Class Foo Extends Object
+    Config(Game);
+
+Function Int Add(int a,int b){
+local int c;
+if(a>b)
+return a-b;
+else{c=a+b;}
+return c;
+}
+";
+        let expected = "\
+class Foo extends Object
+\tconfig(Game);
+
+function Int Add(int a, int b)
+{
+\tlocal int c;
+\tif (a > b)
+\t\treturn a - b;
+\telse
+\t{
+\t\tc = a + b;
+\t}
+\treturn c;
+}
+";
+        assert_eq!(format(source, &Options::default()), expected);
+
+        let same_line = Options {
+            brace_style: BraceStyle::SameLine,
+            ..Options::default()
+        };
+        let expected = "\
+class Foo extends Object
+\tconfig(Game);
+
+function Int Add(int a, int b) {
+\tlocal int c;
+\tif (a > b)
+\t\treturn a - b;
+\telse {
+\t\tc = a + b;
+\t}
+\treturn c;
+}
+";
+        assert_eq!(format(source, &same_line), expected);
+    }
+
+    #[test]
+    fn operators() {
+        let source = "\
+function F()
+{
+\tX+=-Y*(Z-1)>>2;
+\tS=A@\"b\"$C;
+\tB=!bFoo&&I++<10?Arr[I]:-1;
+\tArr[I]=class<Actor>(Obj).default.Foo;
+\tO=new(self)class'Foo';
+}
+";
+        let expected = "\
+function F()
+{
+\tX += -Y * (Z - 1) >> 2;
+\tS = A @ \"b\" $ C;
+\tB = !bFoo && I++ < 10 ? Arr[I] : -1;
+\tArr[I] = class<Actor>(Obj).default.Foo;
+\tO = new (self) class'Foo';
+}
+";
+        assert_eq!(format(source, &Options::default()), expected);
+    }
+
+    #[test]
+    fn comments_directives_and_cpp() {
+        let source = "\
+var private{private} array<int> Arr; // trailing
+  `if(`isdefined(DEBUG))
+var int   X;
+  `else
+var int Y;
+  `endif
+
+cpptext
+{
+  void   Foo() { }
+}
+
+state Idle
+{
+Begin:
+  /* block */ Sleep(1.0);
+  switch (X)
+  {
+  case 1:
+  `log(\"one\");
+  break;
+  default:
+  break;
+  }
+}
+
+defaultproperties
+{
+  Begin Object Class=Foo Name=Bar
+  Prop=True
+  End Object
+  X=(A=1, B=None)
+}
+";
+        let expected = "\
+var private{private} array<int> Arr; // trailing
+`if(`isdefined(DEBUG))
+var int X;
+`else
+var int Y;
+`endif
+
+cpptext
+{
+  void   Foo() { }
+}
+
+state Idle
+{
+Begin:
+\t/* block */ Sleep(1.0);
+\tswitch (X)
+\t{
+\t\tcase 1:
+\t\t\t`log(\"one\");
+\t\t\tbreak;
+\t\tdefault:
+\t\t\tbreak;
+\t}
+}
+
+defaultproperties
+{
+\tbegin object Class=Foo Name=Bar
+\t\tProp=true
+\tend object
+\tX=(A=1, B=none)
+}
+";
+        assert_eq!(format(source, &Options::default()), expected);
+    }
+}
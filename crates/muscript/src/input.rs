@@ -1,27 +1,197 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use muscript_analysis::{ClassSourceFile, ClassSources, CompilerInput};
-use muscript_foundation::{errors::DiagnosticSink, ident::CaseInsensitive, source::SourceFileId};
-use muscript_lexer::{sources::OwnedSources, token::Token};
-use muscript_preprocessor::Definitions;
+use anyhow::Context;
+use camino::Utf8Path;
+use muscript_analysis::{
+    partition::UntypedClassPartition, ClassSourceFile, ClassSources, CompilerInput,
+};
+use muscript_foundation::{
+    errors::{codes, Diagnostic, DiagnosticSink},
+    ident::CaseInsensitive,
+    source::{SourceFileId, SourceFileSet},
+};
+use muscript_lexer::{
+    lint_directives::apply_lint_directives,
+    sources::{LexedSources, OwnedSources},
+    token::Token,
+};
+use muscript_preprocessor::{Definitions, IncludeFiles, Modes};
+use muscript_syntax::cst;
+use rayon::ThreadPool;
+use tracing::info_span;
 
-use crate::parse::parse_source;
+use crate::{
+    cache::Cache,
+    files::read_source_file,
+    parse::{parse_source, parse_sources_in_parallel, ParsedSource},
+};
 
 struct Sources {
     source_files: Vec<SourceFileId>,
 }
 
+#[derive(Default)]
 pub struct Input {
     class_sources: HashMap<CaseInsensitive<String>, Sources>,
     pub global_definitions: Definitions,
+    /// Files `` `include `` directives may refer to.
+    pub include_files: IncludeFiles,
+    /// Modes source files are preprocessed in.
+    pub preprocessor_modes: Modes,
+    cache: Option<Cache>,
+    /// Files which may be loaded from and saved to the cache.
+    cached_files: HashSet<SourceFileId>,
+    /// Partitions of classes kept in memory for later compilations, if enabled.
+    kept_partitions: Option<RefCell<HashMap<CaseInsensitive<String>, Vec<UntypedClassPartition>>>>,
+    /// Files parsed ahead of time by [`Input::parse_in_parallel`], waiting to be requested by
+    /// the compiler.
+    parsed_in_advance: RefCell<HashMap<SourceFileId, ParsedSource<cst::File>>>,
 }
 
 impl Input {
     pub fn new() -> Self {
-        Self {
-            class_sources: Default::default(),
-            global_definitions: Definitions::default(),
+        Self::default()
+    }
+
+    /// Enables caching of the given files. Classes are only cached if all of their files are.
+    pub fn set_cache(&mut self, cache: Cache, cached_files: HashSet<SourceFileId>) {
+        self.cache = Some(cache);
+        self.cached_files = cached_files;
+    }
+
+    /// Keeps the partitions of classes in memory, so that compiling with the same token arena
+    /// again does not have to parse the classes anew. Partitions of classes whose sources change
+    /// must be discarded with [`Input::forget_partitions`].
+    pub fn keep_partitions_in_memory(&mut self) {
+        self.kept_partitions = Some(RefCell::new(HashMap::new()));
+    }
+
+    pub fn forget_partitions(&mut self, class_name: &str) {
+        if let Some(kept_partitions) = &mut self.kept_partitions {
+            kept_partitions
+                .get_mut()
+                .remove(CaseInsensitive::new_ref(class_name));
+        }
+    }
+
+    /// Returns whether the partitions of the class are kept in memory.
+    #[cfg(test)]
+    pub fn has_kept_partitions(&self, class_name: &str) -> bool {
+        self.kept_partitions
+            .as_ref()
+            .is_some_and(|kept_partitions| {
+                kept_partitions
+                    .borrow()
+                    .contains_key(CaseInsensitive::new_ref(class_name))
+            })
+    }
+
+    /// Discards the partitions of all classes, such as when the token arena they refer to is
+    /// started anew.
+    pub fn forget_all_partitions(&mut self) {
+        if let Some(kept_partitions) = &mut self.kept_partitions {
+            kept_partitions.get_mut().clear();
+        }
+    }
+
+    /// Parses the source files of the given classes ahead of time on the thread pool, so that the
+    /// compiler does not have to parse them one by one as it requests them.
+    ///
+    /// Classes whose partitions are available without parsing are skipped. Files parsed by an
+    /// earlier call that were never requested are discarded.
+    pub fn parse_in_parallel(
+        &mut self,
+        thread_pool: &ThreadPool,
+        sources: &mut OwnedSources<'_>,
+        class_names: &[String],
+    ) {
+        let ids: Vec<_> = class_names
+            .iter()
+            .filter(|class_name| {
+                !self
+                    .kept_partitions
+                    .as_ref()
+                    .is_some_and(|kept_partitions| {
+                        kept_partitions
+                            .borrow()
+                            .contains_key(CaseInsensitive::new_ref(class_name))
+                    })
+            })
+            .filter_map(|class_name| self.class_sources.get(CaseInsensitive::new_ref(class_name)))
+            .flat_map(|sources| sources.source_files.iter().copied())
+            .filter(|&id| {
+                !self.cached_files.contains(&id) && sources.source_file_set.get(id).is_loaded()
+            })
+            .collect();
+        let parsed = parse_sources_in_parallel(
+            thread_pool,
+            sources,
+            &self.global_definitions,
+            &self.include_files,
+            &self.preprocessor_modes,
+            &ids,
+        );
+        *self.parsed_in_advance.get_mut() = parsed
+            .into_iter()
+            .map(|parsed| (parsed.id, parsed))
+            .collect();
+    }
+
+    /// Loads the source code of the class's files that were added to the source file set without
+    /// it. Returns whether all of the class's files could be loaded; files that could not be
+    /// loaded are reported to `diagnostics`.
+    fn load_class_sources(
+        &self,
+        source_file_set: &SourceFileSet,
+        class_name: &str,
+        diagnostics: &mut dyn DiagnosticSink<Token>,
+    ) -> bool {
+        let Some(sources) = self.class_sources.get(CaseInsensitive::new_ref(class_name)) else {
+            return false;
+        };
+        let mut all_loaded = true;
+        for &id in &sources.source_files {
+            let source_file = source_file_set.get(id);
+            if source_file.is_loaded() {
+                continue;
+            }
+
+            let _span = info_span!("load_source_file", source_file.filename).entered();
+            let result = Utf8Path::from_path(&source_file.full_path)
+                .context("source file path contains invalid UTF-8")
+                .and_then(read_source_file);
+            match result {
+                Ok(source) => source_file_set.load(id, Arc::from(source)),
+                Err(error) => {
+                    diagnostics.emit(
+                        Diagnostic::error(format!(
+                            "cannot read source file `{}`",
+                            source_file.filename
+                        ))
+                        .with_code(codes::M0008)
+                        .with_note(format!("note: {error:#}")),
+                    );
+                    all_loaded = false;
+                }
+            }
         }
+        all_loaded
+    }
+
+    fn cache_for(&self, class_name: &str) -> Option<(&Cache, &[SourceFileId])> {
+        let cache = self.cache.as_ref()?;
+        let sources = self
+            .class_sources
+            .get(CaseInsensitive::new_ref(class_name))?;
+        sources
+            .source_files
+            .iter()
+            .all(|id| self.cached_files.contains(id))
+            .then_some((cache, &sources.source_files[..]))
     }
 
     pub fn add(&mut self, class_name: &str, source_file: SourceFileId) {
@@ -39,6 +209,16 @@ impl Input {
             );
         }
     }
+
+    pub fn remove(&mut self, class_name: &str, source_file: SourceFileId) {
+        let class_name = CaseInsensitive::new_ref(class_name);
+        if let Some(sources) = self.class_sources.get_mut(class_name) {
+            sources.source_files.retain(|&id| id != source_file);
+            if sources.source_files.is_empty() {
+                self.class_sources.remove(class_name);
+            }
+        }
+    }
 }
 
 impl CompilerInput for Input {
@@ -47,6 +227,14 @@ impl CompilerInput for Input {
             .contains_key(CaseInsensitive::new_ref(class_name))
     }
 
+    fn class_names(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(
+            self.class_sources
+                .keys()
+                .map(|class_name| class_name.as_str()),
+        )
+    }
+
     fn class_source_ids(&self, class_name: &str) -> Option<Vec<SourceFileId>> {
         self.class_sources
             .get(CaseInsensitive::new_ref(class_name))
@@ -59,23 +247,91 @@ impl CompilerInput for Input {
         class_name: &str,
         diagnostics: &mut dyn DiagnosticSink<Token>,
     ) -> Option<ClassSources> {
+        self.load_class_sources(owned_sources.source_file_set, class_name, diagnostics);
         self.class_sources
             .get(CaseInsensitive::new_ref(class_name))
             .map(|sources| {
                 sources
                     .source_files
                     .iter()
+                    .filter(|&&id| owned_sources.source_file_set.get(id).is_loaded())
                     .flat_map(|&id| {
-                        let result = parse_source(
-                            owned_sources,
-                            &mut self.global_definitions.clone(),
-                            id,
-                            diagnostics,
-                        );
+                        if let Some(parsed) = self.parsed_in_advance.borrow_mut().remove(&id) {
+                            parsed.diagnostics.replay_into(diagnostics);
+                            return parsed
+                                .result
+                                .map(|file| ClassSourceFile { id, parsed: file });
+                        }
+                        let definitions = &mut self.global_definitions.clone();
+                        let result = match &self.cache {
+                            Some(cache) if self.cached_files.contains(&id) => cache.parse_source(
+                                owned_sources,
+                                definitions,
+                                &self.include_files,
+                                id,
+                                diagnostics,
+                            ),
+                            _ => parse_source(
+                                owned_sources,
+                                definitions,
+                                &self.include_files,
+                                &self.preprocessor_modes,
+                                id,
+                                diagnostics,
+                            ),
+                        };
                         result.map(|file| ClassSourceFile { id, parsed: file })
                     })
                     .collect()
             })
             .map(|source_files| ClassSources { source_files })
     }
+
+    fn cached_class_partitions(
+        &self,
+        sources: &mut OwnedSources<'_>,
+        class_name: &str,
+        diagnostics: &mut dyn DiagnosticSink<Token>,
+    ) -> Option<Vec<UntypedClassPartition>> {
+        if let Some(kept_partitions) = &self.kept_partitions {
+            if let Some(partitions) = kept_partitions
+                .borrow()
+                .get(CaseInsensitive::new_ref(class_name))
+            {
+                // The tokens are still in the arena, but lint levels need to be set again.
+                for &id in &self.class_sources[CaseInsensitive::new_ref(class_name)].source_files {
+                    apply_lint_directives(
+                        &sources.token_arena,
+                        sources.source_file_set.source(id),
+                        sources.token_arena.source_file_span(id),
+                        diagnostics,
+                    );
+                }
+                return Some(partitions.clone());
+            }
+        }
+        let (cache, ids) = self.cache_for(class_name)?;
+        // Files that cannot be loaded are reported once the class is parsed.
+        if !self.load_class_sources(sources.source_file_set, class_name, &mut ()) {
+            return None;
+        }
+        cache.class_partitions(sources, ids, diagnostics)
+    }
+
+    fn save_class_partitions(
+        &self,
+        sources: &LexedSources<'_>,
+        class_name: &str,
+        partitions: &[UntypedClassPartition],
+    ) {
+        if let Some(kept_partitions) = &self.kept_partitions {
+            kept_partitions.borrow_mut().insert(
+                CaseInsensitive::new(class_name.to_owned()),
+                partitions.to_vec(),
+            );
+        }
+        if let Some((cache, ids)) = self.cache_for(class_name) {
+            cache.save_class_partitions(sources, ids, partitions);
+        }
+    }
 }
@@ -0,0 +1,13 @@
+//! Shared infrastructure for tools that drive the MuScript compiler.
+
+pub mod cache;
+pub mod config;
+pub mod doc;
+pub mod expand;
+pub mod files;
+pub mod fix;
+pub mod format;
+pub mod input;
+pub mod parse;
+pub mod session;
+pub mod watch;
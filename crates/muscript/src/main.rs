@@ -1,45 +1,150 @@
-mod input;
-mod parse;
+use std::{collections::HashSet, num::NonZeroUsize, path::PathBuf, sync::Arc, time::Instant};
 
-use std::{collections::HashSet, path::PathBuf, rc::Rc};
-
-use anyhow::{anyhow, bail, Context};
+use anyhow::Context;
 use camino::{Utf8Path, Utf8PathBuf};
-use clap::Parser;
-use muscript_analysis::{ir::dump::DumpFunction, Compiler, Environment, Package};
+use clap::{Parser, Subcommand, ValueEnum};
+use muscript::{
+    cache::Cache,
+    config::{definitions_source, parse_macro_name, Define, ProjectConfig, CONFIG_FILE_NAME},
+    doc::{self, DocClass},
+    expand::preprocessed_source,
+    files::{canonical_path, read_source_file, read_source_file_with_encoding, read_source_files},
+    fix::{apply_edits, unified_diff, Fixes},
+    format::format_source,
+    parse::{lex_source, parse_tokens, preprocess_include_files, preprocess_tokens},
+    session::{self, Compilation, Session},
+    watch::{Changes, SourceWatcher},
+};
+use muscript_analysis::{
+    ir::{
+        dump::{DumpCfg, DumpFunction},
+        optimize::{OptimizationLevel, PassManager},
+    },
+    Compiler, CompilerInput, Environment, Package,
+};
 use muscript_foundation::{
-    errors::{DiagnosticConfig, Severity},
+    errors::{
+        codes,
+        lints::{LintLevel, LintSelector, LintSettings},
+        sarif_log, Diagnostic, DiagnosticConfig, Severity,
+    },
+    ident::CaseInsensitive,
     source::{SourceFile, SourceFileSet},
     source_arena::SourceArena,
 };
-use muscript_lexer::{sources::OwnedSources, LexerErrors};
-use muscript_syntax::cst;
-use parse::parse_source;
+use muscript_lexer::{
+    sources::{OwnedSources, SynthesizedTokens},
+    token::Token,
+    LexerErrors,
+};
+use muscript_preprocessor::{Definitions, IncludeFiles, Modes};
+use muscript_syntax::{
+    cst,
+    dump::{DumpContext, DumpCst},
+};
 use tracing::{error, info, info_span, metadata::LevelFilter, warn};
 use tracing_subscriber::{prelude::*, EnvFilter};
-use walkdir::WalkDir;
-
-use crate::input::Input;
 
 #[derive(Debug, Parser)]
 pub struct Args {
-    /// Directory containing the package sources (one directory above `Classes`).
+    /// Directory containing the package sources (one directory above `Classes`), or a directory
+    /// containing a `muscript.toml` project file. Defaults to the current directory, which must
+    /// then contain a project file.
     ///
     /// The `Classes` directory within will be walked to find source files to compile.
-    package: Utf8PathBuf,
+    package: Option<Utf8PathBuf>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 
-    /// External source packages. At least `Core` should be provided here.
-    #[clap(short = 's', long)]
+    /// External source packages. At least `Core` should be provided here, unless it's listed in
+    /// the project file. These are searched after the project file's sources.
+    #[clap(short = 's', long, global = true)]
     source: Vec<Utf8PathBuf>,
 
     /// Print debug notes for diagnostics that have them.
-    #[clap(long)]
+    #[clap(long, global = true)]
     diagnostics_debug_info: bool,
 
     /// Do not filter out diagnostics from external packages.
-    #[clap(long)]
+    #[clap(long, global = true)]
     diagnostics_external: bool,
 
+    /// Report diagnostics from the given lint as warnings. `warnings` refers to all lints.
+    #[clap(short = 'W', long = "warn", value_name = "LINT", global = true)]
+    warn: Vec<LintSelector>,
+
+    /// Discard diagnostics from the given lint. `warnings` refers to all lints.
+    #[clap(short = 'A', long = "allow", value_name = "LINT", global = true)]
+    allow: Vec<LintSelector>,
+
+    /// Report diagnostics from the given lint as errors. `warnings` refers to all lints.
+    /// There is no short form, since `-D` defines preprocessor macros.
+    #[clap(long = "deny", value_name = "LINT", global = true)]
+    deny: Vec<LintSelector>,
+
+    /// Define a preprocessor macro, as `NAME` or `NAME=tokens`. Command line definitions are
+    /// made after those from the project file and the selected profile, and before any include
+    /// files are processed.
+    #[clap(
+        short = 'D',
+        long = "define",
+        value_name = "NAME[=TOKENS]",
+        global = true
+    )]
+    define: Vec<Define>,
+
+    /// Undefine a preprocessor macro defined by the project file, the selected profile, or
+    /// `--define`.
+    #[clap(
+        short = 'U',
+        long = "undefine",
+        value_name = "NAME",
+        value_parser = parse_macro_name,
+        global = true
+    )]
+    undefine: Vec<String>,
+
+    /// Build profile to select preprocessor definitions from. `debug` defines `DEBUG` and
+    /// `release` defines `FINAL_RELEASE`; further profiles can be set up in the project file.
+    #[clap(long, value_name = "NAME", global = true)]
+    profile: Option<String>,
+
+    /// How diagnostics should be printed to standard error.
+    #[clap(long, value_enum, default_value_t = MessageFormat::Human, global = true)]
+    message_format: MessageFormat,
+
+    /// Apply machine-applicable suggestions from diagnostics to the package's source files.
+    #[clap(long)]
+    fix: bool,
+
+    /// Print the changes `--fix` would make as a unified diff, without modifying any files.
+    #[clap(long, conflicts_with = "fix")]
+    fix_dry_run: bool,
+
+    /// Do not load or save cached tokens and syntax trees of external packages.
+    #[clap(long, global = true)]
+    no_cache: bool,
+
+    /// Directory to store the cache in. Defaults to a `muscript` directory within the user's
+    /// cache directory.
+    #[clap(long, value_name = "DIR", conflicts_with = "no_cache", global = true)]
+    cache_dir: Option<PathBuf>,
+
+    /// Number of threads to load, lex, and parse source files on. Defaults to the number of
+    /// available CPU cores.
+    #[clap(short = 'j', long, value_name = "N", global = true)]
+    jobs: Option<NonZeroUsize>,
+
+    /// Keep running, and recompile the package whenever its source files change. Only the
+    /// changed classes and the classes depending on them are recompiled.
+    #[clap(long, conflicts_with_all = ["fix", "fix_dry_run"])]
+    watch: bool,
+
+    /// Print a detailed explanation of the given error code (such as `M0427`) and exit.
+    #[clap(long, value_name = "CODE")]
+    explain: Option<String>,
+
     /// Print the analyzed package.
     #[clap(long)]
     dump_analysis_output: bool,
@@ -48,320 +153,870 @@ pub struct Args {
     #[clap(long)]
     dump_global_definitions: bool,
 
+    /// Print the given source file as the parser sees it after preprocessing, and exit. The file
+    /// may belong to the package or to any of its external packages.
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["watch", "fix", "fix_dry_run"])]
+    dump_preprocessed: Option<Utf8PathBuf>,
+
+    /// Mark the tokens each macro expanded to in the output of `--dump-preprocessed`.
+    #[clap(long, requires = "dump_preprocessed")]
+    annotate_expansions: bool,
+
     /// Print function IRs.
     #[clap(long)]
     dump_ir: bool,
 
+    /// Write the control flow graph of each function into the given directory, as one Graphviz
+    /// `.dot` file per function.
+    #[clap(long)]
+    dump_cfg: Option<Utf8PathBuf>,
+
+    /// Optimization level. 0 disables optimizations, 1 only cleans up redundant IR, and 2 runs
+    /// all optimization passes.
+    #[clap(short = 'O', default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2))]
+    opt_level: u8,
+
+    /// Check that function IRs are well-formed. This is always done in debug builds.
+    #[clap(long)]
+    verify_ir: bool,
+
     /// Output a performance trace (in Chrome trace event format) to the specified path. https://profiler.firefox.com/
     #[clap(long)]
     trace: Option<PathBuf>,
 }
 
-pub fn fallible_main(args: Args) -> anyhow::Result<()> {
-    let _span = info_span!("muscript").entered();
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Write API reference documentation for the classes of the package and its external
+    /// packages, instead of compiling the package.
+    Doc(DocArgs),
+    /// Reformat the source files of the package's classes, according to the `[format]` section
+    /// of the project file.
+    Fmt(FmtArgs),
+    /// Parse a single source file on its own, without loading a package, and report its syntax
+    /// errors. Only preprocessor definitions from `--define` and `--undefine` are available to it.
+    Parse(ParseArgs),
+}
 
-    let mut include_files = vec![];
+#[derive(Debug, clap::Args)]
+pub struct DocArgs {
+    /// Directory to write the documentation into. It's created if it does not exist.
+    #[clap(short = 'o', long, value_name = "DIR", default_value = "doc")]
+    output: Utf8PathBuf,
 
-    let main_package_name = Rc::from(get_package_name(&args.package)?);
-    let compiled_sources = {
-        let _span = info_span!("list_main_package_sources", %main_package_name).entered();
-        let mut listing = list_source_files_in_package(&args.package)?;
-        info!(
-            source_count = listing.source.len(),
-            include_count = listing.include.len()
-        );
-        include_files.append(&mut listing.include);
-        listing.source
-    };
+    /// Format of the generated pages.
+    #[clap(long, value_enum, default_value_t = DocFormat::Html)]
+    format: DocFormat,
+}
 
-    let (external_sources, package_names) = {
-        let _span = info_span!("list_sources_of_external_packages").entered();
+#[derive(Debug, clap::Args)]
+pub struct FmtArgs {
+    /// Print the changes formatting would make as a unified diff, without modifying any files,
+    /// and fail if there are any.
+    #[clap(long)]
+    check: bool,
+}
 
-        let mut external_sources = vec![];
-        let package_names: Vec<Rc<str>> = args
-            .source
-            .iter()
-            .map(|package_path| get_package_name(package_path).map(Rc::from))
-            .collect::<Result<Vec<_>, _>>()?;
-        for (i, external_dir) in args.source.iter().enumerate() {
-            let _span = info_span!(
-                "list_external_package_sources",
-                package_name = %package_names[i]
-            )
-            .entered();
-
-            let mut listing = list_source_files_in_package(external_dir)?;
-            info!(
-                source_count = listing.source.len(),
-                include_count = listing.include.len()
-            );
-            external_sources.extend(listing.source.into_iter().map(|path| (i, path)));
-            include_files.append(&mut listing.include);
-        }
+#[derive(Debug, clap::Args)]
+pub struct ParseArgs {
+    /// Source file to parse.
+    file: Utf8PathBuf,
 
-        info!(source_file_count = external_sources.len());
-        (external_sources, package_names)
-    };
+    /// Print the concrete syntax tree of the file to standard output.
+    #[clap(long, value_enum, value_name = "FORMAT")]
+    dump_cst: Option<CstFormat>,
+}
 
-    // This is kind of inefficient right now because we also load files that we aren't particularly
-    // going to use. Thankfully OS-level caching helps alleviate this a bit, but cold compilations
-    // are still quite slow because of this extra step.
-    let (source_file_set, include_file_ids, main_package_source_file_ids) = {
-        let _span = info_span!("build_source_file_set").entered();
-
-        let mut source_file_set = SourceFileSet::new();
-
-        let include_file_ids = {
-            let _span = info_span!("load_include_files").entered();
-
-            let mut include_file_ids = vec![];
-            let include_package = Rc::from("<include>");
-            for path in include_files {
-                let source = read_source_file(&path)?;
-                include_file_ids.push(source_file_set.add(SourceFile::new(
-                    Rc::clone(&include_package),
-                    path.to_string(),
-                    PathBuf::from(path),
-                    Rc::from(source),
-                )));
-            }
-            include_file_ids
-        };
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CstFormat {
+    /// A JSON object per node, holding the node's kind, byte range, and fields.
+    Json,
+}
 
-        let main_package_source_file_ids = {
-            let _span = info_span!("load_main_package_sources").entered();
-
-            let mut main_package_source_file_ids = HashSet::new();
-            for path in compiled_sources {
-                let source = read_source_file(&path)?;
-                let filename = pretty_file_name(&args.package, &path);
-                main_package_source_file_ids.insert(source_file_set.add(SourceFile::new(
-                    Rc::clone(&main_package_name),
-                    filename,
-                    PathBuf::from(path),
-                    Rc::from(source),
-                )));
-            }
-            main_package_source_file_ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DocFormat {
+    /// A static HTML site.
+    Html,
+    /// Markdown files, for hosting alongside other documentation.
+    Markdown,
+}
+
+fn apply_fixes(
+    source_file_set: &SourceFileSet,
+    fixes: &Fixes,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    for (file_id, source_file) in source_file_set.iter() {
+        let Some(edits) = fixes.edits.get(&file_id).filter(|edits| !edits.is_empty()) else {
+            continue;
         };
+        let fixed = apply_edits(source_file.source(), edits);
+        if dry_run {
+            print!(
+                "{}",
+                unified_diff(&source_file.filename, source_file.source(), &fixed)
+            );
+            continue;
+        }
 
-        {
-            let _span = info_span!("load_external_sources").entered();
-
-            for (i, path) in external_sources {
-                let external_source_path = &args.source[i];
-                let package_name = Rc::clone(&package_names[i]);
-                let filename = pretty_file_name(external_source_path, &path);
-                let source = read_source_file(&path)?;
-                source_file_set.add(SourceFile::new(
-                    package_name,
-                    filename,
-                    PathBuf::from(path),
-                    Rc::from(source),
-                ));
-            }
+        let path = Utf8Path::from_path(&source_file.full_path)
+            .with_context(|| format!("path {:?} contains invalid UTF-8", source_file.full_path))?;
+        // The file is read again to find out its encoding, which also guards against
+        // overwriting changes made while the compiler was running.
+        let (source_on_disk, encoding) = read_source_file_with_encoding(path)?;
+        if *source_on_disk != **source_file.source() {
+            warn!(
+                "{} was modified during compilation; not applying fixes to it",
+                source_file.filename
+            );
+            continue;
         }
+        std::fs::write(path, encoding.encode(&fixed))
+            .with_context(|| format!("cannot write fixed source file to {path:?}"))?;
+        eprintln!(
+            "fixed {} ({} {})",
+            source_file.filename,
+            edits.len(),
+            if edits.len() == 1 { "fix" } else { "fixes" }
+        );
+    }
+    if !fixes.overlapping.is_empty() {
+        warn!(
+            "{} suggestions were not applied because they overlap with other suggestions; run with --fix again to apply them",
+            fixes.overlapping.len()
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    /// Rendered diagnostics with source code snippets.
+    Human,
+    /// One JSON object per diagnostic, one diagnostic per line.
+    Json,
+    /// A single SARIF log containing all diagnostics, printed once compilation finishes.
+    Sarif,
+    /// One `file:line:column: severity: message` line per diagnostic.
+    Short,
+}
+
+/// Lint levels set with `-W`, `-A`, and `--deny`. When a lint is given to more than one of these
+/// flags, the strictest level wins.
+fn command_line_lint_settings(args: &Args) -> LintSettings {
+    let mut settings = LintSettings::new();
+    for (selectors, level) in [
+        (&args.allow, LintLevel::Allow),
+        (&args.warn, LintLevel::Warn),
+        (&args.deny, LintLevel::Deny),
+    ] {
+        for &selector in selectors {
+            settings.set(selector, level);
+        }
+    }
+    settings
+}
+
+/// Preprocesses a single source file and prints the result, along with any diagnostics
+/// emitted while preprocessing it.
+fn dump_preprocessed(session: &mut Session, args: &Args, path: &Utf8Path) -> anyhow::Result<()> {
+    let canonical = canonical_path(path.as_std_path());
+    let id = session
+        .source_file_set
+        .iter()
+        .find(|(_, source_file)| canonical_path(&source_file.full_path) == canonical)
+        .map(|(id, _)| id)
+        .with_context(|| {
+            format!("{path} is not a source file of the package or its external packages")
+        })?;
+    if !session.source_file_set.get(id).is_loaded() {
+        let source = read_source_file(path)?;
+        session.source_file_set.load(id, Arc::from(source));
+    }
 
-        (
-            source_file_set,
-            include_file_ids,
-            main_package_source_file_ids,
+    let mut diagnostics = vec![];
+    let mut sources = OwnedSources {
+        source_file_set: &session.source_file_set,
+        token_arena: std::mem::take(&mut session.token_arena),
+        lexer_errors: std::mem::take(&mut session.lexer_errors),
+        synthesized_tokens: std::mem::take(&mut session.synthesized_tokens),
+    };
+    preprocess_include_files(
+        &mut sources,
+        &mut session.input.global_definitions,
+        &session.input.include_files,
+        &session.input.preprocessor_modes,
+        &session.include_file_ids,
+        &mut diagnostics,
+    );
+    let token_span = lex_source(&mut sources, id, &mut diagnostics);
+    let mut preprocessed = preprocess_tokens(
+        sources.as_borrowed(),
+        &mut session.input.global_definitions.clone(),
+        &session.input.include_files,
+        &session.input.preprocessor_modes,
+        token_span,
+        &mut diagnostics,
+    );
+    sources.synthesize(&mut preprocessed);
+    print!(
+        "{}",
+        preprocessed_source(
+            sources.as_borrowed(),
+            &preprocessed,
+            args.annotate_expansions
         )
+    );
+
+    session.token_arena = sources.token_arena;
+    session.lexer_errors = sources.lexer_errors;
+    session.synthesized_tokens = sources.synthesized_tokens;
+    emit_diagnostics(args, session, &diagnostics)
+}
+
+/// Writes documentation for all classes of the package and its external packages.
+fn document(session: &mut Session, args: &Args, doc_args: &DocArgs) -> anyhow::Result<()> {
+    let unloaded: Vec<_> = session
+        .source_file_set
+        .iter()
+        .filter(|(_, source_file)| !source_file.is_loaded())
+        .map(|(id, source_file)| (id, source_file.full_path.clone()))
+        .collect();
+    let paths = unloaded
+        .iter()
+        .map(|(_, path)| {
+            Utf8PathBuf::try_from(path.clone())
+                .with_context(|| format!("path {path:?} contains invalid UTF-8"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let sources = read_source_files(&session.thread_pool, &paths)?;
+    for ((id, _), source) in unloaded.into_iter().zip(sources) {
+        session.source_file_set.load(id, Arc::from(source));
+    }
+
+    let mut env = Environment::new();
+    let mut sources = OwnedSources {
+        source_file_set: &session.source_file_set,
+        token_arena: std::mem::take(&mut session.token_arena),
+        lexer_errors: std::mem::take(&mut session.lexer_errors),
+        synthesized_tokens: std::mem::take(&mut session.synthesized_tokens),
+    };
+    preprocess_include_files(
+        &mut sources,
+        &mut session.input.global_definitions,
+        &session.input.include_files,
+        &session.input.preprocessor_modes,
+        &session.include_file_ids,
+        &mut env,
+    );
+    let class_names: Vec<_> = session.input.class_names().map(String::from).collect();
+    session
+        .input
+        .parse_in_parallel(&session.thread_pool, &mut sources, &class_names);
+
+    let compiler = &mut Compiler {
+        sources: &mut sources,
+        env: &mut env,
+        input: &session.input,
     };
+    let class_ids: Vec<_> = class_names
+        .iter()
+        .map(|class_name| compiler.env.get_or_create_class(class_name))
+        .collect();
+    for &class_id in &class_ids {
+        compiler.untyped_class_partitions(class_id);
+    }
+    let classes: Vec<_> = class_ids
+        .iter()
+        .filter_map(|&class_id| {
+            let partitions = compiler.env.untyped_class_partitions(class_id)?;
+            let file = compiler
+                .sources
+                .token_arena
+                .source_file_id(partitions.first()?.name.id);
+            Some(DocClass {
+                package: &session.source_file_set.get(file).package,
+                partitions,
+            })
+        })
+        .collect();
+    let format = match doc_args.format {
+        DocFormat::Html => doc::Format::Html,
+        DocFormat::Markdown => doc::Format::Markdown,
+    };
+    let pages = doc::generate(&compiler.sources.as_borrowed(), &classes, format);
+
+    let output = &doc_args.output;
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("cannot create directory {output:?}"))?;
+    for page in &pages {
+        let path = output.join(&page.filename);
+        std::fs::write(&path, &page.contents)
+            .with_context(|| format!("cannot write documentation page {path:?}"))?;
+    }
+    eprintln!(
+        "documented {} classes in {output}",
+        pages.len().saturating_sub(1)
+    );
+
+    session.token_arena = std::mem::take(&mut compiler.sources.token_arena);
+    session.lexer_errors = std::mem::take(&mut compiler.sources.lexer_errors);
+    session.synthesized_tokens = std::mem::take(&mut compiler.sources.synthesized_tokens);
+    emit_diagnostics(args, session, &env.diagnostics)
+}
+
+/// Formats the source files of the main package. Files with syntax errors are not formatted,
+/// and their diagnostics are emitted instead.
+fn format(session: &mut Session, args: &Args, fmt_args: &FmtArgs) -> anyhow::Result<()> {
+    let options = session.config.format_options()?;
 
+    let mut diagnostics = vec![];
     let mut sources = OwnedSources {
-        source_file_set: &source_file_set,
-        token_arena: SourceArena::new(),
-        lexer_errors: LexerErrors::default(),
+        source_file_set: &session.source_file_set,
+        token_arena: std::mem::take(&mut session.token_arena),
+        lexer_errors: std::mem::take(&mut session.lexer_errors),
+        synthesized_tokens: std::mem::take(&mut session.synthesized_tokens),
     };
+    preprocess_include_files(
+        &mut sources,
+        &mut session.input.global_definitions,
+        &session.input.include_files,
+        &session.input.preprocessor_modes,
+        &session.include_file_ids,
+        &mut diagnostics,
+    );
 
-    let (mut input, mut env, classes_to_compile) = {
-        let _span = info_span!("compiler_input").entered();
-
-        let mut input = Input::new();
-        let mut env = Environment::new();
-        let mut classes_to_compile = vec![];
-        for (source_file_id, source_file) in source_file_set.iter() {
-            match source_file.class_name() {
-                Ok(class_name) => {
-                    if main_package_source_file_ids.contains(&source_file_id) {
-                        let class_id = env.get_or_create_class(class_name);
-                        classes_to_compile.push(class_id);
-                    }
-                    input.add(class_name, source_file_id)
-                }
-                Err(error) => error!("Error with file {}: {:?}", source_file.filename, error),
+    let mut file_count = 0;
+    let mut changed_count = 0;
+    let mut failed_count = 0;
+    for (id, source_file) in session.source_file_set.iter() {
+        if !session.main_package_source_file_ids.contains(&id) {
+            continue;
+        }
+        file_count += 1;
+
+        // The formatter only looks at tokens, so files are parsed to make sure it does not
+        // get to see code that is not valid.
+        let mut file_diagnostics = vec![];
+        let token_span = lex_source(&mut sources, id, &mut file_diagnostics);
+        let parsed = parse_tokens::<cst::File>(
+            &mut sources,
+            &mut session.input.global_definitions.clone(),
+            &session.input.include_files,
+            &session.input.preprocessor_modes,
+            token_span,
+            &mut file_diagnostics,
+        );
+        let has_errors = parsed.is_err()
+            || file_diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.severity >= Severity::Error);
+        diagnostics.extend(file_diagnostics);
+        if has_errors {
+            error!(
+                "{} has syntax errors; not formatting it",
+                source_file.filename
+            );
+            failed_count += 1;
+            continue;
+        }
+
+        let formatted = match format_source(sources.as_borrowed(), token_span, &options) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                error!("{error:#}");
+                failed_count += 1;
+                continue;
             }
+        };
+        if formatted == **source_file.source() {
+            continue;
+        }
+        changed_count += 1;
+        if fmt_args.check {
+            print!(
+                "{}",
+                unified_diff(&source_file.filename, source_file.source(), &formatted)
+            );
+            continue;
         }
-        (input, env, classes_to_compile)
-    };
 
-    {
-        let _span = info_span!("include_files").entered();
-
-        for &source_file_id in &include_file_ids {
-            // TODO: Don't ignore the CST, do something with it.
-            let _cst = parse_source::<cst::BareFile>(
-                &mut sources,
-                &mut input.global_definitions,
-                source_file_id,
-                &mut env,
+        let path = Utf8Path::from_path(&source_file.full_path)
+            .with_context(|| format!("path {:?} contains invalid UTF-8", source_file.full_path))?;
+        let (source_on_disk, encoding) = read_source_file_with_encoding(path)?;
+        if *source_on_disk != **source_file.source() {
+            warn!(
+                "{} was modified while formatting; not writing it",
+                source_file.filename
             );
+            continue;
         }
-    };
+        std::fs::write(path, encoding.encode(&formatted))
+            .with_context(|| format!("cannot write formatted source file to {path:?}"))?;
+        eprintln!("formatted {}", source_file.filename);
+    }
 
+    session.token_arena = sources.token_arena;
+    session.lexer_errors = sources.lexer_errors;
+    session.synthesized_tokens = sources.synthesized_tokens;
+    emit_diagnostics(args, session, &diagnostics)?;
+
+    if failed_count > 0 {
+        anyhow::bail!("{failed_count} of {file_count} files could not be formatted");
+    }
+    if fmt_args.check && changed_count > 0 {
+        anyhow::bail!("{changed_count} of {file_count} files are not formatted");
+    }
+    Ok(())
+}
+
+/// Prints the macros defined by include files, if requested with `--dump-global-definitions`.
+fn print_global_definitions(args: &Args, session: &Session) {
     if args.dump_global_definitions {
-        for (name, definition) in &input.global_definitions.map {
+        let sources = session.sources();
+        for (name, definition) in &session.input.global_definitions.map {
             let source = sources.source(&definition.source_span);
             println!("define {name:?} = {source:?}");
         }
     }
+}
 
-    let compiler = &mut Compiler {
-        sources: &mut sources,
-        env: &mut env,
-        input: &input,
+/// Runs the verifier and optimization passes over the package's IR.
+fn process_ir(args: &Args, env: &mut Environment, package: &Package) {
+    let _span = info_span!("process_ir").entered();
+
+    let opt_level = match args.opt_level {
+        0 => OptimizationLevel::None,
+        1 => OptimizationLevel::Basic,
+        _ => OptimizationLevel::Full,
     };
-    let compilation_result = Package::compile(compiler, &classes_to_compile);
+    let verify_ir = args.verify_ir || cfg!(debug_assertions);
+    // Optimization passes expect well-formed IR, so don't run them if that's not the case.
+    if !verify_ir || package.verify(env) {
+        package.optimize(env, &PassManager::for_level(opt_level));
+        if verify_ir && opt_level != OptimizationLevel::None {
+            package.verify(env);
+        }
+    }
+}
 
-    {
-        let _span = info_span!("emit_diagnostics").entered();
-        for diagnostic in &compiler.env.diagnostics {
-            let is_from_external_package = diagnostic.labels.iter().any(|label| {
-                if let Some(start) = label.span.start() {
-                    let source_file = compiler.sources.token_arena.source_file_id(start);
-                    !main_package_source_file_ids.contains(&source_file)
-                } else {
-                    false
-                }
-            });
-            // !main_package_source_file_ids.contains(&diagnostic.source_file);
-            if diagnostic.severity >= Severity::Error
-                || !is_from_external_package
+fn emit_diagnostics(
+    args: &Args,
+    session: &Session,
+    diagnostics: &[Diagnostic<Token>],
+) -> anyhow::Result<()> {
+    let _span = info_span!("emit_diagnostics").entered();
+    print_diagnostics(
+        args,
+        &session.source_file_set,
+        &session.token_arena,
+        diagnostics.iter().filter(|diagnostic| {
+            session.is_error(diagnostic)
+                || !session.is_from_external_package(diagnostic)
                 || args.diagnostics_external
-            {
-                _ = diagnostic.emit_to_stderr(
-                    &source_file_set,
-                    &compiler.sources.token_arena,
-                    &DiagnosticConfig {
-                        show_debug_info: args.diagnostics_debug_info,
-                    },
-                );
+        }),
+    )
+}
+
+/// Prints diagnostics in the format selected with `--message-format`.
+fn print_diagnostics<'a>(
+    args: &Args,
+    source_file_set: &SourceFileSet,
+    token_arena: &SourceArena<Token>,
+    diagnostics: impl IntoIterator<Item = &'a Diagnostic<Token>>,
+) -> anyhow::Result<()> {
+    let config = DiagnosticConfig {
+        show_debug_info: args.diagnostics_debug_info,
+    };
+    let mut sarif_results = vec![];
+    for diagnostic in diagnostics {
+        match args.message_format {
+            MessageFormat::Human => {
+                _ = diagnostic.emit_to_stderr(source_file_set, token_arena, &config);
             }
+            MessageFormat::Json => eprintln!(
+                "{}",
+                serde_json::to_string(&diagnostic.to_json(source_file_set, token_arena, &config))?
+            ),
+            MessageFormat::Sarif => {
+                sarif_results.push(diagnostic.to_json(source_file_set, token_arena, &config))
+            }
+            MessageFormat::Short => eprint!(
+                "{}",
+                diagnostic
+                    .to_json(source_file_set, token_arena, &config)
+                    .to_short()
+            ),
         }
     }
+    if args.message_format == MessageFormat::Sarif {
+        eprintln!(
+            "{}",
+            serde_json::to_string_pretty(&sarif_log(&sarif_results))?
+        );
+    }
+    Ok(())
+}
 
-    if let Ok(package) = compilation_result {
-        // TODO: Code generation.
-        if args.dump_analysis_output {
-            let _span = info_span!("dump_analysis_output").entered();
-            println!("{:#?}", compiler.env);
-            println!("{package:#?}");
-        }
-        if args.dump_ir {
-            let _span = info_span!("dump_ir").entered();
-            for (&class_id, class) in &package.classes {
+/// Prints the outputs requested with the `--dump-*` flags.
+fn dump_package(
+    args: &Args,
+    session: &Session,
+    env: &Environment,
+    package: &Package,
+) -> anyhow::Result<()> {
+    // TODO: Code generation.
+    if args.dump_analysis_output {
+        let _span = info_span!("dump_analysis_output").entered();
+        println!("{env:#?}");
+        println!("{package:#?}");
+    }
+    if args.dump_ir {
+        let _span = info_span!("dump_ir").entered();
+        for (&class_id, class) in &package.classes {
+            println!(
+                "\n{}\n----------------------------------------------------------------",
+                env.class_name(class_id)
+            );
+            for &function_id in &class.functions {
+                let function = env.get_function(function_id);
+                let ir = env.get_function_ir(function_id);
                 println!(
-                    "\n{}\n----------------------------------------------------------------",
-                    compiler.env.class_name(class_id)
+                    "\n{} {:?}",
+                    function.mangled_name,
+                    DumpFunction {
+                        sources: &session.sources(),
+                        env,
+                        function,
+                        ir,
+                    }
                 );
-                for &function_id in &class.functions {
-                    let function = compiler.env.get_function(function_id);
-                    let ir = compiler.env.get_function_ir(function_id);
-                    println!(
-                        "\n{} {:?}",
-                        function.mangled_name,
-                        DumpFunction {
-                            sources: &compiler.sources.as_borrowed(),
-                            env: compiler.env,
-                            function,
-                            ir,
-                        }
-                    );
-                }
             }
         }
-    } else {
-        error!("Compilation failed, no packages emitted")
     }
+    if let Some(cfg_dir) = &args.dump_cfg {
+        let _span = info_span!("dump_cfg").entered();
+        std::fs::create_dir_all(cfg_dir)
+            .with_context(|| format!("cannot create directory {cfg_dir:?}"))?;
+        for (&class_id, class) in &package.classes {
+            let class_name = env.class_name(class_id);
+            for &function_id in &class.functions {
+                let function = env.get_function(function_id);
+                let Some(ir) = env.get_function_ir(function_id) else {
+                    continue;
+                };
+                let path = cfg_dir.join(format!("{class_name}.{}.dot", function.mangled_name));
+                let dot = format!(
+                    "{:?}",
+                    DumpCfg {
+                        sources: &session.sources(),
+                        env,
+                        function,
+                        ir,
+                    }
+                );
+                std::fs::write(&path, dot)
+                    .with_context(|| format!("cannot write control flow graph to {path:?}"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compiles the package, and then recompiles the parts of it that change until interrupted.
+fn watch(args: &Args, mut session: Session, lint_settings: &[LintSettings]) -> anyhow::Result<()> {
+    let watcher = SourceWatcher::new(&session.package)?;
+    session.input.keep_partitions_in_memory();
 
+    // Diagnostics from all runs, of which those reported in files that weren't recompiled are
+    // carried over into the next run.
+    let mut diagnostics: Vec<Diagnostic<Token>> = vec![];
+    let mut changes = Changes::Everything;
+    for run in 1.. {
+        let start = Instant::now();
+
+        if session.has_outdated_tokens() {
+            // All diagnostics are dropped along with the tokens their spans refer to, so
+            // everything has to be compiled again to report them anew.
+            session.discard_tokens();
+            diagnostics.clear();
+            changes = Changes::Everything;
+        }
+
+        let all_class_names = session.main_class_names();
+        let class_names: Vec<_> = match &changes {
+            Changes::Everything => all_class_names.clone(),
+            Changes::Classes(changed) => {
+                let dirty = session.with_dependents(changed.clone());
+                all_class_names
+                    .iter()
+                    .filter(|&class_name| dirty.contains(CaseInsensitive::new_ref(class_name)))
+                    .cloned()
+                    .collect()
+            }
+        };
+        let recompiled_files: HashSet<_> = class_names
+            .iter()
+            .flat_map(|class_name| {
+                session
+                    .input
+                    .class_source_ids(class_name)
+                    .unwrap_or_default()
+            })
+            .collect();
+        let is_carried_over = |session: &Session, diagnostic: &Diagnostic<Token>| {
+            session.diagnostic_file(diagnostic).is_some_and(|file| {
+                session.main_package_source_file_ids.contains(&file)
+                    && !recompiled_files.contains(&file)
+            })
+        };
+
+        let Compilation { mut env, result } = session.compile(lint_settings, &class_names);
+        print_global_definitions(args, &session);
+        diagnostics.retain(|diagnostic| is_carried_over(&session, diagnostic));
+        diagnostics.extend(
+            std::mem::take(&mut env.diagnostics)
+                .into_iter()
+                .filter(|diagnostic| !is_carried_over(&session, diagnostic)),
+        );
+
+        let compiled_without_errors = !diagnostics.iter().any(|d| session.is_error(d));
+        if let Ok(package) = &result {
+            session.record_class_references(&env, package);
+        }
+        if let (Ok(package), true) = (&result, compiled_without_errors) {
+            process_ir(args, &mut env, package);
+            diagnostics.append(&mut env.diagnostics);
+        }
+        let error_count = diagnostics.iter().filter(|d| session.is_error(d)).count();
+
+        print_run_separator(args, run, &class_names, all_class_names.len(), start);
+        emit_diagnostics(args, &session, &diagnostics)?;
+        if let Ok(package) = &result {
+            dump_package(args, &session, &env, package)?;
+        }
+        let warning_count = diagnostics
+            .iter()
+            .filter(|d| !session.is_error(d) && d.severity == Severity::Warning)
+            .filter(|d| !session.is_from_external_package(d) || args.diagnostics_external)
+            .count();
+        print_run_summary(args, error_count, warning_count);
+
+        changes = loop {
+            let paths = watcher.wait_for_changes()?;
+            match session.apply_changes(&paths)? {
+                Changes::Classes(changed) if changed.is_empty() => continue,
+                changes => break changes,
+            }
+        };
+    }
     Ok(())
 }
 
-fn get_package_name(package: &Utf8Path) -> anyhow::Result<String> {
-    package
-        .file_name()
-        .ok_or_else(|| anyhow!("path {package:?} has no package name"))
-        .map(|package_name| package_name.to_owned())
+fn print_run_separator(
+    args: &Args,
+    run: usize,
+    class_names: &[String],
+    class_count: usize,
+    start: Instant,
+) {
+    let elapsed = start.elapsed();
+    if args.message_format == MessageFormat::Json {
+        eprintln!(
+            "{}",
+            serde_json::json!({
+                "watch_run": run,
+                "recompiled_classes": class_names,
+                "elapsed_seconds": elapsed.as_secs_f64(),
+            })
+        );
+    } else {
+        eprintln!(
+            "\n======== run {run}: compiled {} of {class_count} classes in {elapsed:.2?} ========\n",
+            class_names.len(),
+        );
+    }
 }
 
-#[derive(Debug, Default)]
-struct SourceFileListing {
-    pub source: Vec<Utf8PathBuf>,
-    pub include: Vec<Utf8PathBuf>,
+fn print_run_summary(args: &Args, error_count: usize, warning_count: usize) {
+    if args.message_format != MessageFormat::Json {
+        let plural = |count: usize, word: &str| {
+            format!("{count} {word}{}", if count == 1 { "" } else { "s" })
+        };
+        eprintln!(
+            "{}, {}; waiting for changes...",
+            plural(error_count, "error"),
+            plural(warning_count, "warning")
+        );
+    }
 }
 
-fn list_source_files_in_package(package: &Utf8Path) -> anyhow::Result<SourceFileListing> {
-    let classes_dir = package.join("Classes");
-    if !classes_dir.is_dir() {
-        bail!("{classes_dir:?} is not a directory");
-    }
-
-    let mut listing = SourceFileListing::default();
-    for entry in WalkDir::new(classes_dir) {
-        let entry = entry?;
-        let path = entry.path();
-        if let Some(path) = Utf8Path::from_path(path) {
-            if path.is_file() {
-                match path.extension() {
-                    Some("uc") => listing.source.push(path.to_owned()),
-                    Some("uci") => listing.include.push(path.to_owned()),
-                    _ => (),
-                }
-            }
-        } else {
-            warn!("path contains invalid UTF-8: {path:?}");
+/// Parses a single source file outside of any package, for `muscript parse`.
+fn parse_file(args: &Args, parse_args: &ParseArgs) -> anyhow::Result<()> {
+    let path = &parse_args.file;
+    let mut source_file_set = SourceFileSet::new();
+    let mut include_file_ids = vec![];
+    if !args.define.is_empty() || !args.undefine.is_empty() {
+        let source = definitions_source(
+            args.define
+                .iter()
+                .map(|define| (define.name.as_str(), define.value.as_str())),
+            args.undefine.iter().map(|name| name.as_str()),
+        );
+        include_file_ids.push(source_file_set.add(SourceFile::new(
+            Arc::from("<include>"),
+            "<definitions>".to_owned(),
+            PathBuf::new(),
+            Arc::from(source),
+        )));
+    }
+    let id = source_file_set.add(SourceFile::new(
+        Arc::from("<file>"),
+        path.to_string(),
+        PathBuf::from(path.clone()),
+        Arc::from(read_source_file(path)?),
+    ));
+
+    let mut diagnostics = vec![];
+    let mut sources = OwnedSources {
+        source_file_set: &source_file_set,
+        token_arena: SourceArena::new(),
+        lexer_errors: LexerErrors::default(),
+        synthesized_tokens: SynthesizedTokens::new(),
+    };
+    let mut definitions = Definitions::default();
+    let include_files = IncludeFiles::new();
+    let modes = Modes::new();
+    preprocess_include_files(
+        &mut sources,
+        &mut definitions,
+        &include_files,
+        &modes,
+        &include_file_ids,
+        &mut diagnostics,
+    );
+    let token_span = lex_source(&mut sources, id, &mut diagnostics);
+    let file = parse_tokens::<cst::File>(
+        &mut sources,
+        &mut definitions,
+        &include_files,
+        &modes,
+        token_span,
+        &mut diagnostics,
+    );
+
+    if let Ok(file) = &file {
+        // Function bodies are only parsed while dumping, so the tree is dumped even when it's not
+        // printed, to report their syntax errors.
+        let mut cx = DumpContext::new(sources.as_borrowed(), &mut diagnostics);
+        let cst = file.dump_cst(&mut cx);
+        match parse_args.dump_cst {
+            Some(CstFormat::Json) => println!("{}", serde_json::to_string_pretty(&cst)?),
+            None => (),
         }
     }
 
-    // Special case for Globals.uci, which lives outside the Classes folder.
-    let globals_uci = package.join("Globals.uci");
-    if globals_uci.is_file() {
-        listing.include.push(globals_uci);
+    print_diagnostics(args, &source_file_set, &sources.token_arena, &diagnostics)?;
+    if file.is_err()
+        || diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity >= Severity::Error)
+    {
+        anyhow::bail!("{path} has syntax errors");
     }
-    Ok(listing)
+    Ok(())
 }
 
-fn read_source_file(path: &Utf8Path) -> anyhow::Result<String> {
-    let source_bytes =
-        std::fs::read(path).with_context(|| format!("cannot read source file at {path:?}"))?;
+pub fn fallible_main(args: Args) -> anyhow::Result<()> {
+    if let Some(code) = &args.explain {
+        let explanation = codes::explanation(code)
+            .with_context(|| format!("no explanation is available for error code {code}"))?;
+        print!("{explanation}");
+        return Ok(());
+    }
+    if let Some(Command::Parse(parse_args)) = &args.command {
+        return parse_file(&args, parse_args);
+    }
+    let project_dir = match &args.package {
+        Some(package) => package.clone(),
+        None => {
+            let current_dir = std::env::current_dir().context("cannot get current directory")?;
+            let current_dir = Utf8PathBuf::try_from(current_dir)
+                .context("current directory path contains invalid UTF-8")?;
+            if !ProjectConfig::exists_in(&current_dir) {
+                anyhow::bail!(
+                    "no package directory given, and no {CONFIG_FILE_NAME} found in the current directory"
+                );
+            }
+            current_dir
+        }
+    };
 
-    if source_bytes.starts_with(&[0xFE, 0xFF]) {
-        // UTF-16 big-endian
-        let words: Vec<_> = source_bytes
-            .chunks_exact(2)
-            .map(|arr| (arr[0] as u16) << 8 | arr[1] as u16)
-            .collect();
-        String::from_utf16(&words[1..]).context("encoding error in UTF-16 (big-endian) file")
-    } else if source_bytes.starts_with(&[0xFF, 0xFE]) {
-        // UTF-16 little-endian
-        let words: Vec<_> = source_bytes
-            .chunks_exact(2)
-            .map(|arr| (arr[0]) as u16 | (arr[1] as u16) << 8)
-            .collect();
-        String::from_utf16(&words[1..]).context("encoding error in UTF-16 (little-endian) file")
-    } else {
-        // UTF-8
-        String::from_utf8(source_bytes).context("encoding error in UTF-8 file")
+    let _span = info_span!("muscript").entered();
+
+    let config = ProjectConfig::load(&project_dir)?;
+    let lint_settings = [config.lint_settings()?, command_line_lint_settings(&args)];
+
+    let options = session::Options {
+        sources: args.source.clone(),
+        profile: args.profile.clone(),
+        defines: args.define.clone(),
+        undefines: args.undefine.clone(),
+        jobs: args.jobs,
+        cache_dir: if args.no_cache {
+            None
+        } else {
+            args.cache_dir.clone().or_else(Cache::default_dir)
+        },
+    };
+    let mut session = Session::load(&options, &config)?;
+    if let Some(path) = &args.dump_preprocessed {
+        return dump_preprocessed(&mut session, &args, path);
     }
-}
+    match &args.command {
+        Some(Command::Doc(doc_args)) => return document(&mut session, &args, doc_args),
+        Some(Command::Fmt(fmt_args)) => return format(&mut session, &args, fmt_args),
+        Some(Command::Parse(_)) | None => (),
+    }
+    if args.watch {
+        return watch(&args, session, &lint_settings);
+    }
+
+    let class_names = session.main_class_names();
+    let Compilation { mut env, result } = session.compile(&lint_settings, &class_names);
+    print_global_definitions(&args, &session);
 
-fn pretty_file_name(package_root: &Utf8Path, source_file: &Utf8Path) -> String {
-    let package_root = package_root.parent().unwrap_or(package_root);
-    source_file
-        .strip_prefix(package_root)
-        .expect("source_file must start with package_root")
-        .to_string()
+    // Code that failed to compile may produce IR that is not well-formed, which would only result
+    // in confusing bug reports from the verifier, so IR is only processed when there were no errors.
+    let compiled_without_errors = !env.diagnostics.iter().any(|d| session.is_error(d));
+    if let (Ok(package), true) = (&result, compiled_without_errors) {
+        process_ir(&args, &mut env, package);
+    }
+
+    emit_diagnostics(&args, &session, &env.diagnostics)?;
+
+    if args.fix || args.fix_dry_run {
+        let _span = info_span!("fix").entered();
+        let fixes = Fixes::collect(&env.diagnostics, |file| {
+            session.main_package_source_file_ids.contains(&file)
+        });
+        apply_fixes(&session.source_file_set, &fixes, args.fix_dry_run)?;
+    }
+
+    let compilation_succeeded = result.is_ok() && compiled_without_errors;
+    if let Ok(package) = &result {
+        dump_package(&args, &session, &env, package)?;
+    }
+
+    if let (Some(output), true) = (config.output_path(), compilation_succeeded) {
+        info!(%output, "code generation is not implemented yet; no package was written");
+    }
+
+    if !compilation_succeeded {
+        anyhow::bail!("Compilation failed, no packages emitted");
+    }
+
+    Ok(())
 }
 
 fn main() {
@@ -400,6 +1055,9 @@ fn main() {
 
     match fallible_main(args) {
         Ok(_) => (),
-        Err(error) => error!("{error:?}"),
+        Err(error) => {
+            error!("{error:?}");
+            std::process::exit(1);
+        }
     }
 }
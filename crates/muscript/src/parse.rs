@@ -1,17 +1,28 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use muscript_foundation::{errors::DiagnosticSink, source::SourceFileId};
+use muscript_foundation::{
+    errors::{DiagnosticSink, RecordingSink},
+    source::SourceFileId,
+    source_arena::SourceArena,
+};
 use muscript_lexer::{
-    sliced_tokens::SlicedTokens, sources::OwnedSources, token::Token,
-    token_stream::TokenSpanCursor, Lexer,
+    lint_directives::apply_lint_directives,
+    sliced_tokens::SlicedTokens,
+    sources::{LexedSources, OwnedSources},
+    token::{Token, TokenSpan},
+    token_stream::{TokenSpanCursor, TokenStream},
+    Lexer, LexerErrors,
 };
-use muscript_preprocessor::{Definitions, Preprocessor};
-use muscript_syntax::{Parse, Parser};
+use muscript_preprocessor::{Definitions, IncludeFiles, Modes, Preprocessor};
+use muscript_syntax::{cst, Parse, Parser};
+use rayon::{prelude::*, ThreadPool};
 use tracing::info_span;
 
 pub fn parse_source<T>(
     sources: &mut OwnedSources<'_>,
     definitions: &mut Definitions,
+    includes: &IncludeFiles,
+    modes: &Modes,
     id: SourceFileId,
     diagnostics: &mut dyn DiagnosticSink<Token>,
 ) -> Result<T, muscript_syntax::ParseError>
@@ -21,40 +32,259 @@ where
     let source_file = sources.source_file_set.get(id);
     let _span = info_span!("parse_source", source_file.filename).entered();
 
+    let token_span = lex_source(sources, id, diagnostics);
+    parse_tokens(
+        sources,
+        definitions,
+        includes,
+        modes,
+        token_span,
+        diagnostics,
+    )
+}
+
+/// Lexes and preprocesses include files in order, collecting the macros they define into
+/// `definitions`.
+///
+/// All of the files are lexed before any of them is preprocessed, so that they can `` `include ``
+/// each other regardless of the order they're processed in.
+pub fn preprocess_include_files(
+    sources: &mut OwnedSources<'_>,
+    definitions: &mut Definitions,
+    includes: &IncludeFiles,
+    modes: &Modes,
+    ids: &[SourceFileId],
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) {
+    let token_spans: Vec<_> = ids
+        .iter()
+        .map(|&id| lex_source(sources, id, diagnostics))
+        .collect();
+    for (&id, token_span) in ids.iter().zip(token_spans) {
+        let source_file = sources.source_file_set.get(id);
+        let _span = info_span!("parse_source", source_file.filename).entered();
+        // TODO: Don't ignore the CST, do something with it.
+        let _cst = parse_tokens::<cst::BareFile>(
+            sources,
+            definitions,
+            includes,
+            modes,
+            token_span,
+            diagnostics,
+        );
+    }
+}
+
+/// Lexes a source file into the token arena, and applies lint directives found in it.
+pub fn lex_source(
+    sources: &mut OwnedSources<'_>,
+    id: SourceFileId,
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) -> TokenSpan {
+    let source_file = sources.source_file_set.get(id);
     let token_span = {
         let _span = info_span!("lex").entered();
         let lexer = Lexer::new(
             sources.token_arena.build_source_file(id),
             id,
-            Rc::clone(&source_file.source),
+            Arc::clone(source_file.source()),
             &mut sources.lexer_errors,
         );
         lexer.lex()
     };
+    apply_lint_directives(
+        &sources.token_arena,
+        source_file.source(),
+        token_span,
+        diagnostics,
+    );
+    token_span
+}
 
-    let preprocessed = {
-        let _span = info_span!("preprocess").entered();
-        let mut preprocessed = SlicedTokens::new();
-        let mut preprocessor = Preprocessor::new(
-            definitions,
-            sources.as_borrowed(),
-            TokenSpanCursor::new(&sources.token_arena, token_span)
-                .expect("token span emitted by lexer must not be empty"),
-            &mut preprocessed,
-            diagnostics,
-        );
-        preprocessor.preprocess();
-        preprocessed
+/// Preprocesses and parses tokens previously produced by [`lex_source`].
+pub fn parse_tokens<T>(
+    sources: &mut OwnedSources<'_>,
+    definitions: &mut Definitions,
+    includes: &IncludeFiles,
+    modes: &Modes,
+    token_span: TokenSpan,
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) -> Result<T, muscript_syntax::ParseError>
+where
+    T: Parse,
+{
+    let mut preprocessed = preprocess_tokens(
+        sources.as_borrowed(),
+        definitions,
+        includes,
+        modes,
+        token_span,
+        diagnostics,
+    );
+    sources.synthesize(&mut preprocessed);
+    parse_preprocessed(sources.as_borrowed(), &preprocessed, diagnostics)
+}
+
+/// Preprocesses tokens previously produced by [`lex_source`], in the mode of the file they come
+/// from. Tokens synthesized by the preprocessor have to be added to the token arena with
+/// [`OwnedSources::synthesize`] before the result is parsed.
+pub fn preprocess_tokens(
+    sources: LexedSources<'_>,
+    definitions: &mut Definitions,
+    includes: &IncludeFiles,
+    modes: &Modes,
+    token_span: TokenSpan,
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) -> SlicedTokens {
+    let _span = info_span!("preprocess").entered();
+    let tokens = TokenSpanCursor::new(sources.token_arena, token_span)
+        .expect("token span emitted by lexer must not be empty");
+    let mode = modes.get(sources.token_arena.source_file_id(tokens.position()));
+    let mut preprocessed = SlicedTokens::new();
+    let mut preprocessor = Preprocessor::new(
+        definitions,
+        includes,
+        sources,
+        tokens,
+        &mut preprocessed,
+        diagnostics,
+    )
+    .with_mode(mode);
+    preprocessor.preprocess();
+    preprocessed
+}
+
+/// Parses the output of [`preprocess_tokens`].
+pub fn parse_preprocessed<T>(
+    sources: LexedSources<'_>,
+    preprocessed: &SlicedTokens,
+    diagnostics: &mut dyn DiagnosticSink<Token>,
+) -> Result<T, muscript_syntax::ParseError>
+where
+    T: Parse,
+{
+    let _span = info_span!("parse").entered();
+    let tokens = preprocessed
+        .stream(sources.token_arena)
+        .expect("token slices emitted by preprocessor must not be empty");
+    let mut parser = Parser::new(sources, tokens, diagnostics);
+    parser.parse::<T>()
+}
+
+/// A source file parsed by [`parse_sources_in_parallel`].
+pub struct ParsedSource<T> {
+    pub id: SourceFileId,
+    pub result: Result<T, muscript_syntax::ParseError>,
+    /// Diagnostics and lint levels emitted while lexing and parsing the file, to be replayed
+    /// into the compiler's diagnostic sink once the file is used.
+    pub diagnostics: RecordingSink<Token>,
+}
+
+/// Lexes, preprocesses, and parses source files on the given thread pool.
+///
+/// Each file is lexed into its own token arena first, and the arenas are then appended to
+/// `sources` in the order the files were given in, so that token IDs do not depend on how the
+/// work was scheduled. Each file is preprocessed with its own copy of `definitions`, the same way
+/// [`parse_source`] would be called for each file.
+pub fn parse_sources_in_parallel<T>(
+    thread_pool: &ThreadPool,
+    sources: &mut OwnedSources<'_>,
+    definitions: &Definitions,
+    includes: &IncludeFiles,
+    modes: &Modes,
+    ids: &[SourceFileId],
+) -> Vec<ParsedSource<T>>
+where
+    T: Parse + Send,
+{
+    let source_file_set = sources.source_file_set;
+    let lexed: Vec<_> = {
+        let _span = info_span!("lex_in_parallel", file_count = ids.len()).entered();
+        thread_pool.install(|| {
+            ids.par_iter()
+                .map(|&id| {
+                    let source_file = source_file_set.get(id);
+                    let _span = info_span!("lex", source_file.filename).entered();
+                    let mut token_arena = SourceArena::new();
+                    let mut lexer_errors = LexerErrors::default();
+                    let lexer = Lexer::new(
+                        token_arena.build_source_file(id),
+                        id,
+                        Arc::clone(source_file.source()),
+                        &mut lexer_errors,
+                    );
+                    lexer.lex();
+                    (token_arena, lexer_errors)
+                })
+                .collect()
+        })
     };
 
-    let result = {
-        let _span = info_span!("parse").entered();
-        let tokens = preprocessed
-            .stream(&sources.token_arena)
-            .expect("token slices emitted by preprocessor must not be empty");
-        let mut parser = Parser::new(sources.as_borrowed(), tokens, diagnostics);
-        parser.parse::<T>()
+    let token_spans: Vec<_> = ids
+        .iter()
+        .zip(lexed)
+        .map(|(&id, (token_arena, lexer_errors))| {
+            let relocation = sources.token_arena.append(token_arena);
+            sources.lexer_errors.append(lexer_errors, &relocation);
+            sources.token_arena.source_file_span(id)
+        })
+        .collect();
+
+    let preprocessed: Vec<_> = {
+        let _span = info_span!("preprocess_in_parallel", file_count = ids.len()).entered();
+        let lexed_sources = sources.as_borrowed();
+        thread_pool.install(|| {
+            ids.par_iter()
+                .zip(token_spans)
+                .map(|(&id, token_span)| {
+                    let source_file = source_file_set.get(id);
+                    let _span = info_span!("preprocess_source", source_file.filename).entered();
+                    let mut diagnostics = RecordingSink::new();
+                    apply_lint_directives(
+                        lexed_sources.token_arena,
+                        source_file.source(),
+                        token_span,
+                        &mut diagnostics,
+                    );
+                    let preprocessed = preprocess_tokens(
+                        lexed_sources,
+                        &mut definitions.clone(),
+                        includes,
+                        modes,
+                        token_span,
+                        &mut diagnostics,
+                    );
+                    (preprocessed, diagnostics)
+                })
+                .collect()
+        })
     };
 
-    result
+    // Synthesized tokens are added to the arena in the order the files were given in, for the
+    // same reason as with lexing.
+    let preprocessed: Vec<_> = preprocessed
+        .into_iter()
+        .map(|(mut preprocessed, diagnostics)| {
+            sources.synthesize(&mut preprocessed);
+            (preprocessed, diagnostics)
+        })
+        .collect();
+
+    let _span = info_span!("parse_in_parallel", file_count = ids.len()).entered();
+    let lexed_sources = sources.as_borrowed();
+    thread_pool.install(|| {
+        ids.par_iter()
+            .zip(preprocessed)
+            .map(|(&id, (preprocessed, mut diagnostics))| {
+                let source_file = source_file_set.get(id);
+                let _span = info_span!("parse_source", source_file.filename).entered();
+                let result = parse_preprocessed(lexed_sources, &preprocessed, &mut diagnostics);
+                ParsedSource {
+                    id,
+                    result,
+                    diagnostics,
+                }
+            })
+            .collect()
+    })
 }
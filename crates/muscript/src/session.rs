@@ -0,0 +1,581 @@
+//! Loading a package along with its external packages, and compiling it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use camino::{Utf8Path, Utf8PathBuf};
+use muscript_analysis::{CompileError, Compiler, Environment, Package};
+use muscript_foundation::{
+    errors::{
+        lints::{lint_by_code, LintSettings},
+        Diagnostic, LabelStyle, Severity,
+    },
+    ident::CaseInsensitive,
+    source::{SourceFile, SourceFileId, SourceFileSet},
+    source_arena::SourceArena,
+};
+use muscript_lexer::{
+    sources::{LexedSources, OwnedSources, SynthesizedTokens},
+    token::Token,
+    LexerErrors,
+};
+use muscript_preprocessor::{Definitions, IncludeFiles, Modes};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use tracing::{error, info, info_span, warn};
+
+use crate::{
+    cache::Cache,
+    config::{definitions_source, Define, ProjectConfig},
+    files::{
+        canonical_path, get_package_name, list_source_files_in_package, pretty_file_name,
+        read_source_files,
+    },
+    input::Input,
+    parse::preprocess_include_files,
+};
+
+/// Settings a [`Session`] is loaded with, on top of those from the project file.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// External source packages, searched after the project file's sources.
+    pub sources: Vec<Utf8PathBuf>,
+    /// Build profile to select preprocessor definitions from.
+    pub profile: Option<String>,
+    /// Macros to define after those from the project file and the profile.
+    pub defines: Vec<Define>,
+    /// Macros to undefine after all definitions have been made.
+    pub undefines: Vec<String>,
+    /// Number of threads to load, lex, and parse source files on. Defaults to the number of
+    /// available CPU cores.
+    pub jobs: Option<NonZeroUsize>,
+    /// Directory to cache tokens and syntax trees of external packages in. No cache is used if
+    /// this is `None`.
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// Source files of the package being compiled and of its external packages, along with the
+/// compiler state that is kept around in between compilations of the same package.
+pub struct Session {
+    pub config: ProjectConfig,
+    pub(crate) options: Options,
+    pub package: Utf8PathBuf,
+    pub(crate) main_package_name: Arc<str>,
+    pub source_file_set: SourceFileSet,
+    pub main_package_source_file_ids: HashSet<SourceFileId>,
+    /// Source files of the main package, by their canonical paths.
+    pub(crate) main_package_files_by_path: HashMap<PathBuf, SourceFileId>,
+    /// Include files of the main package, by their canonical paths.
+    pub(crate) main_package_include_files_by_path: HashMap<PathBuf, SourceFileId>,
+    pub include_file_ids: Vec<SourceFileId>,
+    pub input: Input,
+    pub token_arena: SourceArena<Token>,
+    pub lexer_errors: LexerErrors,
+    pub synthesized_tokens: SynthesizedTokens,
+    pub thread_pool: ThreadPool,
+    /// Number of tokens in the arena after the last compilation that started with an empty arena.
+    /// See [`Session::has_outdated_tokens`].
+    pub(crate) fresh_token_count: usize,
+    /// Classes each class of the main package made use of when it was last compiled. See
+    /// [`Session::record_class_references`].
+    pub(crate) class_references: HashMap<CaseInsensitive<String>, HashSet<CaseInsensitive<String>>>,
+}
+
+/// Results of compiling a set of classes.
+pub struct Compilation {
+    pub env: Environment,
+    pub result: Result<Package, CompileError>,
+}
+
+impl Session {
+    pub fn load(options: &Options, config: &ProjectConfig) -> anyhow::Result<Self> {
+        let thread_pool = ThreadPoolBuilder::new()
+            .num_threads(options.jobs.map_or(0, NonZeroUsize::get))
+            .thread_name(|i| format!("muscript-worker-{i}"))
+            .build()
+            .context("cannot start worker threads")?;
+
+        let mut include_files = vec![];
+        let mut include_file_index = IncludeFiles::new();
+        let mut preprocessor_modes = Modes::new();
+
+        let package = &config.package_dir();
+        let main_package_name = Arc::from(config.package_name()?);
+        let main_package_mode = config.preprocessor_mode(None);
+        let compiled_sources = {
+            let _span = info_span!("list_main_package_sources", %main_package_name).entered();
+            let listing = list_source_files_in_package(package)?;
+            info!(
+                source_count = listing.source.len(),
+                include_count = listing.include.len()
+            );
+            include_files.extend(
+                listing
+                    .include
+                    .into_iter()
+                    .map(|path| (pretty_file_name(package, &path), path, main_package_mode)),
+            );
+            listing.source
+        };
+        let main_package_include_count = include_files.len();
+
+        let source_dirs: Vec<_> = config
+            .source_dirs()
+            .into_iter()
+            .chain(options.sources.iter().cloned())
+            .collect();
+        let (external_sources, package_names) = {
+            let _span = info_span!("list_sources_of_external_packages").entered();
+
+            let mut external_sources = vec![];
+            let package_names: Vec<Arc<str>> = source_dirs
+                .iter()
+                .map(|package_path| get_package_name(package_path).map(Arc::from))
+                .collect::<Result<Vec<_>, _>>()?;
+            for (i, external_dir) in source_dirs.iter().enumerate() {
+                let _span = info_span!(
+                    "list_external_package_sources",
+                    package_name = %package_names[i]
+                )
+                .entered();
+
+                let listing = list_source_files_in_package(external_dir)?;
+                info!(
+                    source_count = listing.source.len(),
+                    include_count = listing.include.len()
+                );
+                let mode = config.preprocessor_mode(Some(&package_names[i]));
+                external_sources.extend(listing.source.into_iter().map(|path| (i, path)));
+                include_files.extend(
+                    listing
+                        .include
+                        .into_iter()
+                        .map(|path| (pretty_file_name(external_dir, &path), path, mode)),
+                );
+            }
+
+            info!(source_file_count = external_sources.len());
+            (external_sources, package_names)
+        };
+
+        // Include files and the main package's sources are always needed, so they're read up
+        // front. Sources of external packages are only read once the compiler asks for the
+        // classes they declare, since most of them are usually not needed.
+        let (
+            source_file_set,
+            include_file_ids,
+            main_package_include_files_by_path,
+            main_package_files_by_path,
+        ) = {
+            let _span = info_span!("build_source_file_set").entered();
+
+            let mut source_file_set = SourceFileSet::new();
+
+            let (include_file_ids, main_package_include_files_by_path) = {
+                let _span = info_span!("load_include_files").entered();
+
+                let mut include_file_ids = vec![];
+                let mut main_package_include_files_by_path = HashMap::new();
+                let include_package = Arc::from("<include>");
+                // Definitions from the project file, the build profile, and the command line come
+                // first, such that include files can make use of them.
+                let mut definitions = config.definitions(options.profile.as_deref())?;
+                definitions.extend(
+                    options
+                        .defines
+                        .iter()
+                        .map(|define| (define.name.as_str(), define.value.as_str())),
+                );
+                if !definitions.is_empty() || !options.undefines.is_empty() {
+                    let source = definitions_source(
+                        definitions,
+                        options.undefines.iter().map(|name| name.as_str()),
+                    );
+                    include_file_ids.push(source_file_set.add(SourceFile::new(
+                        Arc::clone(&include_package),
+                        "<definitions>".to_owned(),
+                        config.path().into(),
+                        Arc::from(source),
+                    )));
+                }
+                let paths: Vec<_> = include_files
+                    .iter()
+                    .map(|(_, path, _)| path.clone())
+                    .collect();
+                let sources = read_source_files(&thread_pool, &paths)?;
+                for (i, ((filename, path, mode), source)) in
+                    include_files.into_iter().zip(sources).enumerate()
+                {
+                    let id = source_file_set.add(SourceFile::new(
+                        Arc::clone(&include_package),
+                        filename.clone(),
+                        PathBuf::from(path.clone()),
+                        Arc::from(source),
+                    ));
+                    include_file_index.add(&filename, id);
+                    preprocessor_modes.set(id, mode);
+                    include_file_ids.push(id);
+                    if i < main_package_include_count {
+                        main_package_include_files_by_path
+                            .insert(canonical_path(path.as_std_path()), id);
+                    }
+                }
+                (include_file_ids, main_package_include_files_by_path)
+            };
+
+            let main_package_files_by_path = {
+                let _span = info_span!("load_main_package_sources").entered();
+
+                let mut main_package_files_by_path = HashMap::new();
+                let sources = read_source_files(&thread_pool, &compiled_sources)?;
+                for (path, source) in compiled_sources.into_iter().zip(sources) {
+                    let filename = pretty_file_name(package, &path);
+                    let id = source_file_set.add(SourceFile::new(
+                        Arc::clone(&main_package_name),
+                        filename,
+                        PathBuf::from(path.clone()),
+                        Arc::from(source),
+                    ));
+                    preprocessor_modes.set(id, main_package_mode);
+                    main_package_files_by_path.insert(canonical_path(path.as_std_path()), id);
+                }
+                main_package_files_by_path
+            };
+
+            {
+                let _span = info_span!("index_external_sources").entered();
+
+                for (i, path) in external_sources {
+                    let external_source_path = &source_dirs[i];
+                    let package_name = Arc::clone(&package_names[i]);
+                    let filename = pretty_file_name(external_source_path, &path);
+                    let id = source_file_set.add(SourceFile::unloaded(
+                        package_name,
+                        filename,
+                        PathBuf::from(path),
+                    ));
+                    preprocessor_modes.set(id, config.preprocessor_mode(Some(&package_names[i])));
+                }
+            }
+
+            (
+                source_file_set,
+                include_file_ids,
+                main_package_include_files_by_path,
+                main_package_files_by_path,
+            )
+        };
+        let main_package_source_file_ids: HashSet<_> =
+            main_package_files_by_path.values().copied().collect();
+
+        let input = {
+            let _span = info_span!("compiler_input").entered();
+
+            let mut input = Input::new();
+            input.include_files = include_file_index;
+            input.preprocessor_modes = preprocessor_modes;
+            for (source_file_id, source_file) in source_file_set.iter() {
+                if include_file_ids.contains(&source_file_id) {
+                    continue;
+                }
+                match source_file.class_name() {
+                    Ok(class_name) => input.add(class_name, source_file_id),
+                    Err(error) => error!("Error with file {}: {:?}", source_file.filename, error),
+                }
+            }
+            input
+        };
+
+        let mut session = Self {
+            config: config.clone(),
+            options: options.clone(),
+            package: package.to_owned(),
+            main_package_name,
+            source_file_set,
+            main_package_source_file_ids,
+            main_package_files_by_path,
+            main_package_include_files_by_path,
+            include_file_ids,
+            input,
+            token_arena: SourceArena::new(),
+            lexer_errors: LexerErrors::default(),
+            synthesized_tokens: SynthesizedTokens::new(),
+            thread_pool,
+            fresh_token_count: 0,
+            class_references: HashMap::new(),
+        };
+        session.open_cache();
+        Ok(session)
+    }
+
+    /// Opens the cache of external packages, if one is used. Cache entries depend on the contents
+    /// of all include files, so the cache has to be opened again after any of them change.
+    pub(crate) fn open_cache(&mut self) {
+        let Some(cache_dir) = &self.options.cache_dir else {
+            return;
+        };
+        let _span = info_span!("open_cache").entered();
+
+        match Cache::open(
+            cache_dir,
+            &self.source_file_set,
+            &self.include_file_ids,
+            &self.input.preprocessor_modes,
+        ) {
+            Ok(cache) => {
+                let external_source_file_ids = self
+                    .source_file_set
+                    .iter()
+                    .map(|(id, _)| id)
+                    .filter(|id| {
+                        !self.main_package_source_file_ids.contains(id)
+                            && !self.include_file_ids.contains(id)
+                    })
+                    .collect();
+                self.input.set_cache(cache, external_source_file_ids);
+            }
+            Err(error) => warn!("Cache is disabled: {error:#}"),
+        }
+    }
+
+    pub fn sources(&self) -> LexedSources<'_> {
+        LexedSources {
+            source_file_set: &self.source_file_set,
+            token_arena: &self.token_arena,
+            lexer_errors: &self.lexer_errors,
+            synthesized_tokens: &self.synthesized_tokens,
+        }
+    }
+
+    /// Names of all classes in the main package, in the order their files were loaded.
+    pub fn main_class_names(&self) -> Vec<String> {
+        self.source_file_set
+            .iter()
+            .filter(|(id, _)| self.main_package_source_file_ids.contains(id))
+            .filter_map(|(_, source_file)| source_file.class_name().ok())
+            .map(|class_name| class_name.to_owned())
+            .collect()
+    }
+
+    /// Compiles the classes with the given names, along with anything they depend on.
+    pub fn compile(
+        &mut self,
+        lint_settings: &[LintSettings],
+        class_names: &[String],
+    ) -> Compilation {
+        let mut env = Environment::new();
+        env.lint_levels.global.extend(lint_settings.iter().cloned());
+        let classes_to_compile: Vec<_> = class_names
+            .iter()
+            .map(|class_name| env.get_or_create_class(class_name))
+            .collect();
+
+        // The token arena is kept around in between compilations, since partitions kept in memory
+        // refer to it.
+        let starts_fresh = self.token_arena.is_empty();
+        let mut sources = OwnedSources {
+            source_file_set: &self.source_file_set,
+            token_arena: std::mem::take(&mut self.token_arena),
+            lexer_errors: std::mem::take(&mut self.lexer_errors),
+            synthesized_tokens: std::mem::take(&mut self.synthesized_tokens),
+        };
+
+        {
+            let _span = info_span!("include_files").entered();
+
+            self.input.global_definitions = Definitions::default();
+            preprocess_include_files(
+                &mut sources,
+                &mut self.input.global_definitions,
+                &self.input.include_files,
+                &self.input.preprocessor_modes,
+                &self.include_file_ids,
+                &mut env,
+            );
+        };
+
+        self.input
+            .parse_in_parallel(&self.thread_pool, &mut sources, class_names);
+
+        let compiler = &mut Compiler {
+            sources: &mut sources,
+            env: &mut env,
+            input: &self.input,
+        };
+        let result = Package::compile(compiler, &classes_to_compile);
+
+        // The compiler borrows the sources for as long as they live, so the only way to get the
+        // token arena back is to take it out through the compiler.
+        self.token_arena = std::mem::take(&mut compiler.sources.token_arena);
+        self.lexer_errors = std::mem::take(&mut compiler.sources.lexer_errors);
+        self.synthesized_tokens = std::mem::take(&mut compiler.sources.synthesized_tokens);
+        if starts_fresh {
+            self.fresh_token_count = self.token_arena.len();
+        }
+        Compilation { env, result }
+    }
+
+    /// Returns whether most of the token arena is taken up by tokens which are not referred to
+    /// anymore.
+    ///
+    /// Every compilation lexes include files again, and so do compilations after a source file
+    /// changes. The new tokens are appended to the arena, since partitions kept in memory still
+    /// refer to the old ones. Once the arena has grown to twice its size after a compilation that
+    /// started from scratch, it should be cleared with [`Session::discard_tokens`].
+    pub fn has_outdated_tokens(&self) -> bool {
+        self.token_arena.len() > self.fresh_token_count.saturating_mul(2)
+    }
+
+    /// Clears the token arena, along with the partitions kept in memory that refer to it. All
+    /// classes must be compiled again afterwards, and diagnostics from earlier compilations must
+    /// not be emitted anymore, since their spans refer to the discarded tokens.
+    pub fn discard_tokens(&mut self) {
+        self.token_arena = SourceArena::new();
+        self.lexer_errors = LexerErrors::default();
+        self.synthesized_tokens = SynthesizedTokens::new();
+        self.input.forget_all_partitions();
+    }
+
+    /// Adds a source file to the main package, such as one created after the session was loaded.
+    pub fn add_main_package_file(&mut self, path: &Utf8Path, source: String) -> SourceFileId {
+        let id = self.source_file_set.add(SourceFile::new(
+            Arc::clone(&self.main_package_name),
+            pretty_file_name(&self.package, path),
+            PathBuf::from(path),
+            Arc::from(source),
+        ));
+        self.input
+            .preprocessor_modes
+            .set(id, self.config.preprocessor_mode(None));
+        self.main_package_files_by_path
+            .insert(canonical_path(path.as_std_path()), id);
+        self.main_package_source_file_ids.insert(id);
+        let source_file = self.source_file_set.get(id);
+        match source_file.class_name() {
+            Ok(class_name) => self.input.add(class_name, id),
+            Err(error) => error!("Error with file {}: {:?}", source_file.filename, error),
+        }
+        id
+    }
+
+    /// Adds an include file to the main package, such as one created after the session was loaded.
+    pub fn add_main_package_include_file(
+        &mut self,
+        path: &Utf8Path,
+        source: String,
+    ) -> SourceFileId {
+        let filename = pretty_file_name(&self.package, path);
+        let id = self.source_file_set.add(SourceFile::new(
+            Arc::from("<include>"),
+            filename.clone(),
+            PathBuf::from(path),
+            Arc::from(source),
+        ));
+        self.input.include_files.add(&filename, id);
+        self.input
+            .preprocessor_modes
+            .set(id, self.config.preprocessor_mode(None));
+        self.main_package_include_files_by_path
+            .insert(canonical_path(path.as_std_path()), id);
+        self.include_file_ids.push(id);
+        id
+    }
+
+    /// Returns the file a diagnostic is reported in.
+    pub fn diagnostic_file(&self, diagnostic: &Diagnostic<Token>) -> Option<SourceFileId> {
+        diagnostic
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .or_else(|| diagnostic.labels.first())
+            .and_then(|label| label.span.start())
+            .map(|start| self.token_arena.source_file_id(start))
+    }
+
+    pub fn is_from_external_package(&self, diagnostic: &Diagnostic<Token>) -> bool {
+        diagnostic.labels.iter().any(|label| {
+            if let Some(start) = label.span.start() {
+                let source_file = self.token_arena.source_file_id(start);
+                !self.main_package_source_file_ids.contains(&source_file)
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Returns whether the diagnostic fails the compilation.
+    pub fn is_error(&self, diagnostic: &Diagnostic<Token>) -> bool {
+        // Lint levels are meant for the package being compiled, so lints denied in external
+        // packages must not fail the compilation.
+        let is_lint = diagnostic.code.as_deref().and_then(lint_by_code).is_some();
+        diagnostic.severity >= Severity::Error
+            && !(is_lint && self.is_from_external_package(diagnostic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8PathBuf;
+    use muscript_foundation::span::Span;
+
+    use super::{Options, Session};
+    use crate::config::ProjectConfig;
+
+    #[test]
+    fn unused_external_classes_are_not_loaded() {
+        let root = std::env::temp_dir().join(format!(
+            "muscript-session-test-{}-unused_external_classes_are_not_loaded",
+            std::process::id()
+        ));
+        _ = fs::remove_dir_all(&root);
+        let write = |path: &str, source: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        };
+        write("Core/Classes/Object.uc", "class Object;\n");
+        write(
+            "Core/Classes/Used.uc",
+            "class Used extends Object;\n\nfunction Run();\n",
+        );
+        write("Core/Classes/Unused.uc", "class Unused extends Object;\n");
+        write(
+            "Test/Classes/Test.uc",
+            "class Test extends Used;\n\nfunction Test()\n{\n    Run();\n}\n",
+        );
+
+        let root_utf8 = Utf8PathBuf::from_path_buf(root.clone()).unwrap();
+        let options = Options {
+            sources: vec![root_utf8.join("Core")],
+            ..Default::default()
+        };
+        let config = ProjectConfig::load(&root_utf8.join("Test")).unwrap();
+        let mut session = Session::load(&options, &config).unwrap();
+        let compiled = session.compile(&[], &["Test".to_owned()]).result.is_ok();
+        _ = fs::remove_dir_all(&root);
+        assert!(compiled);
+
+        for (filename, is_used) in [
+            ("Core/Classes/Used.uc", true),
+            ("Core/Classes/Unused.uc", false),
+        ] {
+            let (id, source_file) = session
+                .source_file_set
+                .iter()
+                .find(|(_, source_file)| source_file.filename == filename)
+                .unwrap();
+            assert_eq!(source_file.is_loaded(), is_used, "{filename}");
+            assert_eq!(
+                session.token_arena.source_file_span(id) != Span::Empty,
+                is_used,
+                "{filename}"
+            );
+        }
+    }
+}
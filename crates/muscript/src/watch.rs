@@ -0,0 +1,491 @@
+//! Watching a package's source files for changes.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use camino::Utf8Path;
+use muscript_analysis::{Environment, Package};
+use muscript_foundation::{ident::CaseInsensitive, source::SourceFileId};
+use muscript_lexer::token::TokenKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::{debug, error, warn};
+
+use crate::{
+    files::{canonical_path, read_source_file},
+    session::Session,
+};
+
+/// How long to wait for more changes after one is observed. Editors often save files in more
+/// than one step (eg. by writing to a temporary file and renaming it), and all of the steps
+/// should be batched into a single change.
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Watches the `Classes` directory of a package for changes to `.uc` and `.uci` files, as well as
+/// the package's `Globals.uci`.
+///
+/// On Linux, this uses inotify.
+pub struct SourceWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl SourceWatcher {
+    pub fn new(package: &Utf8Path) -> anyhow::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away when the watcher is dropped, at which point there's
+            // nobody interested in the events anymore.
+            _ = sender.send(event);
+        })
+        .context("cannot create file watcher")?;
+
+        let classes_dir = package.join("Classes");
+        watcher
+            .watch(classes_dir.as_std_path(), RecursiveMode::Recursive)
+            .with_context(|| format!("cannot watch {classes_dir:?}"))?;
+        watcher
+            .watch(package.as_std_path(), RecursiveMode::NonRecursive)
+            .with_context(|| format!("cannot watch {package:?}"))?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Blocks until source files change, and returns the paths of all files that changed.
+    /// The paths may refer to files that were removed.
+    pub fn wait_for_changes(&self) -> anyhow::Result<BTreeSet<PathBuf>> {
+        let mut changed = BTreeSet::new();
+        let mut event = self.events.recv().context("file watcher stopped")?;
+        loop {
+            match event {
+                Ok(event) => {
+                    if !matches!(event.kind, EventKind::Access(_)) {
+                        changed.extend(event.paths.into_iter().filter(|path| is_source(path)));
+                    }
+                }
+                Err(error) => warn!("error while watching files: {error}"),
+            }
+            if changed.is_empty() {
+                event = self.events.recv().context("file watcher stopped")?;
+            } else {
+                match self.events.recv_timeout(SETTLE_DELAY) {
+                    Ok(next) => event = next,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => anyhow::bail!("file watcher stopped"),
+                }
+            }
+        }
+        debug!(?changed, "source files changed");
+        Ok(changed)
+    }
+}
+
+fn is_source(path: &std::path::Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("uc" | "uci")
+    )
+}
+
+/// Classes whose sources changed in between compilations in watch mode.
+pub enum Changes {
+    /// An include file changed, which may affect any class of the main package.
+    Everything,
+    Classes(HashSet<CaseInsensitive<String>>),
+}
+
+impl Session {
+    /// Brings the session up to date with the files at the given paths, which were reported as
+    /// changed by the file watcher.
+    pub fn apply_changes(&mut self, paths: &BTreeSet<PathBuf>) -> anyhow::Result<Changes> {
+        let (include_paths, source_paths): (Vec<_>, Vec<_>) = paths
+            .iter()
+            .partition(|path| path.extension().is_some_and(|extension| extension == "uci"));
+        let includes_changed = self.apply_include_changes(&include_paths)?;
+
+        let classes_dir = canonical_path(self.package.join("Classes").as_std_path());
+        let mut changed = HashSet::new();
+        for path in source_paths {
+            let canonical = canonical_path(path);
+            if !canonical.starts_with(&classes_dir) {
+                continue;
+            }
+            let utf8_path = Utf8Path::from_path(path)
+                .with_context(|| format!("path {path:?} contains invalid UTF-8"))?;
+            let known_id = self.main_package_files_by_path.get(&canonical).copied();
+
+            if !path.is_file() {
+                if let Some(id) = known_id {
+                    self.main_package_files_by_path.remove(&canonical);
+                    self.main_package_source_file_ids.remove(&id);
+                    if let Some(class_name) = self.class_name(id) {
+                        self.input.remove(&class_name, id);
+                        changed.insert(CaseInsensitive::new(class_name));
+                    }
+                }
+                continue;
+            }
+
+            let source = match read_source_file(utf8_path) {
+                Ok(source) => source,
+                Err(error) => {
+                    // The file may still be being written to; it will be reported as changed
+                    // again once that's done.
+                    warn!("cannot read changed file: {error:#}");
+                    continue;
+                }
+            };
+            let id = match known_id {
+                Some(id) if *self.source_file_set.source(id) == *source => continue,
+                Some(id) => {
+                    self.source_file_set.replace_source(id, Arc::from(source));
+                    id
+                }
+                None => self.add_main_package_file(utf8_path, source),
+            };
+            if let Some(class_name) = self.class_name(id) {
+                changed.insert(CaseInsensitive::new(class_name));
+            }
+        }
+
+        if includes_changed {
+            // Include files are preprocessed into every file of the package, so all of its
+            // classes have to be parsed again. External packages cannot make use of the main
+            // package's macros, so their classes are kept.
+            for class_name in self.main_class_names() {
+                self.input.forget_partitions(&class_name);
+            }
+            return Ok(Changes::Everything);
+        }
+        for class_name in &changed {
+            self.input.forget_partitions(class_name);
+        }
+        Ok(Changes::Classes(changed))
+    }
+
+    /// Brings the main package's include files up to date with the files at the given paths.
+    /// Returns whether any of them changed.
+    fn apply_include_changes(&mut self, paths: &[&PathBuf]) -> anyhow::Result<bool> {
+        let classes_dir = canonical_path(self.package.join("Classes").as_std_path());
+        let globals_uci = canonical_path(self.package.join("Globals.uci").as_std_path());
+        let mut changed = false;
+        for &path in paths {
+            let canonical = canonical_path(path);
+            if !canonical.starts_with(&classes_dir) && canonical != globals_uci {
+                continue;
+            }
+            let utf8_path = Utf8Path::from_path(path)
+                .with_context(|| format!("path {path:?} contains invalid UTF-8"))?;
+            let known_id = self
+                .main_package_include_files_by_path
+                .get(&canonical)
+                .copied();
+
+            if !path.is_file() {
+                if let Some(id) = known_id {
+                    self.main_package_include_files_by_path.remove(&canonical);
+                    self.include_file_ids.retain(|&include_id| include_id != id);
+                    self.input.include_files.remove(id);
+                    changed = true;
+                }
+                continue;
+            }
+
+            let source = match read_source_file(utf8_path) {
+                Ok(source) => source,
+                Err(error) => {
+                    warn!("cannot read changed file: {error:#}");
+                    continue;
+                }
+            };
+            match known_id {
+                Some(id) if *self.source_file_set.source(id) == *source => continue,
+                Some(id) => self.source_file_set.replace_source(id, Arc::from(source)),
+                None => {
+                    self.add_main_package_include_file(utf8_path, source);
+                }
+            }
+            changed = true;
+        }
+
+        if changed {
+            self.open_cache();
+        }
+        Ok(changed)
+    }
+
+    fn class_name(&self, id: SourceFileId) -> Option<String> {
+        let source_file = self.source_file_set.get(id);
+        match source_file.class_name() {
+            Ok(class_name) => Some(class_name.to_owned()),
+            Err(error) => {
+                error!("Error with file {}: {:?}", source_file.filename, error);
+                None
+            }
+        }
+    }
+
+    /// Remembers which classes the classes of a compiled package made use of, for
+    /// [`Session::with_dependents`] to find out what needs to be recompiled later.
+    pub fn record_class_references(&mut self, env: &Environment, package: &Package) {
+        for (class_id, references) in package.class_references(env) {
+            self.class_references.insert(
+                CaseInsensitive::new(env.class_name(class_id).to_owned()),
+                references
+                    .into_iter()
+                    .map(|class_id| CaseInsensitive::new(env.class_name(class_id).to_owned()))
+                    .collect(),
+            );
+        }
+    }
+
+    /// Returns the changed classes, along with all classes of the main package that refer to any
+    /// of them, directly or not.
+    ///
+    /// A class refers to another class if the analysis of its last compilation made use of the
+    /// other class (see [`Session::record_class_references`]), which also catches uses that never
+    /// spell out the class's name, such as accessing fields of an object returned by a function.
+    /// Since the analysis can only see classes that existed at the time, a class is also assumed
+    /// to refer to every class whose name appears as an identifier in its source code.
+    pub fn with_dependents(
+        &self,
+        changed: HashSet<CaseInsensitive<String>>,
+    ) -> HashSet<CaseInsensitive<String>> {
+        let mut references: Vec<(CaseInsensitive<String>, HashSet<CaseInsensitive<&str>>)> = self
+            .main_package_source_file_ids
+            .iter()
+            .filter_map(|&id| {
+                let class_name = self.source_file_set.get(id).class_name().ok()?;
+                let source = self.source_file_set.source(id);
+                let mut referenced: HashSet<_> = self
+                    .token_arena
+                    .elements_in(self.token_arena.source_file_span(id))
+                    .iter()
+                    .filter(|token| token.kind == TokenKind::Ident)
+                    // Changed files have not been lexed again yet, so their tokens may not match
+                    // the source code anymore. They're already dirty though, so that's fine.
+                    .filter_map(|token| source.get(token.source_range.clone()))
+                    .map(CaseInsensitive::new)
+                    .collect();
+                if let Some(analyzed) = self
+                    .class_references
+                    .get(CaseInsensitive::new_ref(class_name))
+                {
+                    referenced.extend(
+                        analyzed
+                            .iter()
+                            .map(|class_name| CaseInsensitive::new(class_name.as_str())),
+                    );
+                }
+                Some((CaseInsensitive::new(class_name.to_owned()), referenced))
+            })
+            .collect();
+
+        let mut dirty = changed;
+        loop {
+            let (newly_dirty, rest): (Vec<_>, Vec<_>) =
+                references
+                    .into_iter()
+                    .partition(|(class_name, referenced)| {
+                        !dirty.contains(class_name)
+                            && dirty
+                                .iter()
+                                .any(|dirty| referenced.contains(&CaseInsensitive::new(&dirty[..])))
+                    });
+            if newly_dirty.is_empty() {
+                break;
+            }
+            dirty.extend(newly_dirty.into_iter().map(|(class_name, _)| class_name));
+            references = rest;
+        }
+        dirty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashSet},
+        fs,
+        path::PathBuf,
+    };
+
+    use camino::Utf8PathBuf;
+    use muscript_foundation::ident::CaseInsensitive;
+
+    use super::Changes;
+    use crate::{
+        config::ProjectConfig,
+        session::{Options, Session},
+    };
+
+    /// A `Test` package whose classes refer to each other as `C -> B -> A`, along with an
+    /// unrelated class `D`, compiled against a `Core` package.
+    struct Fixture {
+        root: PathBuf,
+        session: Session,
+    }
+
+    impl Fixture {
+        fn new(test_name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "muscript-watch-test-{}-{test_name}",
+                std::process::id()
+            ));
+            _ = fs::remove_dir_all(&root);
+            let write = |path: &str, source: &str| {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, source).unwrap();
+            };
+            write("Core/Classes/Object.uc", "class Object;\n");
+            write("Test/Globals.uci", "`define VALUE 1\n");
+            write(
+                "Test/Classes/A.uc",
+                "class A extends Object;\n\nvar int Value;\n",
+            );
+            write(
+                "Test/Classes/B.uc",
+                "class B extends Object;\n\nvar A Other;\n",
+            );
+            write(
+                "Test/Classes/C.uc",
+                "class C extends Object;\n\nfunction F(B Param);\n",
+            );
+            write("Test/Classes/D.uc", "class D extends Object;\n");
+
+            let root_utf8 = Utf8PathBuf::from_path_buf(root.clone()).unwrap();
+            let options = Options {
+                sources: vec![root_utf8.join("Core")],
+                ..Default::default()
+            };
+            let config = ProjectConfig::load(&root_utf8.join("Test")).unwrap();
+            let mut session = Session::load(&options, &config).unwrap();
+            session.input.keep_partitions_in_memory();
+            let class_names = session.main_class_names();
+            assert!(session.compile(&[], &class_names).result.is_ok());
+            Self { root, session }
+        }
+
+        fn path(&self, path: &str) -> PathBuf {
+            self.root.join(path)
+        }
+
+        fn apply_changes(&mut self, paths: &[&str]) -> Changes {
+            let paths: BTreeSet<_> = paths.iter().map(|path| self.path(path)).collect();
+            self.session.apply_changes(&paths).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn classes(names: &[&str]) -> HashSet<CaseInsensitive<String>> {
+        names
+            .iter()
+            .map(|name| CaseInsensitive::new(name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dependents_are_found_transitively() {
+        let fixture = Fixture::new("dependents_are_found_transitively");
+        let session = &fixture.session;
+        assert_eq!(
+            session.with_dependents(classes(&["a"])),
+            classes(&["A", "B", "C"])
+        );
+        assert_eq!(
+            session.with_dependents(classes(&["B"])),
+            classes(&["B", "C"])
+        );
+        assert_eq!(session.with_dependents(classes(&["C"])), classes(&["C"]));
+        assert_eq!(session.with_dependents(classes(&["D"])), classes(&["D"]));
+        assert_eq!(session.with_dependents(classes(&[])), classes(&[]));
+    }
+
+    #[test]
+    fn changed_sources_invalidate_their_classes() {
+        let mut fixture = Fixture::new("changed_sources_invalidate_their_classes");
+        fs::write(
+            fixture.path("Test/Classes/A.uc"),
+            "class A extends Object;\n\nvar int Value, Other;\n",
+        )
+        .unwrap();
+        fs::remove_file(fixture.path("Test/Classes/D.uc")).unwrap();
+        fs::write(
+            fixture.path("Test/Classes/E.uc"),
+            "class E extends Object;\n",
+        )
+        .unwrap();
+
+        // `B` is reported as changed, but its contents are the same.
+        let changes = fixture.apply_changes(&[
+            "Test/Classes/A.uc",
+            "Test/Classes/B.uc",
+            "Test/Classes/D.uc",
+            "Test/Classes/E.uc",
+        ]);
+        let Changes::Classes(changed) = changes else {
+            panic!("only classes should change");
+        };
+        assert_eq!(changed, classes(&["A", "D", "E"]));
+
+        let input = &fixture.session.input;
+        assert!(!input.has_kept_partitions("A"));
+        assert!(input.has_kept_partitions("B"));
+        assert!(input.has_kept_partitions("Object"));
+        let mut class_names = fixture.session.main_class_names();
+        class_names.sort();
+        assert_eq!(class_names, ["A", "B", "C", "E"]);
+    }
+
+    #[test]
+    fn changed_include_files_invalidate_the_main_package_only() {
+        let mut fixture = Fixture::new("changed_include_files_invalidate_the_main_package_only");
+        assert!(matches!(
+            fixture.apply_changes(&["Test/Globals.uci"]),
+            Changes::Classes(changed) if changed.is_empty()
+        ));
+
+        fs::write(fixture.path("Test/Globals.uci"), "`define VALUE 2\n").unwrap();
+        fs::write(fixture.path("Test/Classes/New.uci"), "`define NEW\n").unwrap();
+        let changes = fixture.apply_changes(&["Test/Globals.uci", "Test/Classes/New.uci"]);
+        assert!(matches!(changes, Changes::Everything));
+
+        let session = &mut fixture.session;
+        for class_name in ["A", "B", "C", "D"] {
+            assert!(!session.input.has_kept_partitions(class_name));
+        }
+        assert!(session.input.has_kept_partitions("Object"));
+
+        let class_names = session.main_class_names();
+        assert!(session.compile(&[], &class_names).result.is_ok());
+        let definitions = &session.input.global_definitions.map;
+        assert!(definitions.contains_key(CaseInsensitive::new_ref("NEW")));
+
+        fs::remove_file(fixture.path("Test/Classes/New.uci")).unwrap();
+        assert!(matches!(
+            fixture.apply_changes(&["Test/Classes/New.uci"]),
+            Changes::Everything
+        ));
+        let session = &mut fixture.session;
+        assert!(session.compile(&[], &class_names).result.is_ok());
+        let definitions = &session.input.global_definitions.map;
+        assert!(!definitions.contains_key(CaseInsensitive::new_ref("NEW")));
+        assert!(definitions.contains_key(CaseInsensitive::new_ref("VALUE")));
+    }
+}
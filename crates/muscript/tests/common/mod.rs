@@ -0,0 +1,84 @@
+//! Harness shared by the integration tests, which run the `muscript` binary over packages written
+//! into a temporary directory.
+
+// Each test file is its own crate and uses only part of the harness.
+#![allow(dead_code)]
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use serde_json::Value;
+
+/// The smallest `Object` class a package can be compiled against.
+pub const OBJECT_UC: &str = "\
+class Object;
+";
+
+/// A temporary directory holding the packages of a single test. It is removed when dropped.
+pub struct Fixture {
+    pub root: PathBuf,
+    /// The `Core` package, passed as `--source` to every compilation.
+    pub core: PathBuf,
+}
+
+impl Fixture {
+    /// Creates the directory for the test `test_name` from the file `kind`, with a `Core`
+    /// package made of [`OBJECT_UC`].
+    pub fn new(kind: &str, test_name: &str) -> Self {
+        Self::with_object(kind, test_name, OBJECT_UC)
+    }
+
+    /// Like [`Fixture::new`], but with the given source for the `Object` class.
+    pub fn with_object(kind: &str, test_name: &str, object_uc: &str) -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "muscript-{kind}-test-{}-{test_name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        let core = write_package(&root, "Core", &[("Object.uc", object_uc)]);
+        Self { root, core }
+    }
+
+    /// Writes a package made of the given files, with paths relative to its `Classes` directory.
+    pub fn write_package(&self, name: &str, files: &[(&str, &str)]) -> PathBuf {
+        write_package(&self.root, name, files)
+    }
+
+    /// Returns a command compiling `package` against `Core`, without the cache.
+    pub fn muscript(&self, package: &Path) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_muscript"));
+        command
+            .arg(package)
+            .arg("--source")
+            .arg(&self.core)
+            .arg("--no-cache");
+        command
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+fn write_package(root: &Path, name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let classes = root.join(name).join("Classes");
+    std::fs::create_dir_all(&classes).unwrap();
+    for (filename, contents) in files {
+        let path = classes.join(filename);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    root.join(name)
+}
+
+/// Parses the diagnostics printed with `--message-format=json`.
+pub fn json_diagnostics(output: &Output) -> Vec<Value> {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .collect()
+}
@@ -0,0 +1,77 @@
+//! Tests of preprocessor definitions made on the command line with `-D` and `-U`, run through
+//! `--dump-preprocessed`.
+
+mod common;
+
+use common::Fixture;
+
+const TEST_UC: &str = "\
+class Test extends Object;
+
+function Test()
+{
+`if(`isdefined(FLAG))
+    Flag();
+`endif
+`if(`VALUE)
+    Value = `VALUE;
+`endif
+}
+";
+
+/// Preprocesses the `Test` class with the given arguments, and returns the statements of its
+/// function, one per line.
+fn preprocess(test_name: &str, args: &[&str]) -> Vec<String> {
+    let fixture = Fixture::new("defines", test_name);
+    let package = fixture.write_package("Test", &[("Test.uc", TEST_UC)]);
+
+    let output = fixture
+        .muscript(&package)
+        .args(args)
+        .arg("--dump-preprocessed")
+        .arg(package.join("Classes/Test.uc"))
+        .output()
+        .expect("cannot run muscript");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let preprocessed = String::from_utf8(output.stdout).unwrap();
+    let body = preprocessed
+        .split_once("{\n")
+        .and_then(|(_, body)| body.rsplit_once('}'))
+        .map_or("", |(body, _)| body);
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn nothing_defined() {
+    assert_eq!(preprocess("nothing_defined", &[]), Vec::<String>::new());
+}
+
+#[test]
+fn define_name() {
+    assert_eq!(preprocess("define_name", &["-D", "FLAG"]), ["Flag();"]);
+}
+
+#[test]
+fn define_name_and_value() {
+    assert_eq!(
+        preprocess("define_name_and_value", &["-D", "VALUE=2", "--define=FLAG"]),
+        ["Flag();", "Value = 2;"]
+    );
+}
+
+#[test]
+fn undefine_after_define() {
+    assert_eq!(
+        preprocess("undefine_after_define", &["-D", "FLAG", "-U", "FLAG"]),
+        Vec::<String>::new()
+    );
+}
@@ -0,0 +1,67 @@
+//! Tests of `muscript doc`, checking that text from the sources is escaped in both formats.
+
+mod common;
+
+use common::Fixture;
+
+const TEST_UC: &str = "\
+class Test extends Object;
+
+/**
+ * Returns whether A < B && B <= 10.
+ *
+ * Use `array<int> & more` for lists; `<b>` is no tag.
+ */
+function bool Compare(array<int> A, int B);
+";
+
+/// Documents a package made of the `Test` class, and returns the contents of its page.
+fn document(test_name: &str, format: &str) -> String {
+    let fixture = Fixture::new("doc", test_name);
+    let package = fixture.write_package("Test", &[("Test.uc", TEST_UC)]);
+    let output_dir = fixture.root.join("doc");
+
+    let output = fixture
+        .muscript(&package)
+        .arg("doc")
+        .arg("--output")
+        .arg(&output_dir)
+        .args(["--format", format])
+        .output()
+        .expect("cannot run muscript");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let extension = if format == "markdown" { "md" } else { format };
+    std::fs::read_to_string(output_dir.join(format!("Test.{extension}"))).unwrap()
+}
+
+#[test]
+fn html_escapes_doc_comments_and_signatures() {
+    let page = document("html", "html");
+    assert!(
+        page.contains(
+            "<p class=\"doc\">Returns whether A &lt; B &amp;&amp; B &lt;= 10.</p>\n\
+             <p class=\"doc\">Use `array&lt;int&gt; &amp; more` for lists; `&lt;b&gt;` is no tag.</p>"
+        ),
+        "{page}"
+    );
+    assert!(page.contains("array&lt;int&gt; A"), "{page}");
+    assert!(!page.contains("<int>") && !page.contains("<b>"), "{page}");
+}
+
+#[test]
+fn markdown_escapes_doc_comments_outside_of_code_spans() {
+    let page = document("markdown", "markdown");
+    assert!(
+        page.contains(
+            "Returns whether A &lt; B &amp;&amp; B &lt;= 10.\n\n\
+             Use `array<int> & more` for lists; `<b>` is no tag.\n\n"
+        ),
+        "{page}"
+    );
+    // Signatures are code spans, which are displayed as is.
+    assert!(page.contains("array<int> A"), "{page}");
+}
@@ -0,0 +1,75 @@
+//! Snapshot test of the control flow graphs `muscript --dump-cfg` writes.
+//!
+//! The expected graph of each function is stored in `dump_cfg/Class.Function.dot`. Set the
+//! `MUSCRIPT_BLESS` environment variable to write the current graphs into the snapshots instead
+//! of comparing against them.
+
+mod common;
+
+use std::path::Path;
+
+use common::Fixture;
+use muscript::fix::unified_diff;
+
+const OBJECT_UC: &str = "\
+class Object;
+
+native(150) static final operator(24) bool < ( int A, int B );
+native(154) static final operator(24) bool == ( int A, int B );
+native(161) static final operator(34) int += ( out int A, int B );
+native(165) static final postoperator int ++ ( out int A );
+";
+
+const TEST_UC: &str = "\
+class Test extends Object;
+
+function int Count(int N)
+{
+    local int I, Total;
+    if (N == 0)
+    {
+        return 0;
+    }
+    Total = 0;
+    for (I = 0; I < N; I++)
+    {
+        Total += I;
+    }
+    return Total;
+}
+";
+
+#[test]
+fn branch_and_loop() {
+    let fixture = Fixture::with_object("dump_cfg", "branch_and_loop", OBJECT_UC);
+    let package = fixture.write_package("Test", &[("Test.uc", TEST_UC)]);
+    let cfg_dir = fixture.root.join("cfg");
+
+    let output = fixture
+        .muscript(&package)
+        .arg("--dump-cfg")
+        .arg(&cfg_dir)
+        .output()
+        .expect("cannot run muscript");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let name = "Test.Count.dot";
+    let dot = std::fs::read_to_string(cfg_dir.join(name)).unwrap();
+    let snapshot_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/dump_cfg")
+        .join(name);
+    if std::env::var_os("MUSCRIPT_BLESS").is_some() {
+        std::fs::write(&snapshot_path, &dot).unwrap();
+        return;
+    }
+    let snapshot = std::fs::read_to_string(&snapshot_path).unwrap_or_default();
+    assert!(
+        snapshot == dot,
+        "control flow graph does not match its snapshot (run with MUSCRIPT_BLESS=1 to update it):\n{}",
+        unified_diff(name, &snapshot, &dot)
+    );
+}
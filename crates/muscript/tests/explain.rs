@@ -0,0 +1,33 @@
+//! Tests of `muscript --explain`.
+
+use std::process::{Command, Output};
+
+fn explain(code: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_muscript"))
+        .args(["--explain", code])
+        .output()
+        .expect("cannot run muscript")
+}
+
+#[test]
+fn known_code() {
+    let output = explain("m427");
+    assert!(output.status.success());
+    let explanation = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        explanation.starts_with("A variable could not be found."),
+        "{explanation}"
+    );
+}
+
+#[test]
+fn unknown_code() {
+    let output = explain("M9999");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("no explanation is available for error code M9999"),
+        "{stderr}"
+    );
+}
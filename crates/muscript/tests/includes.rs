@@ -0,0 +1,160 @@
+//! Tests of `` `include `` directives, run through the compiler's JSON diagnostic output and
+//! `--dump-preprocessed`.
+
+mod common;
+
+use common::{json_diagnostics, Fixture};
+
+struct Compilation {
+    /// `(severity, message)` of each diagnostic.
+    diagnostics: Vec<(String, String)>,
+    /// Output of `--dump-preprocessed` for `Test.uc`.
+    preprocessed: String,
+}
+
+/// Compiles a package made of the given files, which must include `Test.uc`.
+fn compile(test_name: &str, files: &[(&str, &str)]) -> Compilation {
+    let fixture = Fixture::new("includes", test_name);
+    let package = fixture.write_package("Test", files);
+
+    let compiled = fixture
+        .muscript(&package)
+        .arg("--message-format=json")
+        .output()
+        .expect("cannot run muscript");
+    let preprocessed = fixture
+        .muscript(&package)
+        .arg("--dump-preprocessed")
+        .arg(package.join("Classes/Test.uc"))
+        .output()
+        .expect("cannot run muscript");
+
+    Compilation {
+        diagnostics: json_diagnostics(&compiled)
+            .into_iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic["severity"].as_str().unwrap().to_owned(),
+                    diagnostic["message"].as_str().unwrap().to_owned(),
+                )
+            })
+            .collect(),
+        preprocessed: String::from_utf8(preprocessed.stdout).unwrap(),
+    }
+}
+
+fn diagnostic(severity: &str, message: &str) -> (String, String) {
+    (severity.to_owned(), message.to_owned())
+}
+
+#[test]
+fn nested_include() {
+    let compilation = compile(
+        "nested_include",
+        &[
+            (
+                "Test.uc",
+                "\
+class Test extends Object;
+
+`include(Outer.uci)
+
+function int Get()
+{
+    return `INNER_VALUE;
+}
+",
+            ),
+            ("Outer.uci", "`include(Nested/Inner.uci)\n"),
+            (
+                "Nested/Inner.uci",
+                "`define INNER_VALUE 42\nvar int Inner;\n",
+            ),
+        ],
+    );
+    assert_eq!(compilation.diagnostics, []);
+    assert!(
+        compilation.preprocessed.contains("var int Inner;"),
+        "{}",
+        compilation.preprocessed
+    );
+    assert!(
+        compilation.preprocessed.contains("return 42;"),
+        "{}",
+        compilation.preprocessed
+    );
+}
+
+#[test]
+fn self_include_cycle() {
+    let compilation = compile(
+        "self_include_cycle",
+        &[
+            (
+                "Test.uc",
+                "class Test extends Object;\n\n`include(Cycle.uci)\n",
+            ),
+            ("Cycle.uci", "var int BeforeCycle;\n`include(Cycle.uci)\n"),
+        ],
+    );
+    assert!(
+        compilation.diagnostics.contains(&diagnostic(
+            "error",
+            "`Test/Classes/Cycle.uci` includes itself"
+        )),
+        "{:?}",
+        compilation.diagnostics
+    );
+    // The file is still included once.
+    assert_eq!(
+        compilation
+            .preprocessed
+            .matches("var int BeforeCycle;")
+            .count(),
+        1,
+        "{}",
+        compilation.preprocessed
+    );
+}
+
+#[test]
+fn indirect_include_cycle() {
+    let compilation = compile(
+        "indirect_include_cycle",
+        &[
+            ("Test.uc", "class Test extends Object;\n\n`include(A.uci)\n"),
+            ("A.uci", "`include(B.uci)\n"),
+            ("B.uci", "`include(A.uci)\n"),
+        ],
+    );
+    assert!(
+        compilation
+            .diagnostics
+            .contains(&diagnostic("error", "`Test/Classes/A.uci` includes itself")),
+        "{:?}",
+        compilation.diagnostics
+    );
+}
+
+#[test]
+fn unresolved_include_path() {
+    let compilation = compile(
+        "unresolved_include_path",
+        &[(
+            "Test.uc",
+            "class Test extends Object;\n\n`include(Missing.uci)\n\nvar int AfterMissing;\n",
+        )],
+    );
+    assert_eq!(
+        compilation.diagnostics,
+        [diagnostic(
+            "warning",
+            "cannot find include file `Missing.uci`"
+        )]
+    );
+    assert!(
+        compilation.preprocessed.contains("var int AfterMissing;"),
+        "{}",
+        compilation.preprocessed
+    );
+}
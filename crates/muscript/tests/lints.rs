@@ -0,0 +1,278 @@
+//! Tests of the dataflow lints on unused and uninitialized variables, run through the compiler's
+//! JSON diagnostic output.
+
+mod common;
+
+use common::{json_diagnostics, Fixture};
+
+const OBJECT_UC: &str = "\
+class Object;
+
+native(146) static final operator(20) int + ( int A, int B );
+native(154) static final operator(24) bool == ( int A, int B );
+native(161) static final operator(34) int += ( out int A, int B );
+native(165) static final postoperator int ++ ( out int A );
+";
+
+/// Compiles a package made of the given classes, and returns the messages of the diagnostics
+/// reported with the given code.
+fn diagnostics(test_name: &str, files: &[(&str, &str)], code: &str) -> Vec<String> {
+    diagnostics_with_args(test_name, files, code, &[])
+        .into_iter()
+        .map(|(_, message)| message)
+        .collect()
+}
+
+/// Like [`diagnostics`], but passes extra arguments to the compiler and returns the severity of
+/// each diagnostic along with its message.
+fn diagnostics_with_args(
+    test_name: &str,
+    files: &[(&str, &str)],
+    code: &str,
+    args: &[&str],
+) -> Vec<(String, String)> {
+    let fixture = Fixture::with_object("lints", test_name, OBJECT_UC);
+    let package = fixture.write_package("Test", files);
+
+    let output = fixture
+        .muscript(&package)
+        .arg("--message-format=json")
+        .args(args)
+        .output()
+        .expect("cannot run muscript");
+
+    json_diagnostics(&output)
+        .into_iter()
+        .filter(|diagnostic| diagnostic["code"] == code)
+        .map(|diagnostic| {
+            (
+                diagnostic["severity"].as_str().unwrap().to_owned(),
+                diagnostic["message"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn unused_locals_and_params() {
+    let messages = diagnostics(
+        "unused_locals_and_params",
+        &[(
+            "Test.uc",
+            "\
+class Test extends Object;
+
+function int Sum(int A, int B, int C)
+{
+    local int Unused, WriteOnly, Used;
+    WriteOnly = 1;
+    Used = A + B;
+    return Used;
+}
+",
+        )],
+        "M0440",
+    );
+    assert_eq!(
+        messages,
+        [
+            "unused parameter `C`",
+            "unused variable `Unused`",
+            "variable `WriteOnly` is assigned to, but never read",
+        ]
+    );
+}
+
+#[test]
+fn underscore_prefix_silences_unused() {
+    let messages = diagnostics(
+        "underscore_prefix_silences_unused",
+        &[(
+            "Test.uc",
+            "\
+class Test extends Object;
+
+function Run(int _Param)
+{
+    local int _Local;
+}
+",
+        )],
+        "M0440",
+    );
+    assert_eq!(messages, Vec::<String>::new());
+}
+
+#[test]
+fn assigned_out_params_are_used() {
+    let messages = diagnostics(
+        "assigned_out_params_are_used",
+        &[(
+            "Test.uc",
+            "\
+class Test extends Object;
+
+function Get(out int Result, out int Ignored, int Unused)
+{
+    Result = 1;
+}
+",
+        )],
+        "M0440",
+    );
+    assert_eq!(
+        messages,
+        ["unused parameter `Ignored`", "unused parameter `Unused`"]
+    );
+}
+
+#[test]
+fn params_of_overrides_and_events_are_not_reported() {
+    let messages = diagnostics(
+        "params_of_overrides_and_events_are_not_reported",
+        &[
+            (
+                "Base.uc",
+                "\
+class Base extends Object;
+
+function int Get(int Index)
+{
+    return Index;
+}
+",
+            ),
+            (
+                "Derived.uc",
+                "\
+class Derived extends Base;
+
+function int Get(int Index)
+{
+    return 0;
+}
+
+event Touched(int Strength)
+{
+}
+
+function NotAnOverride(int Unused)
+{
+}
+",
+            ),
+        ],
+        "M0440",
+    );
+    assert_eq!(messages, ["unused parameter `Unused`"]);
+}
+
+#[test]
+fn reads_before_assignment() {
+    let messages = diagnostics(
+        "reads_before_assignment",
+        &[(
+            "Test.uc",
+            "\
+class Test extends Object;
+
+function int Branches(int A)
+{
+    local int X, Y;
+    if (A == 0)
+    {
+        X = 1;
+        Y = 1;
+    }
+    else
+    {
+        X = 2;
+    }
+    return X + Y;
+}
+
+function int Twice()
+{
+    local int Z;
+    return Z + Z;
+}
+",
+        )],
+        "M0439",
+    );
+    // `X` is assigned on every path, `Y` only on one. Each variable is only reported once.
+    assert_eq!(
+        messages,
+        [
+            "variable `Y` may be read before it is assigned",
+            "variable `Z` may be read before it is assigned",
+        ]
+    );
+}
+
+#[test]
+fn operators_updating_out_operands_read_them() {
+    let messages = diagnostics(
+        "operators_updating_out_operands_read_them",
+        &[(
+            "Test.uc",
+            "\
+class Test extends Object;
+
+function int Count()
+{
+    local int X, Y, Z;
+    X += 1;
+    Y++;
+    Z = 0;
+    Z += 1;
+    return X + Y + Z;
+}
+",
+        )],
+        "M0439",
+    );
+    assert_eq!(
+        messages,
+        [
+            "variable `X` may be read before it is assigned",
+            "variable `Y` may be read before it is assigned",
+        ]
+    );
+}
+
+#[test]
+fn directives_override_command_line_levels() {
+    let diagnostics = diagnostics_with_args(
+        "directives_override_command_line_levels",
+        &[(
+            "Test.uc",
+            "\
+class Test extends Object;
+
+// muscript: allow(unused_variable)
+function Allowed(int A)
+{
+}
+
+// muscript: warn(warnings)
+function Warned(int B)
+{
+}
+
+function Denied(int C)
+{
+}
+",
+        )],
+        "M0440",
+        &["--deny", "unused_variable"],
+    );
+    assert_eq!(
+        diagnostics,
+        [
+            ("warning".to_owned(), "unused parameter `B`".to_owned()),
+            ("error".to_owned(), "unused parameter `C`".to_owned()),
+        ]
+    );
+}
@@ -0,0 +1,92 @@
+//! Tests comparing how the same source is preprocessed in the `muscript` and `compatible`
+//! preprocessor modes, run through `--dump-preprocessed`.
+
+mod common;
+
+use common::Fixture;
+
+/// Preprocesses the body of a function in the given mode, and returns its preprocessed
+/// statements, one per line.
+fn preprocess(test_name: &str, mode: &str, definitions: &str, body: &str) -> Vec<String> {
+    let fixture = Fixture::new("modes", &format!("{test_name}-{mode}"));
+    let source =
+        format!("class Test extends Object;\n\n{definitions}\nfunction Test()\n{{\n{body}}}\n");
+    let package = fixture.write_package("Test", &[("Test.uc", &source)]);
+    std::fs::write(
+        package.join("muscript.toml"),
+        format!("[preprocessor]\nmode = \"{mode}\"\n"),
+    )
+    .unwrap();
+
+    let output = fixture
+        .muscript(&package)
+        .arg("--dump-preprocessed")
+        .arg(package.join("Classes/Test.uc"))
+        .output()
+        .expect("cannot run muscript");
+
+    let preprocessed = String::from_utf8(output.stdout).unwrap();
+    let body = preprocessed
+        .split_once("{\n")
+        .and_then(|(_, body)| body.rsplit_once('}'))
+        .map_or("", |(body, _)| body);
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn isdefined_and_notdefined_outside_if() {
+    let definitions = "`define DEFINED\n";
+    let body = "\
+    X = `isdefined(DEFINED);
+    X = `notdefined(UNDEFINED);
+    X = `isdefined(UNDEFINED) 0;
+    X = `notdefined(DEFINED) 0;
+";
+    // MuScript only gives meaning to the directives inside `if, so they're left as they are.
+    assert_eq!(
+        preprocess("isdefined", "muscript", definitions, body),
+        [
+            "X = `isdefined(DEFINED);",
+            "X = `notdefined(UNDEFINED);",
+            "X = 0;",
+            "X = 0;",
+        ]
+    );
+    assert_eq!(
+        preprocess("isdefined", "compatible", definitions, body),
+        ["X = 1;", "X = 1;", "X = 0;", "X = 0;"]
+    );
+}
+
+#[test]
+fn if_truthiness() {
+    let definitions = "`define DEFINED\n`define EMPTY\n";
+    let body = "\
+    `if(1) X = 1; `else X = 0; `endif
+    `if(`isdefined(DEFINED)) X = 2; `else X = 0; `endif
+    `if(`isdefined(UNDEFINED)) X = 0; `else X = 3; `endif
+    `if() X = 0; `else X = 4; `endif
+    `if(`EMPTY) X = 0; `else X = 5; `endif
+    `if(`UNDEFINED) X = 0; `else X = 6; `endif
+    `if(`EMPTY 1) X = 7; `else X = 0; `endif
+    `if(`UNDEFINED 1) X = 8; `else X = 9; `endif
+    `if(`UNDEFINED `isdefined(DEFINED)) X = 10; `else X = 11; `endif
+";
+    let both = [
+        "X = 1;", "X = 2;", "X = 3;", "X = 4;", "X = 5;", "X = 6;", "X = 7;",
+    ];
+    // MuScript only looks at the first token of the expanded condition, which is an undefined
+    // macro's failed expansion in the last two cases. UPP looks at the whole condition.
+    assert_eq!(
+        preprocess("if_truthiness", "muscript", definitions, body),
+        [&both[..], &["X = 9;", "X = 11;"]].concat()
+    );
+    assert_eq!(
+        preprocess("if_truthiness", "compatible", definitions, body),
+        [&both[..], &["X = 8;", "X = 10;"]].concat()
+    );
+}
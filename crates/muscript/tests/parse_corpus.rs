@@ -0,0 +1,58 @@
+//! Snapshot tests of the syntax trees `muscript parse --dump-cst=json` prints for the files in
+//! `muscript-syntax/tests/parse`.
+//!
+//! Each `Name.uc` has its expected tree stored next to it in `Name.cst.json`. Set the
+//! `MUSCRIPT_BLESS` environment variable to write the current trees into the snapshots instead
+//! of comparing against them.
+
+use std::{path::Path, process::Command};
+
+use muscript::fix::unified_diff;
+use walkdir::WalkDir;
+
+fn dump_cst(file: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_muscript"))
+        .args(["parse", "--dump-cst=json"])
+        .arg(file)
+        .output()
+        .expect("cannot run muscript");
+    // Files with syntax errors are part of the corpus too, so the exit status does not matter.
+    String::from_utf8(output.stdout).expect("syntax tree is not valid UTF-8")
+}
+
+#[test]
+fn parse_corpus() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("../muscript-syntax/tests/parse");
+    let bless = std::env::var_os("MUSCRIPT_BLESS").is_some();
+
+    let mut source_count = 0;
+    let mut mismatches = vec![];
+    for entry in WalkDir::new(&corpus).sort_by_file_name() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "uc") {
+            continue;
+        }
+        source_count += 1;
+
+        let cst = dump_cst(path);
+        assert!(!cst.is_empty(), "no syntax tree was printed for {path:?}");
+        let snapshot_path = path.with_extension("cst.json");
+        if bless {
+            std::fs::write(&snapshot_path, &cst).unwrap();
+            continue;
+        }
+        let snapshot = std::fs::read_to_string(&snapshot_path).unwrap_or_default();
+        if snapshot != cst {
+            let name = path.strip_prefix(&corpus).unwrap().display().to_string();
+            mismatches.push(unified_diff(&name, &snapshot, &cst));
+        }
+    }
+
+    assert!(source_count > 0, "no source files found in {corpus:?}");
+    assert!(
+        mismatches.is_empty(),
+        "syntax trees do not match their snapshots (run with MUSCRIPT_BLESS=1 to update them):\n{}",
+        mismatches.concat()
+    );
+}
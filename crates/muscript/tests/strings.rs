@@ -0,0 +1,200 @@
+//! Tests of macro expansion, chiefly inside string literals, run through `--dump-preprocessed` and
+//! the compiler's JSON diagnostic output.
+
+mod common;
+
+use common::{json_diagnostics, Fixture};
+use serde_json::Value;
+
+/// Preprocesses a class made of the given source, and returns the preprocessed source along with
+/// the diagnostics reported.
+fn preprocess(test_name: &str, source: &str) -> (String, Vec<Value>) {
+    let fixture = Fixture::new("strings", test_name);
+    let package = fixture.write_package("Test", &[("Test.uc", source)]);
+
+    let output = fixture
+        .muscript(&package)
+        .args(["--message-format=json", "--dump-preprocessed"])
+        .arg(package.join("Classes/Test.uc"))
+        .output()
+        .expect("cannot run muscript");
+
+    (
+        String::from_utf8(output.stdout.clone()).unwrap(),
+        json_diagnostics(&output),
+    )
+}
+
+/// Preprocesses a class whose `Test` function assigns each of the given strings to a variable,
+/// and returns the expanded strings.
+fn expand_strings(test_name: &str, definitions: &str, strings: &[&str]) -> Vec<String> {
+    let mut source = format!("class Test extends Object;\n\n{definitions}\nfunction Test()\n{{\n");
+    for string in strings {
+        source.push_str(&format!("    S = {string};\n"));
+    }
+    source.push_str("}\n");
+
+    let (preprocessed, diagnostics) = preprocess(test_name, &source);
+    assert_eq!(diagnostics, Vec::<Value>::new());
+    preprocessed
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("S = "))
+        .map(|assigned| assigned.strip_suffix(';').unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn macros_expand_inside_strings() {
+    let strings = expand_strings(
+        "macros_expand_inside_strings",
+        "\
+`define NAME World
+`define GREETING Hello, `NAME
+`define SUM(A, B) `A + `B
+",
+        &[
+            r#""plain""#,
+            r#""`GREETING!""#,
+            r#""`{NAME}wide""#,
+            r#""`SUM(1, `SUM(2, 3))""#,
+            r#""[`UNDEFINED]""#,
+            r#""lone ` accent""#,
+        ],
+    );
+    assert_eq!(
+        strings,
+        [
+            r#""plain""#,
+            r#""Hello, World!""#,
+            r#""Worldwide""#,
+            r#""1 + 2 + 3""#,
+            r#""[]""#,
+            r#""lone ` accent""#,
+        ]
+    );
+}
+
+#[test]
+fn macro_arguments_expand_inside_strings_in_macro_bodies() {
+    let strings = expand_strings(
+        "macro_arguments_expand_inside_strings_in_macro_bodies",
+        "`define ShowVar(Var) \"`Var = \" $ `Var\n",
+        &["`ShowVar(Health)"],
+    );
+    // The dump does not preserve the whitespace around expanded tokens, so only the ends are
+    // compared.
+    assert!(
+        strings[0].starts_with(r#""Health = ""#) && strings[0].ends_with("Health"),
+        "{strings:?}"
+    );
+}
+
+#[test]
+fn conditionals_inside_strings() {
+    let strings = expand_strings(
+        "conditionals_inside_strings",
+        "`define DEFINED\n",
+        &[
+            r#""`if(`isdefined(DEFINED))yes`{else}no`endif""#,
+            r#""`if(`notdefined(DEFINED))yes`{else}no`endif""#,
+            r#""`if(`isdefined(UNDEFINED))yes`endif""#,
+        ],
+    );
+    assert_eq!(strings, [r#""yes""#, r#""no""#, r#""""#]);
+}
+
+#[test]
+fn recursive_expansion_inside_string_is_too_deep() {
+    let source = "\
+class Test extends Object;
+
+`define LOOP `LOOP!
+
+function Test()
+{
+    S = \"`LOOP\";
+}
+";
+    let (_, diagnostics) = preprocess("recursive_expansion_inside_string_is_too_deep", source);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:#?}");
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic["code"], "M0111");
+    assert_eq!(
+        diagnostic["message"],
+        "macro expansion inside string is too deep"
+    );
+
+    // The diagnostic is reported at the original string, rather than the one it expanded to.
+    let label = &diagnostic["labels"][0];
+    let start = source.find("\"`LOOP\"").unwrap();
+    assert_eq!(label["byte_start"], start);
+    assert_eq!(label["byte_end"], start + "\"`LOOP\"".len());
+}
+
+#[test]
+fn unbalanced_conditionals_inside_strings() {
+    let source = "\
+class Test extends Object;
+
+function Test()
+{
+    S = \"`endif\";
+    S = \"`if(1) unclosed\";
+}
+";
+    let (_, diagnostics) = preprocess("unbalanced_conditionals_inside_strings", source);
+    let codes: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["M0106", "M0105"]);
+}
+
+#[test]
+fn diagnostics_in_nested_expansions_list_expansions_innermost_first() {
+    let source = "\
+class Test extends Object;
+
+`define INNER `endif
+`define MIDDLE `INNER
+`define OUTER `MIDDLE
+
+function Test()
+{
+    `OUTER
+}
+";
+    let (_, diagnostics) = preprocess(
+        "diagnostics_in_nested_expansions_list_expansions_innermost_first",
+        source,
+    );
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:#?}");
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic["code"], "M0106");
+
+    // Each note points at the invocation of the macro, within the definition of the macro
+    // expanded around it.
+    let notes: Vec<_> = diagnostic["children"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|child| {
+            let label = &child["labels"][0];
+            let start = label["byte_start"].as_u64().unwrap() as usize;
+            let end = label["byte_end"].as_u64().unwrap() as usize;
+            (
+                child["message"].as_str().unwrap(),
+                &source[start..end],
+                source[..start].lines().count(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        notes,
+        [
+            ("in this expansion of macro `INNER`", "`INNER", 4),
+            ("in this expansion of macro `MIDDLE`", "`MIDDLE", 5),
+            ("in this expansion of macro `OUTER`", "`OUTER", 9),
+        ]
+    );
+}
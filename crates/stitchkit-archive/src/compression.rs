@@ -57,8 +57,7 @@ impl Summary {
                 let header = deserializer.deserialize::<CompressedChunkHeader>()?;
                 trace!("Read header: {header:#?}");
 
-                let block_count =
-                    (header.sum.uncompressed_size + header.block_size - 1) / header.block_size;
+                let block_count = header.sum.uncompressed_size.div_ceil(header.block_size);
                 let mut blocks = Vec::with_capacity(block_count as usize);
                 for _ in 0..block_count {
                     blocks.push(deserializer.deserialize::<CompressedChunkBlock>()?);
@@ -7,6 +7,12 @@ pub struct Context<T> {
     pointer: Cell<*const T>,
 }
 
+impl<T> Default for Context<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Context<T> {
     pub const fn new() -> Self {
         Self {
@@ -35,17 +41,17 @@ impl<T> Context<T> {
 ///
 /// Usage:
 /// ```
-/// #use stitchkit_core::context;
+/// # use stitchkit_core::context;
 /// context! {
 ///     let name: i32;
 /// }
 /// ```
 /// Initially, the variable starts out unset, and you can set it in a scope by using `name::with`.
 /// ```
-/// #use stitchkit_core::context;
-/// #context! {
-/// #    let name: i32;
-/// #}
+/// # use stitchkit_core::context;
+/// # context! {
+/// #     let name: i32;
+/// # }
 ///
 /// assert_eq!(name::get(), None);
 ///
@@ -21,7 +21,7 @@ pub struct UnrealString {
 impl UnrealString {
     /// Returns the string's byte representation without the NUL terminator.
     pub fn to_bytes(&self) -> &[u8] {
-        self.bytes.strip_suffix(&[b'\0']).unwrap_or(&self.bytes)
+        self.bytes.strip_suffix(b"\0").unwrap_or(&self.bytes)
     }
 }
 
@@ -39,6 +39,7 @@ where
     ///
     /// # Example
     /// ```
+    /// # use stitchkit_manifest::{Entry, ManifestFlags, ManifestWriter};
     /// let mut manifest = String::new();
     /// let mut writer = ManifestWriter::new(&mut manifest).unwrap();
     /// writer.write_entry(Entry {
@@ -46,14 +47,14 @@ where
     ///     package: "Core",
     ///     flags: ManifestFlags::ABSTRACT,
     ///     groups: std::iter::empty(),
-    /// });
+    /// }).unwrap();
     /// writer.descend();
     /// writer.write_entry(Entry {
     ///     class: "Actor",
     ///     package: "Engine",
     ///     flags: ManifestFlags::default(),
     ///     groups: std::iter::empty(),
-    /// });
+    /// }).unwrap();
     /// writer.ascend();
     /// ```
     pub fn descend(&mut self) {